@@ -0,0 +1,55 @@
+// Mining benchmark — `cargo bench --bench mining`. Times the nonce search at
+// difficulty 16 bits (the old "0000") on a block of 50 signed transactions, next to hashing
+// the whole block again for every nonce, so a slower mining loop shows up.
+
+use std::hint::black_box;
+use std::time::Instant;
+use mini_blockchain::block::Block;
+use mini_blockchain::transaction::Transaction;
+use mini_blockchain::wallet::Wallet;
+
+const HASHES: u64 = 2_000_000;
+const BLOCKS: u64 = 50;
+const DIFFICULTY_BITS: u32 = 16;
+
+fn main() {
+    let sender = Wallet::new();
+    let transactions: Vec<Transaction> = (0..50).map(|nonce| {
+        let mut txn = Transaction::new_nits(sender.address(), Wallet::new().address(), 5);
+        txn.nonce = nonce;
+        txn.sign(&sender).expect("sign");
+        txn
+    }).collect();
+    let mut block = Block::new(1, transactions, "ab".repeat(32), sender.address(), 1_700_000_000);
+    block.difficulty = DIFFICULTY_BITS;
+
+    let started = Instant::now();
+    for nonce in 0..HASHES {
+        block.nonce = nonce;
+        black_box(block.calculate_hash());
+    }
+    report("calculate_hash per nonce", HASHES, started);
+
+    let input = block.prepare_mining_input();
+    let started = Instant::now();
+    for nonce in 0..HASHES {
+        black_box(input.hash(nonce));
+    }
+    report("prepare_mining_input", HASHES, started);
+
+    let started = Instant::now();
+    let mut tried = 0;
+    for round in 0..BLOCKS {
+        let mut candidate = block.clone();
+        candidate.timestamp += round;
+        candidate.nonce = 0;
+        candidate.mine(DIFFICULTY_BITS).expect("mine");
+        tried += candidate.nonce + 1;
+    }
+    report(&format!("mine({}) x {}", DIFFICULTY_BITS, BLOCKS), tried, started);
+}
+
+fn report(name: &str, hashes: u64, started: Instant) {
+    let elapsed = started.elapsed();
+    println!("{:<26}{:>10.0} h/s  ({} hashes in {:.2?})", name, hashes as f64 / elapsed.as_secs_f64(), hashes, elapsed);
+}
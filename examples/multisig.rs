@@ -0,0 +1,52 @@
+// Alice and Bob jointly control funds that Carol alone can't move.
+// cargo run --example multisig
+
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::multisig::Multisig;
+use mini_blockchain::transaction::Transaction;
+use mini_blockchain::wallet::Wallet;
+
+fn main() -> Result<(), String> {
+    let mut bc = Blockchain::with_prefix("0");
+    let (alice, bob, carol) = (Wallet::new(), Wallet::new(), Wallet::new());
+    let keys = vec![alice.address(), bob.address(), carol.address()];
+    let vault = Wallet::multisig_address(&keys, 2)?;
+    println!("2-of-3 vault: {}", vault);
+
+    // alice mines until her rewards can be spent, then pays 10 tokens into the vault
+    for _ in 0..bc.coinbase_maturity.max(1) {
+        bc.mine_pending_transactions(alice.address(), true).map_err(|e| e.to_string())?;
+    }
+    let deposit = alice.sign_transaction(vault.clone(), 10.0, 0.1, bc.next_nonce(&alice.address()))?;
+    bc.add_transaction(deposit).map_err(|e| e.to_string())?;
+    bc.mine_pending_transactions(alice.address(), false).map_err(|e| e.to_string())?;
+    println!("vault balance: {}", bc.get_balance(&vault).map_err(|e| e.to_string())?);
+
+    let nonce = bc.next_nonce(&vault);
+    let spend = || -> Result<Transaction, String> {
+        let mut txn = Transaction::new_with_fee(vault.clone(), carol.address(), 4.0, 0.1)?;
+        txn.nonce = nonce;
+        txn.multisig = Some(Multisig::new(keys.clone(), 2)?);
+        Ok(txn)
+    };
+
+    let mut alone = spend()?;
+    alone.add_signature(&carol)?;
+    match bc.add_transaction(alone) {
+        Ok(_)  => return Err("carol moved the funds on her own".to_string()),
+        Err(e) => println!("carol alone: refused ({})", e),
+    }
+
+    // alice signs, hands the raw hex to bob, and bob adds his signature
+    let mut joint = spend()?;
+    joint.add_signature(&alice)?;
+    let raw = joint.to_hex().map_err(|e| e.to_string())?;
+    let mut joint = Transaction::from_hex(&raw).map_err(|e| e.to_string())?;
+    joint.add_signature(&bob)?;
+    let txid = bc.add_transaction(joint).map_err(|e| e.to_string())?;
+    bc.mine_pending_transactions(alice.address(), false).map_err(|e| e.to_string())?;
+    println!("alice + bob: accepted ({}...)", &txid[..12]);
+    println!("vault balance: {}", bc.get_balance(&vault).map_err(|e| e.to_string())?);
+    println!("carol balance: {}", bc.get_balance(&carol.address()).map_err(|e| e.to_string())?);
+    Ok(())
+}
@@ -0,0 +1,178 @@
+// Chain actor — one thread owns the Blockchain and works through commands
+// sent to it over a channel, one at a time, answering each on a oneshot.
+// Handlers, the auto-miner and sync hold a ChainHandle and await the answer
+// instead of sharing the chain under a lock, so nothing ever borrows into it
+// across an await. Embedders that don't run a node use Blockchain directly.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicBool;
+use std::thread;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, error, warn};
+use crate::block::{Block, MineAborted, MiningStatus};
+use crate::blockchain::Blockchain;
+use crate::error::ChainError;
+use crate::transaction::Transaction;
+
+/// Commands that can wait for the chain thread before senders do.
+pub const COMMAND_QUEUE: usize = 256;
+
+// a panic in a command comes back to whoever sent it, not the chain thread
+type Reply<T> = oneshot::Sender<thread::Result<T>>;
+
+enum Command {
+    SubmitTx     { txn: Transaction, reply: Reply<Result<String, ChainError>> },
+    MineTemplate { miner: String, force: bool, reply: Reply<Result<MiningJob, ChainError>> },
+    CommitBlock  { block: Block, reply: Reply<Result<usize, ChainError>> },
+    AppendBlock  { block: Block, reply: Reply<Result<usize, ChainError>> },
+    Query(Box<dyn FnOnce(&Blockchain) + Send>),
+    Update(Box<dyn FnOnce(&mut Blockchain) + Send>),
+}
+
+/// A block to mine, with this node's settings for the nonce search.
+pub struct MiningJob {
+    pub block:       Block,
+    pub threads:     usize,
+    pub nonce_space: u64,  // nonces per extra_nonce
+    pub status:      Arc<Mutex<MiningStatus>>,  // the chain's, kept up to date as the search goes
+}
+
+impl MiningJob {
+    /// Search for the nonce on the calling thread — never the chain's.
+    pub fn mine(self, cancel: &AtomicBool) -> Result<Block, MineAborted> {
+        let mut block = self.block;
+        block.mine_tracked(block.difficulty, self.threads, self.nonce_space, cancel, &self.status)?;
+        Ok(block)
+    }
+}
+
+/// The node's way to the chain. Cheap to clone; the chain thread stops once
+/// every handle is gone.
+#[derive(Clone)]
+pub struct ChainHandle {
+    tx:            mpsc::Sender<Command>,
+    cancel_mining: Arc<AtomicBool>,  // the chain's own flag, so cancelling needs no round trip
+    mining_status: Arc<Mutex<MiningStatus>>,  // likewise, so reading it doesn't wait behind commands
+}
+
+impl ChainHandle {
+    /// Move `bc` onto a thread of its own and start taking commands.
+    pub fn spawn(bc: Blockchain) -> Self {
+        let (tx, rx) = mpsc::channel(COMMAND_QUEUE);
+        let cancel_mining = bc.cancel_mining.clone();
+        let mining_status = bc.mining_status.clone();
+        thread::Builder::new()
+            .name("chain".to_string())
+            .spawn(move || serve(bc, rx))
+            .expect("couldn't start the chain thread");
+        ChainHandle { tx, cancel_mining, mining_status }
+    }
+
+    /// Set to abort the nonce search under way.
+    pub fn cancel_mining(&self) -> &Arc<AtomicBool> {
+        &self.cancel_mining
+    }
+
+    /// The nonce search under way, or the last one.
+    pub fn mining_status(&self) -> &Arc<Mutex<MiningStatus>> {
+        &self.mining_status
+    }
+
+    /// Read whatever `f` needs off the chain. `f` runs on the chain thread,
+    /// so it should copy out what it wants and return.
+    pub async fn query<R: Send + 'static>(&self, f: impl FnOnce(&Blockchain) -> R + Send + 'static) -> R {
+        self.call(|reply| Command::Query(Box::new(move |bc| answer(reply, || f(bc))))).await
+    }
+
+    /// Like query(), for changes without a command of their own — replace, rollback, signing with the next nonce.
+    pub async fn update<R: Send + 'static>(&self, f: impl FnOnce(&mut Blockchain) -> R + Send + 'static) -> R {
+        self.call(|reply| Command::Update(Box::new(move |bc| answer_mut(bc, reply, |bc| logged(bc, f))))).await
+    }
+
+    /// submit_transaction(); the txid.
+    pub async fn submit(&self, txn: Transaction) -> Result<String, ChainError> {
+        self.call(|reply| Command::SubmitTx { txn, reply }).await
+    }
+
+    /// prepare_block(), and how to search for its nonce.
+    pub async fn template(&self, miner: String, force: bool) -> Result<MiningJob, ChainError> {
+        self.call(|reply| Command::MineTemplate { miner, force, reply }).await
+    }
+
+    /// commit_block() for a block mined here; its height.
+    pub async fn commit(&self, block: Block) -> Result<usize, ChainError> {
+        self.call(|reply| Command::CommitBlock { block, reply }).await
+    }
+
+    /// try_append_block() for a block from outside; its height.
+    pub async fn append(&self, block: Block) -> Result<usize, ChainError> {
+        self.call(|reply| Command::AppendBlock { block, reply }).await
+    }
+
+    async fn call<T>(&self, command: impl FnOnce(Reply<T>) -> Command) -> T {
+        let (reply, answered) = oneshot::channel();
+        // the thread only exits once every handle, this one included, is dropped
+        self.tx.send(command(reply)).await.expect("the chain thread has stopped");
+        match answered.await.expect("the chain thread has stopped") {
+            Ok(value) => value,
+            Err(e)    => panic::resume_unwind(e),
+        }
+    }
+}
+
+// Commands that can change the mempool log it before answering, so a
+// transaction acknowledged as accepted is already on disk — a submission the
+// log can't take is refused.
+fn serve(mut bc: Blockchain, mut rx: mpsc::Receiver<Command>) {
+    while let Some(command) = rx.blocking_recv() {
+        match command {
+            Command::SubmitTx { txn, reply } => answer_mut(&mut bc, reply, |bc| bc.submit_transaction(txn)),
+            Command::MineTemplate { miner, force, reply } => answer(reply, || {
+                bc.prepare_block(miner, force).map(|block| MiningJob {
+                    block, threads: bc.mining_threads, nonce_space: bc.nonce_space, status: bc.mining_status.clone(),
+                })
+            }),
+            Command::CommitBlock { block, reply } => answer_mut(&mut bc, reply, |bc| logged(bc, |bc| {
+                bc.commit_block(block).map(|_| bc.chain.len() - 1)
+            })),
+            Command::AppendBlock { block, reply } => answer_mut(&mut bc, reply, |bc| logged(bc, |bc| {
+                bc.try_append_block(block).map(|_| bc.chain.len() - 1)
+            })),
+            Command::Query(f)  => f(&bc),
+            Command::Update(f) => f(&mut bc),
+        }
+    }
+    debug!("every chain handle dropped, chain thread done");
+}
+
+// `f`, then log_mempool(). Past a submission, what changes the mempool is a
+// block or an operator, not a user waiting on an answer, so a failed write is
+// logged and the node carries on — the shutdown flush gets another go at it.
+fn logged<T>(bc: &mut Blockchain, f: impl FnOnce(&mut Blockchain) -> T) -> T {
+    let out = f(bc);
+    if let Err(e) = bc.log_mempool() {
+        error!(error = %e, "could not log the mempool");
+    }
+    out
+}
+
+// Run `f` and send what it returns, or its panic. A read can't have left
+// anything half-done, so the chain carries on after one that panicked.
+fn answer<T>(reply: Reply<T>, f: impl FnOnce() -> T) {
+    let _ = reply.send(panic::catch_unwind(AssertUnwindSafe(f)));
+}
+
+// answer() for a command that changes the chain. One that panicked may have
+// stopped halfway — blocks unindexed mid-reorg, say — so the indexes are
+// rebuilt from the chain before the next command runs.
+fn answer_mut<T>(bc: &mut Blockchain, reply: Reply<T>, f: impl FnOnce(&mut Blockchain) -> T) {
+    let out = panic::catch_unwind(AssertUnwindSafe(|| f(bc)));
+    if out.is_err() {
+        match bc.recover_from_panic() {
+            Ok(())  => warn!("a chain command panicked, indexes rebuilt"),
+            Err(e)  => error!(error = %e, "a chain command panicked and left the chain invalid"),
+        }
+    }
+    let _ = reply.send(out);
+}
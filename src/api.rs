@@ -1,114 +1,1942 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use actix_web::error::{InternalError, JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::http::{header, StatusCode};
 use serde::{Serialize, Deserialize};
-use std::sync::Mutex;
+use utoipa::{IntoParams, ToSchema};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
 
-use crate::blockchain::Blockchain;
-use crate::transaction::Transaction;
-use crate::wallet::Wallet;
+use crate::actor::ChainHandle;
+use crate::audit::{RejectionEntry, RejectionLog, Rejected, Source};
+use crate::auth::{Admin, ApiKey};
+use crate::block::{Block, BlockHeader};
+use crate::blockchain::{AddressStats, BlockRef, BlockTemplate, BlockTiming, HistoryEntry, ImportOutcome, ReplaceOutcome, ChainStats, TransactionStatus};
+use crate::canonical::HASH_VERSION;
+use crate::error::ChainError;
+use crate::explorer::{self, SearchHit};
+use crate::export;
+use crate::gossip::{Gossip, Announcement};
+use crate::merkle::MerkleProof;
+use crate::multisig::Multisig;
+use crate::openapi::ApiError;
+use crate::idempotency::IdempotencyStore;
+use crate::ratelimit::{RateLimiter, RateLimited};
+use crate::peers::{Peer, PeerSet, PeerError, NODE_KEY_HEADER, NODE_SIGNATURE_HEADER, normalize_url, probe};
+use crate::status::{NodeStatus, Readiness};
+use crate::sync;
+use crate::transaction::{Transaction, Output, NITS_PER_TOKEN, BURN_ADDRESS, tokens_to_nits, parse_tokens};
+use crate::util::LockExt;
+use crate::wallet::{Wallet, WalletInfo, verify_message};
+use crate::miner::{AutoMiner, MinerError, MinerStatus, DEFAULT_MINER_INTERVAL_SECS};
+use crate::webhooks::{Webhook, Webhooks, WebhookError, EventKind};
+use tracing::{error, warn};
 
 pub struct AppState {
-    pub blockchain: Mutex<Blockchain>,
+    pub chain:        ChainHandle,  // the actor that owns the Blockchain
+    pub chain_file:   String,
+    pub peers:        Arc<Mutex<PeerSet>>,
+    pub gossip:       Gossip,
+    pub webhooks:     Webhooks,
+    pub api_key:      ApiKey,       // X-Api-Key for the admin endpoints
+    pub rate_limiter: RateLimiter,  // per-IP budget for submitting and mining
+    pub idempotency:  IdempotencyStore,
+    pub status:       NodeStatus,   // what /health and /ready report
+    pub miner:        AutoMiner,    // POST /miner/start's background loop
+    pub rejections:   RejectionLog, // what /audit/rejections and /metrics report
 }
 
-#[derive(Deserialize)]
+/// Write the chain to disk and record how that went for /ready. A failed
+/// save is logged, not returned — whatever changed is already on the chain.
+pub(crate) async fn save_chain(state: &AppState) {
+    let path = state.chain_file.clone();
+    // the node's store only takes what changed; a bare chain is written whole
+    let result = state.chain.query(move |bc| match bc.has_store() {
+        true  => bc.persist().map(|_| ()),
+        false => bc.save_to_file(&path),
+    }).await;
+    if let Err(e) = &result {
+        error!(error = %e, "could not save chain");
+    }
+    state.status.saved(result);
+}
+
+// amounts can be sent as a JSON number or, to skip f64 entirely, a decimal string
+#[derive(Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum TokenAmount {
+    Number(f64),
+    Text(String),
+}
+
+impl TokenAmount {
+    pub fn to_nits(&self, what: &str) -> Result<u64, String> {
+        match self {
+            TokenAmount::Number(n) => tokens_to_nits(*n, what),
+            TokenAmount::Text(s)   => parse_tokens(s, what),
+        }
+    }
+}
+
+impl Default for TokenAmount {
+    fn default() -> Self { TokenAmount::Number(0.0) }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct OutputRequest {
+    pub to:     String,
+    pub amount: TokenAmount,
+}
+
+// either to + amount, or outputs for paying several addresses under one signature
+#[derive(Deserialize, ToSchema)]
 pub struct TransactionRequest {
+    pub from:             String,
+    pub to:               Option<String>,
+    pub amount:           Option<TokenAmount>,
+    pub outputs:          Option<Vec<OutputRequest>>,
+    #[serde(default)]
+    pub fee:              TokenAmount,
+    pub nonce:            Option<u64>,
+    pub private_key_hex:  String,
+    #[serde(default)]
+    pub memo:             Option<String>,
+    #[serde(default)]
+    pub lock_until_block: Option<u32>,
+}
+
+// POST /burn — a TransactionRequest whose `to` is always BURN_ADDRESS
+#[derive(Deserialize, ToSchema)]
+pub struct BurnRequest {
     pub from:            String,
-    pub to:              String,
-    pub amount:          f64,
+    pub amount:          TokenAmount,
+    #[serde(default)]
+    pub fee:             TokenAmount,
+    pub nonce:           Option<u64>,
     pub private_key_hex: String,
+    #[serde(default)]
+    pub memo:            Option<String>,
+}
+
+// GET /wallet/new is the one place a private key is sent — once, at creation
+#[derive(Serialize, ToSchema)]
+pub struct NewWallet<'a> {
+    pub address:         String,
+    pub private_key_hex: &'a str,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RestoreRequest {
+    pub mnemonic: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MultisigRequest {
+    pub keys:      Vec<String>,
+    pub threshold: u8,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MultisigInfo {
+    pub address:   String,
+    pub keys:      Vec<String>,  // sorted, as the address commits to them
+    pub threshold: u8,
 }
 
-#[derive(Deserialize)]
+// signing happens client-side — the server only ever sees the signature
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyMessageRequest {
+    pub address:   String,
+    pub message:   String,
+    pub signature: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VerifyMessageResult {
+    pub address: String,
+    pub valid:   bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RawTransactionRequest {
+    pub raw: String,
+}
+
+// what POST /transaction/decode reports — nothing is added to the mempool
+#[derive(Serialize, ToSchema)]
+pub struct DecodedTransaction {
+    pub txid:             String,
+    pub transaction:      Transaction,
+    pub signature_valid:  bool,
+    pub validation_error: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub force: bool,  // import even if it has no more work than ours
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct RollbackRequest {
+    pub blocks: usize,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PeerRequest {
+    pub url: String,  // base URL, e.g. http://10.0.0.5:3000
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HealthReport {
+    pub uptime_secs:  u64,
+    pub version:      &'static str,
+    pub height:       usize,
+    pub mempool_size: usize,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BlockSeriesQuery {
+    pub last: Option<usize>,  // 100 by default, at most MAX_HEADER_LIMIT
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditQuery {
+    pub limit: Option<usize>,  // newest first; 50 by default
+}
+
+/// GET /audit/rejections: the kept rejections, and every one since startup by error code.
+#[derive(Serialize, ToSchema)]
+pub struct RejectionsReport {
+    pub capacity:   usize,
+    pub by_reason:  BTreeMap<&'static str, u64>,
+    pub rejections: Vec<RejectionEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ValidationReport {
+    pub valid:  bool,
+    pub height: usize,
+    pub error:  Option<String>,  // what failed, when valid is false
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct WebhookRequest {
+    pub url:            String,
+    pub events:         Vec<EventKind>,  // "block", "tx"
+    pub address_filter: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct MineRequest {
     pub miner_address: String,
+    #[serde(default)]
+    pub allow_empty:   bool,  // a coinbase-only block if the mempool is empty
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct MinerStartRequest {
+    pub address:       String,
+    pub interval_secs: Option<u64>,  // between rounds, default 10
+    #[serde(default)]
+    pub allow_empty:   bool,         // mine every round, even with nothing in the mempool
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProofResponse {
+    pub block_index: u32,
+    pub merkle_root: String,
+    #[schema(value_type = Vec<(String, bool)>)]  // [sibling_hash, sibling_is_left] per level
+    pub proof:       MerkleProof,
+}
+
+// pending transaction as the API shows it
+#[derive(Serialize, ToSchema)]
+pub struct MempoolEntry {
+    pub txid:             String,
+    pub from:             String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to:               Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outputs:          Option<Vec<Output>>,
+    pub amount:           f64,
+    pub fee:              f64,
+    pub nonce:            u64,
+    pub memo:             Option<String>,
+    pub lock_until_block: Option<u32>,
+    pub locked:           bool,  // can't go into the next block yet
+    pub signed:           bool,
+}
+
+impl MempoolEntry {
+    // `next_height` is the index of the block a miner would build now
+    fn from_txn(t: &Transaction, next_height: u32) -> Self {
+        MempoolEntry {
+            txid:             t.id(),
+            from:             t.from.clone(),
+            to:               t.single_recipient().map(str::to_string),
+            outputs:          if t.outputs.len() > 1 { Some(t.outputs.clone()) } else { None },
+            amount:           t.amount_as_tokens(),
+            fee:              t.fee_as_tokens(),
+            nonce:            t.nonce,
+            memo:             t.memo.clone(),
+            lock_until_block: t.lock_until_block,
+            locked:           !t.is_unlocked_at(next_height),
+            signed:           t.signature_hex.is_some(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MempoolResponse {
+    pub count:        usize,
+    pub transactions: Vec<MempoolEntry>,
+}
+
+// fees in tokens, None while the mempool is empty
+#[derive(Serialize, ToSchema)]
+pub struct MempoolStats {
+    pub count:      usize,
+    pub bytes:      usize,
+    pub max_count:  usize,
+    pub max_bytes:  usize,
+    pub min_fee:    Option<f64>,
+    pub median_fee: Option<f64>,
+    pub max_fee:    Option<f64>,
+}
+
+// GET /chain pages through the chain instead of sending all of it
+pub const DEFAULT_PAGE_LIMIT: usize = 50;
+pub const MAX_PAGE_LIMIT: usize = 500;
+pub const MAX_HEADER_LIMIT: usize = 2000;  // headers are small, so pages can be bigger
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChainQuery {
+    pub from:  Option<usize>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQuery {
+    pub limit:        Option<usize>,
+    pub before_block: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ChainPage {
+    pub total_blocks: usize,
+    pub from:         usize,
+    pub limit:        usize,
+    pub blocks:       Vec<Block>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HeadersPage {
+    pub total_blocks: usize,
+    pub from:         usize,
+    pub limit:        usize,
+    pub headers:      Vec<BlockHeader>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ChainHead {
+    pub index:             u32,
+    pub hash:              String,
+    pub timestamp:         u64,
+    pub transaction_count: usize,
+    pub total_work:        u128,
+    pub genesis_hash:      String,  // peers compare this before registering
+    pub node_id:           String,   // lets a node spot itself in a peer list
+    pub recent_blocks:     Vec<BlockRef>,  // 1, 2, 4 … 64 below the tip, to see at a glance where two nodes part
+}
+
+// how far below the tip the blocks in ChainHead.recent_blocks are
+const HEAD_RECENT_DEPTHS: [usize; 7] = [1, 2, 4, 8, 16, 32, 64];
+
+#[derive(Serialize, ToSchema)]
+pub struct RewardInfo {
+    pub next_height:      u32,
+    pub reward:           f64,
+    pub halving_interval: u32,
+}
+
+// spendable = confirmed minus what's already queued to go out.
+// overdrawn means the queue asks for more than is confirmed — spendable is clamped to 0.
+#[derive(Serialize, ToSchema)]
+pub struct BalanceInfo {
+    pub confirmed: f64,
+    pub immature:  f64,  // block rewards still maturing — part of confirmed, not yet spendable
+    pub pending:   f64,
+    pub spendable: f64,
+    pub overdrawn: bool,
+}
+
+// a block height or hash, a txid or an address
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    pub q: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RichListQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Holder {
+    pub address:           String,
+    pub balance:           f64,
+    pub percent_of_supply: f64,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FeeQuery {
+    pub blocks: Option<usize>,
 }
 
-// every endpoint returns { ok, message, data }
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+pub struct FeeEstimate {
+    pub target_blocks: usize,
+    pub fee:           f64,
+    pub fee_nits:      u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SupplyInfo {
+    pub circulating:      f64,
+    pub total_burned:     f64,
+    pub effective_supply: f64,  // circulating minus burned
+    pub max_supply:       Option<f64>,
+    pub percent_issued:   Option<f64>,
+}
+
+// every endpoint returns { ok, message, data, error_code } — error_code is null
+// on success, and on failure a stable name like "INSUFFICIENT_FUNDS" to match on
+#[derive(Serialize, ToSchema)]
 pub struct Res<T: Serialize> {
-    pub ok:      bool,
-    pub message: String,
-    pub data:    Option<T>,
+    pub ok:         bool,
+    pub message:    String,
+    pub data:       Option<T>,
+    pub error_code: Option<String>,
 }
 
 fn ok<T: Serialize>(msg: &str, data: T) -> HttpResponse {
-    HttpResponse::Ok().json(Res { ok: true, message: msg.into(), data: Some(data) })
+    HttpResponse::Ok().json(Res { ok: true, message: msg.into(), data: Some(data), error_code: None })
+}
+
+pub(crate) fn fail(status: StatusCode, code: &str, msg: &str) -> HttpResponse {
+    HttpResponse::build(status).json(Res::<()> {
+        ok: false, message: msg.into(), data: None, error_code: Some(code.into()),
+    })
+}
+
+// the request itself is malformed — bad hex, bad amount, missing fields
+fn err(code: &str, msg: &str) -> HttpResponse {
+    fail(StatusCode::BAD_REQUEST, code, msg)
+}
+
+fn not_found(msg: &str) -> HttpResponse {
+    fail(StatusCode::NOT_FOUND, "NOT_FOUND", msg)
+}
+
+// 400 for input that doesn't even parse, 422 when the submitted data is
+// rejected, 409 when the node's state is in the way, 404 for unknown lookups
+fn chain_err(e: &ChainError) -> HttpResponse {
+    let status = match e {
+        ChainError::NotFound(_)               => StatusCode::NOT_FOUND,
+        ChainError::Pruned { .. }             => StatusCode::GONE,
+        ChainError::MalformedTransaction(_)
+        | ChainError::MalformedSignature(_)
+        | ChainError::InvalidRollback(_)
+        | ChainError::InvalidPrune(_)         => StatusCode::BAD_REQUEST,
+        ChainError::EmptyChain
+        | ChainError::ClockUnavailable
+        | ChainError::MempoolLogFailed(_)     => StatusCode::INTERNAL_SERVER_ERROR,
+        _ if e.is_validation()                => {
+            warn!(error = %e, "rejected");
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+        _                                     => StatusCode::CONFLICT,
+    };
+    fail(status, e.code(), &e.to_string())
+}
+
+// unreachable or not speaking the protocol is the other node's fault → 502
+fn peer_err(e: &PeerError) -> HttpResponse {
+    let status = match e {
+        PeerError::InvalidUrl(_)        => StatusCode::BAD_REQUEST,
+        PeerError::GenesisMismatch(_)   => StatusCode::UNPROCESSABLE_ENTITY,
+        PeerError::AlreadyRegistered(_)
+        | PeerError::SelfRegistration(_) => StatusCode::CONFLICT,
+        PeerError::Unreachable { .. }
+        | PeerError::NotANode { .. }    => StatusCode::BAD_GATEWAY,
+        PeerError::Unsigned             => StatusCode::UNAUTHORIZED,
+        PeerError::UnknownNode(_)
+        | PeerError::BadSignature(_)    => StatusCode::FORBIDDEN,
+    };
+    fail(status, e.code(), &e.to_string())
+}
+
+fn miner_err(e: &MinerError) -> HttpResponse {
+    let status = match e {
+        MinerError::AlreadyRunning(_) => StatusCode::CONFLICT,
+        _                             => StatusCode::BAD_REQUEST,
+    };
+    fail(status, e.code(), &e.to_string())
+}
+
+fn webhook_err(e: &WebhookError) -> HttpResponse {
+    let status = match e {
+        WebhookError::NotFound(_) => StatusCode::NOT_FOUND,
+        _                         => StatusCode::BAD_REQUEST,
+    };
+    fail(status, e.code(), &e.to_string())
+}
+
+/// JsonConfig error handler: a body that isn't the JSON an endpoint expects
+/// gets the usual envelope instead of actix's plain-text 400.
+pub fn json_error(e: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = err("MALFORMED_JSON", &e.to_string());
+    InternalError::from_response(e, response).into()
 }
 
-fn err(msg: &str) -> HttpResponse {
-    HttpResponse::BadRequest().json(Res::<()> { ok: false, message: msg.into(), data: None })
+/// PathConfig error handler — e.g. /block/abc where a height was expected.
+pub fn path_error(e: PathError, _req: &HttpRequest) -> actix_web::Error {
+    let response = err("INVALID_PATH", &e.to_string());
+    InternalError::from_response(e, response).into()
+}
+
+/// QueryConfig error handler, same idea for ?query parameters.
+pub fn query_error(e: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = err("INVALID_QUERY", &e.to_string());
+    InternalError::from_response(e, response).into()
 }
 
 // GET /wallet/new
+#[utoipa::path(get, path = "/wallet/new", tag = "wallets",
+    responses(
+        (status = 200, description = "new wallet — the only time the private key is sent", body = Res<NewWallet>),
+    ),
+)]
 pub async fn new_wallet() -> impl Responder {
     let w = Wallet::new();
-    ok("wallet created", w.to_info())
+    let secret = w.export_secret();
+    ok("wallet created", NewWallet { address: w.address(), private_key_hex: &secret })
+}
+
+// POST /wallet/restore
+// { mnemonic } — re-derives the wallet and returns only its address,
+// so the phrase can be checked before anything is sent to it
+#[utoipa::path(post, path = "/wallet/restore", tag = "wallets",
+    request_body = RestoreRequest,
+    responses(
+        (status = 200, description = "address the mnemonic derives", body = Res<WalletInfo>),
+        (status = 400, description = "bad mnemonic (INVALID_MNEMONIC)", body = ApiError),
+    ),
+)]
+pub async fn restore_wallet(body: web::Json<RestoreRequest>) -> impl Responder {
+    match Wallet::from_mnemonic(&body.mnemonic) {
+        Ok(w)  => ok("wallet restored", w.to_info()),
+        Err(e) => err("INVALID_MNEMONIC", &e),
+    }
+}
+
+// POST /multisig/address
+// { keys, threshold } — the address M of these keys control. Spending from it
+// goes through /transaction/raw once enough co-signers have signed.
+#[utoipa::path(post, path = "/multisig/address", tag = "wallets",
+    request_body = MultisigRequest,
+    responses(
+        (status = 200, description = "address M of these keys control", body = Res<MultisigInfo>),
+        (status = 422, description = "bad key set or threshold (INVALID_MULTISIG)", body = ApiError),
+    ),
+)]
+pub async fn multisig_address(body: web::Json<MultisigRequest>) -> impl Responder {
+    let body = body.into_inner();
+    match Multisig::new(body.keys, body.threshold) {
+        Ok(ms) => ok("multisig address", MultisigInfo {
+            address: ms.address(), keys: ms.keys, threshold: ms.threshold,
+        }),
+        Err(e) => chain_err(&ChainError::InvalidMultisig(e)),
+    }
+}
+
+// POST /message/verify
+// { address, message, signature } — did this address sign this message?
+// A well-formed but wrong signature is ok with valid=false, not an error.
+#[utoipa::path(post, path = "/message/verify", tag = "wallets",
+    request_body = VerifyMessageRequest,
+    responses(
+        (status = 200, description = "valid says whether the signature matches", body = Res<VerifyMessageResult>),
+        (status = 400, description = "not hex (MALFORMED_HEX)", body = ApiError),
+    ),
+)]
+pub async fn verify_signed_message(body: web::Json<VerifyMessageRequest>) -> impl Responder {
+    match verify_message(&body.address, &body.message, &body.signature) {
+        Ok(valid) => ok(if valid { "signature valid" } else { "signature does not match" },
+            VerifyMessageResult { address: body.address.clone(), valid }),
+        Err(e) => err("MALFORMED_HEX", &e),
+    }
 }
 
 // POST /transaction
-// { from, to, amount, private_key_hex }
+// { from, to, amount, fee?, nonce?, private_key_hex }
+// amount/fee in tokens, as a number or a decimal string like "2.5"
+// nonce defaults to the sender's next one if left out
+#[utoipa::path(post, path = "/transaction", tag = "transactions",
+    params(("Idempotency-Key" = Option<String>, Header, description = "replays the first answer for a retried request within the window")),
+    request_body = TransactionRequest,
+    responses(
+        (status = 200, description = "added to the mempool; data is the txid", body = Res<String>),
+        (status = 400, description = "malformed request or amount", body = ApiError),
+        (status = 409, description = "node state in the way (e.g. MEMPOOL_FULL), or an Idempotency-Key reused with a different body (IDEMPOTENCY_KEY_REUSED)", body = ApiError),
+        (status = 422, description = "rejected, e.g. INSUFFICIENT_FUNDS, BAD_NONCE, BAD_SIGNATURE", body = ApiError),
+        (status = 500, description = "the mempool log couldn't be written, so the transaction wasn't taken (MEMPOOL_LOG_FAILED)", body = ApiError),
+        (status = 429, description = "over the per-IP rate limit (RATE_LIMITED); data.retry_after_secs", body = ApiError),
+    ),
+)]
 pub async fn submit_transaction(
+    _limit: RateLimited,
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> impl Responder {
+    state.idempotency.run(&req, &body, || async {
+        match serde_json::from_slice::<TransactionRequest>(&body) {
+            Ok(request) => sign_and_submit(&state, &request).await,
+            Err(e)      => err("MALFORMED_JSON", &format!("invalid transaction JSON: {}", e)),
+        }
+    }).await
+}
+
+// POST /burn
+// { from, amount, fee?, nonce?, private_key_hex } — a transfer to BURN_ADDRESS
+#[utoipa::path(post, path = "/burn", tag = "transactions",
+    request_body = BurnRequest,
+    responses(
+        (status = 200, description = "burn added to the mempool; data is the txid", body = Res<String>),
+        (status = 400, description = "malformed request or amount", body = ApiError),
+        (status = 409, description = "node state in the way, e.g. MEMPOOL_FULL", body = ApiError),
+        (status = 422, description = "rejected, e.g. INSUFFICIENT_FUNDS, BAD_NONCE, BAD_SIGNATURE", body = ApiError),
+        (status = 500, description = "the mempool log couldn't be written, so the transaction wasn't taken (MEMPOOL_LOG_FAILED)", body = ApiError),
+        (status = 429, description = "over the per-IP rate limit (RATE_LIMITED); data.retry_after_secs", body = ApiError),
+    ),
+)]
+pub async fn burn(
+    _limit: RateLimited,
     state: web::Data<AppState>,
-    body: web::Json<TransactionRequest>,
+    body: web::Json<BurnRequest>,
 ) -> impl Responder {
+    let body = body.into_inner();
+    sign_and_submit(&state, &TransactionRequest {
+        from: body.from,
+        to: Some(BURN_ADDRESS.to_string()),
+        amount: Some(body.amount),
+        outputs: None,
+        fee: body.fee,
+        nonce: body.nonce,
+        private_key_hex: body.private_key_hex,
+        memo: body.memo,
+        lock_until_block: None,
+    }).await
+}
+
+async fn sign_and_submit(state: &AppState, body: &TransactionRequest) -> HttpResponse {
     let wallet = match Wallet::from_private_key_hex(&body.private_key_hex) {
         Ok(w) => w,
-        Err(e) => return err(&e),
+        Err(e) => return err("INVALID_PRIVATE_KEY", &e),
     };
 
     if wallet.address() != body.from {
-        return err("private key doesn't match the from address");
+        return fail(StatusCode::UNPROCESSABLE_ENTITY, "KEY_MISMATCH", "private key doesn't match the from address");
+    }
+
+    let requested: Vec<(&String, &TokenAmount)> = match (&body.to, &body.amount, &body.outputs) {
+        (Some(to), Some(amount), None) => vec![(to, amount)],
+        (None, None, Some(outputs))    => outputs.iter().map(|o| (&o.to, &o.amount)).collect(),
+        (None, None, None)             => return err("INVALID_REQUEST", "give to + amount, or outputs"),
+        (_, _, None)                   => return err("INVALID_REQUEST", "to and amount go together"),
+        (_, _, Some(_))                => return err("INVALID_REQUEST", "give either to + amount or outputs, not both"),
+    };
+    let mut outputs = Vec::with_capacity(requested.len());
+    for (to, amount) in requested {
+        match amount.to_nits("amount") {
+            Ok(amount) => outputs.push(Output { to: to.clone(), amount }),
+            Err(e)     => return err("INVALID_AMOUNT", &e),
+        }
     }
+    let fee = match body.fee.to_nits("fee") {
+        Ok(f)  => f,
+        Err(e) => return err("INVALID_AMOUNT", &e),
+    };
 
-    let mut txn = Transaction::new(body.from.clone(), body.to.clone(), body.amount);
-    if let Err(e) = txn.sign(&wallet) {
-        return err(&e);
+    let mut txn = Transaction {
+        from: body.from.clone(), outputs, fee, nonce: 0, signature_hex: None, memo: body.memo.clone(),
+        lock_until_block: body.lock_until_block, multisig: None,
+    };
+    // picking the next nonce and adding happen in one command, so two
+    // submits from the same sender can't both get the same nonce
+    let nonce = body.nonce;
+    let submitted = state.chain.update(move |bc| -> Result<_, String> {
+        txn.nonce = nonce.unwrap_or_else(|| bc.next_nonce(&txn.from));
+        txn.sign(&wallet)?;
+        Ok((bc.submit_transaction(txn.clone()), txn))
+    }).await;
+
+    match submitted {
+        Ok((Ok(txid), txn)) => {
+            // accepted, so validate() already saw that it encodes
+            if let Ok(raw) = txn.to_hex() {
+                state.gossip.announce(Announcement::Transaction { txid: txid.clone(), raw });
+            }
+            state.webhooks.transaction_pending(&txn);
+            ok("transaction added to mempool", txid)
+        }
+        Ok((Err(e), txn)) => {
+            state.rejections.record(Source::Api, Rejected::Transaction, Some(txn.id()), &e);
+            chain_err(&e)
+        }
+        Err(e) => fail(StatusCode::UNPROCESSABLE_ENTITY, "KEY_MISMATCH", &e),
     }
+}
 
-    let mut bc = state.blockchain.lock().unwrap();
-    match bc.add_transaction(txn) {
-        Ok(_)  => ok("transaction added to mempool", body.amount),
-        Err(e) => err(&e),
+// POST /transaction/raw
+// { raw } — hex from Transaction::to_hex(), already signed client-side.
+// The private key never reaches the server on this path.
+#[utoipa::path(post, path = "/transaction/raw", tag = "transactions",
+    params(("Idempotency-Key" = Option<String>, Header, description = "replays the first answer for a retried request within the window")),
+    request_body = RawTransactionRequest,
+    responses(
+        (status = 200, description = "added to the mempool; data is the txid", body = Res<String>),
+        (status = 400, description = "malformed request or amount", body = ApiError),
+        (status = 409, description = "node state in the way (e.g. MEMPOOL_FULL), or an Idempotency-Key reused with a different body (IDEMPOTENCY_KEY_REUSED)", body = ApiError),
+        (status = 422, description = "rejected, e.g. INSUFFICIENT_FUNDS, BAD_NONCE, BAD_SIGNATURE", body = ApiError),
+        (status = 500, description = "the mempool log couldn't be written, so the transaction wasn't taken (MEMPOOL_LOG_FAILED)", body = ApiError),
+        (status = 429, description = "over the per-IP rate limit (RATE_LIMITED); data.retry_after_secs", body = ApiError),
+    ),
+)]
+pub async fn submit_raw_transaction(
+    _limit: RateLimited,
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> impl Responder {
+    state.idempotency.run(&req, &body, || async {
+        match serde_json::from_slice::<RawTransactionRequest>(&body) {
+            Ok(request) => accept_raw_transaction(&state, &request.raw, Source::Api).await,
+            Err(e)      => err("MALFORMED_JSON", &format!("invalid transaction JSON: {}", e)),
+        }
+    }).await
+}
+
+// shared by /transaction/raw and /peer/transaction
+async fn accept_raw_transaction(state: &AppState, raw: &str, source: Source) -> HttpResponse {
+    let txn = match Transaction::from_hex(raw) {
+        Ok(t)  => t,
+        Err(e) => return chain_err(&e),
+    };
+    let raw = match txn.to_hex() {
+        Ok(raw) => raw,
+        Err(e)  => return chain_err(&e),
+    };
+    match state.chain.submit(txn.clone()).await {
+        Ok(txid) => {
+            state.gossip.announce(Announcement::Transaction { txid: txid.clone(), raw });
+            state.webhooks.transaction_pending(&txn);
+            ok("transaction added to mempool", txid)
+        }
+        Err(e) => {
+            state.rejections.record(source, Rejected::Transaction, Some(txn.id()), &e);
+            chain_err(&e)
+        }
+    }
+}
+
+// POST /transaction/decode
+// { raw } — parse and check a raw transaction without broadcasting it
+#[utoipa::path(post, path = "/transaction/decode", tag = "transactions",
+    request_body = RawTransactionRequest,
+    responses(
+        (status = 200, description = "decoded; nothing is submitted", body = Res<DecodedTransaction>),
+        (status = 400, description = "not a raw transaction (MALFORMED_TRANSACTION)", body = ApiError),
+    ),
+)]
+pub async fn decode_raw_transaction(body: web::Json<RawTransactionRequest>) -> impl Responder {
+    let txn = match Transaction::from_hex(&body.raw) {
+        Ok(t)  => t,
+        Err(e) => return chain_err(&e),
+    };
+    let validation = txn.validate();
+    ok("transaction decoded", DecodedTransaction {
+        txid: txn.id(),
+        signature_valid: validation.is_ok(),
+        validation_error: validation.err().map(|e| e.to_string()),
+        transaction: txn,
+    })
+}
+
+// GET /transaction/:txid
+// confirmed → block index + confirmations, pending → confirmations 0
+#[utoipa::path(get, path = "/transaction/{txid}", tag = "transactions",
+    params(("txid" = String, Path, description = "transaction id")),
+    responses(
+        (status = 200, description = "where it is; confirmations 0 while pending", body = Res<TransactionStatus>),
+        (status = 404, description = "unknown txid (NOT_FOUND)", body = ApiError),
+    ),
+)]
+pub async fn get_transaction(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let txid = path.into_inner();
+    match state.chain.query(move |bc| bc.find_transaction(&txid)).await {
+        Some(status) => ok("transaction found", status),
+        None         => not_found("transaction not found"),
     }
 }
 
 // POST /mine
-// { miner_address }
+// { miner_address, allow_empty? }
+// The nonce search runs on a blocking thread, off the chain thread,
+// so every other endpoint keeps answering while we mine.
+#[utoipa::path(post, path = "/mine", tag = "mining",
+    request_body = MineRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "mined; data is the new block's height", body = Res<usize>),
+        (status = 409, description = "nothing to mine (and allow_empty not set), tip moved, or cancelled", body = ApiError),
+        (status = 429, description = "over the per-IP rate limit (RATE_LIMITED); data.retry_after_secs", body = ApiError),
+        (status = 401, description = "no X-Api-Key (API_KEY_REQUIRED)", body = ApiError),
+        (status = 403, description = "wrong X-Api-Key (BAD_API_KEY)", body = ApiError),
+    ),
+)]
 pub async fn mine_block(
+    _admin: Admin,
+    _limit: RateLimited,
     state: web::Data<AppState>,
     body: web::Json<MineRequest>,
 ) -> impl Responder {
-    let mut bc = state.blockchain.lock().unwrap();
-    match bc.mine_pending_transactions(body.miner_address.clone()) {
-        Ok(_)  => ok("block mined", bc.chain.len() - 1),
-        Err(e) => err(&e),
+    let job = match state.chain.template(body.miner_address.clone(), body.allow_empty).await {
+        Ok(job) => job,
+        Err(e)  => return chain_err(&e),
+    };
+
+    let cancel = state.chain.cancel_mining().clone();
+    cancel.store(false, Ordering::Relaxed);
+    let mined = web::block(move || job.mine(&cancel)).await;
+    let block = match mined {
+        Ok(Ok(b))  => b,
+        Ok(Err(e)) => return chain_err(&e.into()),
+        Err(e)     => return fail(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", &format!("mining thread failed: {}", e)),
+    };
+
+    match commit_mined(&state, block).await {
+        Ok(height) => ok("block mined", height),
+        Err(e)     => chain_err(&e),
     }
 }
 
-// GET /chain
-pub async fn get_chain(state: web::Data<AppState>) -> impl Responder {
-    let bc = state.blockchain.lock().unwrap();
-    ok("here's the chain", &bc.chain)
+/// Put a block mined on this node on the chain, save it, and tell webhooks
+/// and peers. Returns its height. POST /mine and the auto-miner both end here.
+pub(crate) async fn commit_mined(state: &AppState, block: Block) -> Result<usize, ChainError> {
+    let height = state.chain.commit(block.clone()).await?;
+    // the block is already on chain — a failed save shouldn't undo that
+    save_chain(state).await;
+    state.webhooks.block_added(&block);
+    state.gossip.announce(Announcement::Block(Box::new(block)));
+    Ok(height)
+}
+
+// GET /mining/template?miner_address=&allow_empty=
+// the next block minus its proof of work — add the coinbase, find a nonce, POST /block
+#[utoipa::path(get, path = "/mining/template", tag = "mining",
+    params(MineRequest),
+    responses(
+        (status = 200, description = "the next block minus coinbase and proof of work", body = Res<BlockTemplate>),
+        (status = 422, description = "bad miner address", body = ApiError),
+    ),
+)]
+pub async fn get_mining_template(
+    state: web::Data<AppState>,
+    query: web::Query<MineRequest>,
+) -> impl Responder {
+    let (miner, force) = (query.miner_address.clone(), query.allow_empty);
+    match state.chain.query(move |bc| bc.build_block_template(miner, force)).await {
+        Ok(template) => ok("block template", template),
+        Err(e)       => chain_err(&e),
+    }
+}
+
+// POST /block
+// a fully formed, already mined block — from another node or an external miner.
+// Built on an older tip → 409 stale tip, fetch fresh work and retry.
+// Parent we've never seen → 409 unknown parent, and we go and sync from our peers.
+#[utoipa::path(post, path = "/block", tag = "mining",
+    request_body = Block,
+    responses(
+        (status = 200, description = "appended; data is its height", body = Res<usize>),
+        (status = 409, description = "stale tip or unknown parent", body = ApiError),
+        (status = 422, description = "invalid block", body = ApiError),
+        (status = 429, description = "over the per-IP rate limit (RATE_LIMITED); data.retry_after_secs", body = ApiError),
+    ),
+)]
+pub async fn submit_block(
+    _limit: RateLimited,
+    state: web::Data<AppState>,
+    body: web::Json<Block>,
+) -> impl Responder {
+    accept_block(&state, body.into_inner(), None).await
+}
+
+// shared by /block and /peer/block. On an unknown parent we sync — from the
+// announcing peer if we know who that is, otherwise from everyone, unless a
+// round started that way is still running.
+async fn accept_block(state: &web::Data<AppState>, block: Block, from_peer: Option<String>) -> HttpResponse {
+    match state.chain.append(block.clone()).await {
+        Ok(height) => {
+            save_chain(state).await;
+            state.webhooks.block_added(&block);
+            state.gossip.announce(Announcement::Block(Box::new(block)));
+            ok("block accepted", height)
+        }
+        Err(e) => {
+            let source = if from_peer.is_some() { Source::Peer } else { Source::Api };
+            state.rejections.record(source, Rejected::Block, Some(block.hash), &e);
+            if matches!(e, ChainError::UnknownParent { .. }) {
+                let state = state.clone();
+                actix_web::rt::spawn(async move {
+                    match from_peer {
+                        Some(url) => {
+                            if let Err(e) = sync::sync_with_peer(&state, &url).await {
+                                warn!(peer = %url, error = %e, "sync failed");
+                            }
+                        }
+                        None => sync::sync_all_coalesced(&state).await,
+                    }
+                });
+            }
+            chain_err(&e)
+        }
+    }
+}
+
+// Which registered peer signed this announcement — checked before the body is parsed.
+fn verify_peer(state: &AppState, req: &HttpRequest, body: &[u8]) -> Result<String, PeerError> {
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok());
+    state.peers.locked()
+        .verify_announcement(header(NODE_KEY_HEADER), header(NODE_SIGNATURE_HEADER), body)
+}
+
+// POST /peer/block
+// a block announced by a registered peer — signed with its node key (X-Node-Key, X-Node-Signature)
+#[utoipa::path(post, path = "/peer/block", tag = "peers",
+    params(
+        ("X-Node-Key" = String, Header, description = "announcing node's identity key"),
+        ("X-Node-Signature" = String, Header, description = "its signature over the raw body")
+    ),
+    request_body = Block,
+    responses(
+        (status = 200, description = "appended; data is its height", body = Res<usize>),
+        (status = 401, description = "unsigned announcement", body = ApiError),
+        (status = 403, description = "unknown node or bad signature", body = ApiError),
+        (status = 409, description = "stale tip or unknown parent", body = ApiError),
+        (status = 422, description = "invalid block", body = ApiError),
+    ),
+)]
+pub async fn peer_block(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> impl Responder {
+    let url = match verify_peer(&state, &req, &body) {
+        Ok(u)  => u,
+        Err(e) => return peer_err(&e),
+    };
+    match serde_json::from_slice::<Block>(&body) {
+        Ok(block) => accept_block(&state, block, Some(url)).await,
+        Err(e)    => err("MALFORMED_JSON", &format!("invalid block JSON: {}", e)),
+    }
+}
+
+// POST /peer/transaction
+// { raw } announced by a registered peer, signed the same way as /peer/block
+#[utoipa::path(post, path = "/peer/transaction", tag = "peers",
+    params(
+        ("X-Node-Key" = String, Header, description = "announcing node's identity key"),
+        ("X-Node-Signature" = String, Header, description = "its signature over the raw body")
+    ),
+    request_body = RawTransactionRequest,
+    responses(
+        (status = 200, description = "added to the mempool; data is the txid", body = Res<String>),
+        (status = 401, description = "unsigned announcement", body = ApiError),
+        (status = 403, description = "unknown node or bad signature", body = ApiError),
+        (status = 400, description = "malformed request or amount", body = ApiError),
+        (status = 409, description = "node state in the way, e.g. MEMPOOL_FULL", body = ApiError),
+        (status = 422, description = "rejected, e.g. INSUFFICIENT_FUNDS, BAD_NONCE, BAD_SIGNATURE", body = ApiError),
+        (status = 500, description = "the mempool log couldn't be written, so the transaction wasn't taken (MEMPOOL_LOG_FAILED)", body = ApiError),
+    ),
+)]
+pub async fn peer_transaction(
+    state: web::Data<AppState>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> impl Responder {
+    if let Err(e) = verify_peer(&state, &req, &body) {
+        return peer_err(&e);
+    }
+    match serde_json::from_slice::<RawTransactionRequest>(&body) {
+        Ok(request) => accept_raw_transaction(&state, &request.raw, Source::Peer).await,
+        Err(e)      => err("MALFORMED_JSON", &format!("invalid transaction JSON: {}", e)),
+    }
+}
+
+// POST /mine/cancel
+// stops whatever mine is in progress — the mempool is left untouched
+#[utoipa::path(post, path = "/mine/cancel", tag = "mining",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "any mine in progress is stopped", body = Res<bool>),
+        (status = 401, description = "no X-Api-Key (API_KEY_REQUIRED)", body = ApiError),
+        (status = 403, description = "wrong X-Api-Key (BAD_API_KEY)", body = ApiError),
+    ),
+)]
+pub async fn cancel_mining(_admin: Admin, state: web::Data<AppState>) -> impl Responder {
+    state.chain.cancel_mining().store(true, Ordering::Relaxed);
+    ok("mining cancelled", true)
+}
+
+// POST /miner/start
+// { address, interval_secs?, allow_empty? } — mine the mempool into blocks for
+// `address` whenever it has something (every round, with allow_empty), checking
+// again every interval_secs
+#[utoipa::path(post, path = "/miner/start", tag = "mining",
+    request_body = MinerStartRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "the miner is running", body = Res<MinerStatus>),
+        (status = 400, description = "bad address or interval_secs of 0", body = ApiError),
+        (status = 401, description = "no X-Api-Key (API_KEY_REQUIRED)", body = ApiError),
+        (status = 403, description = "wrong X-Api-Key (BAD_API_KEY)", body = ApiError),
+        (status = 409, description = "already running (MINER_RUNNING)", body = ApiError),
+    ),
+)]
+pub async fn start_miner(
+    _admin: Admin,
+    state: web::Data<AppState>,
+    body: web::Json<MinerStartRequest>,
+) -> impl Responder {
+    let body = body.into_inner();
+    let interval = body.interval_secs.unwrap_or(DEFAULT_MINER_INTERVAL_SECS);
+    match state.miner.start(state.clone(), body.address, interval, body.allow_empty) {
+        Ok(status) => ok("miner started", status),
+        Err(e)     => miner_err(&e),
+    }
+}
+
+// POST /miner/stop
+// aborts any search under way; stopping an idle miner is fine too
+#[utoipa::path(post, path = "/miner/stop", tag = "mining",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "the miner is idle", body = Res<MinerStatus>),
+        (status = 401, description = "no X-Api-Key (API_KEY_REQUIRED)", body = ApiError),
+        (status = 403, description = "wrong X-Api-Key (BAD_API_KEY)", body = ApiError),
+    ),
+)]
+pub async fn stop_miner(_admin: Admin, state: web::Data<AppState>) -> impl Responder {
+    ok("miner stopped", state.miner.stop().await)
+}
+
+// GET /miner/status
+#[utoipa::path(get, path = "/miner/status", tag = "mining",
+    responses(
+        (status = 200, description = "running or idle, blocks mined since the start, hash rate", body = Res<MinerStatus>),
+    ),
+)]
+pub async fn miner_status(state: web::Data<AppState>) -> impl Responder {
+    ok("miner status", state.miner.status())
+}
+
+// GET /chain?from=&limit=
+#[utoipa::path(get, path = "/chain", tag = "chain",
+    params(ChainQuery),
+    responses(
+        (status = 200, description = "one page of blocks", body = Res<ChainPage>),
+        (status = 410, description = "the page starts below this node's prune point (PRUNED)", body = ApiError),
+    ),
+)]
+pub async fn get_chain(
+    state: web::Data<AppState>,
+    query: web::Query<ChainQuery>,
+) -> impl Responder {
+    let from = query.from.unwrap_or(0);
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+    let page = state.chain.query(move |bc| {
+        let total_blocks = bc.chain.len();
+        let from = from.min(total_blocks);
+        let to = from.saturating_add(limit).min(total_blocks);
+        bc.check_unpruned(from, to)?;
+        Ok(ChainPage {
+            total_blocks,
+            from,
+            limit,
+            blocks: bc.chain[from..to].to_vec(),
+        })
+    }).await;
+    match page {
+        Ok(page) => ok("here's the chain", page),
+        Err(e)   => chain_err(&e),
+    }
+}
+
+// GET /headers?from=&limit=
+// same paging as /chain, but only headers — what headers-first sync downloads first
+#[utoipa::path(get, path = "/headers", tag = "chain",
+    params(ChainQuery),
+    responses(
+        (status = 200, description = "one page of headers, up to 2000", body = Res<HeadersPage>),
+    ),
+)]
+pub async fn get_headers(
+    state: web::Data<AppState>,
+    query: web::Query<ChainQuery>,
+) -> impl Responder {
+    let from = query.from.unwrap_or(0);
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_HEADER_LIMIT);
+    let page = state.chain.query(move |bc| {
+        let total_blocks = bc.chain.len();
+        let from = from.min(total_blocks);
+        let to = from.saturating_add(limit).min(total_blocks);
+        HeadersPage {
+            total_blocks,
+            from,
+            limit,
+            headers: bc.chain[from..to].iter().map(Block::header).collect(),
+        }
+    }).await;
+    ok("block headers", page)
+}
+
+// POST /chain/replace
+// [ block, block, ... ] — a full chain from another node.
+// Adopted only if it's valid, shares our genesis and has more total work than ours.
+#[utoipa::path(post, path = "/chain/replace", tag = "chain",
+    request_body = Vec<Block>,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "adopted", body = Res<ReplaceOutcome>),
+        (status = 409, description = "not more work than ours (CHAIN_NOT_BETTER)", body = ApiError),
+        (status = 422, description = "invalid chain or different genesis", body = ApiError),
+        (status = 401, description = "no X-Api-Key (API_KEY_REQUIRED)", body = ApiError),
+        (status = 403, description = "wrong X-Api-Key (BAD_API_KEY)", body = ApiError),
+    ),
+)]
+pub async fn replace_chain(
+    _admin: Admin,
+    state: web::Data<AppState>,
+    body: web::Json<Vec<Block>>,
+) -> impl Responder {
+    let candidate = body.into_inner();
+    let tip = candidate.last().map(|b| b.hash.clone());
+    let replaced = state.chain.update(move |bc| {
+        bc.replace_chain(candidate).map(|outcome| {
+            let adopted = bc.blocks_after(outcome.fork_height - 1).to_vec();
+            (outcome, adopted)
+        })
+    }).await;
+    match replaced {
+        Ok((outcome, adopted)) => {
+            save_chain(&state).await;
+            for block in &adopted {
+                state.webhooks.block_added(block);
+            }
+            ok("chain replaced", outcome)
+        }
+        Err(e) => {
+            state.rejections.record(Source::Api, Rejected::Chain, tip, &e);
+            chain_err(&e)
+        }
+    }
+}
+
+// POST /chain/import?force=
+// A whole exported chain: the save file, GET /chain's response (envelope or
+// just its data) or a bare list of blocks. Checked from genesis, then swapped
+// in under replace_chain's rules — unless force, it needs more work than ours.
+#[utoipa::path(post, path = "/chain/import", tag = "chain",
+    params(ImportQuery),
+    request_body(content = Vec<Block>,
+        description = "a list of blocks, a chain file ({ hash_version, chain, ... }) or a GET /chain page covering the whole chain"),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "imported; the old chain's length and tip for checking", body = Res<ImportOutcome>),
+        (status = 400, description = "not a chain export, a partial page or another hash version (MALFORMED_CHAIN)", body = ApiError),
+        (status = 409, description = "not more work than ours and force not set (CHAIN_NOT_BETTER)", body = ApiError),
+        (status = 422, description = "invalid chain or different genesis", body = ApiError),
+        (status = 401, description = "no X-Api-Key (API_KEY_REQUIRED)", body = ApiError),
+        (status = 403, description = "wrong X-Api-Key (BAD_API_KEY)", body = ApiError),
+    ),
+)]
+pub async fn import_chain(
+    _admin: Admin,
+    state: web::Data<AppState>,
+    query: web::Query<ImportQuery>,
+    body: web::Json<serde_json::Value>,
+) -> impl Responder {
+    let blocks = match exported_blocks(body.into_inner()) {
+        Ok(blocks) => blocks,
+        Err(e)     => return err("MALFORMED_CHAIN", &e),
+    };
+    let force = query.force;
+    let tip = blocks.last().map(|b| b.hash.clone());
+    let imported = state.chain.update(move |bc| {
+        bc.import_chain(blocks, force).map(|outcome| {
+            let adopted = bc.chain[outcome.fork_height..].to_vec();
+            (outcome, adopted)
+        })
+    }).await;
+    match imported {
+        Ok((outcome, adopted)) => {
+            save_chain(&state).await;
+            for block in &adopted {
+                state.webhooks.block_added(block);
+            }
+            ok("chain imported", outcome)
+        }
+        Err(e) => {
+            state.rejections.record(Source::Api, Rejected::Chain, tip, &e);
+            chain_err(&e)
+        }
+    }
+}
+
+// the blocks out of whichever export was posted
+fn exported_blocks(mut body: serde_json::Value) -> Result<Vec<Block>, String> {
+    // GET /chain's envelope
+    if body.get("ok").is_some() {
+        body = body["data"].take();
+    }
+    if let Some(version) = body.get("hash_version") {
+        if version.as_u64() != Some(HASH_VERSION as u64) {
+            return Err(format!("the file was written with hash version {} and this node hashes with version {}",
+                version, HASH_VERSION));
+        }
+    }
+    if let Some(total) = body.get("total_blocks").and_then(|t| t.as_u64()) {
+        let from = body["from"].as_u64().unwrap_or(0);
+        let count = body["blocks"].as_array().map_or(0, |b| b.len()) as u64;
+        if from != 0 || count != total {
+            return Err(format!("that's one page of /chain ({} of {} blocks, from #{}) — export the save file, \
+                or fetch from=0 with a limit covering the whole chain", count, total, from));
+        }
+    }
+    let blocks = match body {
+        serde_json::Value::Array(_)          => body,
+        serde_json::Value::Object(mut fields) => fields.remove("chain").or_else(|| fields.remove("blocks"))
+            .ok_or("expected a list of blocks, a chain file or a /chain page")?,
+        _ => return Err("expected a list of blocks, a chain file or a /chain page".to_string()),
+    };
+    let blocks: Vec<Block> = serde_json::from_value(blocks).map_err(|e| format!("not a list of blocks: {}", e))?;
+    if blocks.is_empty() {
+        return Err("the chain has no blocks".to_string());
+    }
+    Ok(blocks)
+}
+
+// POST /chain/rollback
+// { blocks } — drop that many blocks off the tip, their transactions go back to the mempool
+#[utoipa::path(post, path = "/chain/rollback", tag = "chain",
+    request_body = RollbackRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "hashes of the removed blocks", body = Res<Vec<String>>),
+        (status = 400, description = "can't roll back that far (INVALID_ROLLBACK)", body = ApiError),
+        (status = 401, description = "no X-Api-Key (API_KEY_REQUIRED)", body = ApiError),
+        (status = 403, description = "wrong X-Api-Key (BAD_API_KEY)", body = ApiError),
+    ),
+)]
+pub async fn rollback_chain(
+    _admin: Admin,
+    state: web::Data<AppState>,
+    body: web::Json<RollbackRequest>,
+) -> impl Responder {
+    let blocks = body.blocks;
+    match state.chain.update(move |bc| bc.rollback(blocks)).await {
+        Ok(removed) => {
+            save_chain(&state).await;
+            let hashes: Vec<String> = removed.into_iter().map(|b| b.hash).collect();
+            ok("blocks rolled back", hashes)
+        }
+        Err(e) => chain_err(&e),
+    }
+}
+
+// GET /chain/head
+#[utoipa::path(get, path = "/chain/head", tag = "chain",
+    responses(
+        (status = 200, description = "the tip", body = Res<ChainHead>),
+    ),
+)]
+pub async fn get_chain_head(state: web::Data<AppState>) -> impl Responder {
+    let node_id = state.peers.locked().node_id();
+    let head = state.chain.query(move |bc| match (bc.chain.first(), bc.chain.last()) {
+        (Some(genesis), Some(tip)) => Ok(ChainHead {
+            index:             tip.index,
+            hash:              tip.hash.clone(),
+            timestamp:         tip.timestamp,
+            transaction_count: tip.transactions.len(),
+            total_work:        bc.total_work(),
+            genesis_hash:      genesis.hash.clone(),
+            node_id,
+            recent_blocks:     HEAD_RECENT_DEPTHS.iter()
+                .filter_map(|depth| (tip.index as usize).checked_sub(*depth))
+                .map(|height| BlockRef { height, hash: bc.chain[height].hash.clone() })
+                .collect(),
+        }),
+        _ => Err(ChainError::EmptyChain),
+    }).await;
+    match head {
+        Ok(head) => ok("chain head", head),
+        Err(e)   => chain_err(&e),
+    }
+}
+
+// GET /chain/locator
+#[utoipa::path(get, path = "/chain/locator", tag = "chain",
+    responses(
+        (status = 200, description = "hashes from the tip back to genesis, ten one apart and then ever sparser", body = Res<Vec<BlockRef>>),
+    ),
+)]
+pub async fn get_chain_locator(state: web::Data<AppState>) -> impl Responder {
+    let locator = state.chain.query(|bc| bc.block_locator()).await;
+    ok("block locator", locator)
+}
+
+// GET /block/:index
+#[utoipa::path(get, path = "/block/{index}", tag = "chain",
+    params(("index" = usize, Path, description = "block height")),
+    responses(
+        (status = 200, description = "the block", body = Res<Block>),
+        (status = 404, description = "no block at that height", body = ApiError),
+        (status = 410, description = "pruned — only its header is kept (PRUNED)", body = ApiError),
+    ),
+)]
+pub async fn get_block(
+    state: web::Data<AppState>,
+    path: web::Path<usize>,
+) -> impl Responder {
+    let index = path.into_inner();
+    let found = state.chain.query(move |bc| {
+        bc.check_unpruned(index, index.saturating_add(1)).map(|_| bc.get_block(index).cloned())
+    }).await;
+    match found {
+        Ok(Some(block)) => ok("block fetched", block),
+        Ok(None)        => not_found(&format!("no block at height {}", index)),
+        Err(e)          => chain_err(&e),
+    }
+}
+
+// GET /block/hash/:hash
+#[utoipa::path(get, path = "/block/hash/{hash}", tag = "chain",
+    params(("hash" = String, Path, description = "block hash")),
+    responses(
+        (status = 200, description = "the block", body = Res<Block>),
+        (status = 404, description = "no block with that hash", body = ApiError),
+        (status = 410, description = "pruned — only its header is kept (PRUNED)", body = ApiError),
+    ),
+)]
+pub async fn get_block_by_hash(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let hash = path.into_inner();
+    let found = state.chain.query(move |bc| match bc.get_block_by_hash(&hash) {
+        Some(block) => bc.check_unpruned(block.index as usize, block.index as usize + 1).map(|_| Some(block.clone())),
+        None        => Ok(None),
+    }).await;
+    match found {
+        Ok(Some(block)) => ok("block fetched", block),
+        Ok(None)        => not_found("no block with that hash"),
+        Err(e)          => chain_err(&e),
+    }
+}
+
+// GET /mempool — in the order they would be mined
+#[utoipa::path(get, path = "/mempool", tag = "mempool",
+    responses(
+        (status = 200, description = "pending transactions in mining order", body = Res<MempoolResponse>),
+    ),
+)]
+pub async fn get_mempool(state: web::Data<AppState>) -> impl Responder {
+    let pending = state.chain.query(|bc| MempoolResponse {
+        count: bc.mempool.len(),
+        transactions: bc.mempool.mining_order().into_iter()
+            .map(|t| MempoolEntry::from_txn(t, bc.chain.len() as u32))
+            .collect(),
+    }).await;
+    ok("pending transactions", pending)
+}
+
+// GET /mempool/stats
+#[utoipa::path(get, path = "/mempool/stats", tag = "mempool",
+    responses(
+        (status = 200, description = "count, size and fees", body = Res<MempoolStats>),
+    ),
+)]
+pub async fn get_mempool_stats(state: web::Data<AppState>) -> impl Responder {
+    let stats = state.chain.query(|bc| {
+        let mut fees: Vec<u64> = bc.mempool.iter().map(|t| t.fee).collect();
+        fees.sort_unstable();
+        let tokens = |nits: &u64| *nits as f64 / NITS_PER_TOKEN as f64;
+        MempoolStats {
+            count:      bc.mempool.len(),
+            bytes:      bc.mempool_bytes(),
+            max_count:  bc.max_mempool_txs,
+            max_bytes:  bc.max_mempool_bytes,
+            min_fee:    fees.first().map(tokens),
+            median_fee: fees.get(fees.len() / 2).map(tokens),
+            max_fee:    fees.last().map(tokens),
+        }
+    }).await;
+    ok("mempool stats", stats)
+}
+
+// GET /mempool/:txid
+#[utoipa::path(get, path = "/mempool/{txid}", tag = "mempool",
+    params(("txid" = String, Path, description = "transaction id")),
+    responses(
+        (status = 200, description = "the pending transaction", body = Res<MempoolEntry>),
+        (status = 404, description = "not in the mempool", body = ApiError),
+    ),
+)]
+pub async fn get_mempool_transaction(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let txid = path.into_inner();
+    let entry = state.chain.query(move |bc| {
+        bc.mempool.get(&txid).map(|t| MempoolEntry::from_txn(t, bc.chain.len() as u32))
+    }).await;
+    match entry {
+        Some(entry) => ok("pending transaction", entry),
+        None        => not_found("transaction not in mempool"),
+    }
 }
 
 // GET /balance/:address
+// { confirmed, pending, spendable, overdrawn } in tokens
+#[utoipa::path(get, path = "/balance/{address}", tag = "addresses",
+    params(("address" = String, Path, description = "wallet address")),
+    responses(
+        (status = 200, description = "balances in tokens", body = Res<BalanceInfo>),
+        (status = 422, description = "not a valid address", body = ApiError),
+    ),
+)]
 pub async fn get_balance(
     state: web::Data<AppState>,
     path: web::Path<String>,
 ) -> impl Responder {
     let address = path.into_inner();
-    let bc = state.blockchain.lock().unwrap();
-    match bc.get_balance(&address) {
-        Ok(bal) => ok("balance fetched", bal),
-        Err(e)  => err(&e),
+    let balance = state.chain.query(move |bc| {
+        let confirmed = bc.get_balance_nits(&address)?;
+        let pending = bc.get_pending_balance(&address)?;
+        let outgoing = bc.pending_outgoing(&address);
+        let immature = bc.immature_balance(&address, bc.chain.len());
+        let tokens = |nits: u64| nits as f64 / NITS_PER_TOKEN as f64;
+        Ok::<_, ChainError>(BalanceInfo {
+            confirmed: tokens(confirmed),
+            immature:  tokens(immature),
+            pending:   tokens(pending),
+            spendable: tokens(confirmed.saturating_sub(immature).saturating_sub(outgoing)),
+            overdrawn: outgoing > confirmed,
+        })
+    }).await;
+    match balance {
+        Ok(balance) => ok("balance fetched", balance),
+        Err(e)      => chain_err(&e),
+    }
+}
+
+// GET /richlist?limit=20
+// largest balances first, with each one's share of circulating supply
+#[utoipa::path(get, path = "/richlist", tag = "addresses",
+    params(RichListQuery),
+    responses(
+        (status = 200, description = "largest balances first", body = Res<Vec<Holder>>),
+    ),
+)]
+pub async fn get_rich_list(
+    state: web::Data<AppState>,
+    query: web::Query<RichListQuery>,
+) -> impl Responder {
+    let limit = query.limit.unwrap_or(20).min(MAX_PAGE_LIMIT);
+    let holders: Vec<Holder> = state.chain.query(move |bc| {
+        let supply = bc.total_supply();
+        bc.top_holders(limit).into_iter()
+            .map(|(address, nits)| Holder {
+                address,
+                balance: nits as f64 / NITS_PER_TOKEN as f64,
+                percent_of_supply: if supply == 0 { 0.0 } else { nits as f64 / supply as f64 * 100.0 },
+            })
+            .collect()
+    }).await;
+    ok("top holders", holders)
+}
+
+// GET /address/:address/transactions?limit=&before_block=
+#[utoipa::path(get, path = "/address/{address}/transactions", tag = "addresses",
+    params(
+        ("address" = String, Path, description = "wallet address"),
+        HistoryQuery
+    ),
+    responses(
+        (status = 200, description = "confirmed history, newest first", body = Res<Vec<HistoryEntry>>),
+        (status = 410, description = "reaches below this node's prune point (PRUNED)", body = ApiError),
+    ),
+)]
+pub async fn get_address_history(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    query: web::Query<HistoryQuery>,
+) -> impl Responder {
+    let address = path.into_inner();
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_LIMIT).min(MAX_PAGE_LIMIT);
+    let before_block = query.before_block;
+    match state.chain.query(move |bc| bc.address_history(&address, limit, before_block)).await {
+        Ok(history) => ok("address history", history),
+        Err(e)      => chain_err(&e),
+    }
+}
+
+// GET /address/:address/stats
+// lifetime totals, kept up to date per block — zeros for an address never seen
+#[utoipa::path(get, path = "/address/{address}/stats", tag = "addresses",
+    params(("address" = String, Path, description = "wallet address")),
+    responses(
+        (status = 200, description = "received, sent, fees, transaction count, first and last block, mining", body = Res<AddressStats>),
+        (status = 422, description = "not a valid address", body = ApiError),
+    ),
+)]
+pub async fn get_address_stats(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let address = path.into_inner();
+    match state.chain.query(move |bc| bc.address_stats(&address)).await {
+        Ok(stats) => ok("address stats", stats),
+        Err(e)    => chain_err(&e),
+    }
+}
+
+// GET /search?q= — 303 to whatever q names; the body says what that was
+#[utoipa::path(get, path = "/search", tag = "chain",
+    params(SearchQuery),
+    responses(
+        (status = 303, description = "found; Location is the block, transaction or address history", body = Res<SearchHit>),
+        (status = 400, description = "empty query (EMPTY_QUERY)", body = ApiError),
+        (status = 404, description = "no block, transaction or address matches", body = ApiError),
+    ),
+)]
+pub async fn search(
+    state: web::Data<AppState>,
+    query: web::Query<SearchQuery>,
+) -> impl Responder {
+    let q = query.into_inner().q;
+    if q.trim().is_empty() {
+        return err("EMPTY_QUERY", "Search for a block height or hash, a txid or an address");
+    }
+    match state.chain.query(move |bc| explorer::classify(bc, &q)).await {
+        Some(hit) => HttpResponse::SeeOther()
+            .insert_header((header::LOCATION, hit.location.clone()))
+            .json(Res { ok: true, message: format!("{} found", hit.kind), data: Some(hit), error_code: None }),
+        None      => not_found("no block, transaction or address matches that"),
+    }
+}
+
+// GET /export/transactions.csv — streamed a batch of blocks at a time
+#[utoipa::path(get, path = "/export/transactions.csv", tag = "chain",
+    responses(
+        (status = 200, description = "a row per confirmed transaction output, up to the tip when the export started",
+            content_type = "text/csv", body = String),
+        (status = 410, description = "this node is pruned and can't list every transaction (PRUNED)", body = ApiError),
+    ),
+)]
+pub async fn export_transactions(state: web::Data<AppState>) -> impl Responder {
+    if let Err(e) = state.chain.query(|bc| bc.check_unpruned(0, bc.chain.len())).await {
+        return chain_err(&e);
+    }
+    HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(header::ContentDisposition::attachment("transactions.csv"))
+        .body(export::transactions_csv(state.chain.clone()))
+}
+
+// GET /export/balances.json — address → tokens, a snapshot streamed in chunks
+#[utoipa::path(get, path = "/export/balances.json", tag = "addresses",
+    responses(
+        (status = 200, description = "every non-zero confirmed balance, in address order; not the envelope",
+            content_type = "application/json", body = std::collections::BTreeMap<String, f64>),
+    ),
+)]
+pub async fn export_balances(state: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok()
+        .content_type(header::ContentType::json())
+        .insert_header(header::ContentDisposition::attachment("balances.json"))
+        .body(export::balances_json(state.chain.clone()))
+}
+
+// GET /reward
+// what the next block's coinbase pays before fees
+#[utoipa::path(get, path = "/reward", tag = "economics",
+    responses(
+        (status = 200, description = "next block's coinbase before fees", body = Res<RewardInfo>),
+    ),
+)]
+pub async fn get_reward(state: web::Data<AppState>) -> impl Responder {
+    let reward = state.chain.query(|bc| {
+        let next_height = bc.chain.len() as u32;
+        RewardInfo {
+            next_height,
+            reward: bc.reward_at(next_height) as f64 / NITS_PER_TOKEN as f64,
+            halving_interval: bc.halving_interval,
+        }
+    }).await;
+    ok("current block reward", reward)
+}
+
+// GET /fee/estimate?blocks=2
+// fee to attach to be mined within that many blocks (default 1)
+#[utoipa::path(get, path = "/fee/estimate", tag = "economics",
+    params(FeeQuery),
+    responses(
+        (status = 200, description = "suggested fee", body = Res<FeeEstimate>),
+    ),
+)]
+pub async fn estimate_fee(
+    state: web::Data<AppState>,
+    query: web::Query<FeeQuery>,
+) -> impl Responder {
+    let target_blocks = query.blocks.unwrap_or(1).max(1);
+    let fee_nits = state.chain.query(move |bc| bc.estimate_fee(target_blocks)).await;
+    ok("fee estimate", FeeEstimate {
+        target_blocks,
+        fee: fee_nits as f64 / NITS_PER_TOKEN as f64,
+        fee_nits,
+    })
+}
+
+// GET /stats
+#[utoipa::path(get, path = "/stats", tag = "economics",
+    responses(
+        (status = 200, description = "chain-wide numbers", body = Res<ChainStats>),
+    ),
+)]
+pub async fn get_stats(state: web::Data<AppState>) -> impl Responder {
+    ok("chain stats", state.chain.query(|bc| bc.stats()).await)
+}
+
+// GET /stats/blocks?last=100
+// per-block timing and difficulty, oldest first — enough to chart how retargeting behaves
+#[utoipa::path(get, path = "/stats/blocks", tag = "economics",
+    params(BlockSeriesQuery),
+    responses(
+        (status = 200, description = "the last N blocks' timestamps, gaps, difficulty, transaction counts and nonces", body = Res<Vec<BlockTiming>>),
+    ),
+)]
+pub async fn get_block_stats(
+    state: web::Data<AppState>,
+    query: web::Query<BlockSeriesQuery>,
+) -> impl Responder {
+    let last = query.last.unwrap_or(100).min(MAX_HEADER_LIMIT);
+    ok("block time series", state.chain.query(move |bc| bc.block_time_series(last)).await)
+}
+
+// GET /supply
+#[utoipa::path(get, path = "/supply", tag = "economics",
+    responses(
+        (status = 200, description = "issued, burned, effective", body = Res<SupplyInfo>),
+    ),
+)]
+pub async fn get_supply(state: web::Data<AppState>) -> impl Responder {
+    let supply = state.chain.query(|bc| {
+        let issued = bc.total_supply();
+        let burned = bc.total_burned();
+        SupplyInfo {
+            circulating: issued as f64 / NITS_PER_TOKEN as f64,
+            total_burned: burned as f64 / NITS_PER_TOKEN as f64,
+            effective_supply: issued.saturating_sub(burned) as f64 / NITS_PER_TOKEN as f64,
+            max_supply: bc.max_supply_nits.map(|cap| cap as f64 / NITS_PER_TOKEN as f64),
+            percent_issued: bc.max_supply_nits
+                .filter(|&cap| cap > 0)
+                .map(|cap| issued as f64 / cap as f64 * 100.0),
+        }
+    }).await;
+    ok("supply", supply)
+}
+
+// GET /nonce/:address
+#[utoipa::path(get, path = "/nonce/{address}", tag = "addresses",
+    params(("address" = String, Path, description = "wallet address")),
+    responses(
+        (status = 200, description = "next nonce to sign with", body = Res<u64>),
+    ),
+)]
+pub async fn get_nonce(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let address = path.into_inner();
+    ok("next nonce to sign with", state.chain.query(move |bc| bc.next_nonce(&address)).await)
+}
+
+// GET /proof/:txid
+// merkle path so a header-only client can check inclusion
+#[utoipa::path(get, path = "/proof/{txid}", tag = "chain",
+    params(("txid" = String, Path, description = "transaction id")),
+    responses(
+        (status = 200, description = "merkle inclusion proof", body = Res<ProofResponse>),
+        (status = 404, description = "not in any block", body = ApiError),
+    ),
+)]
+pub async fn get_proof(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let txid = path.into_inner();
+    let found = state.chain.query(move |bc| {
+        bc.chain.iter().find_map(|block| block.merkle_proof(&txid).map(|proof| ProofResponse {
+            block_index: block.index,
+            merkle_root: block.merkle_root.clone(),
+            proof,
+        }))
+    }).await;
+    match found {
+        Some(proof) => ok("merkle proof", proof),
+        None        => not_found("transaction not found in any block"),
     }
 }
 
 // GET /validate
+// Always 200 — the check itself ran. A broken chain is ok=false with the
+// failure's error_code, and data.valid says the same for clients that only read data.
+#[utoipa::path(get, path = "/validate", tag = "node",
+    responses(
+        (status = 200, description = "always 200; ok=false and error_code set when the chain is broken", body = Res<ValidationReport>),
+    ),
+)]
 pub async fn validate_chain(state: web::Data<AppState>) -> impl Responder {
-    let bc = state.blockchain.lock().unwrap();
-    match bc.validate() {
-        Ok(_)  => ok("chain is valid", true),
-        Err(e) => ok(&e, false),
+    let (height, result) = state.chain.query(|bc| (bc.chain.len().saturating_sub(1), bc.validate())).await;
+    state.status.validated(result.clone().map_err(|e| e.to_string()));
+    match result {
+        Ok(_)  => ok("chain is valid", ValidationReport { valid: true, height, error: None }),
+        Err(e) => HttpResponse::Ok().json(Res {
+            ok:         false,
+            message:    e.to_string(),
+            data:       Some(ValidationReport { valid: false, height, error: Some(e.to_string()) }),
+            error_code: Some(e.code().to_string()),
+        }),
+    }
+}
+
+// GET /health
+// always 200 while the process is serving — for liveness probes
+#[utoipa::path(get, path = "/health", tag = "node",
+    responses(
+        (status = 200, description = "alive — always 200 while serving", body = Res<HealthReport>),
+    ),
+)]
+pub async fn health(state: web::Data<AppState>) -> impl Responder {
+    let (height, mempool_size) = state.chain.query(|bc| (bc.chain.len().saturating_sub(1), bc.mempool.len())).await;
+    ok("alive", HealthReport {
+        uptime_secs:  state.status.uptime_secs(),
+        version:      env!("CARGO_PKG_VERSION"),
+        height,
+        mempool_size,
+    })
+}
+
+// GET /ready
+// 200 once the chain is loaded and validated, 503 NOT_READY while starting or degraded
+#[utoipa::path(get, path = "/ready", tag = "node",
+    responses(
+        (status = 200, description = "chain loaded and healthy", body = Res<Readiness>),
+        (status = 503, description = "starting or degraded (NOT_READY); data is the readiness report", body = ApiError),
+    ),
+)]
+pub async fn ready(state: web::Data<AppState>) -> impl Responder {
+    let readiness = state.status.readiness();
+    if readiness.is_ready() {
+        return ok("ready", readiness);
+    }
+    HttpResponse::ServiceUnavailable().json(Res {
+        ok:         false,
+        message:    format!("node is {}", readiness.status),
+        data:       Some(readiness),
+        error_code: Some("NOT_READY".to_string()),
+    })
+}
+
+// GET /audit/rejections?limit=
+// what this node turned away and why — transactions, blocks and chains, newest first
+#[utoipa::path(get, path = "/audit/rejections", tag = "node",
+    params(AuditQuery),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "recent rejections, and counts by error code since startup", body = Res<RejectionsReport>),
+        (status = 401, description = "no X-Api-Key (API_KEY_REQUIRED)", body = ApiError),
+        (status = 403, description = "wrong X-Api-Key (BAD_API_KEY)", body = ApiError),
+    ),
+)]
+pub async fn get_rejections(
+    _admin: Admin,
+    state: web::Data<AppState>,
+    query: web::Query<AuditQuery>,
+) -> impl Responder {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+    ok("recent rejections", RejectionsReport {
+        capacity:   state.rejections.capacity(),
+        by_reason:  state.rejections.counts(),
+        rejections: state.rejections.recent(limit),
+    })
+}
+
+// GET /metrics
+// Prometheus' text format, not the envelope
+#[utoipa::path(get, path = "/metrics", tag = "node",
+    responses(
+        (status = 200, description = "rejections by error code, as Prometheus counters", content_type = "text/plain", body = String),
+    ),
+)]
+pub async fn metrics(state: web::Data<AppState>) -> impl Responder {
+    let mut out = String::new();
+    state.rejections.write_metrics(&mut out);
+    HttpResponse::Ok().content_type("text/plain; version=0.0.4").body(out)
+}
+
+// POST /peers
+// { url } — only registered if it answers /chain/head, isn't this node, and has our genesis block
+#[utoipa::path(post, path = "/peers", tag = "peers",
+    request_body = PeerRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "registered", body = Res<Peer>),
+        (status = 400, description = "bad URL", body = ApiError),
+        (status = 409, description = "already registered or this node", body = ApiError),
+        (status = 422, description = "different genesis", body = ApiError),
+        (status = 502, description = "unreachable or not a node", body = ApiError),
+        (status = 401, description = "no X-Api-Key (API_KEY_REQUIRED)", body = ApiError),
+        (status = 403, description = "wrong X-Api-Key (BAD_API_KEY)", body = ApiError),
+    ),
+)]
+pub async fn register_peer(
+    _admin: Admin,
+    state: web::Data<AppState>,
+    body: web::Json<PeerRequest>,
+) -> impl Responder {
+    match add_peer(&state, &body.url).await {
+        Ok(peer) => ok("peer registered", peer),
+        Err(e)   => peer_err(&e),
     }
-}
\ No newline at end of file
+}
+
+/// Probe `url` and add it to the peer set — POST /peers, and the node's
+/// --peers list at startup.
+pub async fn add_peer(state: &AppState, url: &str) -> Result<Peer, PeerError> {
+    let url = normalize_url(url)?;
+    let (client, node_id) = {
+        let peers = state.peers.locked();
+        if peers.contains(&url) {
+            return Err(PeerError::AlreadyRegistered(url));
+        }
+        (peers.client(), peers.node_id())
+    };
+    // a chain always has its genesis block
+    let genesis_hash = state.chain.query(|bc| bc.chain[0].hash.clone()).await;
+    // no lock held while we wait on the network
+    let head = probe(&client, &url, &genesis_hash, &node_id).await?;
+    let mut peers = state.peers.locked();
+    peers.insert(url, head.node_id).cloned()
+}
+
+// GET /peers
+#[utoipa::path(get, path = "/peers", tag = "peers",
+    responses(
+        (status = 200, description = "known peers", body = Res<Vec<Peer>>),
+    ),
+)]
+pub async fn list_peers(state: web::Data<AppState>) -> impl Responder {
+    let peers = state.peers.locked();
+    ok("known peers", peers.list())
+}
+
+// POST /webhooks
+// { url, events: ["block", "tx"], address_filter? } — the filter narrows tx
+// events to ones where that address sends or receives; block events still fire
+#[utoipa::path(post, path = "/webhooks", tag = "webhooks",
+    request_body = WebhookRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "registered", body = Res<Webhook>),
+        (status = 400, description = "bad URL, no events or bad address filter", body = ApiError),
+        (status = 401, description = "no X-Api-Key (API_KEY_REQUIRED)", body = ApiError),
+        (status = 403, description = "wrong X-Api-Key (BAD_API_KEY)", body = ApiError),
+    ),
+)]
+pub async fn register_webhook(
+    _admin: Admin,
+    state: web::Data<AppState>,
+    body: web::Json<WebhookRequest>,
+) -> impl Responder {
+    let body = body.into_inner();
+    let mut registry = state.webhooks.registry.locked();
+    match registry.register(&body.url, body.events, body.address_filter) {
+        Ok(hook) => ok("webhook registered", hook),
+        Err(e)   => webhook_err(&e),
+    }
+}
+
+// GET /webhooks
+#[utoipa::path(get, path = "/webhooks", tag = "webhooks",
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "registered webhooks", body = Res<Vec<Webhook>>),
+        (status = 401, description = "no X-Api-Key (API_KEY_REQUIRED)", body = ApiError),
+        (status = 403, description = "wrong X-Api-Key (BAD_API_KEY)", body = ApiError),
+    ),
+)]
+pub async fn list_webhooks(_admin: Admin, state: web::Data<AppState>) -> impl Responder {
+    let registry = state.webhooks.registry.locked();
+    ok("registered webhooks", registry.list())
+}
+
+// DELETE /webhooks/:id
+#[utoipa::path(delete, path = "/webhooks/{id}", tag = "webhooks",
+    params(("id" = String, Path, description = "webhook id")),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "the removed webhook", body = Res<Webhook>),
+        (status = 404, description = "unknown id", body = ApiError),
+        (status = 401, description = "no X-Api-Key (API_KEY_REQUIRED)", body = ApiError),
+        (status = 403, description = "wrong X-Api-Key (BAD_API_KEY)", body = ApiError),
+    ),
+)]
+pub async fn delete_webhook(
+    _admin: Admin,
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> impl Responder {
+    let mut registry = state.webhooks.registry.locked();
+    match registry.remove(&path.into_inner()) {
+        Ok(hook) => ok("webhook removed", hook),
+        Err(e)   => webhook_err(&e),
+    }
+}
@@ -0,0 +1,193 @@
+// Audit — what this node turned away, so "my transaction disappeared" has an
+// answer. Every transaction add_transaction() refused, block try_append_block()
+// refused and chain replace_chain() refused goes into a ring buffer in memory,
+// newest first out. GET /audit/rejections lists them, /metrics counts them by
+// error code. Recording keeps the ChainError as is; it's only turned into text
+// when someone asks.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::Mutex;
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::clock::{Clock, SystemClock};
+use crate::error::ChainError;
+use crate::util::LockExt;
+
+/// Rejections kept when AUDIT_LOG_CAPACITY isn't set.
+pub const DEFAULT_AUDIT_CAPACITY: usize = 1000;
+
+/// Who sent what was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Api,   // a client of this node's API
+    Peer,  // a peer's announcement, or a chain synced from one
+}
+
+/// What was rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Rejected {
+    Transaction,
+    Block,
+    Chain,
+}
+
+struct Rejection {
+    at:     u64,
+    source: Source,
+    what:   Rejected,
+    id:     Option<String>,  // txid, block hash, or a chain's tip hash
+    error:  ChainError,
+}
+
+/// One rejection, as GET /audit/rejections reports it.
+#[derive(Serialize, ToSchema)]
+pub struct RejectionEntry {
+    pub timestamp:  u64,
+    pub source:     Source,
+    pub rejected:   Rejected,
+    pub id:         Option<String>,
+    pub error_code: &'static str,
+    pub error:      String,
+}
+
+struct Rejections {
+    recent:    VecDeque<Rejection>,
+    by_reason: HashMap<&'static str, u64>,  // every rejection since startup, not just the kept ones
+}
+
+/// The last `capacity` rejections, and a count of all of them by error code.
+/// A capacity of 0 keeps only the counts.
+pub struct RejectionLog {
+    capacity: usize,
+    clock:    Box<dyn Clock>,
+    state:    Mutex<Rejections>,
+}
+
+impl RejectionLog {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_clock(capacity, Box::new(SystemClock))
+    }
+
+    pub fn with_clock(capacity: usize, clock: Box<dyn Clock>) -> Self {
+        RejectionLog {
+            capacity,
+            clock,
+            state: Mutex::new(Rejections { recent: VecDeque::with_capacity(capacity), by_reason: HashMap::new() }),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Note that `what` from `source` was refused with `error`. A peer telling
+    /// us about a transaction we already have is its gossip echoing ours back,
+    /// not a rejection, and isn't recorded.
+    pub fn record(&self, source: Source, what: Rejected, id: Option<String>, error: &ChainError) {
+        if source == Source::Peer && matches!(error, ChainError::AlreadyInMempool | ChainError::AlreadyConfirmed { .. }) {
+            return;
+        }
+        let at = self.clock.now_secs();
+        let mut state = self.state.locked();
+        *state.by_reason.entry(error.code()).or_default() += 1;
+        if self.capacity == 0 {
+            return;
+        }
+        if state.recent.len() == self.capacity {
+            state.recent.pop_front();
+        }
+        state.recent.push_back(Rejection { at, source, what, id, error: error.clone() });
+    }
+
+    /// Up to `limit` of the kept rejections, newest first.
+    pub fn recent(&self, limit: usize) -> Vec<RejectionEntry> {
+        self.state.locked().recent.iter().rev().take(limit).map(|r| RejectionEntry {
+            timestamp:  r.at,
+            source:     r.source,
+            rejected:   r.what,
+            id:         r.id.clone(),
+            error_code: r.error.code(),
+            error:      r.error.to_string(),
+        }).collect()
+    }
+
+    /// How many rejections there have been since startup, by error code.
+    pub fn counts(&self) -> BTreeMap<&'static str, u64> {
+        self.state.locked().by_reason.iter().map(|(&code, &n)| (code, n)).collect()
+    }
+
+    /// The counts in Prometheus' text format, as one counter labelled by reason.
+    pub fn write_metrics(&self, out: &mut String) {
+        out.push_str("# HELP mini_blockchain_rejections_total Transactions, blocks and chains this node refused, by error code.\n");
+        out.push_str("# TYPE mini_blockchain_rejections_total counter\n");
+        for (code, n) in self.counts() {
+            // writing into a String can't fail
+            let _ = writeln!(out, "mini_blockchain_rejections_total{{reason=\"{}\"}} {}", code, n);
+        }
+    }
+}
+
+impl Default for RejectionLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_AUDIT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+
+    fn bad_nonce(got: u64) -> ChainError {
+        ChainError::BadNonce { expected: 0, got }
+    }
+
+    #[test]
+    fn keeps_the_newest_and_counts_them_all() {
+        let log = RejectionLog::with_clock(3, Box::new(FixedClock(42)));
+        for n in 0..5 {
+            log.record(Source::Api, Rejected::Transaction, Some(format!("tx{}", n)), &bad_nonce(n));
+        }
+        log.record(Source::Api, Rejected::Block, None, &ChainError::StaleTip);
+
+        let recent = log.recent(10);
+        let ids: Vec<Option<&str>> = recent.iter().map(|r| r.id.as_deref()).collect();
+        assert_eq!(ids, [None, Some("tx4"), Some("tx3")]);
+        assert_eq!((recent[0].error_code, recent[0].rejected, recent[0].timestamp), ("STALE_TIP", Rejected::Block, 42));
+        assert_eq!(recent[1].error, bad_nonce(4).to_string());
+        assert_eq!(log.recent(1).len(), 1);
+        assert_eq!(log.counts(), BTreeMap::from([("BAD_NONCE", 5), ("STALE_TIP", 1)]));
+    }
+
+    #[test]
+    fn peers_echoing_our_gossip_arent_rejections() {
+        let log = RejectionLog::new(10);
+        log.record(Source::Peer, Rejected::Transaction, None, &ChainError::AlreadyInMempool);
+        assert!(log.recent(10).is_empty() && log.counts().is_empty());
+        // from a client, it's worth knowing about
+        log.record(Source::Api, Rejected::Transaction, None, &ChainError::AlreadyInMempool);
+        assert_eq!(log.recent(10).len(), 1);
+    }
+
+    #[test]
+    fn zero_capacity_only_counts() {
+        let log = RejectionLog::new(0);
+        log.record(Source::Api, Rejected::Block, None, &ChainError::StaleTip);
+        assert!(log.recent(5).is_empty());
+        assert_eq!(log.counts()["STALE_TIP"], 1);
+    }
+
+    #[test]
+    fn metrics_are_one_counter_by_reason() {
+        let log = RejectionLog::new(10);
+        log.record(Source::Api, Rejected::Transaction, None, &bad_nonce(1));
+        log.record(Source::Api, Rejected::Transaction, None, &bad_nonce(2));
+        let mut out = String::new();
+        log.write_metrics(&mut out);
+        assert!(out.contains("# TYPE mini_blockchain_rejections_total counter\n"));
+        assert!(out.ends_with("mini_blockchain_rejections_total{reason=\"BAD_NONCE\"} 2\n"), "{}", out);
+    }
+}
@@ -0,0 +1,87 @@
+// Auth — the admin API key that guards endpoints which change what the node does
+// (mining, rewriting the chain, peers, webhooks). Wallet and miner endpoints like
+// /transaction and /block stay open: their data is signed or proof-of-worked.
+
+use std::future::{ready, Ready};
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use sha2::{Sha256, Digest};
+use tracing::info;
+use crate::api::{AppState, fail};
+
+/// Header the admin key travels in.
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+#[derive(Clone)]
+pub struct ApiKey(String);
+
+impl ApiKey {
+    pub fn new(key: &str) -> Self {
+        ApiKey(key.trim().to_string())
+    }
+
+    /// 32 random bytes, hex.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        ApiKey(hex::encode(bytes))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Constant-time: both sides are hashed first, so neither the length nor
+    /// the first differing byte shows up in the timing.
+    pub fn matches(&self, candidate: &str) -> bool {
+        let ours = Sha256::digest(self.0.as_bytes());
+        let theirs = Sha256::digest(candidate.as_bytes());
+        ours.iter().zip(theirs.iter()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+    }
+}
+
+/// The admin key from `path`, or a new one saved there. A new key is logged
+/// once so the operator can copy it; after that it's only in the file.
+pub fn load_or_create_api_key(path: &str) -> Result<ApiKey, String> {
+    if std::path::Path::new(path).exists() {
+        let key = std::fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read API key {}: {}", path, e))?;
+        if key.trim().is_empty() {
+            return Err(format!("API key file {} is empty", path));
+        }
+        info!(path, "admin API key loaded");
+        return Ok(ApiKey::new(&key));
+    }
+    let key = ApiKey::generate();
+    std::fs::write(path, key.expose())
+        .map_err(|e| format!("Couldn't save API key {}: {}", path, e))?;
+    info!(path, key = key.expose(), "new admin API key — send it as {} on admin endpoints", API_KEY_HEADER);
+    Ok(key)
+}
+
+/// Extractor for admin-only handlers: put `_admin: Admin` first in the argument
+/// list and the handler only runs with the right X-Api-Key. Missing → 401
+/// API_KEY_REQUIRED, wrong → 403 BAD_API_KEY, both in the usual envelope.
+pub struct Admin;
+
+impl FromRequest for Admin {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let key = req.app_data::<web::Data<AppState>>().map(|state| &state.api_key);
+        let given = req.headers().get(API_KEY_HEADER).map(|v| v.to_str().unwrap_or(""));
+        ready(match (key, given) {
+            (Some(key), Some(given)) if key.matches(given) => Ok(Admin),
+            (_, None)    => Err(reject(StatusCode::UNAUTHORIZED, "API_KEY_REQUIRED", "this endpoint needs the admin key in X-Api-Key")),
+            (_, Some(_)) => Err(reject(StatusCode::FORBIDDEN, "BAD_API_KEY", "wrong API key")),
+        })
+    }
+}
+
+fn reject(status: StatusCode, code: &str, message: &'static str) -> actix_web::Error {
+    InternalError::from_response(message, fail(status, code, message)).into()
+}
@@ -0,0 +1,441 @@
+// Command-line client — wallets on this machine, everything else through a
+// node's HTTP API. Private keys never leave: `send` signs locally and submits
+// the raw transaction, the same path as POST /transaction/raw from a browser.
+//
+//   cli wallet new                          encrypted keystore in wallet.json
+//   cli send --to <address> --amount 2.5    sign with it and submit
+//   cli mine --to <address>                 spinner with the live hash rate until it's found
+//   cli chart blocktimes --last 50          sparkline of the gaps between recent blocks
+//   cli --json tx status <txid>             the node's JSON, for scripts
+
+use std::future::Future;
+use std::io::{IsTerminal, Write};
+use std::process::ExitCode;
+use std::time::Duration;
+use clap::{Parser, Subcommand};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+use mini_blockchain::auth::API_KEY_HEADER;
+use mini_blockchain::idempotency::IDEMPOTENCY_KEY_HEADER;
+use mini_blockchain::keystore::{Keystore, KeystoreError};
+use mini_blockchain::transaction::{Transaction, NITS_PER_TOKEN, parse_tokens};
+use mini_blockchain::wallet::Wallet;
+
+#[derive(Parser)]
+#[command(name = "cli", version, about = "Wallet and node client for mini-blockchain")]
+struct Cli {
+    /// Node to talk to
+    #[arg(long, env = "NODE_URL", default_value = "http://localhost:3000", global = true)]
+    node: String,
+
+    /// Print the node's JSON response instead of a table
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create or inspect an encrypted keystore
+    #[command(subcommand)]
+    Wallet(WalletCommand),
+    /// Confirmed, pending and spendable balance of an address
+    Balance { address: String },
+    /// Sign a transfer with a keystore and submit it
+    Send {
+        #[arg(long)]
+        to:       String,
+        /// In tokens, e.g. 2.5
+        #[arg(long)]
+        amount:   String,
+        /// In tokens, paid to the miner
+        #[arg(long, default_value = "0")]
+        fee:      String,
+        #[arg(long, env = "KEYSTORE", default_value = "wallet.json")]
+        keystore: String,
+        /// Defaults to the sender's next nonce, asked from the node
+        #[arg(long)]
+        nonce:    Option<u64>,
+        #[arg(long)]
+        memo:     Option<String>,
+    },
+    /// Mine the mempool into a block, reward paid to --to (needs the admin key)
+    Mine {
+        #[arg(long)]
+        to:      String,
+        #[arg(long, env = "API_KEY")]
+        api_key: Option<String>,
+    },
+    /// The chain
+    #[command(subcommand)]
+    Chain(ChainCommand),
+    /// Transactions
+    #[command(subcommand)]
+    Tx(TxCommand),
+    /// Charts drawn in the terminal
+    #[command(subcommand)]
+    Chart(ChartCommand),
+}
+
+#[derive(Subcommand)]
+enum WalletCommand {
+    /// Generate a wallet and write it, encrypted, to the keystore file
+    New {
+        #[arg(long, env = "KEYSTORE", default_value = "wallet.json")]
+        keystore: String,
+    },
+    /// The keystore's address (no password needed)
+    Address {
+        #[arg(long, env = "KEYSTORE", default_value = "wallet.json")]
+        keystore: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ChainCommand {
+    /// Height, hash and total work of the tip
+    Head,
+}
+
+#[derive(Subcommand)]
+enum TxCommand {
+    /// Pending or confirmed, and how deep
+    Status { txid: String },
+}
+
+#[derive(Subcommand)]
+enum ChartCommand {
+    /// Seconds between the last blocks, and their difficulty, as sparklines
+    Blocktimes {
+        #[arg(long, default_value_t = 100)]
+        last: usize,
+    },
+}
+
+#[derive(Debug, Error)]
+enum CliError {
+    #[error("{message} ({code})")]
+    Api { code: String, message: String },
+    #[error("couldn't reach {url}: {reason}")]
+    Unreachable { url: String, reason: String },
+    #[error(transparent)]
+    Keystore(#[from] KeystoreError),
+    #[error("{0}")]
+    Input(String),
+}
+
+// the node's { ok, message, data, error_code }
+#[derive(Deserialize)]
+struct Envelope {
+    ok:         bool,
+    message:    String,
+    #[serde(default)]
+    data:       Value,
+    error_code: Option<String>,
+}
+
+struct Node {
+    url:    String,
+    client: reqwest::Client,
+    json:   bool,
+}
+
+impl Node {
+    async fn get(&self, path: &str) -> Result<Value, CliError> {
+        self.send(self.client.get(format!("{}{}", self.url, path)), self.json).await
+    }
+
+    // a lookup along the way to another request — never echoed with --json
+    async fn lookup(&self, path: &str) -> Result<Value, CliError> {
+        self.send(self.client.get(format!("{}{}", self.url, path)), false).await
+    }
+
+    async fn post(&self, path: &str, body: &Value, headers: &[(&str, &str)]) -> Result<Value, CliError> {
+        let mut request = self.client.post(format!("{}{}", self.url, path)).json(body);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        self.send(request, self.json).await
+    }
+
+    // Ok(data) when ok is true. With `echo` the envelope is printed either way,
+    // so a script sees error_code too.
+    async fn send(&self, request: reqwest::RequestBuilder, echo: bool) -> Result<Value, CliError> {
+        let unreachable = |e: reqwest::Error| CliError::Unreachable { url: self.url.clone(), reason: e.to_string() };
+        let response = request.send().await.map_err(unreachable)?;
+        let status = response.status();
+        let text = response.text().await.map_err(unreachable)?;
+        let envelope: Envelope = serde_json::from_str(&text).map_err(|_| CliError::Unreachable {
+            url:    self.url.clone(),
+            reason: format!("answered {} with something that isn't the API's JSON", status),
+        })?;
+        if echo {
+            println!("{}", text);
+        }
+        if envelope.ok {
+            Ok(envelope.data)
+        } else {
+            Err(CliError::Api {
+                code:    envelope.error_code.unwrap_or_else(|| status.as_u16().to_string()),
+                message: envelope.message,
+            })
+        }
+    }
+}
+
+// key: value lines with the values lined up
+fn print_table(rows: &[(&str, String)]) {
+    let width = rows.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+    for (key, value) in rows {
+        println!("{:width$}  {}", key, value, width = width);
+    }
+}
+
+// a JSON field as display text — strings without their quotes, null as "-"
+fn field(data: &Value, key: &str) -> String {
+    match &data[key] {
+        Value::Null      => "-".to_string(),
+        Value::String(s) => s.clone(),
+        other            => other.to_string(),
+    }
+}
+
+// WALLET_PASSWORD for scripts, otherwise ask on the terminal
+fn password(prompt: &str, confirm: bool) -> Result<String, CliError> {
+    if let Ok(password) = std::env::var("WALLET_PASSWORD") {
+        return Ok(password);
+    }
+    let read = |p: &str| rpassword::prompt_password(p).map_err(|e| CliError::Input(format!("couldn't read password: {}", e)));
+    let password = read(prompt)?;
+    if password.is_empty() {
+        return Err(CliError::Input("password can't be empty".to_string()));
+    }
+    if confirm && read("Repeat password: ")? != password {
+        return Err(CliError::Input("passwords don't match".to_string()));
+    }
+    Ok(password)
+}
+
+async fn run(cli: Cli) -> Result<(), CliError> {
+    let node = Node {
+        url:    cli.node.trim_end_matches('/').to_string(),
+        client: reqwest::Client::new(),
+        json:   cli.json,
+    };
+
+    match cli.command {
+        Command::Wallet(WalletCommand::New { keystore }) => {
+            if std::path::Path::new(&keystore).exists() {
+                return Err(CliError::Input(format!("{} already exists — pick another --keystore", keystore)));
+            }
+            let password = password("New keystore password: ", true)?;
+            let (wallet, mnemonic) = Wallet::generate_with_mnemonic();
+            Keystore::encrypt(&wallet, &password)?.save(&keystore)?;
+            if cli.json {
+                println!("{}", json!({ "address": wallet.address(), "keystore": keystore, "mnemonic": mnemonic }));
+            } else {
+                print_table(&[
+                    ("address", wallet.address()),
+                    ("keystore", keystore),
+                    ("recovery phrase", mnemonic),
+                ]);
+                println!("\nWrite the recovery phrase down — it restores this wallet if the keystore or its password is lost.");
+            }
+        }
+
+        Command::Wallet(WalletCommand::Address { keystore }) => {
+            let address = Keystore::load(&keystore)?.address;
+            if cli.json {
+                println!("{}", json!({ "address": address }));
+            } else {
+                println!("{}", address);
+            }
+        }
+
+        Command::Balance { address } => {
+            let data = node.get(&format!("/balance/{}", address)).await?;
+            if !cli.json {
+                print_table(&[
+                    ("confirmed", field(&data, "confirmed")),
+                    ("immature", field(&data, "immature")),
+                    ("pending", field(&data, "pending")),
+                    ("spendable", field(&data, "spendable")),
+                ]);
+            }
+        }
+
+        Command::Send { to, amount, fee, keystore, nonce, memo } => {
+            let amount = parse_tokens(&amount, "amount").map_err(CliError::Input)?;
+            let fee = parse_tokens(&fee, "fee").map_err(CliError::Input)?;
+            let store = Keystore::load(&keystore)?;
+            let wallet = store.decrypt(&password(&format!("Password for {}: ", keystore), false)?)?;
+            let from = wallet.address();
+            let nonce = match nonce {
+                Some(n) => n,
+                None    => node.lookup(&format!("/nonce/{}", from)).await?.as_u64()
+                    .ok_or_else(|| CliError::Input("node sent a nonce that isn't a number".to_string()))?,
+            };
+            submit(&node, &wallet, to, amount, fee, nonce, memo).await?;
+        }
+
+        Command::Mine { to, api_key } => {
+            let key = api_key.ok_or_else(|| CliError::Input("mining needs the node's admin key — pass --api-key or set API_KEY".to_string()))?;
+            let (body, headers) = (json!({ "miner_address": to }), [(API_KEY_HEADER, key.as_str())]);
+            let mined = node.post("/mine", &body, &headers);
+            if cli.json {
+                mined.await?;
+            } else {
+                let data = if std::io::stderr().is_terminal() { with_spinner(&node, mined).await? } else { mined.await? };
+                // the finished search is still there for the asking
+                let search = node.lookup("/miner/status").await.unwrap_or_default();
+                print_table(&[
+                    ("mined block", data.to_string()),
+                    ("attempts", field(&search, "attempts")),
+                    ("hash rate", search["hashes_per_sec"].as_f64().map_or("-".to_string(), hash_rate)),
+                ]);
+            }
+        }
+
+        Command::Chain(ChainCommand::Head) => {
+            let data = node.get("/chain/head").await?;
+            if !cli.json {
+                print_table(&[
+                    ("height", field(&data, "index")),
+                    ("hash", field(&data, "hash")),
+                    ("timestamp", field(&data, "timestamp")),
+                    ("transactions", field(&data, "transaction_count")),
+                    ("total work", field(&data, "total_work")),
+                    ("genesis", field(&data, "genesis_hash")),
+                ]);
+            }
+        }
+
+        Command::Tx(TxCommand::Status { txid }) => {
+            let data = node.get(&format!("/transaction/{}", txid)).await?;
+            if !cli.json {
+                let txn = &data["transaction"];
+                let status = if data["block_index"].is_null() { "pending".to_string() } else { "confirmed".to_string() };
+                print_table(&[
+                    ("txid", txid),
+                    ("status", status),
+                    ("block", field(&data, "block_index")),
+                    ("confirmations", field(&data, "confirmations")),
+                    ("from", field(txn, "from")),
+                    ("to", if txn["to"].is_null() { field(txn, "outputs") } else { field(txn, "to") }),
+                    ("amount", field(txn, "amount_tokens")),
+                    ("fee", txn["fee"].as_u64().map_or("-".to_string(), |nits| (nits as f64 / NITS_PER_TOKEN as f64).to_string())),
+                ]);
+            }
+        }
+
+        Command::Chart(ChartCommand::Blocktimes { last }) => {
+            let data = node.get(&format!("/stats/blocks?last={}", last)).await?;
+            if !cli.json {
+                print_block_times(data.as_array().map(Vec::as_slice).unwrap_or_default());
+            }
+        }
+    }
+    Ok(())
+}
+
+// Wait on `request`, meanwhile polling /miner/status and redrawing a spinner
+// with the search's attempts and hash rate on stderr.
+async fn with_spinner(node: &Node, request: impl Future<Output = Result<Value, CliError>>) -> Result<Value, CliError> {
+    const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+    tokio::pin!(request);
+    let mut tick = tokio::time::interval(Duration::from_millis(200));
+    let mut frames = FRAMES.iter().cycle();
+    let result = loop {
+        tokio::select! {
+            result = &mut request => break result,
+            _ = tick.tick() => {
+                // a poll that fails just leaves the last line up
+                let Ok(search) = node.lookup("/miner/status").await else { continue };
+                if search["mining"] == true {
+                    let rate = search["hashes_per_sec"].as_f64().map_or("-".to_string(), hash_rate);
+                    eprint!("\r\x1b[2K{} mining — {} attempts, {}", frames.next().unwrap_or(&' '), field(&search, "attempts"), rate);
+                    let _ = std::io::stderr().flush();
+                }
+            }
+        }
+    };
+    eprint!("\r\x1b[2K");
+    result
+}
+
+// the gaps and difficulties as sparklines over a summary; genesis has no gap, so it's left out
+fn print_block_times(blocks: &[Value]) {
+    let mined: Vec<&Value> = blocks.iter().filter(|b| !b["secs_since_previous"].is_null()).collect();
+    let (Some(first), Some(last)) = (mined.first(), mined.last()) else {
+        println!("nothing mined yet — no block times to chart");
+        return;
+    };
+    let number = |b: &Value, key: &str| b[key].as_u64().unwrap_or(0);
+    let gaps: Vec<u64> = mined.iter().map(|b| number(b, "secs_since_previous")).collect();
+    let bits: Vec<u64> = mined.iter().map(|b| number(b, "difficulty")).collect();
+    let clamped = mined.iter().filter(|b| b["clamped"] == true).count();
+    let range = |v: &[u64]| (v.iter().min().copied().unwrap_or(0), v.iter().max().copied().unwrap_or(0));
+    let ((fastest, slowest), (lowest, highest)) = (range(&gaps), range(&bits));
+    print_table(&[
+        ("block time", sparkline(&gaps)),
+        ("difficulty", sparkline(&bits)),
+    ]);
+    println!();
+    print_table(&[
+        ("blocks", format!("#{} to #{}", field(first, "index"), field(last, "index"))),
+        ("block time", format!("min {}s, avg {:.1}s, max {}s",
+            fastest, gaps.iter().sum::<u64>() as f64 / gaps.len() as f64, slowest)),
+        ("difficulty", format!("{} to {} bits", lowest, highest)),
+        ("clamped", format!("{} stamped before their parent", clamped)),
+    ]);
+}
+
+// one bar per value, scaled from the smallest to the largest
+fn sparkline(values: &[u64]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let (lo, hi) = (values.iter().min().copied().unwrap_or(0), values.iter().max().copied().unwrap_or(0));
+    values.iter().map(|&v| match hi - lo {
+        0     => BARS[0],
+        range => BARS[((v - lo) * 7 / range) as usize],
+    }).collect()
+}
+
+// 1234567.0 → "1.23 MH/s"
+fn hash_rate(per_sec: f64) -> String {
+    match per_sec {
+        r if r >= 1e9 => format!("{:.2} GH/s", r / 1e9),
+        r if r >= 1e6 => format!("{:.2} MH/s", r / 1e6),
+        r if r >= 1e3 => format!("{:.2} kH/s", r / 1e3),
+        r             => format!("{:.0} H/s", r),
+    }
+}
+
+// sign locally and POST the raw hex. The txid doubles as the Idempotency-Key,
+// so running the same send again after a timeout can't pay twice.
+async fn submit(node: &Node, wallet: &Wallet, to: String, amount: u64, fee: u64, nonce: u64, memo: Option<String>) -> Result<(), CliError> {
+    let mut txn = Transaction::new_nits(wallet.address(), to, amount);
+    txn.fee = fee;
+    txn.nonce = nonce;
+    txn.memo = memo;
+    txn.sign(wallet).map_err(CliError::Input)?;
+    let txid = txn.id();
+    let data = node.post("/transaction/raw", &json!({ "raw": txn.to_hex().map_err(|e| CliError::Input(e.to_string()))? }), &[(IDEMPOTENCY_KEY_HEADER, &txid)]).await?;
+    if !node.json {
+        print_table(&[("submitted", data.as_str().unwrap_or(&txid).to_string()), ("nonce", nonce.to_string())]);
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run(Cli::parse()).await {
+        Ok(())  => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
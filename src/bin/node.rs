@@ -0,0 +1,188 @@
+// Node — the server, configured from flags and/or a TOML file rather than
+// env vars. A flag beats the same setting in the file; a setting in neither
+// gets its default. The admin key, rate limit, idempotency window and audit
+// log size still come from the env vars main.rs reads.
+//
+//   node --bind 0.0.0.0:8080 --difficulty-bits 16 --data-dir ./chain-data
+//   node --config node.toml --peers http://10.0.0.2:3000,http://10.0.0.3:3000
+//
+// node.toml takes the same names:
+//
+//   bind            = "0.0.0.0:8080"
+//   difficulty_bits = 16             # or difficulty = "0000", the old prefix form
+//   data_dir        = "./chain-data"
+//   reward          = 50             # or "2.5" — fractions as a string
+//   peers      = ["http://10.0.0.2:3000"]
+//   allow_empty_blocks = true
+//   storage    = "sqlite"            # chain.sqlite instead of chain.json
+//   prune_depth = 1000               # headers only for blocks deeper than this
+//   compress   = true                # chain.json.zst instead of chain.json
+
+use std::path::Path;
+use std::process::ExitCode;
+use clap::Parser;
+use serde::Deserialize;
+use mini_blockchain::block::{check_difficulty, difficulty_from_prefix};
+use mini_blockchain::blockchain::MIN_PRUNE_DEPTH;
+use mini_blockchain::server::{self, NodeConfig};
+use mini_blockchain::transaction::parse_tokens;
+
+#[derive(Parser)]
+#[command(name = "node", version, about = "Run a mini-blockchain node")]
+struct Args {
+    /// TOML file with any of the settings below
+    #[arg(long)]
+    config:             Option<String>,
+    /// Address to listen on [default: 0.0.0.0:3000]
+    #[arg(long)]
+    bind:               Option<String>,
+    /// Leading zero bits a new chain's block hashes need [default: 4]
+    #[arg(long, conflicts_with = "difficulty")]
+    difficulty_bits:    Option<u32>,
+    /// The same as a run of hex zeros, 4 bits each — "0000" is 16 bits
+    #[arg(long)]
+    difficulty:         Option<String>,
+    /// Where chain.json (or chain.json.zst), node.key and api.key live [default: ./chain-data]
+    #[arg(long)]
+    data_dir:           Option<String>,
+    /// A new chain's block reward in tokens [default: 50]
+    #[arg(long)]
+    reward:             Option<String>,
+    /// Peers to register at startup, comma-separated
+    #[arg(long, value_delimiter = ',')]
+    peers:              Option<Vec<String>>,
+    /// Mine coinbase-only blocks when the mempool is empty
+    #[arg(long)]
+    allow_empty_blocks: bool,
+    /// json or sqlite [default: json]
+    #[arg(long)]
+    storage:            Option<String>,
+    /// Keep only headers for blocks more than this many below the tip [default: keep everything]
+    #[arg(long)]
+    prune_depth:        Option<usize>,
+    /// Keep the chain file zstd-compressed, as chain.json.zst
+    #[arg(long)]
+    compress:           bool,
+    /// json or pretty
+    #[arg(long, env = "LOG_FORMAT", default_value = "pretty")]
+    log_format:         String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    bind:               Option<String>,
+    difficulty_bits:    Option<u32>,
+    difficulty:         Option<String>,
+    data_dir:           Option<String>,
+    reward:             Option<Tokens>,
+    peers:              Option<Vec<String>>,
+    allow_empty_blocks: Option<bool>,
+    storage:            Option<String>,
+    prune_depth:        Option<usize>,
+    compress:           Option<bool>,
+}
+
+// TOML has no decimal type, and rewards never go through f64
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Tokens {
+    Whole(u64),
+    Text(String),
+}
+
+impl Tokens {
+    fn text(self) -> String {
+        match self {
+            Tokens::Whole(n) => n.to_string(),
+            Tokens::Text(s)  => s,
+        }
+    }
+}
+
+fn read_config(path: &str) -> Result<FileConfig, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("couldn't read {}: {}", path, e))?;
+    toml::from_str(&text).map_err(|e| format!("{} is not a valid config: {}", path, e))
+}
+
+fn prefix_bits(prefix: &str) -> Result<u32, String> {
+    difficulty_from_prefix(prefix).map_err(|e| format!("difficulty {:?} is not usable — {}", prefix, e))
+}
+
+// flags over the file over the defaults
+fn resolve(args: Args) -> Result<NodeConfig, String> {
+    let file = match &args.config {
+        Some(path) => read_config(path)?,
+        None       => FileConfig::default(),
+    };
+
+    if file.difficulty_bits.is_some() && file.difficulty.is_some() {
+        return Err("set difficulty_bits or difficulty in the config, not both".to_string());
+    }
+    let difficulty = match (args.difficulty_bits, args.difficulty) {
+        (Some(bits), _)     => Some(bits),
+        (None, Some(zeros)) => Some(prefix_bits(&zeros)?),
+        (None, None)        => match (file.difficulty_bits, file.difficulty) {
+            (Some(bits), _)  => Some(bits),
+            (_, Some(zeros)) => Some(prefix_bits(&zeros)?),
+            (None, None)     => None,
+        },
+    };
+    if let Some(bits) = difficulty {
+        check_difficulty(bits).map_err(|e| format!("difficulty {} bits is not usable — {}", bits, e))?;
+    }
+    let reward = match args.reward.or(file.reward.map(Tokens::text)) {
+        Some(text) => Some(parse_tokens(&text, "reward")?),
+        None       => None,
+    };
+
+    let data_dir = args.data_dir.or(file.data_dir).unwrap_or_else(|| "./chain-data".to_string());
+    std::fs::create_dir_all(&data_dir).map_err(|e| format!("couldn't create {}: {}", data_dir, e))?;
+    let in_data_dir = |name: &str| Path::new(&data_dir).join(name).to_string_lossy().into_owned();
+
+    let mut config = NodeConfig::from_env()?;
+    config.bind          = args.bind.or(file.bind).unwrap_or_else(|| "0.0.0.0:3000".to_string());
+    config.chain_file    = match args.compress || file.compress.unwrap_or(false) {
+        true  => in_data_dir("chain.json.zst"),
+        false => in_data_dir("chain.json"),
+    };
+    config.node_key_file = in_data_dir("node.key");
+    config.api_key_file  = in_data_dir("api.key");
+    config.difficulty    = difficulty;
+    config.reward        = reward;
+    config.peers         = args.peers.or(file.peers).unwrap_or_default();
+    // a bare flag can only turn it on; without it the file decides, then ALLOW_EMPTY_BLOCKS
+    config.allow_empty_blocks = args.allow_empty_blocks || file.allow_empty_blocks.unwrap_or(config.allow_empty_blocks);
+    if let Some(storage) = args.storage.or(file.storage) {
+        config.storage = storage.parse()?;
+    }
+    config.prune_depth = args.prune_depth.or(file.prune_depth);
+    if let Some(depth) = config.prune_depth.filter(|&d| d < MIN_PRUNE_DEPTH) {
+        return Err(format!("prune depth {} is too shallow — at least {} blocks stay whole", depth, MIN_PRUNE_DEPTH));
+    }
+    Ok(config)
+}
+
+#[actix_web::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    if let Err(e) = server::init_logging(&args.log_format) {
+        eprintln!("error: {}", e);
+        return ExitCode::FAILURE;
+    }
+    let config = match resolve(args) {
+        Ok(c)  => c,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match server::run(config).await {
+        Ok(())  => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
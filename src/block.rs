@@ -1,73 +1,515 @@
 // Block — holds a list of transactions, mines using Proof of Work.
-// Hash covers all transaction data so any tampering is detected.
+// Hash covers all transaction data so any tampering is detected. Difficulty
+// is a count of leading zero bits: read as a 256-bit big-endian number, the
+// hash has to be at or below the target that count implies.
 
 use sha2::{Sha256, Digest};
-use std::time::{SystemTime, UNIX_EPOCH};
-use serde::{Serialize, Deserialize};
-use crate::transaction::Transaction;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use std::fmt;
+use serde::{Serialize, Serializer, Deserialize};
+use serde::ser::SerializeStruct;
+use utoipa::{PartialSchema, ToSchema};
+use utoipa::openapi::{RefOr, schema::Schema};
+use crate::canonical::{CanonicalEncode, DOMAIN_HEADER};
+use crate::transaction::{Transaction, NITS_PER_TOKEN};
+use crate::error::ChainError;
+use crate::util::{short, LockExt};
+use crate::merkle::{merkle_root, merkle_proof, MerkleProof};
+use tracing::{debug, info};
 
 pub const GENESIS_PREV_HASH: &str =
     "0000000000000000000000000000000000000000000000000000000000000000";
 
-#[derive(Serialize, Deserialize, Clone)]
+// mining threads add to a shared hash counter this often, not on every hash
+const HASH_COUNT_BATCH: u64 = 1024;
+
+/// A mining progress callback hears about it every this many attempts.
+pub const PROGRESS_INTERVAL: u64 = 16_384;
+
+/// Leading zero bits a difficulty can ask for — all of them.
+pub const MAX_DIFFICULTY_BITS: u32 = 256;
+
+/// Nonces tried under one extra_nonce when nothing smaller is asked for:
+/// the whole u64 range, so extra_nonce only moves once that runs out.
+pub const FULL_NONCE_SPACE: u64 = u64::MAX;
+
+/// Why mining stopped without finding a hash.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MineAborted {
+    Cancelled,
+    NonceLimit(u64),
+    InvalidDifficulty(String),
+}
+
+impl fmt::Display for MineAborted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MineAborted::Cancelled              => write!(f, "Mining cancelled"),
+            MineAborted::NonceLimit(n)          => write!(f, "No valid hash within {} nonces", n),
+            MineAborted::InvalidDifficulty(msg) => write!(f, "Invalid difficulty: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MineAborted {}
+
+/// Called with the attempts made so far and the time since the search began.
+pub type Progress<'a> = &'a (dyn Fn(u64, Duration) + Sync);
+
+/// What finding a block's nonce took on this node. Kept on the Block but
+/// never hashed, saved or sent to peers — it's only true here.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MiningEffort {
+    pub attempts: u64,
+    pub secs:     f64,
+}
+
+/// The nonce search under way on this node, or else the last one — what
+/// GET /miner/status reports whoever started it. Shared like cancel_mining.
+#[derive(Serialize, Clone, Debug, Default, ToSchema)]
+pub struct MiningStatus {
+    pub mining:         bool,         // a nonce search is under way right now
+    pub attempts:       u64,          // hashes tried so far
+    pub hashes_per_sec: f64,
+    pub started_at:     Option<u64>,  // unix seconds
+}
+
+impl MiningStatus {
+    fn update(&mut self, attempts: u64, elapsed: Duration) {
+        self.attempts = attempts;
+        let secs = elapsed.as_secs_f64();
+        self.hashes_per_sec = if secs > 0.0 { attempts as f64 / secs } else { 0.0 };
+    }
+}
+
+/// Zero bits would accept any hash, and a hash only has 256. Reject
+/// either before mining starts.
+pub fn check_difficulty(bits: u32) -> Result<(), MineAborted> {
+    if bits == 0 {
+        return Err(MineAborted::InvalidDifficulty("needs at least 1 bit".to_string()));
+    }
+    if bits > MAX_DIFFICULTY_BITS {
+        return Err(MineAborted::InvalidDifficulty(format!("{} bits is more than a hash has", bits)));
+    }
+    Ok(())
+}
+
+/// The bit count an old-style hex prefix like "0000" stood for — 4 per zero.
+/// Only zeros convert; a prefix such as "00a" never had a bit count.
+pub fn difficulty_from_prefix(prefix: &str) -> Result<u32, MineAborted> {
+    if prefix.is_empty() || !prefix.chars().all(|c| c == '0') {
+        return Err(MineAborted::InvalidDifficulty(format!("{:?} is not a run of zeros", prefix)));
+    }
+    let bits = u32::try_from(prefix.len()).unwrap_or(u32::MAX).saturating_mul(4);
+    check_difficulty(bits)?;
+    Ok(bits)
+}
+
+/// The highest hash that meets `bits`: 2^(256 - bits) - 1, big-endian.
+pub fn target(bits: u32) -> [u8; 32] {
+    let mut target = [0xffu8; 32];
+    let bits = bits.min(MAX_DIFFICULTY_BITS) as usize;
+    target[..bits / 8].fill(0);
+    if !bits.is_multiple_of(8) {
+        target[bits / 8] = 0xff >> (bits % 8);
+    }
+    target
+}
+
+/// A hex hash back to its bytes, or None if it isn't 32 bytes of hex.
+pub fn hash_bytes(hash: &str) -> Option<[u8; 32]> {
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(hash, &mut bytes).ok().map(|_| bytes)
+}
+
+// numeric comparison: for equal-length big-endian arrays, byte order is number order
+fn meets_target(hash: &str, bits: u32) -> bool {
+    bits > 0 && hash_bytes(hash).is_some_and(|h| h <= target(bits))
+}
+
+// Serialize is written out below, to add the derived economics fields.
+#[derive(Deserialize, Clone)]
 #[allow(dead_code)]
 pub struct Block {
     pub index:         u32,
     pub timestamp:     u64,
     pub transactions:  Vec<Transaction>,
+    pub merkle_root:   String,
     pub previous_hash: String,
     pub nonce:         u64,
+    #[serde(default)]
+    pub extra_nonce:   u64,     // bumped, and the nonce restarted, each time the nonce space runs out
+    pub difficulty:    u32,     // leading zero bits in force when this block was mined
     pub hash:          String,
     pub miner:         String,
+    #[serde(skip)]
+    pub effort:        Option<MiningEffort>,  // set when this node mined the block
+}
+
+/// Everything a block commits to except the transactions themselves — enough to
+/// check proof of work and linkage without downloading bodies. Transactions are
+/// covered through merkle_root.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct BlockHeader {
+    pub index:         u32,
+    pub timestamp:     u64,
+    pub previous_hash: String,
+    pub merkle_root:   String,
+    pub miner:         String,  // part of the hashed header, so it has to travel with it
+    pub nonce:         u64,
+    #[serde(default)]
+    pub extra_nonce:   u64,
+    pub difficulty:    u32,
+    pub hash:          String,
+}
+
+impl BlockHeader {
+    pub fn calculate_hash(&self) -> String {
+        header_hash(self.index, self.timestamp, &self.merkle_root, &self.previous_hash,
+            &self.miner, self.difficulty, self.extra_nonce, self.nonce)
+    }
+
+    /// Same as Block::work().
+    pub fn work(&self) -> u128 {
+        1u128.checked_shl(self.difficulty).unwrap_or(u128::MAX)
+    }
+
+    pub fn meets_difficulty(&self) -> bool {
+        meets_target(&self.hash, self.difficulty)
+    }
+}
+
+/// Check a run of headers that should sit on top of the block hashed `parent_hash`:
+/// each one's hash is genuine, meets its difficulty and links to the one before.
+/// Returns their combined work. Difficulty schedule and timestamps need the
+/// full chain, so those are left to validate().
+pub fn validate_headers(parent_hash: &str, headers: &[BlockHeader]) -> Result<u128, ChainError> {
+    let mut parent = parent_hash;
+    let mut work = 0u128;
+    for header in headers {
+        let index = header.index;
+        if header.hash != header.calculate_hash() {
+            return Err(ChainError::HashMismatch { index });
+        }
+        if header.previous_hash != parent {
+            return Err(ChainError::BrokenLink { index });
+        }
+        if !header.meets_difficulty() {
+            return Err(ChainError::DifficultyNotMet { index, difficulty: header.difficulty });
+        }
+        work = work.saturating_add(header.work());
+        parent = &header.hash;
+    }
+    Ok(work)
+}
+
+/// A header ready for the nonce search. Everything but the nonce is encoded
+/// and fed to SHA-256 once, and each attempt starts from a copy of that
+/// midstate, so trying a nonce hashes 8 more bytes and allocates nothing.
+#[derive(Clone)]
+pub struct MiningInput {
+    midstate: Sha256,
+}
+
+impl MiningInput {
+    // the one place the header layout is fixed — Block and BlockHeader both hash through here.
+    // The nonce goes last, the only field that changes between most mining attempts.
+    fn new(index: u32, timestamp: u64, merkle_root: &str, previous_hash: &str,
+           miner: &str, difficulty: u32, extra_nonce: u64) -> Self {
+        let mut buf = Vec::with_capacity(256);
+        DOMAIN_HEADER.encode(&mut buf);
+        index.encode(&mut buf);
+        timestamp.encode(&mut buf);
+        merkle_root.encode(&mut buf);
+        previous_hash.encode(&mut buf);
+        miner.encode(&mut buf);
+        difficulty.encode(&mut buf);
+        extra_nonce.encode(&mut buf);
+        MiningInput { midstate: Sha256::new_with_prefix(&buf) }
+    }
+
+    /// The header's hash with this nonce, as raw bytes.
+    pub fn hash(&self, nonce: u64) -> [u8; 32] {
+        let mut hasher = self.midstate.clone();
+        hasher.update(nonce.to_le_bytes());
+        hasher.finalize().into()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn header_hash(index: u32, timestamp: u64, merkle_root: &str, previous_hash: &str,
+               miner: &str, difficulty: u32, extra_nonce: u64, nonce: u64) -> String {
+    hex::encode(MiningInput::new(index, timestamp, merkle_root, previous_hash, miner, difficulty, extra_nonce)
+        .hash(nonce))
+}
+
+// What every worker of one mine_parallel_with_progress() round shares.
+struct Search<'a> {
+    input:    &'a MiningInput,
+    target:   &'a [u8; 32],
+    cancel:   &'a AtomicBool,
+    hashes:   &'a AtomicU64,  // tried so far, across rounds
+    started:  Instant,
+    progress: Progress<'a>,
+}
+
+impl Search<'_> {
+    // One pass over nonces 0..nonce_space for a single extra_nonce. None if
+    // nothing under it met the target, or `cancel` was set.
+    fn run(&self, threads: u64, nonce_space: u64) -> Option<(u64, [u8; 32])> {
+        let found = AtomicBool::new(false);
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::scope(|scope| {
+            for offset in 0..threads {
+                let found = &found;
+                let tx = tx.clone();
+                scope.spawn(move || {
+                    let mut nonce = offset;
+                    let mut tried = 0u64;
+                    while nonce < nonce_space && !found.load(Ordering::Relaxed) && !self.cancel.load(Ordering::Relaxed) {
+                        let hash = self.input.hash(nonce);
+                        tried += 1;
+                        if tried == HASH_COUNT_BATCH {
+                            self.count(tried);
+                            tried = 0;
+                        }
+                        if &hash <= self.target {
+                            if !found.swap(true, Ordering::Relaxed) {
+                                let _ = tx.send((nonce, hash));
+                            }
+                            break;
+                        }
+                        nonce = match nonce.checked_add(threads) {
+                            Some(n) => n,
+                            None => break,
+                        };
+                    }
+                    self.count(tried);
+                });
+            }
+        });
+        drop(tx);
+        rx.recv().ok()
+    }
+
+    // add a worker's batch to the total, reporting if it crossed a PROGRESS_INTERVAL
+    fn count(&self, tried: u64) {
+        let total = self.hashes.fetch_add(tried, Ordering::Relaxed) + tried;
+        if total / PROGRESS_INTERVAL != (total - tried) / PROGRESS_INTERVAL {
+            (self.progress)(total, self.started.elapsed());
+        }
+    }
 }
 
 impl Block {
-    pub fn new(index: u32, transactions: Vec<Transaction>, previous_hash: String, miner: String) -> Self {
+    /// `timestamp` comes from the caller's Clock, so the same inputs always hash the same.
+    pub fn new(index: u32, transactions: Vec<Transaction>, previous_hash: String, miner: String, timestamp: u64) -> Self {
+        let merkle_root = Self::compute_merkle_root(&transactions);
         Block {
             index,
-            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            timestamp,
             transactions,
+            merkle_root,
             previous_hash,
             nonce: 0,
+            extra_nonce: 0,
+            difficulty: 0,
             hash: String::new(),
             miner,
+            effort: None,
         }
     }
 
-    /// Hash covers every field including all transaction data.
-    /// Change anything in any transaction → completely different hash.
+    /// Merkle root over the txids of the given transactions.
+    pub fn compute_merkle_root(transactions: &[Transaction]) -> String {
+        let txids: Vec<String> = transactions.iter().map(|t| t.id()).collect();
+        merkle_root(&txids)
+    }
+
+    /// Inclusion proof for one of this block's transactions, None if it isn't here.
+    pub fn merkle_proof(&self, txid: &str) -> Option<MerkleProof> {
+        let txids: Vec<String> = self.transactions.iter().map(|t| t.id()).collect();
+        let index = txids.iter().position(|id| id == txid)?;
+        merkle_proof(&txids, index)
+    }
+
+    /// Hash covers the header — and through merkle_root, every transaction
+    /// (txids include signatures). Change anything → completely different hash.
     pub fn calculate_hash(&self) -> String {
-        let txn_data: String = self.transactions
-            .iter()
-            .map(|t| format!("{}|{}|{}", t.from, t.to, t.amount))
-            .collect::<Vec<_>>()
-            .join("::");
+        header_hash(self.index, self.timestamp, &self.merkle_root, &self.previous_hash,
+            &self.miner, self.difficulty, self.extra_nonce, self.nonce)
+    }
+
+    /// The header minus the nonce, encoded and hashed as far as it goes —
+    /// mine() and mine_parallel() set difficulty first, then search from this,
+    /// preparing it again whenever they bump extra_nonce.
+    pub fn prepare_mining_input(&self) -> MiningInput {
+        MiningInput::new(self.index, self.timestamp, &self.merkle_root, &self.previous_hash,
+            &self.miner, self.difficulty, self.extra_nonce)
+    }
 
-        let input = format!("{}::{}::{}::{}::{}",
-            self.index, self.timestamp, txn_data, self.previous_hash, self.nonce);
+    // the nonce space under this extra_nonce is used up: move to the next one
+    fn roll_extra_nonce(&mut self) -> Result<(), MineAborted> {
+        self.extra_nonce = self.extra_nonce.checked_add(1).ok_or(MineAborted::NonceLimit(u64::MAX))?;
+        self.nonce = 0;
+        debug!(block = self.index, extra_nonce = self.extra_nonce, "nonce space exhausted, extra_nonce bumped");
+        Ok(())
+    }
 
-        let mut hasher = Sha256::new();
-        hasher.update(input.as_bytes());
-        format!("{:x}", hasher.finalize())
+    /// This block minus its transactions.
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            index:         self.index,
+            timestamp:     self.timestamp,
+            previous_hash: self.previous_hash.clone(),
+            merkle_root:   self.merkle_root.clone(),
+            miner:         self.miner.clone(),
+            nonce:         self.nonce,
+            extra_nonce:   self.extra_nonce,
+            difficulty:    self.difficulty,
+            hash:          self.hash.clone(),
+        }
     }
 
-    /// Proof of Work — increment nonce until hash starts with difficulty_prefix.
-    pub fn mine(&mut self, difficulty_prefix: &str) {
+    /// Proof of Work — increment nonce until the hash has `bits` leading zero bits.
+    /// Should the nonce run out, extra_nonce goes up by one and the nonce starts over.
+    pub fn mine(&mut self, bits: u32) -> Result<(), MineAborted> {
+        self.mine_with_cancel(bits, &AtomicBool::new(false))
+    }
+
+    /// mine(), but gives up as soon as `cancel` is set.
+    pub fn mine_with_cancel(&mut self, bits: u32, cancel: &AtomicBool) -> Result<(), MineAborted> {
+        self.mine_bounded(bits, cancel, u64::MAX, FULL_NONCE_SPACE, &mut |_, _| {})
+    }
+
+    /// mine(), calling `progress` every PROGRESS_INTERVAL attempts.
+    pub fn mine_with_progress(&mut self, bits: u32, mut progress: impl FnMut(u64, Duration)) -> Result<(), MineAborted> {
+        self.mine_bounded(bits, &AtomicBool::new(false), u64::MAX, FULL_NONCE_SPACE, &mut progress)
+    }
+
+    /// mine(), but gives up after trying `max_nonces` nonces.
+    pub fn mine_until(&mut self, bits: u32, max_nonces: u64) -> Result<(), String> {
+        self.mine_bounded(bits, &AtomicBool::new(false), max_nonces, FULL_NONCE_SPACE, &mut |_, _| {})
+            .map_err(|e| e.to_string())
+    }
+
+    /// mine(), but bumps extra_nonce after every `nonce_space` nonces instead
+    /// of only when the u64 runs out.
+    pub fn mine_with_nonce_space(&mut self, bits: u32, nonce_space: u64) -> Result<(), MineAborted> {
+        self.mine_bounded(bits, &AtomicBool::new(false), u64::MAX, nonce_space, &mut |_, _| {})
+    }
+
+    fn mine_bounded(&mut self, bits: u32, cancel: &AtomicBool, max_nonces: u64, nonce_space: u64,
+                    progress: &mut dyn FnMut(u64, Duration)) -> Result<(), MineAborted> {
+        check_difficulty(bits)?;
+        self.difficulty = bits;
+        let target = target(bits);
+        let started = Instant::now();
+        let mut input = self.prepare_mining_input();
+        for attempts in 1..=max_nonces {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(MineAborted::Cancelled);
+            }
+            let hash = input.hash(self.nonce);
+            if hash <= target {
+                self.hash = hex::encode(hash);
+                self.effort = Some(MiningEffort { attempts, secs: started.elapsed().as_secs_f64() });
+                info!(block = self.index, nonce = self.nonce, extra_nonce = self.extra_nonce, attempts,
+                    hash = %short(&self.hash, 16), "block mined");
+                return Ok(());
+            }
+            if attempts.is_multiple_of(PROGRESS_INTERVAL) {
+                progress(attempts, started.elapsed());
+            }
+            match self.nonce.checked_add(1).filter(|&n| n < nonce_space) {
+                Some(n) => self.nonce = n,
+                None    => {
+                    self.roll_extra_nonce()?;
+                    input = self.prepare_mining_input();
+                }
+            }
+        }
+        Err(MineAborted::NonceLimit(max_nonces))
+    }
+
+    /// Same proof of work as mine(), split across `threads` workers.
+    /// Worker i tries nonces i, i+threads, i+2*threads, ... and the first
+    /// one to find a hash flips `found` so the rest stop. Setting `cancel` stops them all.
+    pub fn mine_parallel(&mut self, bits: u32, threads: usize, cancel: &AtomicBool) -> Result<(), MineAborted> {
+        self.mine_parallel_with_progress(bits, threads, FULL_NONCE_SPACE, cancel, &|_, _| {})
+    }
+
+    /// mine_parallel(), calling `progress` from whichever worker takes the
+    /// attempt count past another PROGRESS_INTERVAL. The workers share nonces
+    /// 0..nonce_space; once all of them come up empty, extra_nonce is bumped
+    /// and they go again over a freshly prepared header.
+    pub fn mine_parallel_with_progress(&mut self, bits: u32, threads: usize, nonce_space: u64, cancel: &AtomicBool,
+                                       progress: Progress) -> Result<(), MineAborted> {
+        check_difficulty(bits)?;
+        let threads = threads.max(1) as u64;
+        self.difficulty = bits;
+        let target = target(bits);
+        let hashes = AtomicU64::new(0);
+        let started = Instant::now();
         loop {
-            self.hash = self.calculate_hash();
-            if self.hash.starts_with(difficulty_prefix) {
-                println!("  ⛏️  Block #{} mined  nonce={}  hash={}...",
-                    self.index, self.nonce, &self.hash[..16]);
-                break;
+            let input = self.prepare_mining_input();
+            let search = Search { input: &input, target: &target, cancel, hashes: &hashes, started, progress };
+            if let Some((nonce, hash)) = search.run(threads, nonce_space) {
+                self.nonce = nonce;
+                self.hash = hex::encode(hash);
+                let attempts = hashes.load(Ordering::Relaxed);
+                self.effort = Some(MiningEffort { attempts, secs: started.elapsed().as_secs_f64() });
+                info!(block = self.index, nonce = self.nonce, extra_nonce = self.extra_nonce, attempts,
+                    hash = %short(&self.hash, 16), threads, "block mined");
+                return Ok(());
+            }
+            if cancel.load(Ordering::Relaxed) {
+                return Err(MineAborted::Cancelled);
             }
-            self.nonce += 1;
+            self.roll_extra_nonce()?;
+        }
+    }
+
+    /// mine_parallel_with_progress(), keeping `status` up to date: reset when
+    /// the search starts, attempts and rate as it goes, the totals once it's over.
+    pub fn mine_tracked(&mut self, bits: u32, threads: usize, nonce_space: u64, cancel: &AtomicBool,
+                        status: &Mutex<MiningStatus>) -> Result<(), MineAborted> {
+        *status.locked() = MiningStatus { mining: true, started_at: Some(self.timestamp), ..MiningStatus::default() };
+        let mined = self.mine_parallel_with_progress(bits, threads, nonce_space, cancel,
+            &|attempts, elapsed| status.locked().update(attempts, elapsed));
+        let mut status = status.locked();
+        status.mining = false;
+        if let Some(effort) = self.effort.filter(|_| mined.is_ok()) {
+            status.update(effort.attempts, Duration::from_secs_f64(effort.secs));
         }
+        mined
+    }
+
+    /// Expected hashes it took to mine this block — 2^difficulty.
+    /// Summed along a chain, this is what fork choice compares.
+    pub fn work(&self) -> u128 {
+        1u128.checked_shl(self.difficulty).unwrap_or(u128::MAX)
+    }
+
+    /// Proof of work check — the stored hash is at or below the recorded difficulty's target.
+    pub fn meets_difficulty(&self) -> bool {
+        meets_target(&self.hash, self.difficulty)
     }
 
-    pub fn validate_transactions(&self) -> Result<(), String> {
+    /// The block's own hash check: the stored hash matches the header, and the
+    /// merkle root matches the transactions. Says nothing about the chain around it.
+    pub fn is_intact(&self) -> bool {
+        self.hash == self.calculate_hash() && self.merkle_root == Block::compute_merkle_root(&self.transactions)
+    }
+
+    pub fn validate_transactions(&self) -> Result<(), ChainError> {
         for (i, txn) in self.transactions.iter().enumerate() {
-            txn.validate().map_err(|e| {
-                format!("Block #{} transaction {}: {}", self.index, i, e)
+            txn.validate().map_err(|e| ChainError::InvalidTransaction {
+                index: self.index, position: i, source: Box::new(e),
             })?;
         }
         Ok(())
@@ -78,17 +520,114 @@ impl Block {
         self.validate_transactions().is_ok()
     }
 
-    pub fn display(&self) {
-        let hash_short = if self.hash.len() >= 20 { &self.hash[..20] } else { &self.hash };
-        let prev_short = if self.previous_hash.len() >= 20 { &self.previous_hash[..20] } else { &self.previous_hash };
-        let miner_short = if self.miner.len() >= 12 { &self.miner[..12] } else { &self.miner };
-        println!("┌─ Block #{} ────────────────────────────────", self.index);
-        println!("│  Hash      : {}...", hash_short);
-        println!("│  Prev Hash : {}...", prev_short);
-        println!("│  Miner     : {}...", miner_short);
-        println!("│  Nonce     : {}", self.nonce);
-        println!("│  Txns ({}):", self.transactions.len());
-        for txn in &self.transactions { println!("│    • {}", txn.display()); }
-        println!("└───────────────────────────────────────────");
-    }
-}
\ No newline at end of file
+    // the coinbase, if the last transaction is one, and everything before it
+    fn split_coinbase(&self) -> (Option<&Transaction>, &[Transaction]) {
+        match self.transactions.split_last() {
+            Some((last, rest)) if last.from == "NETWORK" => (Some(last), rest),
+            _                                            => (None, &self.transactions),
+        }
+    }
+
+    /// Fees paid by the block's transactions, in nits — what the miner gets on
+    /// top of the subsidy. Transactions saved before fees existed count as 0.
+    pub fn total_fees(&self) -> u64 {
+        self.split_coinbase().1.iter().fold(0u64, |acc, t| acc.saturating_add(t.fee))
+    }
+
+    /// What the coinbase pays, subsidy and fees together, in nits; 0 without one.
+    pub fn coinbase_amount(&self) -> u64 {
+        self.split_coinbase().0.map_or(0, |coinbase| coinbase.amount())
+    }
+
+    /// Every output of the block's transactions except the coinbase's, in nits.
+    pub fn total_transferred(&self) -> u64 {
+        self.split_coinbase().1.iter().fold(0u64, |acc, t| acc.saturating_add(t.amount()))
+    }
+
+    /// A boxed summary for printing, one line per field and per transaction.
+    pub fn display(&self) -> String {
+        let mut out = format!("┌─ Block #{} ────────────────────────────────\n", self.index);
+        out += &format!("│  Hash      : {}...\n", short(&self.hash, 20));
+        out += &format!("│  Prev Hash : {}...\n", short(&self.previous_hash, 20));
+        out += &format!("│  Miner     : {}...\n", short(&self.miner, 12));
+        out += &format!("│  Nonce     : {}\n", self.nonce);
+        let fees = self.total_fees();
+        out += &format!("│  Reward    : {:.3} + {:.3} fees\n",
+            tokens(self.coinbase_amount().saturating_sub(fees)), tokens(fees));
+        out += &format!("│  Moved     : {:.3}\n", tokens(self.total_transferred()));
+        out += &format!("│  Txns ({}):\n", self.transactions.len());
+        for txn in &self.transactions {
+            out += &format!("│    • {}\n", txn.display());
+        }
+        out += "└───────────────────────────────────────────";
+        out
+    }
+}
+
+fn tokens(nits: u64) -> f64 {
+    nits as f64 / NITS_PER_TOKEN as f64
+}
+
+// The stored fields, then total_fees, coinbase_amount and total_transferred.
+// Those three are worked out from the transactions on the way out and ignored
+// on the way in, so a block reads back the same whether it has them or not.
+impl Serialize for Block {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut st = serializer.serialize_struct("Block", 13)?;
+        st.serialize_field("index", &self.index)?;
+        st.serialize_field("timestamp", &self.timestamp)?;
+        st.serialize_field("transactions", &self.transactions)?;
+        st.serialize_field("merkle_root", &self.merkle_root)?;
+        st.serialize_field("previous_hash", &self.previous_hash)?;
+        st.serialize_field("nonce", &self.nonce)?;
+        st.serialize_field("extra_nonce", &self.extra_nonce)?;
+        st.serialize_field("difficulty", &self.difficulty)?;
+        st.serialize_field("hash", &self.hash)?;
+        st.serialize_field("miner", &self.miner)?;
+        st.serialize_field("total_fees", &self.total_fees())?;
+        st.serialize_field("coinbase_amount", &self.coinbase_amount())?;
+        st.serialize_field("total_transferred", &self.total_transferred())?;
+        st.end()
+    }
+}
+
+// What the hand-written Serialize above produces, for the OpenAPI spec.
+#[derive(ToSchema)]
+#[schema(as = Block)]
+#[allow(dead_code)]
+struct BlockSchema {
+    index:             u32,
+    timestamp:         u64,
+    /// the coinbase, if any, is last
+    transactions:      Vec<Transaction>,
+    merkle_root:       String,
+    previous_hash:     String,
+    nonce:             u64,
+    extra_nonce:       u64,
+    /// leading zero bits
+    difficulty:        u32,
+    hash:              String,
+    miner:             String,
+    /// derived: fees of every non-coinbase transaction, in nits
+    total_fees:        u64,
+    /// derived: subsidy plus fees, in nits
+    coinbase_amount:   u64,
+    /// derived: outputs of every non-coinbase transaction, in nits
+    total_transferred: u64,
+}
+
+impl PartialSchema for Block {
+    fn schema() -> RefOr<Schema> {
+        BlockSchema::schema()
+    }
+}
+
+impl ToSchema for Block {
+    fn name() -> std::borrow::Cow<'static, str> {
+        BlockSchema::name()
+    }
+
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        BlockSchema::schemas(schemas)
+    }
+}
@@ -1,106 +1,2345 @@
 // Blockchain — the chain itself, plus mempool and balance logic.
 
-use crate::block::{Block, GENESIS_PREV_HASH};
-use crate::transaction::{Transaction, NITS_PER_TOKEN};
-#[allow(dead_code)]
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+use crate::canonical::HASH_VERSION;
+use crate::block::{Block, BlockHeader, MiningStatus, GENESIS_PREV_HASH, FULL_NONCE_SPACE, check_difficulty, difficulty_from_prefix, target};
+use crate::transaction::{Transaction, NITS_PER_TOKEN, BURN_ADDRESS};
+use crate::error::ChainError;
+use crate::export::{TRANSACTIONS_CSV_HEADER, write_balances_json, write_transaction_rows};
+use crate::clock::{Clock, SystemClock};
+use crate::mempool::{Mempool, FeeRate, Taken};
+use crate::store::{ChainStore, read_chain_file, write_chain_file};
+use crate::wal::MempoolWal;
+use crate::util::{short, chain_stem, is_valid_address, LockExt};
+use tracing::{debug, debug_span, info, info_span, warn};
+
+// Retargeting never moves the difficulty outside this range, in leading zero bits...
+pub const MIN_DIFFICULTY_BITS: u32 = 4;
+pub const MAX_DIFFICULTY_BITS: u32 = 24;
+// ...nor by more than this many bits (a factor of 4) at once.
+pub const MAX_RETARGET_STEP_BITS: u32 = 2;
+
+// Mempool caps — past either one, newcomers have to outbid the cheapest pending tx.
+pub const DEFAULT_MAX_MEMPOOL_TXS: usize = 5_000;
+pub const DEFAULT_MAX_MEMPOOL_BYTES: usize = 1_000_000;
+
+// Fee estimation looks this many blocks back, and never suggests less than the relay fee.
+pub const FEE_ESTIMATE_WINDOW: usize = 10;
+pub const DEFAULT_MIN_RELAY_FEE: u64 = 1;
+
+// A block's timestamp must be at least the median of this many blocks before it...
+pub const MEDIAN_TIME_SPAN: usize = 11;
+// ...and no further ahead of our clock than this.
+pub const DEFAULT_MAX_FUTURE_SKEW_SECS: u64 = 2 * 60 * 60;
+
+// stats() averages block intervals over this many recent blocks.
+pub const STATS_WINDOW: usize = 20;
+
+// Confirmations a block reward needs before it can be spent, so a short
+// reorg can't pull the rug from under payments made with it.
+pub const DEFAULT_COINBASE_MATURITY: u32 = 5;
+
+// Pruning always leaves at least this many blocks (and coinbase_maturity) whole
+// below the tip — fee estimates, maturity checks and the explorer look back that far.
+pub const MIN_PRUNE_DEPTH: usize = 20;
+
+/// block_locator() steps back one block at a time for this many entries, then
+/// twice as far each step.
+pub const LOCATOR_DENSE_BLOCKS: usize = 10;
+
+// What goes on disk — the chain plus the settings needed to validate it.
+// Mempool is deliberately left out.
+// Borrowed when saving, so nothing is copied on the way out.
+#[derive(Serialize, Deserialize)]
+struct ChainFile<'a> {
+    #[serde(flatten)]
+    settings: ChainSettings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pruned:   Option<Cow<'a, PruneState>>,
+    chain:    Cow<'a, [Block]>,
+}
+
+// The settings half of ChainFile; a ChainStore keeps each field as a meta entry.
+#[derive(Serialize, Deserialize)]
+struct ChainSettings {
+    // files from before canonical hashing have no version, and can't be loaded
+    #[serde(default = "legacy_hash_version")]
+    hash_version:           u32,
+    difficulty:             u32,
+    reward:                 u64,
+    #[serde(default = "default_halving_interval")]
+    halving_interval:       u32,
+    #[serde(default)]
+    max_supply_nits:        Option<u64>,
+    #[serde(default = "default_max_transactions_per_block")]
+    max_transactions_per_block: usize,
+    // files from before maturity existed may spend fresh rewards — 0 keeps them valid
+    #[serde(default)]
+    coinbase_maturity:      u32,
+    target_block_time_secs: u64,
+    retarget_interval:      u32,
+}
+
+fn legacy_hash_version() -> u32 {
+    1
+}
+
+fn default_halving_interval() -> u32 {
+    100
+}
+
+fn default_max_transactions_per_block() -> usize {
+    100
+}
+
+/// Move one transaction's value between balances. NETWORK mints, so it's never debited.
+/// Saturates rather than fails — validated chains never overdraw, and
+/// replay_block() is there to catch the ones that do.
+fn apply_transfer(balances: &mut HashMap<String, u64>, txn: &Transaction) {
+    if txn.from != "NETWORK" {
+        let spent = txn.total_cost().unwrap_or(u64::MAX);
+        let from = balances.entry(txn.from.clone()).or_default();
+        *from = from.saturating_sub(spent);
+    }
+    for output in &txn.outputs {
+        let to = balances.entry(output.to.clone()).or_default();
+        *to = to.saturating_add(output.amount);
+    }
+}
+
+/// Count one confirmed transaction at `height` into the per-address tallies.
+/// A sender's own outputs back to itself move nothing, so they count as neither
+/// sent nor received.
+fn tally_transaction(tallies: &mut HashMap<String, AddressTally>, height: usize, txn: &Transaction) {
+    let mut sent = 0u64;
+    for output in txn.outputs.iter().filter(|o| o.to != txn.from) {
+        let recipient = touch(tallies, &output.to, height);
+        recipient.received = recipient.received.saturating_add(output.amount);
+        sent = sent.saturating_add(output.amount);
+    }
+    let sender = touch(tallies, &txn.from, height);
+    sender.sent = sender.sent.saturating_add(sent);
+    sender.fees = sender.fees.saturating_add(txn.fee);
+}
+
+/// Take one transaction back out of the tallies — tally_transaction() in
+/// reverse. `last_active` is where the address was last seen without it, None
+/// if nowhere.
+fn untally_transaction(tallies: &mut HashMap<String, AddressTally>, txn: &Transaction,
+                       last_active: impl Fn(&str) -> Option<usize>) {
+    let mut sent = 0u64;
+    for output in txn.outputs.iter().filter(|o| o.to != txn.from) {
+        let recipient = untouch(tallies, &output.to, &last_active);
+        recipient.received = recipient.received.saturating_sub(output.amount);
+        sent = sent.saturating_add(output.amount);
+    }
+    let sender = untouch(tallies, &txn.from, &last_active);
+    sender.sent = sender.sent.saturating_sub(sent);
+    sender.fees = sender.fees.saturating_sub(txn.fee);
+}
+
+// `address`'s tally, with one transaction fewer counted
+fn untouch<'a>(tallies: &'a mut HashMap<String, AddressTally>, address: &str,
+               last_active: impl Fn(&str) -> Option<usize>) -> &'a mut AddressTally {
+    let tally = tallies.entry(address.to_string()).or_default();
+    tally.transactions = tally.transactions.saturating_sub(1);
+    tally.last_active = last_active(address);
+    if tally.last_active.is_none() {
+        tally.first_seen = None;
+    }
+    tally
+}
+
+// `address`'s tally, with one more transaction at `height` counted
+fn touch<'a>(tallies: &'a mut HashMap<String, AddressTally>, address: &str, height: usize) -> &'a mut AddressTally {
+    let tally = tallies.entry(address.to_string()).or_default();
+    tally.transactions += 1;
+    tally.first_seen.get_or_insert(height);
+    tally.last_active = Some(height);
+    tally
+}
+
+// apply_transfer() in reverse
+fn revert_transfer(balances: &mut HashMap<String, u64>, txn: &Transaction) {
+    for output in &txn.outputs {
+        let to = balances.entry(output.to.clone()).or_default();
+        *to = to.saturating_sub(output.amount);
+    }
+    if txn.from != "NETWORK" {
+        let spent = txn.total_cost().unwrap_or(u64::MAX);
+        let from = balances.entry(txn.from.clone()).or_default();
+        *from = from.saturating_add(spent);
+    }
+}
+
+/// Replay one block onto `balances` in stored order, refusing any debit its sender
+/// can't cover at that point.
+fn replay_block(balances: &mut HashMap<String, u64>, block: &Block) -> Result<(), ChainError> {
+    for (position, txn) in block.transactions.iter().enumerate() {
+        if txn.from != "NETWORK" {
+            let need = txn.total_cost().ok_or(ChainError::BalanceOverflow)?;
+            let from = balances.entry(txn.from.clone()).or_default();
+            *from = from.checked_sub(need).ok_or_else(|| overdraft(block, position, *from, need))?;
+        }
+        for output in &txn.outputs {
+            let to = balances.entry(output.to.clone()).or_default();
+            *to = to.checked_add(output.amount).ok_or(ChainError::BalanceOverflow)?;
+        }
+    }
+    Ok(())
+}
+
+/// check_spends()'s duplicate and nonce checks for a chain without indexes to
+/// ask. `nonces` holds each sender's next nonce and `confirmed` the height of
+/// every transaction checked so far; both move past the block at `height`.
+fn check_sequence(
+    height: usize,
+    block: &Block,
+    nonces: &mut HashMap<String, u64>,
+    confirmed: &mut HashMap<String, usize>,
+) -> Result<(), ChainError> {
+    for (position, txn) in block.transactions.iter().enumerate() {
+        if txn.from == "NETWORK" {
+            continue;
+        }
+        let reject = |source: ChainError| Err(ChainError::InvalidTransaction {
+            index: block.index, position, source: Box::new(source),
+        });
+        let txid = txn.id();
+        if let Some(&at) = confirmed.get(&txid) {
+            return reject(ChainError::AlreadyConfirmed { index: at });
+        }
+        let expected = nonces.entry(txn.from.clone()).or_default();
+        if txn.nonce != *expected {
+            return reject(ChainError::BadNonce { expected: *expected, got: txn.nonce });
+        }
+        *expected += 1;
+        confirmed.insert(txid, height);
+    }
+    Ok(())
+}
+
+/// The transaction at `position` needs `need` but its sender only has `have`.
+/// If credits further down the same block would cover it, the block adds up
+/// and is just in the wrong order — InvalidOrdering rather than an overdraft.
+fn overdraft(block: &Block, position: usize, have: u64, need: u64) -> ChainError {
+    let from = &block.transactions[position].from;
+    let later = block.transactions[position + 1..].iter()
+        .fold(0u64, |acc, t| acc.saturating_add(t.amount_to(from)));
+    if have.saturating_add(later) >= need {
+        ChainError::InvalidOrdering { index: block.index, position }
+    } else {
+        ChainError::InvalidTransaction {
+            index: block.index, position,
+            source: Box::new(ChainError::InsufficientFunds { have, need }),
+        }
+    }
+}
+
+/// Nothing in a block may be timelocked past that block's index.
+fn check_locks(block: &Block) -> Result<(), ChainError> {
+    match block.transactions.iter().position(|t| !t.is_unlocked_at(block.index)) {
+        Some(position) => Err(ChainError::TransactionLocked {
+            index: block.index,
+            position,
+            until: block.transactions[position].lock_until_block.unwrap_or_default(),
+        }),
+        None => Ok(()),
+    }
+}
+
+fn chain_work(chain: &[Block]) -> u128 {
+    chain.iter().fold(0u128, |acc, b| acc.saturating_add(b.work()))
+}
+
+/// One mining thread per CPU core.
+fn default_mining_threads() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Where the mempool waits out a restart: chain.json → chain.mempool.json.
+pub fn mempool_path(chain_file: &str) -> String {
+    format!("{}.mempool.json", chain_stem(chain_file))
+}
 
+#[allow(dead_code)]
 pub struct Blockchain {
-    pub chain:      Vec<Block>,
-    pub difficulty: String,
-    pub mempool:    Vec<Transaction>,
-    pub reward:     u64,
+    pub chain:                  Vec<Block>,
+    pub difficulty:             u32,     // leading zero bits the next block has to meet
+    pub mempool:                Mempool,
+    pub reward:                 u64,     // initial coinbase, halved every halving_interval blocks
+    pub halving_interval:       u32,
+    pub max_supply_nits:        Option<u64>,  // hard cap on issuance, None = unlimited
+    pub max_transactions_per_block: usize,    // not counting the coinbase
+    pub coinbase_maturity:      u32,     // confirmations before a block reward is spendable
+    pub target_block_time_secs: u64,
+    pub retarget_interval:      u32,     // blocks between difficulty adjustments
+    pub mining_threads:         usize,
+    pub nonce_space:            u64,     // nonces tried per extra_nonce before it's bumped
+    pub allow_empty_blocks:     bool,    // mine coinbase-only blocks when the mempool is empty
+    pub max_mempool_txs:        usize,
+    pub max_mempool_bytes:      usize,
+    pub min_relay_fee:          u64,     // nits — fee estimates never go below this
+    pub clock:                  Arc<dyn Clock>,
+    pub max_future_skew_secs:   u64,
+    pub cancel_mining:          Arc<AtomicBool>,  // set to abort an in-progress mine
+    pub mining_status:          Arc<Mutex<MiningStatus>>,  // the search under way, or the last one
+    pub prune_depth:            Option<usize>,  // prune everything this far below the tip as blocks arrive
+    hash_index:                 HashMap<String, usize>,  // block hash → height
+    txid_index:                 HashMap<String, (usize, usize)>,  // txid → (height, position)
+    address_index:              HashMap<String, Vec<(usize, usize)>>,  // address → its (height, position)s
+    balances:                   HashMap<String, u64>,  // address → confirmed balance in nits
+    address_tallies:            HashMap<String, AddressTally>,  // address → what address_stats() reports
+    totals:                     ChainTotals,  // running sums for stats(), updated per block
+    pruned:                     Option<PruneState>,  // set once prune_below() dropped any bodies
+    checkpoint:                 Mutex<Option<Checkpoint>>,  // last tip validate() fully checked
+    store:                      Option<Mutex<Box<dyn ChainStore>>>,  // where persist() writes, if attached
+    mempool_wal:                Option<MempoolWal>,  // where log_mempool() writes, if opened
+}
+
+// Where the last successful validate() got to. Blocks up to here aren't
+// re-verified unless validate_full() is called or the block at `height` changed.
+#[derive(Clone)]
+struct Checkpoint {
+    height: usize,
+    hash:   String,
+    issued: u64,  // subsidy issued up to and including `height`
+}
+
+// Sums over the whole chain that only change when a block is added.
+#[derive(Default, Clone, Serialize, Deserialize)]
+struct ChainTotals {
+    transactions: u64,  // not counting coinbases
+    empty_blocks: u64,  // mined blocks with nothing but a coinbase
+    issued:       u64,  // coinbase minus the fees it recycled
+    fees:         u64,
+    burned:       u64,  // everything ever sent to BURN_ADDRESS
+}
+
+impl ChainTotals {
+    /// Count one block in.
+    fn add(&mut self, block: &Block) {
+        for txn in &block.transactions {
+            self.burned = self.burned.saturating_add(txn.amount_to(BURN_ADDRESS));
+        }
+        if let Some((coinbase, rest)) = block.transactions.split_last() {
+            if coinbase.from == "NETWORK" {
+                let fees = block.total_fees();
+                self.transactions += rest.len() as u64;
+                if rest.is_empty() {
+                    self.empty_blocks += 1;
+                }
+                self.fees = self.fees.saturating_add(fees);
+                self.issued = self.issued.saturating_add(block.coinbase_amount().saturating_sub(fees));
+            }
+        }
+    }
+
+    /// Count one block back out — add() in reverse.
+    fn remove(&mut self, block: &Block) {
+        for txn in &block.transactions {
+            self.burned = self.burned.saturating_sub(txn.amount_to(BURN_ADDRESS));
+        }
+        if let Some((coinbase, rest)) = block.transactions.split_last() {
+            if coinbase.from == "NETWORK" {
+                let fees = block.total_fees();
+                self.transactions = self.transactions.saturating_sub(rest.len() as u64);
+                if rest.is_empty() {
+                    self.empty_blocks = self.empty_blocks.saturating_sub(1);
+                }
+                self.fees = self.fees.saturating_sub(fees);
+                self.issued = self.issued.saturating_sub(block.coinbase_amount().saturating_sub(fees));
+            }
+        }
+    }
+}
+
+// Running totals for one address, kept alongside its balance. Amounts in nits.
+#[derive(Default, Clone, Serialize, Deserialize)]
+struct AddressTally {
+    received:     u64,  // from other addresses, coinbases included
+    sent:         u64,  // to other addresses, fees not included
+    fees:         u64,
+    transactions: u64,  // confirmed transactions it sent or received in
+    first_seen:   Option<usize>,
+    last_active:  Option<usize>,
+    #[serde(skip)]
+    blocks_mined: u64,  // counted from headers, which pruning keeps, so never in a snapshot
+}
+
+// What prune_below() keeps of the bodies it drops: the indexes as of the
+// first block that still has one, so they can be rebuilt from there on.
+#[derive(Clone, Serialize, Deserialize)]
+struct PruneState {
+    height:   usize,                 // blocks below this are headers only
+    balances: HashMap<String, u64>,  // confirmed balances in nits before `height`
+    sent:     HashMap<String, u64>,  // transactions each address sent before `height`, for nonces
+    totals:   ChainTotals,
+    #[serde(default)]
+    addresses: HashMap<String, AddressTally>,  // address_stats() totals before `height`
+}
+
+// What admit_transaction() changed in the mempool, for submit_transaction() to undo.
+struct Admission {
+    txid:     String,
+    evicted:  Vec<Taken>,          // made room for it, in the order they went
+    replaced: Option<Transaction>, // what it replaced by fee
+}
+
+/// Aggregate numbers for GET /stats. Amounts in tokens.
+#[derive(Serialize, ToSchema)]
+pub struct ChainStats {
+    pub block_count:                usize,
+    pub total_transactions:         u64,
+    pub total_issued:               f64,
+    pub total_fees:                 f64,
+    pub total_burned:               f64,
+    pub avg_transactions_per_block: f64,     // over mined blocks, empty ones included
+    pub empty_blocks:               u64,
+    pub avg_block_interval_secs:    Option<f64>,  // over the last STATS_WINDOW blocks
+    pub difficulty:                 u32,     // leading zero bits
+    pub total_work:                 u128,
+    pub mempool_size:               usize,
+    pub estimated_hashrate:         Option<f64>,  // hashes per second
+    pub hashrate_measured:          bool,    // from searches this node ran, not worked out from difficulty
+    pub pruned_below:               Option<usize>,  // blocks below this height are headers only
+    pub prune_depth:                Option<usize>,  // blocks kept whole below the tip, if pruning
+}
+
+/// A block by height and hash — what /chain/head and /chain/locator list.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct BlockRef {
+    pub height: usize,
+    pub hash:   String,
+}
+
+/// One block of block_time_series().
+#[derive(Serialize, ToSchema)]
+pub struct BlockTiming {
+    pub index:               u32,
+    pub timestamp:           u64,
+    pub secs_since_previous: Option<u64>,  // None for genesis
+    pub clamped:             bool,     // stamped before its parent, so secs_since_previous is 0
+    pub difficulty:          u32,      // leading zero bits
+    pub transaction_count:   Option<usize>,  // coinbase included; None below the prune point
+    pub nonce:               u64,
+}
+
+/// What replace_chain() did.
+#[derive(Serialize, ToSchema)]
+pub struct ReplaceOutcome {
+    pub old_height:          usize,
+    pub new_height:          usize,
+    pub fork_height:         usize,  // first height where the two chains differ
+    pub blocks_abandoned:    usize,
+    pub returned_to_mempool: usize,  // abandoned transactions re-queued
+}
+
+/// What import_chain() did. The old chain's length and tip are there so an
+/// operator can check what was replaced.
+#[derive(Serialize, ToSchema)]
+pub struct ImportOutcome {
+    pub old_length:          usize,
+    pub old_tip_hash:        String,
+    pub new_length:          usize,
+    pub new_tip_hash:        String,
+    pub fork_height:         usize,  // first height where the two chains differ
+    pub blocks_abandoned:    usize,
+    pub returned_to_mempool: usize,  // abandoned transactions re-queued
+}
+
+/// Everything needed to build the next block, for GET /mining/template.
+/// An external miner adds the coinbase, searches nonces itself and submits via POST /block.
+#[derive(Serialize, Clone, ToSchema)]
+pub struct BlockTemplate {
+    pub index:           u32,
+    pub previous_hash:   String,
+    pub transactions:    Vec<Transaction>,  // from the mempool, coinbase not included
+    pub coinbase_amount: u64,     // nits — subsidy plus every fee above
+    pub difficulty:      u32,     // leading zero bits the block hash needs
+    pub target:          String,  // the same, as the highest acceptable hash
+    pub timestamp:       u64,     // suggested — anything the timestamp rules accept works
+    pub miner:           String,
+}
+
+impl BlockTemplate {
+    /// The unmined block: the template's transactions plus a coinbase paying the miner.
+    pub fn to_block(&self) -> Block {
+        let mut coinbase = Transaction::new_nits("NETWORK".to_string(), self.miner.clone(), self.coinbase_amount);
+        // coinbase nonce = block height, so every coinbase gets a distinct txid
+        coinbase.nonce = self.index as u64;
+        let mut transactions = self.transactions.clone();
+        transactions.push(coinbase);
+        let mut block = Block::new(self.index, transactions, self.previous_hash.clone(),
+            self.miner.clone(), self.timestamp);
+        block.difficulty = self.difficulty;
+        block
+    }
+}
+
+/// One line of an address's transaction history.
+#[derive(Serialize, ToSchema)]
+pub struct HistoryEntry {
+    pub block_index:   usize,
+    pub txid:          String,
+    pub direction:     &'static str,  // "in", "out" or "self"
+    pub amount:        f64,
+    pub confirmations: u64,
+}
+
+/// An address's lifetime totals in tokens, all zero for one the chain has never seen.
+#[derive(Serialize, ToSchema)]
+pub struct AddressStats {
+    pub address:           String,
+    pub total_received:    f64,     // from other addresses, block rewards included
+    pub total_sent:        f64,     // to other addresses, fees not included
+    pub total_fees:        f64,
+    pub transaction_count: u64,
+    pub first_seen_block:  Option<usize>,
+    pub last_active_block: Option<usize>,
+    pub blocks_mined:      u64,
+    pub has_mined:         bool,
+}
+
+/// Where a transaction stands — confirmations is 0 while it's still in the mempool.
+#[derive(Serialize, ToSchema)]
+pub struct TransactionStatus {
+    pub transaction:   Transaction,
+    pub block_index:   Option<usize>,
+    pub confirmations: u64,
 }
 
 impl Blockchain {
-    pub fn new(difficulty: &str) -> Self {
-        println!("🔗 Initializing blockchain [difficulty={}]", difficulty);
+    /// A new chain whose blocks need `difficulty` leading zero bits.
+    /// Panics on a difficulty that can never be mined (0 or over 256 bits) —
+    /// better to fail at startup than to hang mining the genesis block.
+    pub fn new(difficulty: u32) -> Self {
+        Self::with_clock(difficulty, Arc::new(SystemClock))
+    }
+
+    /// new() from an old-style hex prefix: "0000" is 16 bits. Panics on
+    /// anything but a run of zeros.
+    pub fn with_prefix(prefix: &str) -> Self {
+        match difficulty_from_prefix(prefix) {
+            Ok(bits) => Self::new(bits),
+            Err(e)   => panic!("{}", e),
+        }
+    }
+
+    /// new(), but every timestamp (genesis included) comes from `clock`.
+    pub fn with_clock(difficulty: u32, clock: Arc<dyn Clock>) -> Self {
+        debug!(difficulty, "initializing blockchain");
+        if let Err(e) = check_difficulty(difficulty) {
+            panic!("{}", e);
+        }
         let genesis = {
-            let mut b = Block::new(0, vec![], GENESIS_PREV_HASH.to_string(), "NETWORK".to_string());
-            b.mine(difficulty);
+            let mut b = Block::new(0, vec![], GENESIS_PREV_HASH.to_string(), "NETWORK".to_string(), clock.now_secs());
+            b.mine(difficulty).expect("genesis difficulty already checked");
+            // a loaded chain's genesis has no effort either, so stats don't depend on which
+            b.effort = None;
             b
         };
+        let mut bc = Self::with_defaults(vec![genesis], difficulty, clock);
+        bc.rebuild_indexes();
+        bc
+    }
+
+    // `chain` with the default settings and no indexes yet
+    fn with_defaults(chain: Vec<Block>, difficulty: u32, clock: Arc<dyn Clock>) -> Self {
         Blockchain {
-            chain: vec![genesis],
-            difficulty: difficulty.to_string(),
-            mempool: vec![],
+            chain,
+            difficulty,
+            mempool: Mempool::new(),
             reward: 50 * NITS_PER_TOKEN,
+            halving_interval: default_halving_interval(),
+            max_supply_nits: None,
+            max_transactions_per_block: default_max_transactions_per_block(),
+            coinbase_maturity: DEFAULT_COINBASE_MATURITY,
+            target_block_time_secs: 10,
+            retarget_interval: 10,
+            mining_threads: default_mining_threads(),
+            nonce_space: FULL_NONCE_SPACE,
+            allow_empty_blocks: false,
+            max_mempool_txs: DEFAULT_MAX_MEMPOOL_TXS,
+            max_mempool_bytes: DEFAULT_MAX_MEMPOOL_BYTES,
+            min_relay_fee: DEFAULT_MIN_RELAY_FEE,
+            clock,
+            max_future_skew_secs: DEFAULT_MAX_FUTURE_SKEW_SECS,
+            cancel_mining: Arc::new(AtomicBool::new(false)),
+            mining_status: Arc::new(Mutex::new(MiningStatus::default())),
+            prune_depth: None,
+            hash_index: HashMap::new(),
+            txid_index: HashMap::new(),
+            address_index: HashMap::new(),
+            balances: HashMap::new(),
+            address_tallies: HashMap::new(),
+            totals: ChainTotals::default(),
+            pruned: None,
+            checkpoint: Mutex::new(None),
+            store: None,
+            mempool_wal: None,
+        }
+    }
+
+    /// A chain around `blocks`, exported from a node or saved by an earlier run,
+    /// with the default settings. Everything is checked from genesis before it's
+    /// returned; the indexes are built from it and the mempool starts empty.
+    pub fn from_blocks(blocks: Vec<Block>) -> Result<Self, String> {
+        let difficulty = blocks.first().map_or(MIN_DIFFICULTY_BITS, |genesis| genesis.difficulty);
+        let mut bc = Self::with_defaults(blocks, difficulty, Arc::new(SystemClock));
+        bc.check_from_genesis().map_err(|e| format!("Refusing to import the chain: {}", e))?;
+        bc.rebuild_indexes();
+        Ok(bc)
+    }
+
+    // an imported chain: the genesis block itself, which validate() takes on trust, then validate()
+    fn check_from_genesis(&mut self) -> Result<(), ChainError> {
+        let genesis = self.chain.first()
+            .ok_or_else(|| ChainError::InvalidGenesis("the chain has no blocks".to_string()))?;
+        if genesis.index != 0 || genesis.previous_hash != GENESIS_PREV_HASH {
+            return Err(ChainError::InvalidGenesis("not a genesis block — index 0 with no parent".to_string()));
+        }
+        if !genesis.is_intact() || !genesis.meets_difficulty() {
+            return Err(ChainError::InvalidGenesis("hash doesn't check out".to_string()));
+        }
+        self.difficulty = self.difficulty_for_height(self.chain.len());
+        self.validate()
+    }
+
+    /// Recompute every lookup index from the chain itself — from the prune
+    /// point's saved balances and totals on a pruned chain.
+    /// A block that fails its own hash check is left out, so one corrupted block
+    /// can't skew every balance — validate() is what reports it.
+    fn rebuild_indexes(&mut self) {
+        self.hash_index.clear();
+        self.txid_index.clear();
+        self.address_index.clear();
+        self.balances = self.pruned.as_ref().map(|p| p.balances.clone()).unwrap_or_default();
+        self.address_tallies = self.pruned.as_ref().map(|p| p.addresses.clone()).unwrap_or_default();
+        self.totals = self.pruned.as_ref().map(|p| p.totals.clone()).unwrap_or_default();
+        for height in 0..self.chain.len() {
+            // a pruned block has only its header left to check
+            let block = &self.chain[height];
+            let intact = match height < self.pruned_below() {
+                true  => block.hash == block.calculate_hash(),
+                false => block.is_intact(),
+            };
+            if !intact {
+                warn!(block = height, "block fails its hash check, left out of the indexes");
+                continue;
+            }
+            self.index_block(height);
+        }
+    }
+
+    /// After a change that panicked partway: rebuild the indexes from the chain
+    /// as it stands, drop what it now confirms from the mempool and forget the
+    /// checkpoint, so nothing answers from half-updated state. Returns what
+    /// validate() makes of the chain after that.
+    pub(crate) fn recover_from_panic(&mut self) -> Result<(), ChainError> {
+        self.rebuild_indexes();
+        self.difficulty = self.difficulty_for_height(self.chain.len());
+        let confirmed = &self.txid_index;
+        self.mempool.retain(|t| !confirmed.contains_key(&t.id()));
+        self.checkpoint.locked().take();
+        self.validate()
+    }
+
+    /// Add one block's hash, txids, addresses and balance changes to the lookup indexes.
+    fn index_block(&mut self, height: usize) {
+        let block = &self.chain[height];
+        self.hash_index.insert(block.hash.clone(), height);
+        self.totals.add(block);
+        // genesis is nobody's
+        if height > 0 {
+            self.address_tallies.entry(block.miner.clone()).or_default().blocks_mined += 1;
+        }
+        for (pos, txn) in block.transactions.iter().enumerate() {
+            self.txid_index.insert(txn.id(), (height, pos));
+            apply_transfer(&mut self.balances, txn);
+            tally_transaction(&mut self.address_tallies, height, txn);
+            self.address_index.entry(txn.from.clone()).or_default().push((height, pos));
+            for output in &txn.outputs {
+                let positions = self.address_index.entry(output.to.clone()).or_default();
+                if output.to != txn.from && positions.last() != Some(&(height, pos)) {
+                    positions.push((height, pos));
+                }
+            }
+        }
+    }
+
+    /// Take the block at `height` back out of every index — index_block() in
+    /// reverse, so a reorg only pays for the blocks it detaches. Everything
+    /// above it has to be out already.
+    fn unindex_block(&mut self, height: usize) {
+        let block = &self.chain[height];
+        self.hash_index.remove(&block.hash);
+        self.totals.remove(block);
+        if height > 0 {
+            if let Some(tally) = self.address_tallies.get_mut(&block.miner) {
+                tally.blocks_mined = tally.blocks_mined.saturating_sub(1);
+            }
+        }
+        let pruned = self.pruned.as_ref().map(|p| &p.addresses);
+        for (pos, txn) in block.transactions.iter().enumerate().rev() {
+            self.txid_index.remove(&txn.id());
+            revert_transfer(&mut self.balances, txn);
+            for address in txn.outputs.iter().map(|o| &o.to).chain([&txn.from]) {
+                if let Some(positions) = self.address_index.get_mut(address) {
+                    if positions.last() == Some(&(height, pos)) {
+                        positions.pop();
+                    }
+                    if positions.is_empty() {
+                        self.address_index.remove(address);
+                    }
+                }
+            }
+            // with this block gone, an address was last active at its newest
+            // remaining position, or else wherever the prune point last saw it
+            let address_index = &self.address_index;
+            untally_transaction(&mut self.address_tallies, txn, |address| {
+                address_index.get(address).and_then(|positions| positions.last()).map(|&(h, _)| h)
+                    .or_else(|| pruned.and_then(|p| p.get(address)).and_then(|t| t.last_active))
+            });
+        }
+    }
+
+    /// Confirmed transactions touching `address`, newest first.
+    /// `before_block` only returns transactions from blocks below that height.
+    /// Pruned if that's at or below the prune point, or if there's nothing
+    /// above it and the address was active before it.
+    pub fn address_history(&self, address: &str, limit: usize, before_block: Option<usize>) -> Result<Vec<HistoryEntry>, ChainError> {
+        if let Some(pruned) = &self.pruned {
+            let older = pruned.balances.contains_key(address) || pruned.sent.contains_key(address);
+            let above = self.address_index.get(address)
+                .is_some_and(|positions| positions.iter().any(|(h, _)| before_block.is_none_or(|b| *h < b)));
+            if before_block.is_some_and(|b| b <= pruned.height) || (older && !above) {
+                return Err(ChainError::Pruned { below: pruned.height });
+            }
+        }
+        let Some(positions) = self.address_index.get(address) else { return Ok(vec![]) };
+        let tip = self.chain.len() - 1;
+        let history = positions.iter()
+            .rev()
+            .filter(|(height, _)| before_block.is_none_or(|b| *height < b))
+            .take(limit)
+            .map(|&(height, pos)| {
+                let txn = &self.chain[height].transactions[pos];
+                let (direction, amount) =
+                    if txn.from != address { ("in", txn.amount_to(address)) }
+                    else if txn.outputs.iter().all(|o| o.to == address) { ("self", txn.amount()) }
+                    else { ("out", txn.amount()) };
+                HistoryEntry {
+                    block_index: height,
+                    txid: txn.id(),
+                    direction,
+                    amount: amount as f64 / NITS_PER_TOKEN as f64,
+                    confirmations: (tip - height + 1) as u64,
+                }
+            })
+            .collect();
+        Ok(history)
+    }
+
+    /// Lifetime totals for `address` out of the running tallies, so it's one
+    /// lookup however long its history. Pruning doesn't change them.
+    pub fn address_stats(&self, address: &str) -> Result<AddressStats, ChainError> {
+        if !is_valid_address(address) {
+            return Err(ChainError::InvalidAddress(
+                format!("{:?} is not 64 hex chars, a multisig address or NETWORK", short(address, 16))));
+        }
+        let tally = self.address_tallies.get(address).cloned().unwrap_or_default();
+        let tokens = |nits: u64| nits as f64 / NITS_PER_TOKEN as f64;
+        Ok(AddressStats {
+            address:           address.to_string(),
+            total_received:    tokens(tally.received),
+            total_sent:        tokens(tally.sent),
+            total_fees:        tokens(tally.fees),
+            transaction_count: tally.transactions,
+            first_seen_block:  tally.first_seen,
+            last_active_block: tally.last_active,
+            blocks_mined:      tally.blocks_mined,
+            has_mined:         tally.blocks_mined > 0,
+        })
+    }
+
+    pub fn get_block(&self, index: usize) -> Option<&Block> {
+        self.chain.get(index)
+    }
+
+    /// Height below which blocks are headers only; 0 on an unpruned chain.
+    pub fn pruned_below(&self) -> usize {
+        self.pruned.as_ref().map_or(0, |p| p.height)
+    }
+
+    /// Pruned for any part of `from..to` that's below the prune point.
+    pub fn check_unpruned(&self, from: usize, to: usize) -> Result<(), ChainError> {
+        match self.pruned_below() {
+            below if from < below && from < to => Err(ChainError::Pruned { below }),
+            _ => Ok(()),
+        }
+    }
+
+    /// prune_below() everything more than prune_depth blocks under the tip, if
+    /// it's set. A depth under MIN_PRUNE_DEPTH (or coinbase_maturity) counts as that.
+    /// Returns how many blocks were newly pruned.
+    pub fn prune_to_depth(&mut self) -> usize {
+        let Some(depth) = self.prune_depth else { return 0 };
+        let keep = depth.max(MIN_PRUNE_DEPTH).max(self.coinbase_maturity as usize);
+        self.prune_below(self.chain.len().saturating_sub(keep))
+            .unwrap_or_else(|e| { warn!(error = %e, "pruning failed"); 0 })
+    }
+
+    /// Drop the transactions of every block below `height`, keeping their headers,
+    /// so links and proof of work still check out. Balances, nonces and the stats
+    /// totals carry on from a snapshot taken at `height`. At least MIN_PRUNE_DEPTH
+    /// (and coinbase_maturity) blocks below the tip stay whole. Returns how many
+    /// blocks were newly pruned — 0 if `height` is at or below the prune point already.
+    pub fn prune_below(&mut self, height: usize) -> Result<usize, ChainError> {
+        let keep = MIN_PRUNE_DEPTH.max(self.coinbase_maturity as usize);
+        let limit = self.chain.len().saturating_sub(keep);
+        if height > limit {
+            return Err(ChainError::InvalidPrune(format!(
+                "can't prune below #{} — the last {} blocks stay whole, so #{} is as far as it goes", height, keep, limit)));
+        }
+        let from = self.pruned_below();
+        if height <= from {
+            return Ok(0);
+        }
+        let mut state = self.pruned.take().unwrap_or_else(|| PruneState {
+            height: 0, balances: HashMap::new(), sent: HashMap::new(), totals: ChainTotals::default(),
+            addresses: HashMap::new(),
+        });
+        for (block_height, block) in (from..height).zip(&mut self.chain[from..height]) {
+            state.totals.add(block);
+            for txn in &block.transactions {
+                apply_transfer(&mut state.balances, txn);
+                tally_transaction(&mut state.addresses, block_height, txn);
+                self.txid_index.remove(&txn.id());
+                if txn.from != "NETWORK" {
+                    *state.sent.entry(txn.from.clone()).or_default() += 1;
+                }
+            }
+            block.transactions = Vec::new();
+        }
+        for positions in self.address_index.values_mut() {
+            positions.retain(|&(h, _)| h >= height);
+        }
+        self.address_index.retain(|_, positions| !positions.is_empty());
+        state.height = height;
+        self.pruned = Some(state);
+        info!(below = height, blocks = height - from, "pruned block bodies");
+        Ok(height - from)
+    }
+
+    /// O(1) lookup through the hash index — no chain scan.
+    pub fn get_block_by_hash(&self, hash: &str) -> Option<&Block> {
+        self.hash_index.get(hash).and_then(|&i| self.chain.get(i))
+    }
+
+    /// Find a transaction on chain (through the txid index) or in the mempool.
+    pub fn find_transaction(&self, txid: &str) -> Option<TransactionStatus> {
+        if let Some(&(height, pos)) = self.txid_index.get(txid) {
+            let transaction = self.chain.get(height)?.transactions.get(pos)?;
+            let tip = self.chain.len() - 1;
+            return Some(TransactionStatus {
+                transaction: transaction.clone(),
+                block_index: Some(height),
+                confirmations: (tip - height + 1) as u64,
+            });
+        }
+        self.mempool.get(txid)
+            .map(|transaction| TransactionStatus { transaction: transaction.clone(), block_index: None, confirmations: 0 })
+    }
+
+    /// Difficulty in force for the block at `height` (>= 1), derived from the blocks before it.
+    /// Every retarget_interval blocks, scale the work per block by how much faster or slower
+    /// than the target the last interval went: each bit doubles or halves it, so the change is
+    /// log2(expected / elapsed) rounded, at most MAX_RETARGET_STEP_BITS either way.
+    pub fn difficulty_for_height(&self, height: usize) -> u32 {
+        let prev = &self.chain[height - 1];
+        let interval = self.retarget_interval as usize;
+        if interval < 2 || !height.is_multiple_of(interval) {
+            return prev.difficulty;
+        }
+        let first = &self.chain[height - interval];
+        let elapsed = prev.timestamp.saturating_sub(first.timestamp);
+        let expected = self.target_block_time_secs * (interval as u64 - 1);
+
+        let max_step = MAX_RETARGET_STEP_BITS as f64;
+        let step = if elapsed == 0 {
+            max_step
+        } else {
+            (expected as f64 / elapsed as f64).log2().round().clamp(-max_step, max_step)
+        } as i64;
+        // a chain started outside the range may drift toward it, never further away
+        let bits = prev.difficulty as i64 + step;
+        let bits = match step.signum() {
+            1  => bits.min(MAX_DIFFICULTY_BITS.max(prev.difficulty) as i64),
+            -1 => bits.max(MIN_DIFFICULTY_BITS.min(prev.difficulty) as i64),
+            _  => bits,
+        };
+        bits as u32
+    }
+
+    /// Coinbase reward (before fees) for the block at `height`:
+    /// the initial reward halved once per halving_interval blocks, down to zero.
+    pub fn reward_at(&self, height: u32) -> u64 {
+        if self.halving_interval == 0 {
+            return self.reward;
+        }
+        let halvings = height / self.halving_interval;
+        self.reward.checked_shr(halvings).unwrap_or(0)
+    }
+
+    /// New coins the block at `height` may create, given how much was issued before it.
+    /// The scheduled reward, truncated so issuance never passes max_supply_nits.
+    pub fn subsidy_at(&self, height: u32, issued_before: u64) -> u64 {
+        let scheduled = self.reward_at(height);
+        match self.max_supply_nits {
+            Some(cap) => scheduled.min(cap.saturating_sub(issued_before)),
+            None      => scheduled,
+        }
+    }
+
+    /// Coins created so far — every coinbase minus the fees it recycled.
+    pub fn total_supply(&self) -> u64 {
+        self.totals.issued
+    }
+
+    /// Coins sent to BURN_ADDRESS — issued, but never spendable again.
+    pub fn total_burned(&self) -> u64 {
+        self.totals.burned
+    }
+
+    /// Chain-wide numbers for dashboards. Totals come from the running sums kept
+    /// by index_block(); only the recent-interval math looks at blocks directly.
+    pub fn stats(&self) -> ChainStats {
+        let blocks = self.chain.len();
+        let recent = &self.chain[blocks.saturating_sub(STATS_WINDOW)..];
+        let avg_block_interval_secs = match (recent.first(), recent.last()) {
+            (Some(first), Some(last)) if recent.len() > 1 =>
+                Some(last.timestamp.saturating_sub(first.timestamp) as f64 / (recent.len() - 1) as f64),
+            _ => None,
+        };
+        // n leading zero bits take 2^n hashes on average
+        let expected_hashes = 2f64.powi(self.difficulty as i32);
+        // blocks mined here know what they really took, so those win over the estimate
+        let (attempts, secs) = recent.iter().filter_map(|b| b.effort)
+            .fold((0u64, 0f64), |(attempts, secs), e| (attempts.saturating_add(e.attempts), secs + e.secs));
+        let measured = (secs > 0.0).then(|| attempts as f64 / secs);
+        let tokens = |nits: u64| nits as f64 / NITS_PER_TOKEN as f64;
+        ChainStats {
+            block_count: blocks,
+            total_transactions: self.totals.transactions,
+            total_issued: tokens(self.totals.issued),
+            total_fees: tokens(self.totals.fees),
+            total_burned: tokens(self.totals.burned),
+            // genesis isn't mined so it doesn't count; empty blocks do, as zeros
+            avg_transactions_per_block: self.totals.transactions as f64 / blocks.saturating_sub(1).max(1) as f64,
+            empty_blocks: self.totals.empty_blocks,
+            avg_block_interval_secs,
+            difficulty: self.difficulty,
+            total_work: self.total_work(),
+            mempool_size: self.mempool.len(),
+            estimated_hashrate: measured.or(avg_block_interval_secs
+                .filter(|&secs| secs > 0.0)
+                .map(|secs| expected_hashes / secs)),
+            hashrate_measured: measured.is_some(),
+            pruned_below: self.pruned.as_ref().map(|p| p.height),
+            prune_depth: self.prune_depth,
+        }
+    }
+
+    /// A Bitcoin-style block locator: the tip, the LOCATOR_DENSE_BLOCKS below
+    /// it, then blocks ever further apart, doubling each step, down to genesis.
+    /// A few dozen entries cover any chain. Whoever has it calls locate() to find
+    /// the highest block the two chains share; they fork somewhere above it.
+    pub fn block_locator(&self) -> Vec<BlockRef> {
+        let mut locator = Vec::new();
+        let Some(mut height) = self.chain.len().checked_sub(1) else { return locator };
+        let mut step = 1;
+        loop {
+            locator.push(BlockRef { height, hash: self.chain[height].hash.clone() });
+            if height == 0 {
+                return locator;
+            }
+            if locator.len() >= LOCATOR_DENSE_BLOCKS {
+                step *= 2;
+            }
+            height = height.saturating_sub(step);
+        }
+    }
+
+    /// Height of the first block in `locator` that's also on this chain — for
+    /// another chain's block_locator(), the highest block both share. None if
+    /// not even genesis matches.
+    pub fn locate(&self, locator: &[BlockRef]) -> Option<usize> {
+        locator.iter().find_map(|block| self.hash_index.get(&block.hash).copied())
+    }
+
+    /// Timing and difficulty of the last `n` blocks, oldest first, for charting
+    /// how retargeting behaves. Everything but the transaction count comes off
+    /// the header, so it reaches below the prune point too. Timestamps only have
+    /// to beat the median of the last few, so a block can be stamped before its
+    /// parent; its gap is clamped to 0 and flagged rather than going negative.
+    pub fn block_time_series(&self, n: usize) -> Vec<BlockTiming> {
+        let from = self.chain.len().saturating_sub(n);
+        (from..self.chain.len()).map(|height| {
+            let block = &self.chain[height];
+            let previous = height.checked_sub(1).map(|h| self.chain[h].timestamp);
+            BlockTiming {
+                index:               block.index,
+                timestamp:           block.timestamp,
+                secs_since_previous: previous.map(|t| block.timestamp.saturating_sub(t)),
+                clamped:             previous.is_some_and(|t| block.timestamp < t),
+                difficulty:          block.difficulty,
+                transaction_count:   (height >= self.pruned_below()).then_some(block.transactions.len()),
+                nonce:               block.nonce,
+            }
+        }).collect()
+    }
+
+    // what's saved beside the blocks
+    fn settings(&self) -> ChainSettings {
+        ChainSettings {
+            hash_version: HASH_VERSION,
+            difficulty: self.difficulty,
+            reward: self.reward,
+            halving_interval: self.halving_interval,
+            max_supply_nits: self.max_supply_nits,
+            max_transactions_per_block: self.max_transactions_per_block,
+            coinbase_maturity: self.coinbase_maturity,
+            target_block_time_secs: self.target_block_time_secs,
+            retarget_interval: self.retarget_interval,
+        }
+    }
+
+    /// Write the chain to disk as JSON, zstd-compressed if `path` ends in .zst.
+    /// It's serialized straight into the file, not built up in memory first.
+    pub fn save_to_file(&self, path: &str) -> Result<(), String> {
+        let file = ChainFile {
+            settings: self.settings(),
+            pruned:   self.pruned.as_ref().map(Cow::Borrowed),
+            chain:    Cow::Borrowed(&self.chain),
+        };
+        write_chain_file(path, &file)
+    }
+
+    /// Write the pending transactions to disk as JSON; returns how many.
+    pub fn save_mempool(&self, path: &str) -> Result<usize, String> {
+        let pending: Vec<&Transaction> = self.mempool.iter().collect();
+        let json = serde_json::to_string_pretty(&pending)
+            .map_err(|e| format!("Could not serialize mempool: {}", e))?;
+        std::fs::write(path, json)
+            .map_err(|e| format!("Could not write {}: {}", path, e))?;
+        Ok(pending.len())
+    }
+
+    /// Re-submit the transactions save_mempool() wrote, each checked like a
+    /// new arrival — the chain may have moved on since (a nonce used, funds
+    /// spent). Those are dropped. Returns (restored, dropped); no file is (0, 0).
+    pub fn restore_mempool(&mut self, path: &str) -> Result<(usize, usize), String> {
+        if !std::path::Path::new(path).exists() {
+            return Ok((0, 0));
+        }
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path, e))?;
+        let pending: Vec<Transaction> = serde_json::from_str(&json)
+            .map_err(|e| format!("{} is not a valid mempool file: {}", path, e))?;
+        Ok(self.resubmit(pending))
+    }
+
+    /// Replay the mempool log at `path` (see wal.rs) like restore_mempool():
+    /// every transaction still in it is re-submitted, and ones the chain has
+    /// confirmed or invalidated since are dropped. The log is then rewritten to
+    /// match and kept open, and log_mempool() appends to it from then on.
+    /// Returns (restored, dropped).
+    pub fn open_mempool_wal(&mut self, path: &str) -> Result<(usize, usize), String> {
+        let replayed = MempoolWal::replay(path)?;
+        if replayed.skipped_bytes > 0 {
+            warn!(path, skipped_bytes = replayed.skipped_bytes, "mempool log ends in a broken record, replayed up to it");
+        }
+        let outcome = self.resubmit(replayed.pending);
+        self.mempool_wal = Some(MempoolWal::create(path, &self.mempool)?);
+        Ok(outcome)
+    }
+
+    /// Append what entered or left the mempool since the last call to the log
+    /// open_mempool_wal() opened, fsynced. Returns the records written; 0 with no log.
+    pub fn log_mempool(&mut self) -> Result<usize, String> {
+        match self.mempool_wal.as_mut() {
+            Some(wal) => wal.sync(&self.mempool),
+            None      => Ok(0),
+        }
+    }
+
+    /// add_transaction(), logged before it counts: if the mempool log can't be
+    /// written the mempool goes back to how it was and the submission fails,
+    /// rather than acknowledging a transaction a crash would lose.
+    pub fn submit_transaction(&mut self, txn: Transaction) -> Result<String, ChainError> {
+        if self.mempool_wal.is_none() {
+            return self.add_transaction(txn);
+        }
+        let admitted = self.admit_transaction(txn)?;
+        let txid = admitted.txid.clone();
+        if let Err(e) = self.log_mempool() {
+            warn!(txid = %short(&txid, 12), error = %e, "could not log the mempool, transaction refused");
+            self.undo_admission(admitted);
+            return Err(ChainError::MempoolLogFailed(e));
+        }
+        Ok(txid)
+    }
+
+    // put back whatever admit_transaction() changed — an eviction or replace-by-fee too
+    fn undo_admission(&mut self, admitted: Admission) {
+        match admitted.replaced {
+            Some(old) => { self.mempool.replace(old); }
+            None      => { self.mempool.remove(&admitted.txid); }
+        }
+        for taken in admitted.evicted.into_iter().rev() {
+            self.mempool.restore(taken);
+        }
+    }
+
+    /// Rewrite the mempool log down to what's pending; how many that is.
+    /// None when there's no log open.
+    pub fn compact_mempool_wal(&mut self) -> Option<Result<usize, String>> {
+        self.mempool_wal.as_mut().map(|wal| wal.compact(&self.mempool))
+    }
+
+    // add_transaction() each of `pending`, in nonce order; (restored, dropped)
+    fn resubmit(&mut self, mut pending: Vec<Transaction>) -> (usize, usize) {
+        // a sender's transactions have to go back in nonce order
+        pending.sort_by_key(|t| t.nonce);
+        let (mut restored, mut dropped) = (0, 0);
+        for txn in pending {
+            let txid = txn.id();
+            match self.add_transaction(txn) {
+                Ok(_)  => restored += 1,
+                Err(e) => {
+                    warn!(txid = %short(&txid, 12), error = %e, "saved mempool transaction dropped");
+                    dropped += 1;
+                }
+            }
+        }
+        (restored, dropped)
+    }
+
+    /// Load a chain saved by save_to_file(), compressed or not — the file's
+    /// first bytes tell. Refuses anything that fails validate().
+    pub fn load_from_file(path: &str) -> Result<Self, String> {
+        let file: ChainFile = read_chain_file(path)?;
+        Self::from_saved(file.settings, file.pruned.map(Cow::into_owned), file.chain.into_owned(), path)
+    }
+
+    /// Load the chain in `store`, as load_from_file() does a file, and keep the
+    /// store: persist() writes back to it from then on.
+    pub fn from_store(store: Box<dyn ChainStore>) -> Result<Self, String> {
+        // the meta keys are ChainSettings' field names
+        let keys = serde_json::to_value(Self::with_defaults(vec![], MIN_DIFFICULTY_BITS, Arc::new(SystemClock)).settings())
+            .map_err(|e| e.to_string())?;
+        let mut meta = serde_json::Map::new();
+        for key in keys.as_object().into_iter().flat_map(|k| k.keys()) {
+            if let Some(value) = store.get_meta(key)? {
+                meta.insert(key.clone(), value);
+            }
+        }
+        let settings: ChainSettings = serde_json::from_value(meta.into())
+            .map_err(|e| format!("the store's chain settings don't parse: {}", e))?;
+        let pruned: Option<PruneState> = store.get_meta("pruned")?.map(serde_json::from_value).transpose()
+            .map_err(|e| format!("the store's prune point doesn't parse: {}", e))?;
+        let chain = store.iter().collect::<Result<Vec<Block>, String>>()?;
+        let mut bc = Self::from_saved(settings, pruned, chain, "the store")?;
+        bc.store = Some(Mutex::new(store));
+        Ok(bc)
+    }
+
+    // a saved chain with its settings, validated; `source` names it in errors
+    fn from_saved(settings: ChainSettings, pruned: Option<PruneState>, chain: Vec<Block>, source: &str) -> Result<Self, String> {
+        if settings.hash_version != HASH_VERSION {
+            return Err(format!("{} was written with hash version {} and this node hashes with version {}, \
+                so none of its hashes would check out — start a new chain", source, settings.hash_version, HASH_VERSION));
+        }
+        let mut bc = Self::with_defaults(chain, settings.difficulty, Arc::new(SystemClock));
+        bc.reward = settings.reward;
+        bc.halving_interval = settings.halving_interval;
+        bc.max_supply_nits = settings.max_supply_nits;
+        bc.max_transactions_per_block = settings.max_transactions_per_block;
+        bc.coinbase_maturity = settings.coinbase_maturity;
+        bc.target_block_time_secs = settings.target_block_time_secs;
+        bc.retarget_interval = settings.retarget_interval;
+        if bc.chain.is_empty() {
+            return Err(format!("{} has no blocks", source));
+        }
+        if let Some(pruned) = pruned {
+            if pruned.height == 0 || pruned.height > bc.chain.len() {
+                return Err(format!("{} is pruned below #{} but has {} blocks", source, pruned.height, bc.chain.len()));
+            }
+            // any bodies left below the prune point are already counted in it
+            for block in &mut bc.chain[..pruned.height] {
+                block.transactions.clear();
+            }
+            bc.pruned = Some(pruned);
+        }
+        bc.difficulty = bc.difficulty_for_height(bc.chain.len());
+        bc.validate().map_err(|e| format!("Refusing to load {}: {}", source, e))?;
+        bc.rebuild_indexes();
+        info!(blocks = bc.chain.len(), source, "chain loaded");
+        Ok(bc)
+    }
+
+    /// Keep this chain in `store` from now on, writing it there in full first.
+    pub fn attach_store(&mut self, mut store: Box<dyn ChainStore>) -> Result<usize, String> {
+        let written = self.write_to_store(store.as_mut())?;
+        self.store = Some(Mutex::new(store));
+        Ok(written)
+    }
+
+    pub fn has_store(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Bring the attached store up to date; returns how many blocks were written.
+    pub fn persist(&self) -> Result<usize, String> {
+        let store = self.store.as_ref().ok_or("no store attached — use save_to_file")?;
+        self.write_to_store(store.locked().as_mut())
+    }
+
+    /// Make `store` hold this chain. Blocks it already has stay put: only the
+    /// ones past the last block we share are dropped, and ours appended from
+    /// there, so a save after mining one block writes one block.
+    pub fn write_to_store(&self, store: &mut dyn ChainStore) -> Result<usize, String> {
+        let stored = store.len()?;
+        let mut shared = stored.min(self.chain.len());
+        // blocks the store pruned further than we did are rewritten with their bodies
+        let pruned = serde_json::to_value(&self.pruned).map_err(|e| e.to_string())?;
+        let stored_pruned = store.get_meta("pruned")?;
+        let stored_below = stored_pruned.as_ref()
+            .and_then(|p| p.get("height")?.as_u64())
+            .unwrap_or(0) as usize;
+        if stored_below > self.pruned_below() {
+            shared = shared.min(self.pruned_below());
+        }
+        while shared > 0 && store.get_block_by_index(shared - 1)?.is_none_or(|b| b.hash != self.chain[shared - 1].hash) {
+            shared -= 1;
+        }
+        if shared < stored {
+            store.truncate(shared)?;
+        }
+        for block in &self.chain[shared..] {
+            store.append_block(block)?;
+        }
+        // an unpruned chain writes no entry at all
+        if stored_pruned.unwrap_or_default() != pruned {
+            store.prune_below(self.pruned_below())?;
+            store.put_meta("pruned", pruned)?;
+        }
+        let settings = serde_json::to_value(self.settings()).map_err(|e| e.to_string())?;
+        for (key, value) in settings.as_object().into_iter().flatten() {
+            if store.get_meta(key)?.as_ref() != Some(value) {
+                store.put_meta(key, value.clone())?;
+            }
+        }
+        store.flush()?;
+        Ok(self.chain.len() - shared)
+    }
+
+    /// Sum of every block's work — the heavier chain wins a fork, not the longer one.
+    pub fn total_work(&self) -> u128 {
+        chain_work(&self.chain)
+    }
+
+    /// The highest height where `other_headers`, from another chain, has the
+    /// same block as ours. None if they share nothing, not even genesis.
+    pub fn find_fork_point(&self, other_headers: &[BlockHeader]) -> Option<u32> {
+        other_headers.iter().rev()
+            .find(|h| self.hash_index.get(&h.hash) == Some(&(h.index as usize)))
+            .map(|h| h.index)
+    }
+
+    /// Our blocks above `height`, lowest first.
+    pub fn blocks_after(&self, height: usize) -> &[Block] {
+        self.chain.get(height + 1..).unwrap_or(&[])
+    }
+
+    /// Adopt `candidate` if it's a valid chain from the same genesis with more total
+    /// work than ours (on a tie in work, more blocks).
+    /// Only its blocks above the fork are checked: ours above it are detached,
+    /// theirs attached one by one with the checks try_append_block() runs, and
+    /// if one fails ours go back. Transactions from the blocks we drop go back in
+    /// the mempool unless the new chain already has them (or they no longer fit —
+    /// a nonce or balance the new chain used up).
+    pub fn replace_chain(&mut self, mut candidate: Vec<Block>) -> Result<ReplaceOutcome, ChainError> {
+        // what they claim first, so a chain that doesn't even claim more work costs nothing
+        let ours = (self.total_work(), self.chain.len());
+        let claimed = (chain_work(&candidate), candidate.len());
+        if claimed <= ours {
+            return Err(ChainError::ChainNotBetter { ours_work: ours.0, theirs_work: claimed.0 });
+        }
+        if candidate[0].hash != self.chain[0].hash {
+            return Err(ChainError::GenesisMismatch);
+        }
+        // the fork point is a height, so each block has to sit at the one it claims
+        if let Some((_, b)) = candidate.iter().enumerate().find(|(i, b)| b.index as usize != *i) {
+            return Err(ChainError::BrokenLink { index: b.index });
+        }
+        let headers: Vec<BlockHeader> = candidate.iter().map(Block::header).collect();
+        let fork_height = self.find_fork_point(&headers).ok_or(ChainError::GenesisMismatch)? as usize + 1;
+        // we have no indexes below the prune point to attach onto
+        if fork_height < self.pruned_below() {
+            return self.replace_whole_chain(candidate);
+        }
+
+        // our blocks below the fork stand in for theirs, so the work is ours up to there
+        let suffix = candidate.split_off(fork_height);
+        let theirs = (chain_work(&self.chain[..fork_height]).saturating_add(chain_work(&suffix)), fork_height + suffix.len());
+        if theirs <= ours {
+            return Err(ChainError::ChainNotBetter { ours_work: ours.0, theirs_work: theirs.0 });
+        }
+        // signatures for the whole suffix at once across threads, consumed in block order
+        let mut signatures = suffix.par_iter()
+            .map(|b| b.validate_transactions())
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        let old_height = self.chain.len() - 1;
+        self.detach_to(fork_height);
+        let abandoned = self.chain.split_off(fork_height);
+        self.checkpoint.locked().take();
+        for block in suffix {
+            let attached = match block.index as usize == self.chain.len() {
+                true  => self.check_next_block(&block, || signatures.next().unwrap_or(Ok(()))),
+                false => Err(ChainError::BrokenLink { index: block.index }),
+            };
+            if let Err(e) = attached {
+                warn!(block = block.index, error = %e, "reorg failed, our blocks restored");
+                self.detach_to(fork_height);
+                self.chain.truncate(fork_height);
+                for block in abandoned {
+                    self.chain.push(block);
+                    self.index_block(self.chain.len() - 1);
+                }
+                return Err(e);
+            }
+            self.chain.push(block);
+            self.index_block(self.chain.len() - 1);
+        }
+        self.difficulty = self.difficulty_for_height(self.chain.len());
+        let returned_to_mempool = self.requeue(&abandoned);
+
+        info!(old_height, new_height = self.chain.len() - 1, fork_height,
+            blocks_abandoned = abandoned.len(), "chain replaced");
+        Ok(ReplaceOutcome {
+            old_height,
+            new_height: self.chain.len() - 1,
+            fork_height,
+            blocks_abandoned: abandoned.len(),
+            returned_to_mempool,
+        })
+    }
+
+    // unindex every block from the tip down to `height`, leaving them in the chain
+    fn detach_to(&mut self, height: usize) {
+        for tip in (height..self.chain.len()).rev() {
+            self.unindex_block(tip);
+        }
+    }
+
+    // replace_chain() for a fork below our prune point: `candidate` has to
+    // bring every body, and is checked whole from genesis
+    fn replace_whole_chain(&mut self, candidate: Vec<Block>) -> Result<ReplaceOutcome, ChainError> {
+        let mut incoming = self.with_chain(candidate);
+        incoming.validate()?;
+
+        let fork_height = self.chain.iter().zip(&incoming.chain)
+            .position(|(ours, theirs)| ours.hash != theirs.hash)
+            .unwrap_or(self.chain.len());
+        let old_height = self.chain.len() - 1;
+        let abandoned = self.chain.split_off(fork_height);
+        self.unprune_from(&incoming.chain);
+        self.chain.extend(incoming.chain.drain(fork_height..));
+        let returned_to_mempool = self.after_reorg(&abandoned);
+
+        info!(old_height, new_height = self.chain.len() - 1, fork_height,
+            blocks_abandoned = abandoned.len(), "chain replaced");
+        Ok(ReplaceOutcome {
+            old_height,
+            new_height: self.chain.len() - 1,
+            fork_height,
+            blocks_abandoned: abandoned.len(),
+            returned_to_mempool,
+        })
+    }
+
+    /// Swap in `blocks` wholesale — a chain exported from a node or its save
+    /// file — once it checks out from genesis under our settings. The rules are
+    /// replace_chain()'s: the same genesis as ours, and more work unless `force`,
+    /// which lets an operator go back to a shorter chain on purpose. Transactions
+    /// only the old chain had go back in the mempool; ones the new chain confirms leave it.
+    pub fn import_chain(&mut self, blocks: Vec<Block>, force: bool) -> Result<ImportOutcome, ChainError> {
+        let mut incoming = self.with_chain(blocks);
+        if incoming.chain.first().is_some_and(|genesis| genesis.hash != self.chain[0].hash) {
+            return Err(ChainError::GenesisMismatch);
+        }
+        let ours = (self.total_work(), self.chain.len());
+        let theirs = (incoming.total_work(), incoming.chain.len());
+        if !force && theirs <= ours {
+            return Err(ChainError::ChainNotBetter { ours_work: ours.0, theirs_work: theirs.0 });
+        }
+        incoming.check_from_genesis()?;
+
+        let fork_height = self.chain.iter().zip(&incoming.chain)
+            .position(|(ours, theirs)| ours.hash != theirs.hash)
+            .unwrap_or(self.chain.len().min(incoming.chain.len()));
+        let old_length = self.chain.len();
+        let old_tip_hash = self.chain[old_length - 1].hash.clone();
+        let abandoned = self.chain.split_off(fork_height);
+        self.unprune_from(&incoming.chain);
+        self.chain.extend(incoming.chain.drain(fork_height..));
+        let returned_to_mempool = self.after_reorg(&abandoned);
+        // validated from genesis just now, so the new chain's checkpoint holds as is
+        *self.checkpoint.locked() = incoming.checkpoint.locked().take();
+
+        let new_tip_hash = self.chain[self.chain.len() - 1].hash.clone();
+        info!(old_length, new_length = self.chain.len(), fork_height, force, "chain imported");
+        Ok(ImportOutcome {
+            old_length,
+            old_tip_hash,
+            new_length: self.chain.len(),
+            new_tip_hash,
+            fork_height,
+            blocks_abandoned: abandoned.len(),
+            returned_to_mempool,
+        })
+    }
+
+    /// Pop `n_blocks` off the tip (never the genesis) and return them, newest last.
+    /// Their transactions go back through add_transaction(), so anything whose
+    /// nonce or funds no longer check out is dropped.
+    pub fn rollback(&mut self, n_blocks: usize) -> Result<Vec<Block>, ChainError> {
+        if n_blocks == 0 {
+            return Err(ChainError::InvalidRollback("nothing to roll back — n_blocks is 0".to_string()));
+        }
+        let height = self.chain.len() - 1;
+        if n_blocks > height {
+            return Err(ChainError::InvalidRollback(
+                format!("can't roll back {} blocks — only {} above genesis", n_blocks, height)));
+        }
+        if self.chain.len() - n_blocks < self.pruned_below() {
+            return Err(ChainError::InvalidRollback(format!(
+                "can't roll back {} blocks — blocks below #{} are pruned", n_blocks, self.pruned_below())));
+        }
+        let removed = self.chain.split_off(self.chain.len() - n_blocks);
+        let returned = self.after_reorg(&removed);
+        info!(blocks = n_blocks, height = self.chain.len() - 1, returned_to_mempool = returned, "rolled back");
+        Ok(removed)
+    }
+
+    /// A chain that forks below our prune point came with every body (with_chain()
+    /// saw to that), so the pruned blocks we share with it get theirs back and
+    /// the chain is whole again, until the next prune_below().
+    fn unprune_from(&mut self, incoming: &[Block]) {
+        let shared = self.chain.len();
+        if shared < self.pruned_below() {
+            self.chain.clone_from_slice(&incoming[..shared]);
+            self.pruned = None;
+            info!(fork_height = shared, "reorg below the prune point, bodies restored from the new chain");
+        }
+    }
+
+    /// Bring difficulty, indexes and mempool in line after blocks were removed
+    /// from (or swapped out of) the tip. Transactions from `abandoned` are
+    /// re-queued ahead of what was already pending, skipping anything the chain
+    /// now contains. Returns how many abandoned transactions made it back.
+    fn after_reorg(&mut self, abandoned: &[Block]) -> usize {
+        self.checkpoint.locked().take();
+        self.difficulty = self.difficulty_for_height(self.chain.len());
+        self.rebuild_indexes();
+        self.requeue(abandoned)
+    }
+
+    // after_reorg()'s mempool half, for when the indexes are already up to date
+    fn requeue(&mut self, abandoned: &[Block]) -> usize {
+        let old_mempool = std::mem::take(&mut self.mempool);
+        let requeue: Vec<Transaction> = abandoned.iter()
+            .flat_map(|b| b.transactions.split_last().map(|(_, rest)| rest).unwrap_or(&[]))
+            .cloned()
+            .collect();
+        let mut returned = 0;
+        for txn in requeue {
+            if !self.txid_index.contains_key(&txn.id()) && self.add_transaction(txn).is_ok() {
+                returned += 1;
+            }
+        }
+        for txn in old_mempool.mining_order() {
+            if !self.txid_index.contains_key(&txn.id()) {
+                let _ = self.add_transaction(txn.clone());
+            }
+        }
+        self.prune_to_depth();
+        returned
+    }
+
+    /// A fresh Blockchain with our consensus settings around someone else's blocks.
+    /// Indexes and mempool start empty — meant for validating before adopting.
+    /// If `chain` runs through our prune point it's pruned there like ours; if
+    /// not, it has to bring every body.
+    fn with_chain(&self, chain: Vec<Block>) -> Blockchain {
+        let pruned = self.pruned.clone().filter(|p| {
+            chain.get(p.height - 1).is_some_and(|b| b.hash == self.chain[p.height - 1].hash)
+        });
+        Blockchain {
+            chain,
+            difficulty: self.difficulty,
+            mempool: Mempool::new(),
+            reward: self.reward,
+            halving_interval: self.halving_interval,
+            max_supply_nits: self.max_supply_nits,
+            max_transactions_per_block: self.max_transactions_per_block,
+            coinbase_maturity: self.coinbase_maturity,
+            target_block_time_secs: self.target_block_time_secs,
+            retarget_interval: self.retarget_interval,
+            mining_threads: self.mining_threads,
+            nonce_space: self.nonce_space,
+            allow_empty_blocks: self.allow_empty_blocks,
+            max_mempool_txs: self.max_mempool_txs,
+            max_mempool_bytes: self.max_mempool_bytes,
+            min_relay_fee: self.min_relay_fee,
+            clock: self.clock.clone(),
+            max_future_skew_secs: self.max_future_skew_secs,
+            cancel_mining: Arc::new(AtomicBool::new(false)),
+            mining_status: Arc::new(Mutex::new(MiningStatus::default())),
+            prune_depth: self.prune_depth,
+            hash_index: HashMap::new(),
+            txid_index: HashMap::new(),
+            address_index: HashMap::new(),
+            balances: HashMap::new(),
+            address_tallies: HashMap::new(),
+            totals: ChainTotals::default(),
+            pruned,
+            checkpoint: Mutex::new(None),
+            store: None,
+            mempool_wal: None,
         }
     }
 
     /// Add a signed transaction to the mempool.
-    /// Rejects invalid or unsigned transactions immediately.
-    pub fn add_transaction(&mut self, txn: Transaction) -> Result<(), String> {
+    /// Rejects invalid or unsigned transactions immediately, exact copies of one
+    /// already pending or confirmed, and anything whose nonce isn't the sender's next one.
+    /// Returns the txid so the caller can track it.
+    pub fn add_transaction(&mut self, txn: Transaction) -> Result<String, ChainError> {
+        self.admit_transaction(txn).map(|a| a.txid)
+    }
+
+    // add_transaction(), returning what it changed in the mempool so it can be undone
+    fn admit_transaction(&mut self, txn: Transaction) -> Result<Admission, ChainError> {
+        // only mine_pending_transactions() gets to create coins
+        if txn.from == "NETWORK" {
+            return Err(ChainError::NetworkTransaction);
+        }
+        let txid = txn.id();
+        if let Some(&(height, _)) = self.txid_index.get(&txid) {
+            return Err(ChainError::AlreadyConfirmed { index: height });
+        }
+        if self.mempool.contains(&txid) {
+            return Err(ChainError::AlreadyInMempool);
+        }
         txn.validate()?;
-        println!("  📥 Mempool: {}", txn.display());
-        self.mempool.push(txn);
-        Ok(())
+
+        // replace-by-fee: same sender + nonce as a pending tx, but paying more
+        let replacing = self.mempool.get_by_nonce(&txn.from, txn.nonce).map(|t| t.id());
+        if let Some(old) = self.mempool.get_by_nonce(&txn.from, txn.nonce) {
+            if txn.fee <= old.fee {
+                return Err(ChainError::ReplacementFeeTooLow { old: old.fee, new: txn.fee });
+            }
+        } else {
+            let expected = self.next_nonce(&txn.from);
+            if txn.nonce != expected {
+                return Err(ChainError::BadNonce { expected, got: txn.nonce });
+            }
+        }
+        self.check_funds(&txn, replacing.as_deref())?;
+        // a replacement takes an existing slot, so only newcomers can overflow the pool
+        let mut evicted = vec![];
+        if replacing.is_none() {
+            for txid in self.eviction_plan(&txn)? {
+                if let Some(old) = self.mempool.take(&txid) {
+                    debug!(txid = %short(&txid, 12), fee = old.txn().fee_as_tokens(), "mempool full, evicted");
+                    evicted.push(old);
+                }
+            }
+        }
+
+        let fee = txn.fee_as_tokens();
+        let replaced = match replacing {
+            Some(_) => {
+                let old = self.mempool.replace(txn);
+                if let Some(old) = &old {
+                    debug!(old = %short(&old.id(), 12), old_fee = old.fee_as_tokens(),
+                        txid = %short(&txid, 12), fee, "mempool replace-by-fee");
+                }
+                old
+            }
+            None => {
+                debug!(txid = %short(&txid, 12), from = %short(&txn.from, 12), amount = txn.amount_as_tokens(), fee, "mempool add");
+                self.mempool.push(txn);
+                None
+            }
+        };
+        Ok(Admission { txid, evicted, replaced })
+    }
+
+    /// Total raw size of everything pending.
+    pub fn mempool_bytes(&self) -> usize {
+        self.mempool.bytes()
     }
 
-    /// Mine all mempool transactions into a new block.
-    /// Miner receives reward as a coinbase transaction.
-    pub fn mine_pending_transactions(&mut self, miner_address: String) -> Result<(), String> {
-        if self.mempool.is_empty() {
-            return Err("Mempool is empty — nothing to mine".to_string());
+    /// Suggested fee in nits to get mined within `target_blocks` blocks.
+    /// If the mempool is deeper than that many blocks can hold, outbid whatever
+    /// sits at the cut-off. Otherwise, if recent blocks were full, pay their median fee.
+    /// Falls back to min_relay_fee when there's no competition.
+    pub fn estimate_fee(&self, target_blocks: usize) -> u64 {
+        let capacity = self.max_transactions_per_block.saturating_mul(target_blocks.max(1));
+        if self.mempool.len() >= capacity {
+            if let Some(cutoff) = self.mempool.mining_order().get(capacity.saturating_sub(1)) {
+                return cutoff.fee.saturating_add(1).max(self.min_relay_fee);
+            }
         }
-        println!("\n⛏️  Mining block #{}...", self.chain.len());
-        let reward_txn = Transaction::new_nits("NETWORK".to_string(), miner_address.clone(), self.reward);
-        let mut transactions: Vec<Transaction> = self.mempool.drain(..).collect();
-        transactions.push(reward_txn);
-        let previous_hash = self.chain.last()
-            .ok_or("Chain is empty")?.hash.clone();
+
+        let recent = &self.chain[self.chain.len().saturating_sub(FEE_ESTIMATE_WINDOW)..];
+        let full = recent.iter()
+            .any(|b| b.transactions.len().saturating_sub(1) >= self.max_transactions_per_block);
+        if !full {
+            return self.min_relay_fee;
+        }
+        let mut fees: Vec<u64> = recent.iter()
+            .filter_map(|b| b.transactions.split_last())
+            .flat_map(|(_, rest)| rest.iter().map(|t| t.fee))
+            .collect();
+        fees.sort_unstable();
+        fees.get(fees.len() / 2).copied().unwrap_or(0).max(self.min_relay_fee)
+    }
+
+    /// Txids to drop so `txn` fits under the caps — cheapest fee rate first.
+    /// Only a sender's highest pending nonce is a candidate, so nothing left behind
+    /// has a gap before it, and the newcomer's own sender is never touched.
+    /// Errs with MempoolFull if `txn` doesn't pay a better rate than what it would displace.
+    fn eviction_plan(&self, txn: &Transaction) -> Result<Vec<String>, ChainError> {
+        let size = txn.size();
+        let rate = FeeRate::sized(txn.fee, size);
+        let mut count = self.mempool.len() + 1;
+        let mut bytes = self.mempool_bytes().saturating_add(size);
+        let mut evict: Vec<String> = vec![];
+        let mut candidates = self.mempool.eviction_order(&txn.from);
+
+        while count > self.max_mempool_txs || bytes > self.max_mempool_bytes {
+            match candidates.next() {
+                Some((t, victim_rate, victim_size)) if rate > victim_rate => {
+                    count -= 1;
+                    bytes -= victim_size;
+                    evict.push(t.id());
+                }
+                _ => return Err(ChainError::MempoolFull),
+            }
+        }
+        Ok(evict)
+    }
+
+    /// Mine up to max_transactions_per_block pending transactions into a new block.
+    /// Miner receives reward plus all fees as a coinbase transaction.
+    /// An empty mempool is EmptyMempool unless `force` or allow_empty_blocks is
+    /// set — then the block is just the coinbase.
+    /// Stops early (Err) if cancel_mining is set while the nonce search runs.
+    pub fn mine_pending_transactions(&mut self, miner_address: String, force: bool) -> Result<(), ChainError> {
+        let _span = info_span!("mine_pending_transactions", block = self.chain.len()).entered();
+        let mut block = self.prepare_block(miner_address, force)?;
+        self.cancel_mining.store(false, Ordering::Relaxed);
+        block.mine_tracked(block.difficulty, self.mining_threads, self.nonce_space,
+            &self.cancel_mining, &self.mining_status)?;
+        self.commit_block(block)
+    }
+
+    /// Up to max_transactions_per_block pending transactions, in mempool mining order
+    /// (best fee rate first, arrival order on ties, each sender's in nonce order),
+    /// except that a transaction spending funds credited by another pending one is
+    /// placed after that credit. Timelocked ones stay pending until their height —
+    /// and so does everything their sender queued after them, since those nonces
+    /// can't skip ahead. Same for anything the confirmed balances plus the credits
+    /// already in the block can't pay for.
+    pub fn select_transactions(&self) -> Vec<Transaction> {
+        let height = self.chain.len() as u32;
+        let mut held_back: HashSet<&str> = HashSet::new();
+        let mut waiting: Vec<&Transaction> = self.mempool.mining_order().into_iter()
+            .filter(|t| {
+                if held_back.contains(t.from.as_str()) {
+                    return false;
+                }
+                if !t.is_unlocked_at(height) {
+                    held_back.insert(&t.from);
+                    return false;
+                }
+                true
+            })
+            .collect();
+
+        // Repeated passes in mining order, each taking whatever is paid for by now.
+        // A sender whose next transaction has to wait for a credit is skipped for the
+        // rest of the pass, so their nonces still go in ascending.
+        // what each address can spend before this block: rewards still maturing don't count
+        let spendable = |address: &str| self.balances.get(address).copied().unwrap_or(0)
+            .saturating_sub(self.immature_balance(address, self.chain.len()));
+        let mut balances: HashMap<String, u64> = HashMap::new();
+        let mut selected: Vec<Transaction> = Vec::new();
+        loop {
+            let before = selected.len();
+            let mut blocked: HashSet<&str> = HashSet::new();
+            waiting.retain(|&t| {
+                if selected.len() >= self.max_transactions_per_block || blocked.contains(t.from.as_str()) {
+                    return true;
+                }
+                let have = *balances.entry(t.from.clone()).or_insert_with(|| spendable(&t.from));
+                if t.from != "NETWORK" && t.total_cost().is_none_or(|need| need > have) {
+                    blocked.insert(&t.from);
+                    return true;
+                }
+                for output in &t.outputs {
+                    balances.entry(output.to.clone()).or_insert_with(|| spendable(&output.to));
+                }
+                apply_transfer(&mut balances, t);
+                selected.push(t.clone());
+                false
+            });
+            if selected.len() == before || selected.len() >= self.max_transactions_per_block {
+                return selected;
+            }
+        }
+    }
+
+    /// Build the next (unmined) block from the best of the mempool.
+    /// Doesn't touch the mempool, so the nonce search can run without holding the chain.
+    pub fn prepare_block(&self, miner_address: String, force: bool) -> Result<Block, ChainError> {
+        let block = self.build_block_template(miner_address, force)?.to_block();
+        debug!(block = block.index, transactions = block.transactions.len(), "block prepared for mining");
+        Ok(block)
+    }
+
+    /// What the next block should contain: select_transactions() from the mempool
+    /// (left untouched), the coinbase amount they earn, difficulty and a timestamp.
+    /// With an empty mempool that's EmptyMempool, unless `force` or
+    /// allow_empty_blocks asks for a coinbase-only block.
+    pub fn build_block_template(&self, miner_address: String, force: bool) -> Result<BlockTemplate, ChainError> {
+        if self.mempool.is_empty() && !(force || self.allow_empty_blocks) {
+            return Err(ChainError::EmptyMempool);
+        }
+        let transactions = self.select_transactions();
+        let fees = transactions.iter()
+            .try_fold(0u64, |acc, t| acc.checked_add(t.fee))
+            .ok_or(ChainError::BalanceOverflow)?;
         let index = self.chain.len() as u32;
-        let mut new_block = Block::new(index, transactions, previous_hash, miner_address);
-        new_block.mine(&self.difficulty);
-        self.chain.push(new_block);
-        println!("  ✅ Block #{} confirmed\n", self.chain.len() - 1);
+        let subsidy = self.subsidy_at(index, self.total_supply());
+        let coinbase_amount = subsidy.checked_add(fees).ok_or(ChainError::BalanceOverflow)?;
+        let previous_hash = self.chain.last()
+            .ok_or(ChainError::EmptyChain)?.hash.clone();
+        let timestamp = self.clock.now_secs();
+        if timestamp == 0 {
+            return Err(ChainError::ClockUnavailable);
+        }
+        let difficulty = self.difficulty_for_height(self.chain.len());
+        Ok(BlockTemplate {
+            index,
+            previous_hash,
+            transactions,
+            coinbase_amount,
+            difficulty,
+            target: hex::encode(target(difficulty)),
+            timestamp,
+            miner: miner_address,
+        })
+    }
+
+    /// Append a block built by prepare_block() once it's been mined.
+    /// Fails if the tip moved or its transactions left the mempool in the meantime.
+    pub fn commit_block(&mut self, block: Block) -> Result<(), ChainError> {
+        let tip = self.chain.last().ok_or(ChainError::EmptyChain)?;
+        if block.index as usize != self.chain.len() || block.previous_hash != tip.hash {
+            return Err(ChainError::StaleTip);
+        }
+        if !block.meets_difficulty() || block.hash != block.calculate_hash() {
+            return Err(ChainError::NotMined { index: block.index });
+        }
+        self.check_timestamp(self.chain.len(), &block)?;
+        check_locks(&block)?;
+        self.check_maturity(self.chain.len(), &block,
+            |a| self.balances.get(a).copied().unwrap_or(0))?;
+        let (_, txns) = block.transactions.split_last().ok_or(ChainError::InvalidCoinbase {
+            index: block.index, reason: "block has no coinbase".to_string(),
+        })?;
+        let txids: Vec<String> = txns.iter().map(|t| t.id()).collect();
+        if txids.iter().any(|id| self.mempool.get(id).is_none()) {
+            return Err(ChainError::MempoolChanged);
+        }
+        self.mempool.retain(|t| !txids.contains(&t.id()));
+        self.push_block(block);
         Ok(())
     }
 
-    /// Replay every transaction from genesis to get current balance.
-    pub fn get_balance(&self, address: &str) -> Result<f64, String> {
-        let mut balance: u64 = 0;
-        for block in &self.chain {
-            for txn in &block.transactions {
-                if txn.to == address {
-                    balance = balance.checked_add(txn.amount)
-                        .ok_or("Balance overflow")?;
+    /// Append a fully formed block from another node or an external miner.
+    /// It has to extend our current tip — anything built on an older one is StaleTip,
+    /// so the miner knows to fetch fresh work, and one whose parent we've never seen
+    /// is UnknownParent: we're behind, or on another branch. That sends us syncing,
+    /// so the block has to carry its own proof of work at today's difficulty first.
+    /// Otherwise it gets every check validate() would run, plus check_spends().
+    /// Its transactions leave the mempool.
+    pub fn try_append_block(&mut self, block: Block) -> Result<(), ChainError> {
+        let height = self.chain.len();
+        let tip = self.chain.last().ok_or(ChainError::EmptyChain)?;
+        if block.index as usize > height || !self.hash_index.contains_key(&block.previous_hash) {
+            let index = block.index;
+            if block.hash != block.calculate_hash() {
+                return Err(ChainError::HashMismatch { index });
+            }
+            if !block.meets_difficulty() {
+                return Err(ChainError::DifficultyNotMet { index, difficulty: block.difficulty });
+            }
+            let expected = self.difficulty_for_height(height);
+            if block.difficulty < expected {
+                return Err(ChainError::WrongDifficulty { index, found: block.difficulty, expected });
+            }
+            return Err(ChainError::UnknownParent { index, parent: block.previous_hash });
+        }
+        if block.index as usize != height || block.previous_hash != tip.hash {
+            return Err(ChainError::StaleTip);
+        }
+        self.check_next_block(&block, || block.validate_transactions())?;
+
+        let txids: HashSet<String> = block.transactions.split_last()
+            .map(|(_, rest)| rest).unwrap_or(&[])
+            .iter().map(|t| t.id()).collect();
+        let foreign = txids.iter().any(|id| !self.mempool.contains(id));
+        self.mempool.retain(|t| !txids.contains(&t.id()));
+        debug!(block = block.index, miner = %short(&block.miner, 12), "external block accepted");
+        self.push_block(block);
+        // transactions we never saw may have used nonces or funds that pending ones counted on
+        if foreign {
+            let pending = std::mem::take(&mut self.mempool);
+            for txn in pending.mining_order() {
+                let _ = self.add_transaction(txn.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Every check a block going on top of our tip has to pass: validate()'s,
+    /// plus check_spends(). `signatures` checks its transactions, for callers
+    /// that verify those ahead of time.
+    fn check_next_block(&self, block: &Block, signatures: impl FnOnce() -> Result<(), ChainError>) -> Result<(), ChainError> {
+        let height = self.chain.len();
+        self.check_header(height, block)?;
+        signatures()?;
+        check_locks(block)?;
+        self.check_spends(block)?;
+        self.check_maturity(height, block, |a| self.balances.get(a).copied().unwrap_or(0))?;
+        self.check_issuance(block, self.totals.issued)?;
+        Ok(())
+    }
+
+    /// Put an already-checked block on top and index it, following its difficulty.
+    fn push_block(&mut self, block: Block) {
+        if block.difficulty != self.difficulty {
+            info!(old = self.difficulty, new = block.difficulty, "difficulty retarget");
+            self.difficulty = block.difficulty;
+        }
+        self.chain.push(block);
+        self.index_block(self.chain.len() - 1);
+        let tip = &self.chain[self.chain.len() - 1];
+        info!(block = tip.index, hash = %short(&tip.hash, 16), transactions = tip.transactions.len(), "block confirmed");
+        self.prune_to_depth();
+    }
+
+    /// Next nonce the sender must sign with: everything they've sent on chain
+    /// plus what they have waiting in the mempool.
+    pub fn next_nonce(&self, address: &str) -> u64 {
+        let pending = self.mempool.iter().filter(|t| t.from == address).count() as u64;
+        self.confirmed_sent(address) + pending
+    }
+
+    /// Confirmed balance minus what the sender already has queued in the mempool
+    /// must cover this transaction — otherwise it would overdraw once mined.
+    /// `replacing` is the txid an RBF bump will overwrite; it isn't counted.
+    fn check_funds(&self, txn: &Transaction, replacing: Option<&str>) -> Result<(), ChainError> {
+        let balance = self.get_balance_nits(&txn.from)?;
+        let have = balance.saturating_sub(self.immature_balance(&txn.from, self.chain.len()));
+        let pending = self.mempool.iter()
+            .filter(|t| t.from == txn.from && replacing.is_none_or(|id| t.id() != id))
+            .try_fold(0u64, |acc, t| t.total_cost().and_then(|c| acc.checked_add(c)))
+            .ok_or(ChainError::BalanceOverflow)?;
+        let need = txn.total_cost()
+            .and_then(|c| c.checked_add(pending))
+            .ok_or(ChainError::BalanceOverflow)?;
+        if need > balance {
+            return Err(ChainError::InsufficientFunds { have: balance, need });
+        }
+        if need > have {
+            return Err(ChainError::ImmatureCoinbase {
+                confirmations_needed: self.blocks_until_mature(&txn.from, self.chain.len(), balance, need),
+            });
+        }
+        Ok(())
+    }
+
+    /// Block rewards paid to `address` that a transaction in the block at `height`
+    /// can't spend yet — any from the last coinbase_maturity - 1 blocks before it.
+    pub fn immature_balance(&self, address: &str, height: usize) -> u64 {
+        self.immature_coinbases(address, height).iter()
+            .fold(0u64, |acc, (_, amount)| acc.saturating_add(*amount))
+    }
+
+    // (height, amount) of each still-maturing reward to `address`, oldest first
+    fn immature_coinbases(&self, address: &str, height: usize) -> Vec<(usize, u64)> {
+        let height = height.min(self.chain.len());
+        let first = (height + 1).saturating_sub(self.coinbase_maturity as usize).max(1);
+        (first..height)
+            .filter_map(|h| {
+                let coinbase = self.chain[h].transactions.last()?;
+                let amount = coinbase.amount_to(address);
+                (coinbase.from == "NETWORK" && amount > 0).then_some((h, amount))
+            })
+            .collect()
+    }
+
+    /// Blocks past `height` until `need` of `balance` is mature, assuming nothing else arrives.
+    fn blocks_until_mature(&self, address: &str, height: usize, balance: u64, need: u64) -> u32 {
+        let mut immature = self.immature_balance(address, height);
+        for (h, amount) in self.immature_coinbases(address, height) {
+            immature -= amount;
+            if balance.saturating_sub(immature) >= need {
+                return (h + self.coinbase_maturity as usize - height) as u32;
+            }
+        }
+        0
+    }
+
+    /// Nothing in the block at `height` may spend a reward younger than coinbase_maturity.
+    /// `balance_before` gives each address's balance before this block.
+    fn check_maturity(&self, height: usize, block: &Block, balance_before: impl Fn(&str) -> u64) -> Result<(), ChainError> {
+        if self.coinbase_maturity <= 1 {
+            return Ok(());
+        }
+        let mut running: HashMap<String, u64> = HashMap::new();
+        for (position, txn) in block.transactions.iter().enumerate() {
+            for address in std::iter::once(&txn.from).chain(txn.outputs.iter().map(|o| &o.to)) {
+                running.entry(address.clone()).or_insert_with(|| balance_before(address));
+            }
+            if txn.from != "NETWORK" {
+                let immature = self.immature_balance(&txn.from, height);
+                let balance = running[&txn.from];
+                let cost = txn.total_cost().unwrap_or(u64::MAX);
+                if immature > 0 && cost > balance.saturating_sub(immature) {
+                    return Err(ChainError::InvalidTransaction {
+                        index: block.index, position,
+                        source: Box::new(ChainError::ImmatureCoinbase {
+                            confirmations_needed: self.blocks_until_mature(&txn.from, height, balance, cost),
+                        }),
+                    });
                 }
-                if txn.from == address {
-                    balance = balance.checked_sub(txn.amount)
-                        .ok_or("Balance underflow — spending more than available")?;
+            }
+            apply_transfer(&mut running, txn);
+        }
+        Ok(())
+    }
+
+    /// Confirmed balance, in tokens.
+    pub fn get_balance(&self, address: &str) -> Result<f64, ChainError> {
+        Ok(self.get_balance_nits(address)? as f64 / NITS_PER_TOKEN as f64)
+    }
+
+    /// get_balance(), but only from a chain that passes validate() — the checkpoint
+    /// keeps that cheap. On an invalid chain it returns the validation error instead.
+    pub fn get_balance_strict(&self, address: &str) -> Result<f64, ChainError> {
+        self.validate()?;
+        self.get_balance(address)
+    }
+
+    /// Confirmed balance in raw nits — a lookup in the balance index, no replay.
+    /// Rejects malformed addresses instead of quietly reporting 0.
+    pub fn get_balance_nits(&self, address: &str) -> Result<u64, ChainError> {
+        if !is_valid_address(address) {
+            return Err(ChainError::InvalidAddress(
+                format!("{:?} is not 64 hex chars, a multisig address or NETWORK", short(address, 16))));
+        }
+        Ok(self.balances.get(address).copied().unwrap_or(0))
+    }
+
+    /// Confirmed balance with every pending transaction touching `address` applied —
+    /// what it'll be once the mempool is mined. Saturates at zero.
+    pub fn get_pending_balance(&self, address: &str) -> Result<u64, ChainError> {
+        let confirmed = self.get_balance_nits(address)?;
+        let incoming: u64 = self.mempool.iter()
+            .fold(0, |acc, t| acc.saturating_add(t.amount_to(address)));
+        Ok(confirmed.saturating_add(incoming).saturating_sub(self.pending_outgoing(address)))
+    }
+
+    /// Amount plus fee of everything `address` has waiting in the mempool.
+    pub fn pending_outgoing(&self, address: &str) -> u64 {
+        self.mempool.iter()
+            .filter(|t| t.from == address)
+            .fold(0, |acc, t| acc.saturating_add(t.total_cost().unwrap_or(u64::MAX)))
+    }
+
+    /// The `n` largest confirmed balances, straight from the balance index.
+    /// NETWORK, the burn address and empty addresses are left out; equal balances sort by address.
+    pub fn top_holders(&self, n: usize) -> Vec<(String, u64)> {
+        let mut holders: Vec<(&String, &u64)> = self.balances.iter()
+            .filter(|(address, &balance)| balance > 0 && address.as_str() != "NETWORK" && address.as_str() != BURN_ADDRESS)
+            .collect();
+        holders.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        holders.into_iter()
+            .take(n)
+            .map(|(address, &balance)| (address.clone(), balance))
+            .collect()
+    }
+
+    /// Every non-zero confirmed balance, in nits, in address order.
+    pub fn balance_snapshot(&self) -> Vec<(String, u64)> {
+        let mut balances: Vec<(String, u64)> = self.balances.iter()
+            .filter(|(_, &balance)| balance > 0)
+            .map(|(address, &balance)| (address.clone(), balance))
+            .collect();
+        balances.sort();
+        balances
+    }
+
+    /// One CSV row per confirmed transaction output, oldest first, under a
+    /// header line — the columns are in export.rs.
+    pub fn export_transactions_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        self.check_unpruned(0, self.chain.len()).map_err(io::Error::other)?;
+        writer.write_all(TRANSACTIONS_CSV_HEADER.as_bytes())?;
+        for (height, block) in self.chain.iter().enumerate() {
+            write_transaction_rows(&mut writer, height, block)?;
+        }
+        writer.flush()
+    }
+
+    /// balance_snapshot() as one JSON object of address → tokens.
+    pub fn export_balances_json(&self, mut writer: impl Write) -> io::Result<()> {
+        write_balances_json(&mut writer, &self.balance_snapshot())?;
+        writer.flush()
+    }
+
+    /// Every balance replayed from genesis (from the prune point's snapshot on a
+    /// pruned chain), ignoring the index — for checking it.
+    /// Unlike the index this fails if any transaction ever overdrew its sender,
+    /// with InvalidOrdering if it was only paid for later in its own block.
+    pub fn recompute_balances(&self) -> Result<HashMap<String, u64>, ChainError> {
+        let mut balances = self.pruned.as_ref().map(|p| p.balances.clone()).unwrap_or_default();
+        for block in &self.chain[self.pruned_below()..] {
+            replay_block(&mut balances, block)?;
+        }
+        Ok(balances)
+    }
+
+    /// Check every block after the last checkpoint, plus its link into it.
+    /// Blocks at or below the checkpoint were verified by an earlier call;
+    /// validate_full() re-checks them too.
+    pub fn validate(&self) -> Result<(), ChainError> {
+        self.validate_genesis()?;
+        // only trust the checkpoint if the block it names is still there, untouched
+        let checkpoint = self.checkpoint.locked().take().filter(|cp| {
+            self.chain.get(cp.height)
+                .is_some_and(|b| b.hash == cp.hash && b.hash == b.calculate_hash())
+        });
+        let (start, issued) = checkpoint.map_or((1, 0), |cp| (cp.height + 1, cp.issued));
+        let issued = self.validate_range(start, issued)
+            .inspect_err(|e| warn!(error = %e, "chain failed validation"))?;
+        let tip = self.chain.len() - 1;
+        *self.checkpoint.locked() = Some(Checkpoint {
+            height: tip, hash: self.chain[tip].hash.clone(), issued,
+        });
+        Ok(())
+    }
+
+    /// validate() from genesis, ignoring any checkpoint.
+    pub fn validate_full(&self) -> Result<(), ChainError> {
+        self.checkpoint.locked().take();
+        self.validate()
+    }
+
+    /// Verify blocks `start..` given `issued` subsidy before them. Returns issuance at the tip.
+    /// Below the prune point there are only headers to check; past it, issuance and
+    /// balances pick up from what the prune point saved.
+    /// Signatures — the slow part — are checked for all blocks at once across threads;
+    /// each result is then consumed in block order, so the error reported is always
+    /// the first failure walking up the chain, whichever thread found it.
+    fn validate_range(&self, start: usize, mut issued: u64) -> Result<u64, ChainError> {
+        let mut start = start.min(self.chain.len());
+        if let Some(pruned) = &self.pruned {
+            for i in start..pruned.height {
+                let _span = debug_span!("validate", block = i, pruned = true).entered();
+                self.check_header(i, &self.chain[i])?;
+            }
+            if start < pruned.height {
+                start = pruned.height;
+                issued = pruned.totals.issued;
+            }
+        }
+        // balances and each sender's next nonce as of `start`, to replay the new
+        // blocks onto — cheap, no signatures
+        let mut balances = self.pruned.as_ref().map(|p| p.balances.clone()).unwrap_or_default();
+        let mut nonces = self.pruned.as_ref().map(|p| p.sent.clone()).unwrap_or_default();
+        if start < self.chain.len() {
+            for txn in self.chain[self.pruned_below()..start].iter().flat_map(|b| &b.transactions) {
+                apply_transfer(&mut balances, txn);
+                if txn.from != "NETWORK" {
+                    *nonces.entry(txn.from.clone()).or_default() += 1;
                 }
             }
         }
-        Ok(balance as f64 / NITS_PER_TOKEN as f64)
+        let mut confirmed = HashMap::new();
+        let mut tx_checks = self.chain[start..].par_iter()
+            .map(|b| b.validate_transactions())
+            .collect::<Vec<_>>()
+            .into_iter();
+        for i in start..self.chain.len() {
+            let _span = debug_span!("validate", block = i).entered();
+            let current = &self.chain[i];
+            self.check_header(i, current)?;
+            tx_checks.next().unwrap_or(Ok(()))?;
+            check_locks(current)?;
+            check_sequence(i, current, &mut nonces, &mut confirmed)?;
+            self.check_maturity(i, current, |a| balances.get(a).copied().unwrap_or(0))?;
+            replay_block(&mut balances, current)?;
+            issued = self.check_issuance(current, issued)?;
+        }
+        Ok(issued)
+    }
+
+    /// Everything about the block at `height` that doesn't need its signatures checked:
+    /// merkle root, hash, link to the block below, proof of work at the scheduled
+    /// difficulty, timestamp and transaction count. A pruned block has no
+    /// transactions left to check the merkle root against.
+    fn check_header(&self, height: usize, block: &Block) -> Result<(), ChainError> {
+        let index = block.index;
+        if height >= self.pruned_below() && block.merkle_root != Block::compute_merkle_root(&block.transactions) {
+            return Err(ChainError::MerkleMismatch { index });
+        }
+        if block.hash != block.calculate_hash() {
+            return Err(ChainError::HashMismatch { index });
+        }
+        if block.previous_hash != self.chain[height - 1].hash {
+            return Err(ChainError::BrokenLink { index });
+        }
+        if !block.meets_difficulty() {
+            return Err(ChainError::DifficultyNotMet { index, difficulty: block.difficulty });
+        }
+        self.check_timestamp(height, block)?;
+        let scheduled = self.difficulty_for_height(height);
+        if block.difficulty != scheduled {
+            return Err(ChainError::WrongDifficulty {
+                index, found: block.difficulty, expected: scheduled,
+            });
+        }
+        let count = block.transactions.len().saturating_sub(1);
+        if count > self.max_transactions_per_block {
+            return Err(ChainError::TooManyTransactions { index, count, max: self.max_transactions_per_block });
+        }
+        Ok(())
     }
 
-    pub fn validate(&self) -> Result<(), String> {
-        for i in 1..self.chain.len() {
-            let current  = &self.chain[i];
-            let previous = &self.chain[i - 1];
-            let expected = current.calculate_hash();
-            if current.hash != expected {
-                return Err(format!("Block #{} hash mismatch", i));
+    /// Coinbase rules plus the supply cap. Returns the issuance including this block.
+    fn check_issuance(&self, block: &Block, issued_before: u64) -> Result<u64, ChainError> {
+        let subsidy = self.validate_coinbase(block, issued_before)?;
+        let issued = issued_before.checked_add(subsidy).ok_or(ChainError::BalanceOverflow)?;
+        if self.max_supply_nits.is_some_and(|cap| issued > cap) {
+            return Err(ChainError::InvalidCoinbase {
+                index: block.index, reason: "issuance exceeds the maximum supply".to_string(),
+            });
+        }
+        Ok(issued)
+    }
+
+    /// What a block from elsewhere has to satisfy at our tip on top of the usual checks:
+    /// every transaction is new, in its sender's nonce order, and paid for by the
+    /// confirmed balances plus whatever earlier transactions in the block sent —
+    /// spending a credit that only comes further down is InvalidOrdering.
+    fn check_spends(&self, block: &Block) -> Result<(), ChainError> {
+        let mut balances: HashMap<String, u64> = HashMap::new();
+        let mut nonces: HashMap<&str, u64> = HashMap::new();
+        for (position, txn) in block.transactions.iter().enumerate() {
+            if txn.from == "NETWORK" {
+                continue;
+            }
+            let reject = |source: ChainError| Err(ChainError::InvalidTransaction {
+                index: block.index, position, source: Box::new(source),
+            });
+            if let Some(&(height, _)) = self.txid_index.get(&txn.id()) {
+                return reject(ChainError::AlreadyConfirmed { index: height });
+            }
+            let expected = nonces.entry(&txn.from).or_insert_with(|| self.confirmed_sent(&txn.from));
+            if txn.nonce != *expected {
+                return reject(ChainError::BadNonce { expected: *expected, got: txn.nonce });
+            }
+            *expected += 1;
+            let have = *balances.entry(txn.from.clone())
+                .or_insert_with(|| self.balances.get(&txn.from).copied().unwrap_or(0));
+            let need = txn.total_cost().ok_or(ChainError::BalanceOverflow)?;
+            if need > have {
+                return Err(overdraft(block, position, have, need));
             }
-            if current.previous_hash != previous.hash {
-                return Err(format!("Block #{} disconnected from chain", i));
+            for output in &txn.outputs {
+                balances.entry(output.to.clone())
+                    .or_insert_with(|| self.balances.get(&output.to).copied().unwrap_or(0));
+            }
+            apply_transfer(&mut balances, txn);
+        }
+        Ok(())
+    }
+
+    /// How many transactions `address` has sent on chain — through the address
+    /// index, plus what the prune point counted below it.
+    fn confirmed_sent(&self, address: &str) -> u64 {
+        let pruned = self.pruned.as_ref().and_then(|p| p.sent.get(address)).copied().unwrap_or(0);
+        pruned + self.address_index.get(address).map_or(0, |positions| {
+            positions.iter()
+                .filter(|&&(height, pos)| self.chain[height].transactions[pos].from == address)
+                .count() as u64
+        })
+    }
+
+    /// A block at `height` can't be older than the median of the MEDIAN_TIME_SPAN
+    /// blocks before it, or more than max_future_skew_secs ahead of our clock.
+    /// The median (not just the parent) lets honest clocks disagree a little.
+    fn check_timestamp(&self, height: usize, block: &Block) -> Result<(), ChainError> {
+        let bad = |reason: String| Err(ChainError::BadTimestamp { index: block.index, reason });
+        let mut recent: Vec<u64> = self.chain[height.saturating_sub(MEDIAN_TIME_SPAN)..height]
+            .iter().map(|b| b.timestamp).collect();
+        recent.sort_unstable();
+        if let Some(&median) = recent.get(recent.len() / 2) {
+            if block.timestamp < median {
+                return bad(format!("{} is before the median of recent blocks ({})", block.timestamp, median));
             }
-            current.validate_transactions()?;
+        }
+        let limit = self.clock.now_secs().saturating_add(self.max_future_skew_secs);
+        if block.timestamp > limit {
+            return bad(format!("{} is more than {}s in the future", block.timestamp, self.max_future_skew_secs));
         }
         Ok(())
     }
 
+    /// Block 0 has no parent to link to, so it gets checked on its own:
+    /// right index and placeholder parent, intact hash, and nothing but coinbases inside.
+    fn validate_genesis(&self) -> Result<(), ChainError> {
+        let genesis = self.chain.first().ok_or(ChainError::EmptyChain)?;
+        let invalid = |reason: &str| Err(ChainError::InvalidGenesis(reason.to_string()));
+        if genesis.index != 0 {
+            return invalid(&format!("index is {}", genesis.index));
+        }
+        if genesis.previous_hash != GENESIS_PREV_HASH {
+            return invalid("previous_hash is not the all-zero placeholder");
+        }
+        if genesis.merkle_root != Block::compute_merkle_root(&genesis.transactions) {
+            return invalid("merkle root doesn't match its transactions");
+        }
+        if genesis.hash != genesis.calculate_hash() {
+            return invalid("hash doesn't match its contents");
+        }
+        if !genesis.meets_difficulty() {
+            return invalid("hash doesn't meet its difficulty");
+        }
+        if genesis.transactions.iter().any(|t| t.from != "NETWORK") {
+            return invalid("contains a value transfer");
+        }
+        Ok(())
+    }
+
+    /// A mined block must end with exactly one NETWORK transaction, paying the miner the
+    /// subsidy (scheduled reward, capped by max supply) plus the fees of everything
+    /// else in the block. Returns the subsidy so the caller can track issuance.
+    fn validate_coinbase(&self, block: &Block, issued_before: u64) -> Result<u64, ChainError> {
+        let invalid = |reason: String| ChainError::InvalidCoinbase { index: block.index, reason };
+        let (coinbase, rest) = block.transactions.split_last()
+            .ok_or_else(|| invalid("block has no coinbase".to_string()))?;
+        if coinbase.from != "NETWORK" {
+            return Err(invalid("last transaction is not a coinbase".to_string()));
+        }
+        if rest.iter().any(|t| t.from == "NETWORK") {
+            return Err(invalid("NETWORK transaction outside the coinbase".to_string()));
+        }
+        let paid_to = coinbase.single_recipient()
+            .ok_or_else(|| invalid(format!("has {} outputs instead of one", coinbase.outputs.len())))?;
+        if paid_to != block.miner {
+            return Err(invalid(format!("pays {} instead of the miner", short(paid_to, 12))));
+        }
+        if coinbase.fee != 0 {
+            return Err(invalid("coinbase carries a fee".to_string()));
+        }
+        let fees = rest.iter()
+            .try_fold(0u64, |acc, t| acc.checked_add(t.fee))
+            .ok_or(ChainError::BalanceOverflow)?;
+        let subsidy = self.subsidy_at(block.index, issued_before);
+        let expected = subsidy.checked_add(fees).ok_or(ChainError::BalanceOverflow)?;
+        if coinbase.amount() != expected {
+            return Err(invalid(format!("pays {} nits, expected {}", coinbase.amount(), expected)));
+        }
+        Ok(subsidy)
+    }
+
     pub fn is_valid(&self) -> bool {
-        match self.validate() {
-            Ok(_)    => true,
-            Err(msg) => { println!("  ❌ {}", msg); false }
+        self.validate().is_ok()
+    }
+
+    /// Every block's display(), under a banner.
+    pub fn display(&self) -> String {
+        let rule = "═".repeat(50);
+        let mut out = format!("{}\n📦 BLOCKCHAIN — {} blocks\n{}\n", rule, self.chain.len(), rule);
+        for block in &self.chain {
+            out += &block.display();
+            out += "\n\n";
         }
+        out
     }
+}
 
-    pub fn print_chain(&self) {
-        println!("\n{}", "═".repeat(50));
-        println!("📦 BLOCKCHAIN — {} blocks", self.chain.len());
-        println!("{}", "═".repeat(50));
-        for block in &self.chain { block.display(); println!(); }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::Wallet;
+
+    #[test]
+    fn submission_the_log_cant_take_is_refused() {
+        let mut bc = Blockchain::with_prefix("0");
+        let (alice, carol) = (Wallet::new(), Wallet::new());
+        for miner in [&carol, &alice] {
+            for _ in 0..bc.coinbase_maturity.max(1) {
+                bc.mine_pending_transactions(miner.address(), true).unwrap();
+            }
+        }
+        let bob = Wallet::new().address();
+        let first = bc.submit_transaction(alice.sign_transaction(bob.clone(), 1.0, 0.1, 0).unwrap()).unwrap();
+
+        let path = std::env::temp_dir().join(format!("mini-blockchain-{}-refused.wal", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        bc.mempool_wal = Some(MempoolWal::failing(&path, &bc.mempool));
+        let err = bc.submit_transaction(alice.sign_transaction(bob.clone(), 1.0, 0.1, 1).unwrap()).unwrap_err();
+        assert_eq!(err.code(), "MEMPOOL_LOG_FAILED");
+        // a replace-by-fee comes undone too: the original is still what's pending
+        let bump = alice.sign_transaction(bob.clone(), 1.0, 0.5, 0).unwrap();
+        assert!(bc.submit_transaction(bump).is_err());
+        assert_eq!(bc.mempool.txids().collect::<Vec<_>>(), vec![first.as_str()]);
+        // and so does an eviction
+        let bytes = bc.mempool_bytes();
+        bc.max_mempool_txs = 1;
+        let err = bc.submit_transaction(carol.sign_transaction(bob, 1.0, 0.5, 0).unwrap()).unwrap_err();
+        assert_eq!(err.code(), "MEMPOOL_LOG_FAILED");
+        assert_eq!(bc.mempool.txids().collect::<Vec<_>>(), vec![first.as_str()]);
+        assert_eq!(bc.mempool_bytes(), bytes);
+        let _ = std::fs::remove_file(&path);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,49 @@
+// Clock — where the node gets "now" from.
+// Blockchain holds one so tests can pin or skew time instead of sleeping,
+// and Block::new takes the timestamp it's given instead of reading the wall clock.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub trait Clock: Send + Sync {
+    /// Seconds since the Unix epoch. 0 means the clock can't be trusted.
+    fn now_secs(&self) -> u64;
+}
+
+/// The real wall clock. Reports 0 if the system clock is set before 1970 —
+/// Blockchain refuses to build blocks on that rather than stamping them with garbage.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_secs(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+    }
+}
+
+/// Always reports the same time.
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now_secs(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Starts at `start` and moves forward `step` seconds every time it's read —
+/// a chain mined against it gets evenly spaced, reproducible timestamps.
+pub struct SteppingClock {
+    next: AtomicU64,
+    step: u64,
+}
+
+impl SteppingClock {
+    pub fn new(start: u64, step: u64) -> Self {
+        SteppingClock { next: AtomicU64::new(start), step }
+    }
+}
+
+impl Clock for SteppingClock {
+    fn now_secs(&self) -> u64 {
+        self.next.fetch_add(self.step, Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,199 @@
+// ChainError — every way a transaction, block or chain operation can fail.
+// Callers match on the variant instead of string-matching the message.
+
+use thiserror::Error;
+use crate::block::MineAborted;
+use crate::transaction::NITS_PER_TOKEN;
+use crate::util::short;
+
+fn tokens(nits: u64) -> f64 {
+    nits as f64 / NITS_PER_TOKEN as f64
+}
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum ChainError {
+    // ── transactions ──
+    #[error("Transaction is unsigned — call sign() first")]
+    UnsignedTransaction,
+    #[error("Signature invalid — transaction may have been tampered")]
+    InvalidSignature,
+    #[error("Malformed signature: {0}")]
+    MalformedSignature(String),
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+    #[error("Amount cannot be zero")]
+    ZeroAmount,
+    #[error("Transaction has no outputs")]
+    NoOutputs,
+    #[error("Transaction has {count} outputs — at most {max} allowed")]
+    TooManyOutputs { count: usize, max: usize },
+    #[error("Recipient {0} appears in more than one output")]
+    DuplicateOutput(String),
+    #[error("NETWORK transactions can't be submitted — coinbase is added by the miner")]
+    NetworkTransaction,
+    #[error("Invalid multisig: {0}")]
+    InvalidMultisig(String),
+    #[error("Not enough signatures — have {have}, need {need}")]
+    NotEnoughSignatures { have: usize, need: usize },
+    #[error("Memo is {len} bytes — at most {max} allowed")]
+    MemoTooLong { len: usize, max: usize },
+    #[error("Memo needs a fee of at least {} (got {})", tokens(*need), tokens(*got))]
+    MemoFeeTooLow { need: u64, got: u64 },
+    #[error("Malformed raw transaction: {0}")]
+    MalformedTransaction(String),
+    #[error("Bad nonce — expected {expected}, got {got}")]
+    BadNonce { expected: u64, got: u64 },
+    #[error("transaction already known (mempool)")]
+    AlreadyInMempool,
+    #[error("transaction already confirmed in block #{index}")]
+    AlreadyConfirmed { index: usize },
+    #[error("replacement fee too low — pending tx pays {}, replacement must pay more than that (got {})", tokens(*old), tokens(*new))]
+    ReplacementFeeTooLow { old: u64, new: u64 },
+
+    #[error("Mempool is full — fee rate too low to displace anything")]
+    MempoolFull,
+    #[error("Could not log the transaction to disk, so it wasn't accepted: {0}")]
+    MempoolLogFailed(String),
+
+    // ── balances ──
+    #[error("insufficient funds: have {}, need {}", tokens(*have), tokens(*need))]
+    InsufficientFunds { have: u64, need: u64 },
+    #[error("coinbase not mature (needs {confirmations_needed} more confirmations)")]
+    ImmatureCoinbase { confirmations_needed: u32 },
+    #[error("Balance overflow")]
+    BalanceOverflow,
+    #[error("Balance underflow — spending more than available")]
+    BalanceUnderflow,
+
+    // ── mining ──
+    #[error("Mempool is empty — nothing to mine")]
+    EmptyMempool,
+    #[error("Chain tip moved while mining — try again")]
+    StaleTip,
+    #[error("Block #{index} has an unknown parent {}... — this node is behind or on another branch", short(parent, 16))]
+    UnknownParent { index: u32, parent: String },
+    #[error("Mempool changed while mining — try again")]
+    MempoolChanged,
+    #[error("System clock is before the Unix epoch — refusing to timestamp a block")]
+    ClockUnavailable,
+    #[error(transparent)]
+    MiningAborted(#[from] MineAborted),
+
+    // ── blocks and chain ──
+    #[error("Chain is empty")]
+    EmptyChain,
+    #[error("Block #0 (genesis) invalid: {0}")]
+    InvalidGenesis(String),
+    #[error("Block #{index} is not mined")]
+    NotMined { index: u32 },
+    #[error("Block #{index} hash mismatch")]
+    HashMismatch { index: u32 },
+    #[error("Block #{index} disconnected from chain")]
+    BrokenLink { index: u32 },
+    #[error("Block #{index} merkle root mismatch")]
+    MerkleMismatch { index: u32 },
+    #[error("Block #{index} hash doesn't meet difficulty {difficulty} bits")]
+    DifficultyNotMet { index: u32, difficulty: u32 },
+    #[error("Block #{index} mined at difficulty {found} bits, expected {expected}")]
+    WrongDifficulty { index: u32, found: u32, expected: u32 },
+    #[error("Block #{index} timestamp rejected: {reason}")]
+    BadTimestamp { index: u32, reason: String },
+    #[error("Block #{index} coinbase invalid: {reason}")]
+    InvalidCoinbase { index: u32, reason: String },
+    #[error("Block #{index} has {count} transactions, limit is {max}")]
+    TooManyTransactions { index: u32, count: usize, max: usize },
+    #[error("Block #{index} transaction {position}: {source}")]
+    InvalidTransaction { index: u32, position: usize, source: Box<ChainError> },
+    #[error("Block #{index} transaction {position} is locked until block #{until}")]
+    TransactionLocked { index: u32, position: usize, until: u32 },
+    #[error("Invalid ordering in block #{index}: transaction {position} spends funds only credited later in the block")]
+    InvalidOrdering { index: u32, position: usize },
+
+    // ── consensus ──
+    #[error("Candidate chain has no more work than ours ({theirs_work} vs {ours_work})")]
+    ChainNotBetter { ours_work: u128, theirs_work: u128 },
+    #[error("Candidate chain starts from a different genesis block")]
+    GenesisMismatch,
+    #[error("Invalid rollback: {0}")]
+    InvalidRollback(String),
+    #[error("Invalid prune: {0}")]
+    InvalidPrune(String),
+
+    // ── lookups ──
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("Blocks below #{below} are pruned on this node — only their headers are kept")]
+    Pruned { below: usize },
+}
+
+impl ChainError {
+    /// True for errors caused by the data being rejected rather than
+    /// by the state of the node (empty mempool, moved tip, ...).
+    pub fn is_validation(&self) -> bool {
+        !matches!(self,
+            ChainError::EmptyMempool | ChainError::MempoolFull | ChainError::StaleTip | ChainError::MempoolChanged
+            | ChainError::UnknownParent { .. }
+            | ChainError::ClockUnavailable | ChainError::MempoolLogFailed(_)
+            | ChainError::MiningAborted(_) | ChainError::EmptyChain
+            | ChainError::ChainNotBetter { .. } | ChainError::NotFound(_) | ChainError::Pruned { .. })
+    }
+
+    /// Stable machine-readable name for the API's error_code — clients match
+    /// on this, never on the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ChainError::UnsignedTransaction         => "UNSIGNED_TRANSACTION",
+            ChainError::InvalidSignature            => "BAD_SIGNATURE",
+            ChainError::MalformedSignature(_)       => "MALFORMED_SIGNATURE",
+            ChainError::InvalidAddress(_)           => "INVALID_ADDRESS",
+            ChainError::ZeroAmount                  => "ZERO_AMOUNT",
+            ChainError::NoOutputs                   => "NO_OUTPUTS",
+            ChainError::TooManyOutputs { .. }       => "TOO_MANY_OUTPUTS",
+            ChainError::DuplicateOutput(_)          => "DUPLICATE_OUTPUT",
+            ChainError::NetworkTransaction          => "NETWORK_TRANSACTION",
+            ChainError::InvalidMultisig(_)          => "INVALID_MULTISIG",
+            ChainError::NotEnoughSignatures { .. }  => "NOT_ENOUGH_SIGNATURES",
+            ChainError::MemoTooLong { .. }          => "MEMO_TOO_LONG",
+            ChainError::MemoFeeTooLow { .. }        => "MEMO_FEE_TOO_LOW",
+            ChainError::MalformedTransaction(_)     => "MALFORMED_TRANSACTION",
+            ChainError::BadNonce { .. }             => "BAD_NONCE",
+            ChainError::AlreadyInMempool            => "ALREADY_IN_MEMPOOL",
+            ChainError::AlreadyConfirmed { .. }     => "ALREADY_CONFIRMED",
+            ChainError::ReplacementFeeTooLow { .. } => "REPLACEMENT_FEE_TOO_LOW",
+            ChainError::MempoolFull                 => "MEMPOOL_FULL",
+            ChainError::MempoolLogFailed(_)         => "MEMPOOL_LOG_FAILED",
+            ChainError::InsufficientFunds { .. }    => "INSUFFICIENT_FUNDS",
+            ChainError::ImmatureCoinbase { .. }     => "IMMATURE_COINBASE",
+            ChainError::BalanceOverflow             => "BALANCE_OVERFLOW",
+            ChainError::BalanceUnderflow            => "BALANCE_UNDERFLOW",
+            ChainError::EmptyMempool                => "MEMPOOL_EMPTY",
+            ChainError::StaleTip                    => "STALE_TIP",
+            ChainError::UnknownParent { .. }        => "UNKNOWN_PARENT",
+            ChainError::MempoolChanged              => "MEMPOOL_CHANGED",
+            ChainError::ClockUnavailable            => "CLOCK_UNAVAILABLE",
+            ChainError::MiningAborted(MineAborted::Cancelled)            => "MINING_CANCELLED",
+            ChainError::MiningAborted(MineAborted::NonceLimit(_))        => "NONCE_LIMIT",
+            ChainError::MiningAborted(MineAborted::InvalidDifficulty(_)) => "INVALID_DIFFICULTY",
+            ChainError::EmptyChain                  => "EMPTY_CHAIN",
+            ChainError::InvalidGenesis(_)           => "INVALID_GENESIS",
+            ChainError::NotMined { .. }             => "BLOCK_NOT_MINED",
+            ChainError::HashMismatch { .. }         => "HASH_MISMATCH",
+            ChainError::BrokenLink { .. }           => "BROKEN_LINK",
+            ChainError::MerkleMismatch { .. }       => "MERKLE_MISMATCH",
+            ChainError::DifficultyNotMet { .. }     => "DIFFICULTY_NOT_MET",
+            ChainError::WrongDifficulty { .. }      => "WRONG_DIFFICULTY",
+            ChainError::BadTimestamp { .. }         => "BAD_TIMESTAMP",
+            ChainError::InvalidCoinbase { .. }      => "INVALID_COINBASE",
+            ChainError::TooManyTransactions { .. }  => "TOO_MANY_TRANSACTIONS",
+            ChainError::InvalidTransaction { .. }   => "INVALID_TRANSACTION",
+            ChainError::TransactionLocked { .. }    => "TRANSACTION_LOCKED",
+            ChainError::InvalidOrdering { .. }      => "INVALID_ORDERING",
+            ChainError::ChainNotBetter { .. }       => "CHAIN_NOT_BETTER",
+            ChainError::GenesisMismatch             => "GENESIS_MISMATCH",
+            ChainError::InvalidRollback(_)          => "INVALID_ROLLBACK",
+            ChainError::InvalidPrune(_)             => "INVALID_PRUNE",
+            ChainError::NotFound(_)                 => "NOT_FOUND",
+            ChainError::Pruned { .. }               => "PRUNED",
+        }
+    }
+}
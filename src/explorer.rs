@@ -0,0 +1,53 @@
+// Explorer — a single-page block explorer at GET /, compiled into the binary
+// with include_str! so a node needs no files beside it. The page only reads
+// the JSON endpoints everyone else uses. /search decides what a query is and
+// redirects to it, so the page and curl resolve a query the same way.
+
+use actix_web::{HttpResponse, Responder};
+use actix_web::http::header::{CacheControl, CacheDirective, ContentType};
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::blockchain::Blockchain;
+use crate::util::is_valid_address;
+
+const EXPLORER_PAGE: &str = include_str!("../static/explorer.html");
+
+// GET / — the explorer. no-cache so a rebuilt node's page is picked up at once
+pub async fn index() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .body(EXPLORER_PAGE)
+}
+
+/// Where a search query led — what it turned out to be and the endpoint for it.
+#[derive(Serialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct SearchHit {
+    pub kind:     &'static str,  // "block", "transaction" or "address"
+    pub location: String,
+}
+
+/// What `query` names on this chain, tried in order: a block height, a block
+/// hash, a txid (mined or pending), then any valid address. Addresses and
+/// hashes look alike, so a hash we don't know reads as an address.
+pub fn classify(bc: &Blockchain, query: &str) -> Option<SearchHit> {
+    let query = query.trim();
+    let hit = |kind, location: String| Some(SearchHit { kind, location });
+    if let Ok(height) = query.parse::<usize>() {
+        // all-digit hashes aren't heights; those fall through to the lookups below
+        if height < bc.chain.len() {
+            return hit("block", format!("/block/{}", height));
+        }
+    }
+    let hex = query.to_ascii_lowercase();
+    if bc.get_block_by_hash(&hex).is_some() {
+        return hit("block", format!("/block/hash/{}", hex));
+    }
+    if bc.find_transaction(&hex).is_some() {
+        return hit("transaction", format!("/transaction/{}", hex));
+    }
+    if is_valid_address(query) {
+        return hit("address", format!("/address/{}/transactions", query));
+    }
+    None
+}
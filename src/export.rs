@@ -0,0 +1,212 @@
+// Export — flat dumps for spreadsheets and grading scripts: every confirmed
+// transaction as CSV, and the balance index as one JSON object. Amounts are
+// exact decimal tokens ("1.500"), never f64. The endpoints stream both a batch
+// at a time, so neither the chain thread nor the response holds the whole file.
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::web::Bytes;
+use tokio::sync::mpsc;
+use tracing::warn;
+use crate::actor::ChainHandle;
+use crate::block::Block;
+use crate::transaction::format_tokens;
+
+pub const TRANSACTIONS_CSV_HEADER: &str = "block,position,txid,from,to,amount_tokens,fee,timestamp,is_coinbase\n";
+
+/// Blocks the transactions export turns into CSV per visit to the chain thread.
+pub const EXPORT_BATCH_BLOCKS: usize = 100;
+/// Balances written per chunk of the balances export.
+pub const EXPORT_BATCH_BALANCES: usize = 1000;
+// chunks that can wait on a slow client before the export pauses
+const EXPORT_QUEUE: usize = 4;
+
+/// `field` as a CSV field: quoted, with quotes doubled, if it holds a comma,
+/// a quote or a line break; as is otherwise.
+pub fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// The CSV rows for the block at `height`. A transaction with several outputs
+/// gets a row for each, sharing its txid and position; the fee goes on the
+/// first, so summing the column still gives the fees paid.
+pub fn write_transaction_rows(w: &mut impl Write, height: usize, block: &Block) -> io::Result<()> {
+    let last = block.transactions.len().saturating_sub(1);
+    for (pos, txn) in block.transactions.iter().enumerate() {
+        let txid = txn.id();
+        let is_coinbase = pos == last && txn.from == "NETWORK";
+        for (i, output) in txn.outputs.iter().enumerate() {
+            let fee = if i == 0 { txn.fee } else { 0 };
+            writeln!(w, "{},{},{},{},{},{},{},{},{}", height, pos, txid, csv_field(&txn.from), csv_field(&output.to),
+                format_tokens(output.amount), format_tokens(fee), block.timestamp, is_coinbase)?;
+        }
+    }
+    Ok(())
+}
+
+/// `balances` (address, nits) as one JSON object of address → tokens.
+pub fn write_balances_json(w: &mut impl Write, balances: &[(String, u64)]) -> io::Result<()> {
+    w.write_all(b"{")?;
+    write_balance_entries(w, balances, true)?;
+    w.write_all(b"\n}\n")
+}
+
+// the object's members, with the comma before each one but the very first
+fn write_balance_entries(w: &mut impl Write, balances: &[(String, u64)], first: bool) -> io::Result<()> {
+    for (i, (address, nits)) in balances.iter().enumerate() {
+        if i > 0 || !first {
+            w.write_all(b",")?;
+        }
+        write!(w, "\n  {}: {}", serde_json::to_string(address)?, format_tokens(*nits))?;
+    }
+    Ok(())
+}
+
+/// A response body fed chunk by chunk from a channel. Dropping it, as actix
+/// does when the client goes away, makes the next send fail and ends the export.
+pub struct ChunkStream {
+    rx: mpsc::Receiver<io::Result<Bytes>>,
+}
+
+impl MessageBody for ChunkStream {
+    type Error = io::Error;
+
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// Every confirmed transaction up to the tip as of the call, as CSV. If the
+/// chain is reorganized under the export it stops with an error rather than
+/// splice two chains together, and the client sees a cut-off download.
+pub fn transactions_csv(chain: ChainHandle) -> ChunkStream {
+    let (tx, rx) = mpsc::channel(EXPORT_QUEUE);
+    tokio::spawn(async move {
+        if tx.send(Ok(Bytes::from_static(TRANSACTIONS_CSV_HEADER.as_bytes()))).await.is_err() {
+            return;
+        }
+        let end = chain.query(|bc| bc.chain.len()).await;
+        let mut from = 0;
+        let mut last_hash: Option<String> = None;
+        while from < end {
+            let to = from.saturating_add(EXPORT_BATCH_BLOCKS).min(end);
+            let expected = last_hash.take();
+            let batch = chain.query(move |bc| {
+                let moved = bc.chain.len() < to
+                    || expected.is_some_and(|hash| bc.chain[from - 1].hash != hash);
+                if moved {
+                    return Err(io::Error::other("the chain was reorganized during the export"));
+                }
+                let mut buf = Vec::new();
+                for height in from..to {
+                    write_transaction_rows(&mut buf, height, &bc.chain[height])?;
+                }
+                Ok((buf, bc.chain[to - 1].hash.clone()))
+            }).await;
+            let chunk = match batch {
+                Ok((buf, hash)) => { last_hash = Some(hash); Ok(Bytes::from(buf)) }
+                Err(e)          => { warn!(error = %e, from, "transaction export stopped"); Err(e) }
+            };
+            let failed = chunk.is_err();
+            if tx.send(chunk).await.is_err() || failed {
+                return;
+            }
+            from = to;
+        }
+    });
+    ChunkStream { rx }
+}
+
+/// Every non-zero balance at the time of the call, as one JSON object in
+/// address order. The snapshot is taken in one go, so it is consistent;
+/// only writing it out is spread over chunks.
+pub fn balances_json(chain: ChainHandle) -> ChunkStream {
+    let (tx, rx) = mpsc::channel(EXPORT_QUEUE);
+    tokio::spawn(async move {
+        let balances = chain.query(|bc| bc.balance_snapshot()).await;
+        let mut buf = b"{".to_vec();
+        for (i, chunk) in balances.chunks(EXPORT_BATCH_BALANCES).enumerate() {
+            // writing into a Vec can't fail
+            let _ = write_balance_entries(&mut buf, chunk, i == 0);
+            if tx.send(Ok(Bytes::from(std::mem::take(&mut buf)))).await.is_err() {
+                return;
+            }
+        }
+        buf.extend_from_slice(b"\n}\n");
+        let _ = tx.send(Ok(Bytes::from(buf))).await;
+    });
+    ChunkStream { rx }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transaction;
+
+    // RFC 4180 as csv_field() writes it: quoted fields may hold anything,
+    // with "" for a quote
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        let (mut rows, mut row, mut field) = (Vec::new(), Vec::new(), String::new());
+        let mut chars = text.chars().peekable();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match (quoted, c) {
+                (true, '"') if chars.peek() == Some(&'"') => { chars.next(); field.push('"'); }
+                (true, '"')  => quoted = false,
+                (true, c)    => field.push(c),
+                (false, '"') => quoted = true,
+                (false, ',') => row.push(std::mem::take(&mut field)),
+                (false, '\n') => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                (false, c)   => field.push(c),
+            }
+        }
+        rows
+    }
+
+    const AWKWARD: [&str; 7] = ["plain", "a,b", "say \"hi\"", "line\nbreak", "crlf\r\nend", "\"", ""];
+
+    #[test]
+    fn awkward_fields_survive_a_round_trip() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        let line = AWKWARD.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",") + "\n";
+        assert_eq!(parse_csv(&line), vec![AWKWARD.to_vec()]);
+    }
+
+    #[test]
+    fn transaction_rows_survive_a_round_trip() {
+        // addresses are validated long before export; these only stand in for
+        // whatever a future column might carry
+        let transactions: Vec<Transaction> = AWKWARD.windows(2)
+            .map(|pair| Transaction::new_nits(pair[0].to_string(), pair[1].to_string(), 1500))
+            .collect();
+        let block = Block::new(7, transactions.clone(), "00".repeat(32), "miner".into(), 1_700_000_000);
+        let mut buf = TRANSACTIONS_CSV_HEADER.as_bytes().to_vec();
+        write_transaction_rows(&mut buf, 7, &block).unwrap();
+
+        let rows = parse_csv(&String::from_utf8(buf).unwrap());
+        assert_eq!(rows[0].join(","), TRANSACTIONS_CSV_HEADER.trim_end());
+        assert_eq!(rows.len(), transactions.len() + 1);
+        for (pos, (row, txn)) in rows[1..].iter().zip(&transactions).enumerate() {
+            assert_eq!(row.len(), 9);
+            assert_eq!(row[1], pos.to_string());
+            assert_eq!((row[3].as_str(), row[4].as_str()), (txn.from.as_str(), txn.outputs[0].to.as_str()));
+            assert_eq!(row[5], "1.500");
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// Gossip — tells peers about what this node just accepted.
+// Handlers drop announcements on a channel and return; one background task
+// signs each one with the node key and does the HTTP, so a slow or dead peer
+// never holds up a request.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde_json::json;
+use tokio::sync::mpsc;
+use crate::block::Block;
+use reqwest::StatusCode;
+use reqwest::header::CONTENT_TYPE;
+use crate::peers::{PeerSet, NODE_KEY_HEADER, NODE_SIGNATURE_HEADER};
+use crate::util::{short, LockExt};
+use tracing::warn;
+
+/// Tries per peer before an announcement is given up on.
+pub const GOSSIP_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry — doubles after each one.
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+#[derive(Clone)]
+pub enum Announcement {
+    Transaction { txid: String, raw: String },  // raw = Transaction::to_hex()
+    Block(Box<Block>),
+}
+
+impl Announcement {
+    fn path(&self) -> &'static str {
+        match self {
+            Announcement::Transaction { .. } => "/peer/transaction",
+            Announcement::Block(_)           => "/peer/block",
+        }
+    }
+
+    fn body(&self) -> serde_json::Value {
+        match self {
+            Announcement::Transaction { raw, .. } => json!({ "raw": raw }),
+            Announcement::Block(block)            => json!(block),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Announcement::Transaction { txid, .. } => format!("tx {}", short(txid, 12)),
+            Announcement::Block(block)             => format!("block #{}", block.index),
+        }
+    }
+}
+
+/// The sending half, kept in AppState. Announcing never blocks and never fails —
+/// with the dispatcher gone, announcements are just dropped.
+#[derive(Clone)]
+pub struct Gossip {
+    tx: mpsc::UnboundedSender<Announcement>,
+}
+
+impl Gossip {
+    /// Start the dispatcher on the current tokio runtime.
+    pub fn spawn(peers: Arc<Mutex<PeerSet>>) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(dispatch(rx, peers));
+        Gossip { tx }
+    }
+
+    pub fn announce(&self, announcement: Announcement) {
+        let _ = self.tx.send(announcement);
+    }
+}
+
+// every announcement goes to every peer known at the time, each delivery on its own task
+async fn dispatch(mut rx: mpsc::UnboundedReceiver<Announcement>, peers: Arc<Mutex<PeerSet>>) {
+    while let Some(announcement) = rx.recv().await {
+        let body = announcement.body().to_string().into_bytes();
+        let (client, urls, (node_key, signature)) = {
+            let peers = peers.locked();
+            (peers.client(), peers.urls(), peers.sign_announcement(&body))
+        };
+        let signed = Arc::new(Signed { body, node_key, signature });
+        for url in urls {
+            tokio::spawn(deliver(client.clone(), url, announcement.describe(), announcement.path(),
+                signed.clone(), peers.clone()));
+        }
+    }
+}
+
+// one announcement's body and signature, shared by every delivery of it
+struct Signed {
+    body:      Vec<u8>,
+    node_key:  String,
+    signature: String,
+}
+
+/// POST one announcement to one peer, retrying network and server errors.
+/// A peer that doesn't know our node key (it never registered us) refuses with
+/// 401/403 — no point retrying that. Any other answer counts as delivered: a peer
+/// that already has the transaction or block says so with a 409, and that's
+/// exactly what stops gossip looping.
+async fn deliver(client: reqwest::Client, url: String, what: String, path: &'static str,
+                 signed: Arc<Signed>, peers: Arc<Mutex<PeerSet>>) {
+    let mut delay = RETRY_DELAY;
+    let mut last_error = String::new();
+    for attempt in 1..=GOSSIP_ATTEMPTS {
+        let sent = client.post(format!("{}{}", url, path))
+            .header(CONTENT_TYPE, "application/json")
+            .header(NODE_KEY_HEADER, &signed.node_key)
+            .header(NODE_SIGNATURE_HEADER, &signed.signature)
+            .body(signed.body.clone())
+            .send().await;
+        match sent {
+            Ok(res) if matches!(res.status(), StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => {
+                last_error = format!("refused (HTTP {}) — does it have us registered as a peer?", res.status());
+                break;
+            }
+            Ok(res) if !res.status().is_server_error() => {
+                peers.locked().mark_seen(&url);
+                return;
+            }
+            Ok(res) => last_error = format!("HTTP {}", res.status()),
+            Err(e)  => last_error = e.to_string(),
+        }
+        if attempt < GOSSIP_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+    warn!(peer = %url, what = %what, error = %last_error, "announcement not delivered");
+    peers.locked().mark_failed(&url, &format!("broadcast of {} failed: {}", what, last_error));
+}
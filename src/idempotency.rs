@@ -0,0 +1,264 @@
+// Idempotency — a client that retries a POST after a timeout sends the same
+// Idempotency-Key again and gets the first answer back, instead of the request
+// running twice. Answers are kept in memory for a window (10 minutes by default).
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::http::StatusCode;
+use actix_web::http::header::ContentType;
+use actix_web::web::Bytes;
+use sha2::{Sha256, Digest};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+use crate::api::fail;
+use crate::util::LockExt;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Set on an answer that came out of the cache rather than a fresh run.
+pub const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+/// How long an answer is kept when IDEMPOTENCY_WINDOW_SECS isn't set.
+pub const DEFAULT_IDEMPOTENCY_WINDOW: Duration = Duration::from_secs(600);
+
+/// Longest key accepted.
+pub const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// A map whose entries vanish `ttl` after they were inserted.
+pub struct ExpiringCache<K, V> {
+    ttl:     Duration,
+    entries: HashMap<K, (Instant, V)>,
+}
+
+impl<K: Eq + Hash, V> ExpiringCache<K, V> {
+    pub fn new(ttl: Duration) -> Self {
+        ExpiringCache { ttl, entries: HashMap::new() }
+    }
+
+    pub fn get_at(&self, key: &K, now: Instant) -> Option<&V> {
+        self.entries.get(key)
+            .filter(|(inserted, _)| now.saturating_duration_since(*inserted) < self.ttl)
+            .map(|(_, value)| value)
+    }
+
+    /// Insert or overwrite; the entry's clock starts again at `now`.
+    pub fn insert_at(&mut self, key: K, value: V, now: Instant) {
+        self.entries.insert(key, (now, value));
+    }
+
+    /// Drop everything expired as of `now`. Returns how many went.
+    pub fn prune_at(&mut self, now: Instant) -> usize {
+        let before = self.entries.len();
+        let ttl = self.ttl;
+        self.entries.retain(|_, (inserted, _)| now.saturating_duration_since(*inserted) < ttl);
+        before - self.entries.len()
+    }
+
+    /// Entries held, expired ones not yet pruned included.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+// a finished answer, and a fingerprint of the request that produced it
+struct StoredResponse {
+    request_hash: [u8; 32],
+    status:       StatusCode,
+    body:         Bytes,
+}
+
+pub struct IdempotencyStore {
+    cache:     Mutex<ExpiringCache<String, StoredResponse>>,
+    in_flight: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,  // one lock per key with a request running
+}
+
+// holds one key's lock, and drops the key from the in-flight map once nobody
+// else is waiting on it — even if the request future is dropped halfway
+struct KeyGuard<'a> {
+    store: &'a IdempotencyStore,
+    key:   &'a str,
+    held:  Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for KeyGuard<'_> {
+    fn drop(&mut self) {
+        self.held.take();
+        let mut in_flight = self.store.in_flight.locked();
+        if in_flight.get(self.key).is_some_and(|lock| Arc::strong_count(lock) == 1) {
+            in_flight.remove(self.key);
+        }
+    }
+}
+
+impl IdempotencyStore {
+    pub fn new(window: Duration) -> Self {
+        IdempotencyStore {
+            cache:     Mutex::new(ExpiringCache::new(window)),
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn lock_key<'a>(&'a self, key: &'a str) -> KeyGuard<'a> {
+        let lock = self.in_flight.locked().entry(key.to_string()).or_default().clone();
+        KeyGuard { store: self, key, held: Some(lock.lock_owned().await) }
+    }
+
+    /// Run `handler` at most once per Idempotency-Key within the window.
+    /// Without the header it just runs. A repeat of the same request gets the
+    /// stored answer verbatim; the same key on a different request is 409
+    /// IDEMPOTENCY_KEY_REUSED. 5xx answers aren't stored, so those can be retried.
+    /// The key stays locked while `handler` runs, so a retry that arrives
+    /// mid-request waits for the first answer instead of racing it; requests
+    /// under other keys don't wait.
+    pub async fn run<F: Future<Output = HttpResponse>>(&self, req: &HttpRequest, body: &[u8],
+                                                        handler: impl FnOnce() -> F) -> HttpResponse {
+        let Some(key) = req.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+            return handler().await;
+        };
+        let key = match key.to_str() {
+            Ok(k) if !k.is_empty() && k.len() <= MAX_IDEMPOTENCY_KEY_LEN => k.to_string(),
+            _ => return fail(StatusCode::BAD_REQUEST, "INVALID_IDEMPOTENCY_KEY",
+                &format!("{} must be 1-{} visible ASCII characters", IDEMPOTENCY_KEY_HEADER, MAX_IDEMPOTENCY_KEY_LEN)),
+        };
+        let request_hash = fingerprint(req.path(), body);
+
+        let _key_lock = self.lock_key(&key).await;
+        let now = Instant::now();
+        {
+            let mut cache = self.cache.locked();
+            cache.prune_at(now);
+            if let Some(stored) = cache.get_at(&key, now) {
+                if stored.request_hash != request_hash {
+                    return fail(StatusCode::CONFLICT, "IDEMPOTENCY_KEY_REUSED",
+                        "this Idempotency-Key was already used for a different request");
+                }
+                return HttpResponse::build(stored.status)
+                    .content_type(ContentType::json())
+                    .insert_header((REPLAYED_HEADER, "true"))
+                    .body(stored.body.clone());
+            }
+        }
+
+        let response = handler().await;
+        if response.status().is_server_error() {
+            return response;
+        }
+        let (response, body) = response.into_parts();
+        match body.try_into_bytes() {
+            Ok(bytes) => {
+                let stored = StoredResponse { request_hash, status: response.status(), body: bytes.clone() };
+                self.cache.locked().insert_at(key.clone(), stored, now);
+                response.set_body(BoxBody::new(bytes))
+            }
+            // a streamed body can't be stored — hand it on as it is
+            Err(body) => response.set_body(body),
+        }
+    }
+}
+
+// the endpoint and body together — the same body sent to another endpoint is a different request
+fn fingerprint(path: &str, body: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(path.as_bytes());
+    hasher.update([0]);
+    hasher.update(body);
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    const TTL: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn get_before_expiry() {
+        let start = Instant::now();
+        let mut cache = ExpiringCache::new(TTL);
+        cache.insert_at("k", 1, start);
+        assert_eq!(cache.get_at(&"k", start), Some(&1));
+        assert_eq!(cache.get_at(&"k", start + TTL - Duration::from_millis(1)), Some(&1));
+        assert_eq!(cache.get_at(&"other", start), None);
+    }
+
+    #[test]
+    fn get_after_expiry() {
+        let start = Instant::now();
+        let mut cache = ExpiringCache::new(TTL);
+        cache.insert_at("k", 1, start);
+        assert_eq!(cache.get_at(&"k", start + TTL), None);
+        // still held until pruned
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn prune_at_counts_what_it_drops() {
+        let start = Instant::now();
+        let mut cache = ExpiringCache::new(TTL);
+        cache.insert_at("old", 1, start);
+        cache.insert_at("older", 2, start);
+        cache.insert_at("new", 3, start + Duration::from_secs(5));
+        assert_eq!(cache.prune_at(start + Duration::from_secs(5)), 0);
+        assert_eq!(cache.prune_at(start + TTL), 2);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.prune_at(start + TTL * 2), 1);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn overwrite_restarts_the_clock() {
+        let start = Instant::now();
+        let mut cache = ExpiringCache::new(TTL);
+        cache.insert_at("k", 1, start);
+        cache.insert_at("k", 2, start + Duration::from_secs(8));
+        assert_eq!(cache.get_at(&"k", start + TTL), Some(&2));
+        assert_eq!(cache.prune_at(start + TTL), 0);
+        assert_eq!(cache.get_at(&"k", start + Duration::from_secs(18)), None);
+    }
+
+    #[actix_web::test]
+    async fn other_keys_dont_wait_for_a_running_request() {
+        let store = IdempotencyStore::new(TTL);
+        let first = TestRequest::post().insert_header((IDEMPOTENCY_KEY_HEADER, "a")).to_http_request();
+        let second = TestRequest::post().insert_header((IDEMPOTENCY_KEY_HEADER, "b")).to_http_request();
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        // the first handler can only finish once the second has run
+        let slow = store.run(&first, b"1", || async {
+            released.await.unwrap();
+            HttpResponse::Ok().body("first")
+        });
+        let fast = store.run(&second, b"2", || async {
+            release.send(()).unwrap();
+            HttpResponse::Ok().body("second")
+        });
+        let both = tokio::time::timeout(Duration::from_secs(5), async { tokio::join!(slow, fast) }).await;
+        let (slow, fast) = both.expect("a request under one key blocked another key");
+        assert!(slow.status().is_success() && fast.status().is_success());
+        assert!(store.in_flight.locked().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn a_retry_waits_for_the_first_answer() {
+        let store = IdempotencyStore::new(TTL);
+        let req = TestRequest::post().insert_header((IDEMPOTENCY_KEY_HEADER, "a")).to_http_request();
+        let runs = std::sync::atomic::AtomicU32::new(0);
+        let handler = || async {
+            runs.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            tokio::task::yield_now().await;
+            HttpResponse::Ok().body("done")
+        };
+        let (first, retry) = tokio::join!(store.run(&req, b"1", handler), store.run(&req, b"1", handler));
+        assert_eq!(runs.into_inner(), 1);
+        assert!(first.headers().get(REPLAYED_HEADER).is_none());
+        assert_eq!(retry.headers().get(REPLAYED_HEADER).unwrap(), "true");
+    }
+}
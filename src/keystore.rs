@@ -0,0 +1,136 @@
+// Keystore — a wallet's private key at rest, encrypted under a password.
+// argon2id stretches the password into a key, ChaCha20-Poly1305 encrypts the
+// 32-byte secret. The address is stored in the clear (so `wallet address`
+// needs no password) and bound in as associated data, so editing it breaks
+// decryption instead of silently pairing the key with the wrong address.
+
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use chacha20poly1305::aead::{Aead, Payload};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+use zeroize::Zeroizing;
+use crate::wallet::Wallet;
+
+pub const KEYSTORE_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum KeystoreError {
+    #[error("Wrong password, or the keystore has been modified")]
+    WrongPassword,
+    #[error("Keystore version {0} not supported (expected {KEYSTORE_VERSION})")]
+    UnsupportedVersion(u32),
+    #[error("Malformed keystore: {0}")]
+    Malformed(String),
+    #[error("{0}")]
+    Io(String),
+}
+
+/// argon2id cost settings, stored so they can be raised later without
+/// breaking keystores written with the old ones.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KdfParams {
+    pub salt:   String,  // hex, 16 bytes
+    pub m_cost: u32,     // KiB
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Keystore {
+    pub version:    u32,
+    pub address:    String,
+    pub kdf:        KdfParams,
+    pub nonce:      String,  // hex, 12 bytes
+    pub ciphertext: String,  // hex — the secret key plus the 16-byte tag
+}
+
+impl Keystore {
+    /// Encrypt `wallet`'s secret key under `password`.
+    pub fn encrypt(wallet: &Wallet, password: &str) -> Result<Self, KeystoreError> {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce);
+        let kdf = KdfParams {
+            salt:   hex::encode(salt),
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        };
+        let address = wallet.address();
+        let secret = Zeroizing::new(hex::decode(wallet.export_secret().as_str())
+            .map_err(|e| KeystoreError::Malformed(e.to_string()))?);
+        let key = derive_key(password, &kdf)?;
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: secret.as_slice(), aad: address.as_bytes() })
+            .map_err(|_| KeystoreError::Malformed("encryption failed".to_string()))?;
+        Ok(Keystore {
+            version: KEYSTORE_VERSION,
+            address,
+            kdf,
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        })
+    }
+
+    /// The wallet back, given the right password.
+    pub fn decrypt(&self, password: &str) -> Result<Wallet, KeystoreError> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(KeystoreError::UnsupportedVersion(self.version));
+        }
+        let nonce = hex::decode(&self.nonce)
+            .ok().filter(|n| n.len() == 12)
+            .ok_or_else(|| KeystoreError::Malformed("nonce must be 12 bytes of hex".to_string()))?;
+        let ciphertext = hex::decode(&self.ciphertext)
+            .map_err(|_| KeystoreError::Malformed("ciphertext is not hex".to_string()))?;
+        let key = derive_key(password, &self.kdf)?;
+        let secret = Zeroizing::new(ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: self.address.as_bytes() })
+            .map_err(|_| KeystoreError::WrongPassword)?);
+        let wallet = Wallet::from_private_key_hex(&Zeroizing::new(hex::encode(secret.as_slice())))
+            .map_err(KeystoreError::Malformed)?;
+        if wallet.address() != self.address {
+            return Err(KeystoreError::Malformed("key doesn't match the stored address".to_string()));
+        }
+        Ok(wallet)
+    }
+
+    /// Write to `path`. Refuses to overwrite — losing a key to a typo'd
+    /// `wallet new` is not recoverable.
+    /// The check and the create are one step, so two writers can't both win.
+    pub fn save(&self, path: &str) -> Result<(), KeystoreError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| KeystoreError::Malformed(e.to_string()))?;
+        let mut file = OpenOptions::new().write(true).create_new(true).open(path)
+            .map_err(|e| match e.kind() {
+                ErrorKind::AlreadyExists => KeystoreError::Io(format!("{} already exists — not overwriting it", path)),
+                _ => KeystoreError::Io(format!("Couldn't write {}: {}", path, e)),
+            })?;
+        file.write_all(json.as_bytes())
+            .map_err(|e| KeystoreError::Io(format!("Couldn't write {}: {}", path, e)))
+    }
+
+    pub fn load(path: &str) -> Result<Self, KeystoreError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| KeystoreError::Io(format!("Couldn't read {}: {}", path, e)))?;
+        serde_json::from_str(&json)
+            .map_err(|e| KeystoreError::Malformed(format!("{}: {}", path, e)))
+    }
+}
+
+fn derive_key(password: &str, kdf: &KdfParams) -> Result<Zeroizing<[u8; 32]>, KeystoreError> {
+    let salt = hex::decode(&kdf.salt)
+        .map_err(|_| KeystoreError::Malformed("salt is not hex".to_string()))?;
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+        .map_err(|e| KeystoreError::Malformed(format!("bad kdf parameters: {}", e)))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), &salt, key.as_mut())
+        .map_err(|e| KeystoreError::Malformed(format!("key derivation failed: {}", e)))?;
+    Ok(key)
+}
@@ -0,0 +1,33 @@
+// Library crate — everything the node is built from, so other projects
+// (and integration tests) can use Wallet/Transaction/Blockchain directly.
+
+pub mod util;
+pub mod canonical;
+pub mod clock;
+pub mod error;
+pub mod wallet;
+pub mod keystore;
+pub mod multisig;
+pub mod transaction;
+pub mod merkle;
+pub mod mempool;
+pub mod wal;
+pub mod block;
+pub mod blockchain;
+pub mod store;
+pub mod export;
+pub mod actor;
+pub mod miner;
+pub mod peers;
+pub mod gossip;
+pub mod sync;
+pub mod webhooks;
+pub mod auth;
+pub mod ratelimit;
+pub mod idempotency;
+pub mod status;
+pub mod audit;
+pub mod openapi;
+pub mod explorer;
+pub mod api;
+pub mod server;
@@ -1,36 +1,19 @@
-mod wallet;
-mod transaction;
-mod block;
-mod blockchain;
-mod api;
+use mini_blockchain::server::{self, NodeConfig};
 
-use actix_web::{web, App, HttpServer};
-use std::sync::Mutex;
+// --log-format json|pretty (or LOG_FORMAT), pretty by default
+fn log_format() -> String {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().position(|a| a == "--log-format")
+        .map(|i| args.get(i + 1).cloned().unwrap_or_default())
+        .or_else(|| args.iter().find_map(|a| a.strip_prefix("--log-format=").map(str::to_string)))
+        .or_else(|| std::env::var("LOG_FORMAT").ok())
+        .unwrap_or_else(|| "pretty".to_string())
+}
 
+// configured from env vars — see NodeConfig::from_env; bin/node.rs takes flags instead
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let state = web::Data::new(api::AppState {
-        blockchain: Mutex::new(blockchain::Blockchain::new("0")),
-    });
-
-    // Railway injects PORT as an environment variable
-    // locally it falls back to 8080
-    let port = std::env::var("PORT").unwrap_or_else(|_| "3000".to_string());
-    let addr = format!("0.0.0.0:{}", port);
-
-    println!("running on http://{}", addr);
-
-    HttpServer::new(move || {
-        App::new()
-            .app_data(state.clone())
-            .route("/wallet/new",        web::get().to(api::new_wallet))
-            .route("/transaction",       web::post().to(api::submit_transaction))
-            .route("/mine",              web::post().to(api::mine_block))
-            .route("/chain",             web::get().to(api::get_chain))
-            .route("/balance/{address}", web::get().to(api::get_balance))
-            .route("/validate",          web::get().to(api::validate_chain))
-    })
-    .bind(&addr)?
-    .run()
-    .await
-}
\ No newline at end of file
+    let invalid = |e: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, e);
+    server::init_logging(&log_format()).map_err(invalid)?;
+    server::run(NodeConfig::from_env().map_err(invalid)?).await
+}
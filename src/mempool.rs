@@ -0,0 +1,240 @@
+// Mempool — pending transactions, kept ordered by fee rate.
+// Ties go to whoever arrived first. Per-sender nonce order is enforced by mining_order().
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use crate::transaction::Transaction;
+
+/// Fee per byte of raw encoding. Compared by cross-multiplying, never as a float.
+#[derive(Clone, Copy, Debug)]
+pub struct FeeRate {
+    fee:  u64,
+    size: u64,
+}
+
+impl FeeRate {
+    pub fn of(txn: &Transaction) -> Self {
+        Self::sized(txn.fee, txn.size())
+    }
+
+    /// For a transaction whose raw size is already known — size() encodes it.
+    pub fn sized(fee: u64, size: usize) -> Self {
+        FeeRate { fee, size: size.max(1) as u64 }
+    }
+
+    pub fn nits_per_byte(&self) -> f64 {
+        self.fee as f64 / self.size as f64
+    }
+}
+
+impl Ord for FeeRate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.fee as u128 * other.size as u128).cmp(&(other.fee as u128 * self.size as u128))
+    }
+}
+
+impl PartialOrd for FeeRate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl PartialEq for FeeRate {
+    fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl Eq for FeeRate {}
+
+// Higher fee rate sorts higher; on a tie the earlier arrival does.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Priority {
+    rate:    FeeRate,
+    arrival: Reverse<u64>,
+}
+
+// A pending transaction, with its txid and raw size worked out once when it arrives.
+#[derive(Clone)]
+struct Entry {
+    txn:  Transaction,
+    txid: String,
+    size: usize,
+}
+
+/// A transaction take() removed, still holding its place in line so
+/// restore() can put it back exactly where it was.
+pub struct Taken {
+    key:   Priority,
+    entry: Entry,
+}
+
+impl Taken {
+    pub fn txn(&self) -> &Transaction {
+        &self.entry.txn
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Mempool {
+    queue:        BTreeMap<Priority, Entry>,
+    by_txid:      HashMap<String, Priority>,                // txid → its key in queue
+    by_sender:    HashMap<String, BTreeMap<u64, Priority>>, // sender → nonce → its key in queue
+    bytes:        usize,                                    // sum of every entry's size
+    next_arrival: u64,
+}
+
+impl Mempool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Best fee rate first. Ignores nonce order — use mining_order() for that.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Transaction> {
+        self.queue.values().rev().map(|e| &e.txn)
+    }
+
+    pub fn push(&mut self, txn: Transaction) {
+        let arrival = Reverse(self.next_arrival);
+        self.next_arrival += 1;
+        let size = txn.size();
+        let key = Priority { rate: FeeRate::sized(txn.fee, size), arrival };
+        self.insert(key, Entry { txid: txn.id(), txn, size });
+    }
+
+    fn insert(&mut self, key: Priority, entry: Entry) {
+        self.by_txid.insert(entry.txid.clone(), key);
+        self.by_sender.entry(entry.txn.from.clone()).or_default().insert(entry.txn.nonce, key);
+        self.bytes += entry.size;
+        self.queue.insert(key, entry);
+    }
+
+    /// Every pending txid, in no particular order.
+    pub fn txids(&self) -> impl Iterator<Item = &str> {
+        self.by_txid.keys().map(String::as_str)
+    }
+
+    pub fn contains(&self, txid: &str) -> bool {
+        self.by_txid.contains_key(txid)
+    }
+
+    pub fn get(&self, txid: &str) -> Option<&Transaction> {
+        self.queue.get(self.by_txid.get(txid)?).map(|e| &e.txn)
+    }
+
+    /// The pending transaction `from` signed with `nonce`, if any.
+    pub fn get_by_nonce(&self, from: &str, nonce: u64) -> Option<&Transaction> {
+        self.queue.get(self.by_sender.get(from)?.get(&nonce)?).map(|e| &e.txn)
+    }
+
+    /// Swap in `txn` for the pending one with the same sender and nonce.
+    /// The replacement keeps the original's place in arrival order.
+    pub fn replace(&mut self, txn: Transaction) -> Option<Transaction> {
+        let key = *self.by_sender.get(&txn.from)?.get(&txn.nonce)?;
+        let old = self.take_key(key)?;
+        let size = txn.size();
+        let key = Priority { rate: FeeRate::sized(txn.fee, size), arrival: key.arrival };
+        self.insert(key, Entry { txid: txn.id(), txn, size });
+        Some(old.entry.txn)
+    }
+
+    pub fn remove(&mut self, txid: &str) -> Option<Transaction> {
+        self.take(txid).map(|t| t.entry.txn)
+    }
+
+    /// remove(), keeping what restore() needs to undo it.
+    pub fn take(&mut self, txid: &str) -> Option<Taken> {
+        let key = *self.by_txid.get(txid)?;
+        self.take_key(key)
+    }
+
+    fn take_key(&mut self, key: Priority) -> Option<Taken> {
+        let entry = self.queue.remove(&key)?;
+        self.by_txid.remove(&entry.txid);
+        self.unlink_sender(&entry.txn, key);
+        self.bytes -= entry.size;
+        Some(Taken { key, entry })
+    }
+
+    // drop `txn`'s nonce from its sender's map, if it still points at `key`
+    fn unlink_sender(&mut self, txn: &Transaction, key: Priority) {
+        if let Some(nonces) = self.by_sender.get_mut(&txn.from) {
+            if nonces.get(&txn.nonce) == Some(&key) {
+                nonces.remove(&txn.nonce);
+            }
+            if nonces.is_empty() {
+                self.by_sender.remove(&txn.from);
+            }
+        }
+    }
+
+    pub fn restore(&mut self, taken: Taken) {
+        self.insert(taken.key, taken.entry);
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&Transaction) -> bool) {
+        let dropped: Vec<Priority> = self.queue.iter()
+            .filter(|(_, e)| !keep(&e.txn))
+            .map(|(key, _)| *key)
+            .collect();
+        for key in dropped {
+            self.take_key(key);
+        }
+    }
+
+    /// Total raw size of everything pending.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// What to evict first, with each one's fee rate and raw size: the cheapest
+    /// sender tip — a sender's highest pending nonce — and once that's taken,
+    /// the same sender's next nonce down joins the tips. So nothing evicted
+    /// ever leaves a nonce gap behind. `sender`'s own are never offered.
+    pub fn eviction_order<'a>(&'a self, sender: &'a str) -> impl Iterator<Item = (&'a Transaction, FeeRate, usize)> + 'a {
+        let mut tips: BinaryHeap<Reverse<(Priority, &str, u64)>> = self.by_sender.iter()
+            .filter(|(from, _)| from.as_str() != sender)
+            .filter_map(|(from, nonces)| nonces.last_key_value().map(|(n, key)| Reverse((*key, from.as_str(), *n))))
+            .collect();
+        std::iter::from_fn(move || {
+            let Reverse((key, from, nonce)) = tips.pop()?;
+            if let Some((n, next)) = self.by_sender[from].range(..nonce).next_back() {
+                tips.push(Reverse((*next, from, *n)));
+            }
+            let entry = &self.queue[&key];
+            Some((&entry.txn, key.rate, entry.size))
+        })
+    }
+
+    /// Everything pending, in the order a miner would take it: best fee rate first,
+    /// but a sender's transactions only ever in nonce order — a high-fee nonce 5
+    /// waits behind the same sender's nonce 4.
+    pub fn mining_order(&self) -> Vec<&Transaction> {
+        let mut by_sender: HashMap<&str, Vec<(Priority, &Transaction)>> = HashMap::new();
+        for (prio, entry) in &self.queue {
+            by_sender.entry(entry.txn.from.as_str()).or_default().push((*prio, &entry.txn));
+        }
+        // lowest nonce last, so pop() hands out each sender's next one
+        for pending in by_sender.values_mut() {
+            pending.sort_by_key(|(_, t)| Reverse(t.nonce));
+        }
+
+        let mut heads: BinaryHeap<(Priority, &str)> = by_sender.iter()
+            .filter_map(|(from, pending)| pending.last().map(|(p, _)| (*p, *from)))
+            .collect();
+        let mut order = Vec::with_capacity(self.queue.len());
+        while let Some((_, from)) = heads.pop() {
+            let pending = by_sender.get_mut(from).expect("sender has a queue");
+            let (_, txn) = pending.pop().expect("head came from this queue");
+            order.push(txn);
+            if let Some((next, _)) = pending.last() {
+                heads.push((*next, from));
+            }
+        }
+        order
+    }
+}
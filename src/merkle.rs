@@ -0,0 +1,65 @@
+// Merkle tree over transaction ids.
+// Lets the block header commit to every transaction with one 32-byte root.
+
+use sha2::{Sha256, Digest};
+
+/// Root used for blocks with no transactions (genesis).
+pub const EMPTY_MERKLE_ROOT: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Parent node = SHA-256 of the two child hashes' raw bytes.
+pub fn hash_pair(left: &str, right: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(hex::decode(left).unwrap_or_default());
+    hasher.update(hex::decode(right).unwrap_or_default());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Standard binary merkle root — odd levels duplicate their last leaf.
+pub fn merkle_root(txids: &[String]) -> String {
+    if txids.is_empty() {
+        return EMPTY_MERKLE_ROOT.to_string();
+    }
+    let mut level: Vec<String> = txids.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+            .collect();
+    }
+    level.remove(0)
+}
+
+/// Path from a leaf up to the root: (sibling_hash, sibling_is_left) per level.
+pub type MerkleProof = Vec<(String, bool)>;
+
+/// Build the inclusion proof for the leaf at `index`.
+pub fn merkle_proof(txids: &[String], mut index: usize) -> Option<MerkleProof> {
+    if index >= txids.len() {
+        return None;
+    }
+    let mut proof = Vec::new();
+    let mut level: Vec<String> = txids.to_vec();
+    while level.len() > 1 {
+        if level.len() % 2 == 1 {
+            level.push(level[level.len() - 1].clone());
+        }
+        let sibling = index ^ 1;
+        proof.push((level[sibling].clone(), sibling < index));
+        level = level
+            .chunks(2)
+            .map(|pair| hash_pair(&pair[0], &pair[1]))
+            .collect();
+        index /= 2;
+    }
+    Some(proof)
+}
+
+/// Fold the proof back up from the txid and check we land on the root.
+/// Needs only the block header's merkle root — not the full block.
+pub fn verify_merkle_proof(root: &str, txid: &str, proof: &MerkleProof) -> bool {
+    let computed = proof.iter().fold(txid.to_string(), |acc, (sibling, is_left)| {
+        if *is_left { hash_pair(sibling, &acc) } else { hash_pair(&acc, sibling) }
+    });
+    computed == root
+}
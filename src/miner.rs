@@ -0,0 +1,196 @@
+// Auto-miner — a background task that keeps mining the mempool into blocks
+// paid to one address, started and stopped over the API. Every round works
+// like POST /mine: ask the chain for a template, search for the nonce off the
+// chain thread, send the block back to commit. HTTP handlers only ever queue
+// behind the template and the commit, never the proof of work.
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use actix_web::web;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use utoipa::ToSchema;
+use crate::api::{AppState, commit_mined};
+use crate::block::{MineAborted, MiningStatus};
+use crate::error::ChainError;
+use crate::util::{is_valid_address, LockExt};
+
+/// How long the miner waits between rounds unless told otherwise.
+pub const DEFAULT_MINER_INTERVAL_SECS: u64 = 10;
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum MinerError {
+    #[error("The miner is already running for {0} — stop it first")]
+    AlreadyRunning(String),
+    #[error("{0:?} is not a valid address")]
+    InvalidAddress(String),
+    #[error("interval_secs must be at least 1")]
+    InvalidInterval,
+}
+
+impl MinerError {
+    /// The API's error_code for this failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            MinerError::AlreadyRunning(_) => "MINER_RUNNING",
+            MinerError::InvalidAddress(_) => "INVALID_ADDRESS",
+            MinerError::InvalidInterval   => "INVALID_INTERVAL",
+        }
+    }
+}
+
+/// What GET /miner/status reports. The search fields cover any nonce search
+/// on this node — POST /mine's too, not only the auto-miner's.
+#[derive(Serialize, Clone, Debug, ToSchema)]
+pub struct MinerStatus {
+    pub status:        &'static str,    // "running" or "idle"
+    pub address:       Option<String>,  // where the rewards go, while running
+    pub interval_secs: Option<u64>,
+    pub allow_empty:   bool,            // mining even when the mempool is empty
+    pub blocks_mined:  u64,             // since the last start
+    pub last_error:    Option<String>,  // cleared by the next block
+    #[serde(flatten)]
+    pub search:        MiningStatus,    // the search under way, otherwise the last one
+}
+
+// what the loop reports back; every start resets it
+#[derive(Default)]
+struct Progress {
+    blocks_mined: u64,
+    last_error:   Option<String>,
+}
+
+struct Session {
+    address:       String,
+    interval_secs: u64,
+    allow_empty:   bool,
+    stop:          watch::Sender<bool>,
+    cancel:        Arc<AtomicBool>,  // the chain's cancel_mining — aborts the search on stop
+    task:          JoinHandle<()>,
+}
+
+pub struct AutoMiner {
+    session:  Mutex<Option<Session>>,
+    progress: Arc<Mutex<Progress>>,
+    search:   Arc<Mutex<MiningStatus>>,  // the chain's mining_status
+}
+
+impl AutoMiner {
+    /// `search` is the chain's mining_status, where every nonce search reports.
+    pub fn new(search: Arc<Mutex<MiningStatus>>) -> Self {
+        AutoMiner {
+            session:  Mutex::new(None),
+            progress: Arc::new(Mutex::new(Progress::default())),
+            search,
+        }
+    }
+
+    /// Spawn the mining loop, paying `address`. It mines whenever the mempool
+    /// has something, then waits `interval_secs` before looking again. With
+    /// `allow_empty` (or the chain's allow_empty_blocks) it mines every round.
+    pub fn start(&self, state: web::Data<AppState>, address: String, interval_secs: u64,
+                 allow_empty: bool) -> Result<MinerStatus, MinerError> {
+        if !is_valid_address(&address) {
+            return Err(MinerError::InvalidAddress(address));
+        }
+        if interval_secs == 0 {
+            return Err(MinerError::InvalidInterval);
+        }
+        let mut session = self.session.locked();
+        if let Some(running) = session.as_ref() {
+            return Err(MinerError::AlreadyRunning(running.address.clone()));
+        }
+        *self.progress.locked() = Progress::default();
+        let cancel = state.chain.cancel_mining().clone();
+        let (stop, stopped) = watch::channel(false);
+        let task = tokio::spawn(mine_loop(state, address.clone(), Duration::from_secs(interval_secs), allow_empty,
+            stopped, self.progress.clone()));
+        info!(%address, interval_secs, allow_empty, "auto-miner started");
+        *session = Some(Session { address, interval_secs, allow_empty, stop, cancel, task });
+        drop(session);
+        Ok(self.status())
+    }
+
+    /// Stop the loop, aborting any search under way, and wait for it to
+    /// finish. Stopping an idle miner is a no-op.
+    pub async fn stop(&self) -> MinerStatus {
+        let session = self.session.locked().take();
+        if let Some(session) = session {
+            let _ = session.stop.send(true);
+            session.cancel.store(true, Ordering::Relaxed);
+            let _ = session.task.await;
+            info!(address = %session.address, blocks = self.progress.locked().blocks_mined, "auto-miner stopped");
+        }
+        self.status()
+    }
+
+    pub fn status(&self) -> MinerStatus {
+        let session = self.session.locked();
+        let progress = self.progress.locked();
+        MinerStatus {
+            status:        if session.is_some() { "running" } else { "idle" },
+            address:       session.as_ref().map(|s| s.address.clone()),
+            interval_secs: session.as_ref().map(|s| s.interval_secs),
+            allow_empty:   session.as_ref().is_some_and(|s| s.allow_empty),
+            blocks_mined:  progress.blocks_mined,
+            last_error:    progress.last_error.clone(),
+            search:        self.search.locked().clone(),
+        }
+    }
+}
+
+async fn mine_loop(state: web::Data<AppState>, address: String, interval: Duration, allow_empty: bool,
+                   mut stopped: watch::Receiver<bool>, progress: Arc<Mutex<Progress>>) {
+    loop {
+        if *stopped.borrow() {
+            return;
+        }
+        match mine_round(&state, &address, allow_empty, &stopped).await {
+            Ok(Some(height)) => {
+                let mut progress = progress.locked();
+                progress.blocks_mined += 1;
+                progress.last_error = None;
+                debug!(height, "auto-miner committed a block");
+            }
+            Ok(None) => {}
+            Err(e)   => {
+                warn!(error = %e, "auto-miner round failed");
+                progress.locked().last_error = Some(e);
+            }
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = stopped.changed()            => return,
+        }
+    }
+}
+
+// one snapshot-mine-commit round; Ok(None) when there was nothing to mine or the search was stopped
+async fn mine_round(state: &web::Data<AppState>, address: &str, allow_empty: bool,
+                    stopped: &watch::Receiver<bool>) -> Result<Option<usize>, String> {
+    let job = match state.chain.template(address.to_string(), allow_empty).await {
+        Ok(job)                      => job,
+        Err(ChainError::EmptyMempool) => return Ok(None),
+        Err(e)                       => return Err(e.to_string()),
+    };
+
+    let cancel = state.chain.cancel_mining().clone();
+    cancel.store(false, Ordering::Relaxed);
+    // a stop that landed before the reset above would otherwise be lost
+    if *stopped.borrow() {
+        return Ok(None);
+    }
+    let mined = web::block(move || job.mine(&cancel)).await;
+
+    match mined {
+        Ok(Ok(block))                   => commit_mined(state, block).await.map(Some).map_err(|e| e.to_string()),
+        // by stop, shutdown or POST /mine/cancel
+        Ok(Err(MineAborted::Cancelled)) => Ok(None),
+        Ok(Err(e))                      => Err(e.to_string()),
+        Err(e)                          => Err(format!("mining thread failed: {}", e)),
+    }
+}
@@ -0,0 +1,138 @@
+// Multisig — an address controlled by M of N public keys.
+// The address is a hash of the sorted keys and M, so the key set can't be swapped later.
+
+use ed25519_dalek::Signature;
+use sha2::{Sha256, Digest};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+use crate::canonical::{CanonicalEncode, DOMAIN_MULTISIG};
+use crate::error::ChainError;
+use crate::transaction::{decode_signature, parse_address};
+use crate::util::short;
+use crate::wallet::{Wallet, verify_signature};
+
+/// Multisig addresses are this prefix + 64 hex chars, so they can't be mistaken for a key.
+pub const MULTISIG_PREFIX: &str = "ms";
+
+/// Most keys one multisig address can be built from.
+pub const MAX_MULTISIG_KEYS: usize = 15;
+
+/// One co-signer's signature over the transaction's message_to_sign().
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct CoSignature {
+    pub key:           String,  // which of the declared keys signed
+    pub signature_hex: String,
+}
+
+impl CanonicalEncode for CoSignature {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.key.encode(buf);
+        self.signature_hex.encode(buf);
+    }
+}
+
+/// The key set behind a multisig `from`, plus the signatures gathered so far.
+/// Travels inside the transaction, so a partly signed one can be passed between co-signers.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct Multisig {
+    pub keys:       Vec<String>,  // sorted, no duplicates
+    pub threshold:  u8,
+    #[serde(default)]
+    pub signatures: Vec<CoSignature>,
+}
+
+impl Multisig {
+    /// `threshold` of `keys` must sign. Keys are sorted and must be distinct public keys.
+    pub fn new(mut keys: Vec<String>, threshold: u8) -> Result<Self, String> {
+        keys.sort();
+        if keys.windows(2).any(|w| w[0] == w[1]) {
+            return Err("multisig keys must be distinct".to_string());
+        }
+        if keys.is_empty() || keys.len() > MAX_MULTISIG_KEYS {
+            return Err(format!("multisig needs 1 to {} keys, got {}", MAX_MULTISIG_KEYS, keys.len()));
+        }
+        if threshold == 0 || threshold as usize > keys.len() {
+            return Err(format!("threshold must be between 1 and {}, got {}", keys.len(), threshold));
+        }
+        for key in &keys {
+            parse_address(key, "multisig key").map_err(|e| e.to_string())?;
+        }
+        Ok(Multisig { keys, threshold, signatures: vec![] })
+    }
+
+    /// "ms" + SHA-256 of the canonical encoding of DOMAIN_MULTISIG, the threshold
+    /// and the sorted keys.
+    pub fn address(&self) -> String {
+        let mut sorted = self.keys.clone();
+        sorted.sort();
+        let mut buf = Vec::with_capacity(64 + 68 * sorted.len());
+        DOMAIN_MULTISIG.encode(&mut buf);
+        self.threshold.encode(&mut buf);
+        sorted.encode(&mut buf);
+        format!("{}{:x}", MULTISIG_PREFIX, Sha256::digest(&buf))
+    }
+
+    /// What new() enforces, for a key set that arrived some other way (JSON, say):
+    /// 1 to MAX_MULTISIG_KEYS keys, no more signatures than keys, each one 64 bytes of hex.
+    pub fn check_shape(&self) -> Result<(), ChainError> {
+        if self.keys.is_empty() || self.keys.len() > MAX_MULTISIG_KEYS {
+            return Err(ChainError::InvalidMultisig(format!("needs 1 to {} keys, has {}", MAX_MULTISIG_KEYS, self.keys.len())));
+        }
+        if self.signatures.len() > self.keys.len() {
+            return Err(ChainError::InvalidMultisig(format!("{} signatures for {} keys", self.signatures.len(), self.keys.len())));
+        }
+        for sig in &self.signatures {
+            decode_signature(&sig.signature_hex)?;
+        }
+        Ok(())
+    }
+
+    /// Add `wallet`'s signature over `message`. Errors if the wallet isn't one of the keys.
+    /// Signing twice just replaces the earlier signature.
+    pub fn add_signature(&mut self, wallet: &Wallet, message: &[u8]) -> Result<(), String> {
+        self.check_shape().map_err(|e| e.to_string())?;
+        let key = wallet.address();
+        if !self.keys.contains(&key) {
+            return Err(format!("{}... is not one of this multisig's keys", short(&key, 12)));
+        }
+        let signature_hex = hex::encode(wallet.sign(message).to_bytes());
+        self.signatures.retain(|s| s.key != key);
+        self.signatures.push(CoSignature { key, signature_hex });
+        self.signatures.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(())
+    }
+
+    /// At least `threshold` distinct declared keys signed `message`, and every
+    /// signature present is genuine — a bad one fails the whole thing.
+    pub fn verify(&self, message: &[u8]) -> Result<(), ChainError> {
+        let invalid = |reason: String| Err(ChainError::InvalidMultisig(reason));
+        if self.threshold == 0 || self.threshold as usize > self.keys.len() {
+            return invalid(format!("threshold {} with {} keys", self.threshold, self.keys.len()));
+        }
+        for (i, sig) in self.signatures.iter().enumerate() {
+            if !self.keys.contains(&sig.key) {
+                return invalid(format!("{}... signed but isn't one of the keys", short(&sig.key, 12)));
+            }
+            if self.signatures[..i].iter().any(|s| s.key == sig.key) {
+                return invalid(format!("{}... signed twice", short(&sig.key, 12)));
+            }
+            let verifying_key = parse_address(&sig.key, "multisig key")?;
+            let signature = Signature::from_bytes(&decode_signature(&sig.signature_hex)?);
+            if !verify_signature(&verifying_key, message, &signature) {
+                return Err(ChainError::InvalidSignature);
+            }
+        }
+        if self.signatures.len() < self.threshold as usize {
+            return Err(ChainError::NotEnoughSignatures {
+                have: self.signatures.len(), need: self.threshold as usize,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// "ms" followed by 64 hex chars.
+pub fn is_multisig_address(address: &str) -> bool {
+    address.strip_prefix(MULTISIG_PREFIX)
+        .is_some_and(|rest| rest.len() == 64 && rest.chars().all(|c| c.is_ascii_hexdigit()))
+}
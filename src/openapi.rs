@@ -0,0 +1,112 @@
+// OpenAPI — the spec behind GET /openapi.json, generated from the handlers'
+// #[utoipa::path] attributes and the request/response types' ToSchema derives.
+// Add a route in main.rs → annotate the handler and list it in ApiDoc below.
+
+use actix_web::{HttpResponse, Responder};
+use actix_web::http::header::ContentType;
+use utoipa::{Modify, OpenApi, ToSchema};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use crate::api;
+use crate::auth::API_KEY_HEADER;
+
+/// The envelope every failure comes back in.
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct ApiError {
+    /// always false
+    ok:         bool,
+    message:    String,
+    /// null, except for 429 (retry_after_secs) and 503 from /ready (the readiness report)
+    #[schema(value_type = Option<Object>)]
+    data:       Option<serde_json::Value>,
+    /// stable name to match on — the message wording may change
+    #[schema(example = "INSUFFICIENT_FUNDS")]
+    error_code: String,
+}
+
+// the 🔑 endpoints name this scheme in their security(...)
+struct AdminKey;
+
+impl Modify for AdminKey {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme("api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))));
+        }
+    }
+}
+
+// utoipa fills info.license from Cargo.toml, which has none — an empty name isn't valid
+struct NoLicense;
+
+impl Modify for NoLicense {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi.info.license = None;
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Mini Blockchain", description = "Every response is the { ok, message, data, error_code } envelope."),
+    paths(
+        api::health, api::ready, api::metrics, api::get_rejections,
+        api::new_wallet, api::restore_wallet, api::multisig_address, api::verify_signed_message,
+        api::submit_transaction, api::submit_raw_transaction, api::decode_raw_transaction,
+        api::get_transaction, api::burn,
+        api::mine_block, api::cancel_mining, api::get_mining_template, api::submit_block,
+        api::start_miner, api::stop_miner, api::miner_status,
+        api::get_chain, api::replace_chain, api::import_chain, api::rollback_chain, api::get_headers, api::get_chain_head, api::get_chain_locator,
+        api::get_block, api::get_block_by_hash,
+        api::get_mempool, api::get_mempool_stats, api::get_mempool_transaction,
+        api::get_balance, api::get_rich_list, api::get_address_history, api::get_address_stats, api::get_nonce, api::search,
+        api::export_transactions, api::export_balances,
+        api::get_reward, api::estimate_fee, api::get_stats, api::get_block_stats, api::get_supply,
+        api::get_proof, api::validate_chain,
+        api::register_peer, api::list_peers, api::peer_block, api::peer_transaction,
+        api::register_webhook, api::list_webhooks, api::delete_webhook,
+    ),
+    components(schemas(ApiError)),
+    modifiers(&AdminKey, &NoLicense),
+    tags(
+        (name = "node",         description = "liveness, readiness, validation, rejections"),
+        (name = "wallets",      description = "keys, addresses and signed messages"),
+        (name = "transactions", description = "submitting and looking up transactions"),
+        (name = "mining",       description = "mining here or for an external miner"),
+        (name = "chain",        description = "blocks, headers and the chain itself"),
+        (name = "mempool",      description = "transactions waiting to be mined"),
+        (name = "addresses",    description = "balances and history"),
+        (name = "economics",    description = "reward, fees and supply"),
+        (name = "peers",        description = "other nodes and their announcements"),
+        (name = "webhooks",     description = "chain events POSTed to your URL"),
+    ),
+)]
+pub struct ApiDoc;
+
+// GET /openapi.json
+pub async fn openapi_json() -> impl Responder {
+    match ApiDoc::openapi().to_pretty_json() {
+        Ok(spec) => HttpResponse::Ok().content_type(ContentType::json()).body(spec),
+        Err(e)   => api::fail(actix_web::http::StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR",
+            &format!("couldn't render the spec: {}", e)),
+    }
+}
+
+// GET /docs — Swagger UI over /openapi.json, loaded from a CDN so nothing is bundled
+pub async fn swagger_ui() -> impl Responder {
+    HttpResponse::Ok().content_type(ContentType::html()).body(SWAGGER_PAGE)
+}
+
+const SWAGGER_PAGE: &str = r##"<!doctype html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>Mini Blockchain API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });</script>
+</body>
+</html>
+"##;
@@ -0,0 +1,308 @@
+// Peers — other nodes this one knows about, keyed by base URL.
+// A peer is only registered after it answers /chain/head with our genesis hash,
+// and from then on its announcements have to be signed by the node key it gave us.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use ed25519_dalek::Signature;
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use sha2::{Sha256, Digest};
+use crate::clock::{Clock, SystemClock};
+use crate::transaction::parse_address;
+use crate::util::{chain_stem, short};
+use crate::wallet::{Wallet, verify_signature};
+use tracing::{info, warn};
+
+/// How long to wait on a peer before counting it as unreachable.
+pub const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Headers every peer announcement carries: the sender's node key, and its
+/// signature over the SHA-256 of the request body.
+pub const NODE_KEY_HEADER: &str = "X-Node-Key";
+pub const NODE_SIGNATURE_HEADER: &str = "X-Node-Signature";
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum PeerError {
+    #[error("Invalid peer URL: {0}")]
+    InvalidUrl(String),
+    #[error("{0} is already registered")]
+    AlreadyRegistered(String),
+    #[error("{0} is this node")]
+    SelfRegistration(String),
+    #[error("{url} is unreachable: {reason}")]
+    Unreachable { url: String, reason: String },
+    #[error("{url} doesn't speak the protocol: {reason}")]
+    NotANode { url: String, reason: String },
+    #[error("{0} is on a different chain — genesis hash doesn't match ours")]
+    GenesisMismatch(String),
+    #[error("Announcement is missing the {} and {} headers", NODE_KEY_HEADER, NODE_SIGNATURE_HEADER)]
+    Unsigned,
+    #[error("Node key {}... doesn't belong to a registered peer", short(.0, 12))]
+    UnknownNode(String),
+    #[error("Announcement from {0} has a bad signature — dropped")]
+    BadSignature(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct Peer {
+    pub url:               String,
+    pub node_key:          String,          // its identity public key, from registration
+    pub added_at:          u64,
+    pub last_seen:         Option<u64>,     // last time it answered
+    pub last_error:        Option<String>,  // cleared by the next successful contact
+    pub failures:          u32,             // failed contacts since it last answered
+    pub bad_announcements: u64,             // unsigned or mis-signed, dropped unread
+}
+
+/// The parts of a peer's GET /chain/head that registration looks at.
+#[derive(Deserialize, Clone, Debug)]
+pub struct RemoteHead {
+    pub index:        u32,
+    pub hash:         String,
+    pub genesis_hash: String,
+    pub node_id:      String,  // the node's identity public key
+    pub total_work:   u128,
+}
+
+// the { ok, message, data } envelope every endpoint answers with
+#[derive(Deserialize)]
+struct Envelope<T> {
+    ok:      bool,
+    message: String,
+    data:    Option<T>,
+}
+
+pub struct PeerSet {
+    pub identity: Wallet,  // signs our announcements; its address is our node_id
+    pub clock:    Arc<dyn Clock>,
+    client:       reqwest::Client,
+    peers:        BTreeMap<String, Peer>,
+}
+
+impl PeerSet {
+    pub fn new(identity: Wallet) -> Self {
+        PeerSet {
+            identity,
+            clock:   Arc::new(SystemClock),
+            client:  reqwest::Client::builder()
+                .timeout(PEER_TIMEOUT)
+                .build()
+                .expect("HTTP client with a timeout always builds"),
+            peers:   BTreeMap::new(),
+        }
+    }
+
+    /// Our identity public key — what peers register us under.
+    pub fn node_id(&self) -> String {
+        self.identity.address()
+    }
+
+    /// The X-Node-Key and X-Node-Signature values for an announcement with this body.
+    pub fn sign_announcement(&self, body: &[u8]) -> (String, String) {
+        let signature = self.identity.sign(&announcement_digest(body));
+        (self.node_id(), hex::encode(signature.to_bytes()))
+    }
+
+    /// Which registered peer sent this announcement. Cheap, so it runs before any
+    /// parsing or validation: a key we don't know or a signature that doesn't check
+    /// out and the body is never looked at. Bad signatures count against the peer.
+    pub fn verify_announcement(&mut self, node_key: Option<&str>, signature_hex: Option<&str>, body: &[u8]) -> Result<String, PeerError> {
+        let (Some(node_key), Some(signature_hex)) = (node_key, signature_hex) else {
+            return Err(PeerError::Unsigned);
+        };
+        let peer = self.peers.values_mut()
+            .find(|p| p.node_key == node_key)
+            .ok_or_else(|| PeerError::UnknownNode(node_key.to_string()))?;
+        let genuine = parse_address(node_key, "node key").ok()
+            .zip(hex::decode(signature_hex).ok().and_then(|b| <[u8; 64]>::try_from(b).ok()))
+            .is_some_and(|(key, sig)| verify_signature(&key, &announcement_digest(body), &Signature::from_bytes(&sig)));
+        if !genuine {
+            peer.bad_announcements += 1;
+            warn!(peer = %peer.url, bad_announcements = peer.bad_announcements, "dropped a badly signed announcement");
+            return Err(PeerError::BadSignature(peer.url.clone()));
+        }
+        Ok(peer.url.clone())
+    }
+
+    /// A handle to the shared HTTP client — cheap to clone, so callers can
+    /// take one and drop the lock before awaiting.
+    pub fn client(&self) -> reqwest::Client {
+        self.client.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    pub fn contains(&self, url: &str) -> bool {
+        self.peers.contains_key(url)
+    }
+
+    pub fn list(&self) -> Vec<&Peer> {
+        self.peers.values().collect()
+    }
+
+    pub fn urls(&self) -> Vec<String> {
+        self.peers.keys().cloned().collect()
+    }
+
+    /// Add an already-probed peer. `url` must be normalized. One node key, one
+    /// registration — the same node under a second URL counts as a duplicate.
+    pub fn insert(&mut self, url: String, node_key: String) -> Result<&Peer, PeerError> {
+        if let Some(existing) = self.peers.values().find(|p| p.url == url || p.node_key == node_key) {
+            return Err(PeerError::AlreadyRegistered(existing.url.clone()));
+        }
+        let now = self.clock.now_secs();
+        info!(peer = %url, node = %short(&node_key, 12), "peer registered");
+        Ok(self.peers.entry(url.clone()).or_insert(Peer {
+            url,
+            node_key,
+            added_at:          now,
+            last_seen:         Some(now),
+            last_error:        None,
+            failures:          0,
+            bad_announcements: 0,
+        }))
+    }
+
+    /// Write the peer list to disk as JSON; returns how many.
+    pub fn save(&self, path: &str) -> Result<usize, String> {
+        let peers: Vec<&Peer> = self.peers.values().collect();
+        let json = serde_json::to_string_pretty(&peers)
+            .map_err(|e| format!("Could not serialize peers: {}", e))?;
+        std::fs::write(path, json)
+            .map_err(|e| format!("Could not write {}: {}", path, e))?;
+        Ok(peers.len())
+    }
+
+    /// Peers save() wrote, taken back as they were — no probe; the next sync
+    /// round finds out which are still up. No file is 0.
+    pub fn restore(&mut self, path: &str) -> Result<usize, String> {
+        if !std::path::Path::new(path).exists() {
+            return Ok(0);
+        }
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path, e))?;
+        let saved: Vec<Peer> = serde_json::from_str(&json)
+            .map_err(|e| format!("{} is not a valid peer file: {}", path, e))?;
+        let count = saved.len();
+        self.peers.extend(saved.into_iter().map(|p| (p.url.clone(), p)));
+        Ok(count)
+    }
+
+    /// The peer answered — reset its failure count.
+    pub fn mark_seen(&mut self, url: &str) {
+        let now = self.clock.now_secs();
+        if let Some(peer) = self.peers.get_mut(url) {
+            peer.last_seen = Some(now);
+            peer.last_error = None;
+            peer.failures = 0;
+        }
+    }
+
+    /// Talking to the peer failed — keep the reason around for GET /peers.
+    pub fn mark_failed(&mut self, url: &str, error: &str) {
+        if let Some(peer) = self.peers.get_mut(url) {
+            peer.last_error = Some(error.to_string());
+            peer.failures += 1;
+        }
+    }
+}
+
+impl PeerError {
+    /// The API's error_code for this failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            PeerError::InvalidUrl(_)        => "INVALID_URL",
+            PeerError::AlreadyRegistered(_) => "ALREADY_REGISTERED",
+            PeerError::SelfRegistration(_)  => "SELF_REGISTRATION",
+            PeerError::Unreachable { .. }   => "PEER_UNREACHABLE",
+            PeerError::NotANode { .. }      => "NOT_A_NODE",
+            PeerError::GenesisMismatch(_)   => "GENESIS_MISMATCH",
+            PeerError::Unsigned             => "UNSIGNED_ANNOUNCEMENT",
+            PeerError::UnknownNode(_)       => "UNKNOWN_NODE",
+            PeerError::BadSignature(_)      => "BAD_SIGNATURE",
+        }
+    }
+}
+
+/// What an announcement signature covers.
+fn announcement_digest(body: &[u8]) -> Vec<u8> {
+    Sha256::digest(body).to_vec()
+}
+
+/// The node's identity wallet, kept in `path` as a hex private key so the node
+/// keeps its key — and its registrations with peers — across restarts.
+pub fn load_or_create_identity(path: &str) -> Result<Wallet, String> {
+    if std::path::Path::new(path).exists() {
+        let hex_key = std::fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read node key {}: {}", path, e))?;
+        return Wallet::from_private_key_hex(hex_key.trim())
+            .map_err(|e| format!("Node key {} is invalid: {}", path, e));
+    }
+    let identity = Wallet::new();
+    std::fs::write(path, identity.export_secret().as_bytes())
+        .map_err(|e| format!("Couldn't save node key {}: {}", path, e))?;
+    info!(node = %short(&identity.address(), 12), path, "new node key saved");
+    Ok(identity)
+}
+
+/// Where the peer list waits out a restart: chain.json → chain.peers.json.
+pub fn peers_path(chain_file: &str) -> String {
+    format!("{}.peers.json", chain_stem(chain_file))
+}
+
+/// "http://host:port" with any trailing slashes dropped, so one node can't be
+/// registered twice under two spellings. Only plain http(s) base URLs.
+pub fn normalize_url(url: &str) -> Result<String, PeerError> {
+    let invalid = |reason: &str| Err(PeerError::InvalidUrl(format!("{:?} {}", url, reason)));
+    let trimmed = url.trim().trim_end_matches('/');
+    let Some(rest) = trimmed.strip_prefix("http://").or_else(|| trimmed.strip_prefix("https://")) else {
+        return invalid("must start with http:// or https://");
+    };
+    if rest.is_empty() {
+        return invalid("has no host");
+    }
+    if rest.chars().any(|c| c.is_whitespace() || matches!(c, '?' | '#')) {
+        return invalid("must be a bare base URL");
+    }
+    Ok(trimmed.to_string())
+}
+
+/// GET {url}{path} and unwrap the { ok, message, data } envelope.
+pub async fn get_json<T: DeserializeOwned>(client: &reqwest::Client, url: &str, path: &str) -> Result<T, PeerError> {
+    let unreachable = |e: reqwest::Error| PeerError::Unreachable { url: url.to_string(), reason: e.to_string() };
+    let not_a_node = |reason: String| PeerError::NotANode { url: url.to_string(), reason };
+    let response = client.get(format!("{}{}", url, path)).send().await.map_err(unreachable)?;
+    let envelope: Envelope<T> = response.json().await
+        .map_err(|e| not_a_node(format!("{} returned unexpected JSON ({})", path, e)))?;
+    match envelope.data {
+        Some(data) if envelope.ok => Ok(data),
+        _ => Err(not_a_node(format!("{} failed: {}", path, envelope.message))),
+    }
+}
+
+/// GET {url}/chain/head.
+pub async fn fetch_head(client: &reqwest::Client, url: &str) -> Result<RemoteHead, PeerError> {
+    get_json(client, url, "/chain/head").await
+}
+
+/// Ask `url` for its head and check it's someone else on our chain.
+pub async fn probe(client: &reqwest::Client, url: &str, genesis_hash: &str, node_id: &str) -> Result<RemoteHead, PeerError> {
+    let head = fetch_head(client, url).await?;
+    if head.node_id == node_id {
+        return Err(PeerError::SelfRegistration(url.to_string()));
+    }
+    if head.genesis_hash != genesis_hash {
+        return Err(PeerError::GenesisMismatch(url.to_string()));
+    }
+    Ok(head)
+}
@@ -0,0 +1,169 @@
+// Rate limiting — a token bucket per client IP in front of the endpoints that
+// cost real work (signature checks, mempool space, mining). Reads aren't limited.
+
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest, HttpResponse};
+use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use actix_web::http::header::RETRY_AFTER;
+use serde::Serialize;
+use crate::api::{AppState, Res};
+use crate::util::LockExt;
+
+/// Requests per minute per IP when RATE_LIMIT_PER_MINUTE isn't set.
+pub const DEFAULT_RATE_LIMIT_PER_MINUTE: u32 = 60;
+
+/// Idle buckets are dropped this often.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Bucket {
+    tokens:  f64,
+    updated: Instant,
+}
+
+/// Each IP gets `per_minute` tokens, refilled continuously; every limited
+/// request takes one. A per_minute of 0 turns the limiter off.
+pub struct RateLimiter {
+    per_minute: u32,
+    state:      Mutex<Buckets>,
+}
+
+struct Buckets {
+    by_ip:       HashMap<IpAddr, Bucket>,
+    last_pruned: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: u32) -> Self {
+        RateLimiter {
+            per_minute,
+            state: Mutex::new(Buckets { by_ip: HashMap::new(), last_pruned: Instant::now() }),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self::new(0)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.per_minute > 0
+    }
+
+    /// Take a token for `ip`. Err(seconds until the next one) when the bucket is empty.
+    pub fn check(&self, ip: IpAddr) -> Result<(), u64> {
+        self.check_at(ip, Instant::now())
+    }
+
+    /// check() at a given instant, so the refill can be tested without sleeping.
+    pub fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), u64> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let capacity = self.per_minute as f64;
+        let per_sec = capacity / 60.0;
+        let mut state = self.state.locked();
+        // a bucket idle long enough to be full again is the same as no bucket
+        if now.saturating_duration_since(state.last_pruned) >= PRUNE_INTERVAL {
+            state.by_ip.retain(|_, b| b.tokens + now.saturating_duration_since(b.updated).as_secs_f64() * per_sec < capacity);
+            state.last_pruned = now;
+        }
+        let bucket = state.by_ip.entry(ip).or_insert(Bucket { tokens: capacity, updated: now });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_sec).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / per_sec).ceil().max(1.0) as u64)
+        }
+    }
+
+    /// How many IPs currently have a bucket.
+    pub fn tracked(&self) -> usize {
+        self.state.locked().by_ip.len()
+    }
+}
+
+#[derive(Serialize)]
+struct RetryAfter {
+    retry_after_secs: u64,
+}
+
+/// Extractor for rate-limited handlers: put `_limit: RateLimited` in the
+/// argument list and a client over its budget gets 429 RATE_LIMITED with
+/// data.retry_after_secs (and a Retry-After header) instead.
+pub struct RateLimited;
+
+impl FromRequest for RateLimited {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(state) = req.app_data::<web::Data<AppState>>() else {
+            return ready(Ok(RateLimited));
+        };
+        // requests with no peer address (tests, unix sockets) share one bucket
+        let ip = req.peer_addr().map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |a| a.ip());
+        ready(match state.rate_limiter.check(ip) {
+            Ok(())           => Ok(RateLimited),
+            Err(retry_after) => {
+                let response = HttpResponse::build(StatusCode::TOO_MANY_REQUESTS)
+                    .insert_header((RETRY_AFTER, retry_after.to_string()))
+                    .json(Res {
+                        ok:         false,
+                        message:    format!("too many requests — try again in {}s", retry_after),
+                        data:       Some(RetryAfter { retry_after_secs: retry_after }),
+                        error_code: Some("RATE_LIMITED".to_string()),
+                    });
+                Err(InternalError::from_response("rate limited", response).into())
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn empty_bucket_says_when_to_come_back() {
+        let limiter = RateLimiter::new(3);
+        let t0 = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(ip("1.2.3.4"), t0), Ok(()));
+        }
+        // 3 a minute is one every 20s
+        assert_eq!(limiter.check_at(ip("1.2.3.4"), t0), Err(20));
+        assert_eq!(limiter.check_at(ip("5.6.7.8"), t0), Ok(()));
+        assert_eq!(limiter.check_at(ip("1.2.3.4"), t0 + Duration::from_secs(20)), Ok(()));
+        assert!(limiter.check_at(ip("1.2.3.4"), t0 + Duration::from_secs(21)).is_err());
+    }
+
+    #[test]
+    fn refilled_buckets_are_pruned() {
+        let limiter = RateLimiter::new(3);
+        let t0 = Instant::now();
+        limiter.check_at(ip("1.2.3.4"), t0).unwrap();
+        limiter.check_at(ip("5.6.7.8"), t0).unwrap();
+        assert_eq!(limiter.tracked(), 2);
+        limiter.check_at(ip("9.9.9.9"), t0 + PRUNE_INTERVAL * 2).unwrap();
+        assert_eq!(limiter.tracked(), 1);
+    }
+
+    #[test]
+    fn zero_per_minute_is_off() {
+        let limiter = RateLimiter::disabled();
+        let t0 = Instant::now();
+        assert!((0..1000).all(|_| limiter.check_at(ip("1.2.3.4"), t0).is_ok()));
+        assert_eq!(limiter.tracked(), 0);
+    }
+}
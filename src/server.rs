@@ -0,0 +1,377 @@
+// Server — everything between a NodeConfig and a running node: load or create
+// the chain, mempool, peers, identity, webhooks and admin key, serve every
+// route until Ctrl-C or SIGTERM, then write them back out for the next start.
+// main.rs configures it from env vars, bin/node.rs from flags and a TOML file.
+
+use std::io::{Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use actix_web::{web, App, HttpServer};
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
+use crate::{api, audit, auth, explorer, gossip, idempotency, miner, openapi, peers, ratelimit, status, sync, webhooks};
+use crate::block::check_difficulty;
+use crate::actor::ChainHandle;
+use crate::api::AppState;
+use crate::blockchain::{Blockchain, mempool_path};
+use crate::peers::PeerError;
+use crate::store::{ChainStore, JsonStore, SqliteStore, Storage, other_format, sqlite_path};
+use crate::wal::wal_path;
+use crate::util::LockExt;
+
+const DEFAULT_DIFFICULTY: u32 = 4;
+
+pub struct NodeConfig {
+    pub bind:                  String,
+    pub chain_file:            String,
+    pub node_key_file:         String,
+    pub api_key:               Option<String>,  // wins over api_key_file
+    pub api_key_file:          String,
+    pub rate_limit_per_minute: u32,
+    pub idempotency_window:    Duration,
+    pub difficulty:            Option<u32>,     // a new chain's, in leading zero bits; a saved chain keeps its own
+    pub reward:                Option<u64>,     // in nits, likewise
+    pub peers:                 Vec<String>,     // registered at startup
+    pub allow_empty_blocks:    bool,            // /mine and the auto-miner may mine coinbase-only blocks
+    pub storage:               Storage,         // sqlite keeps the chain beside chain_file, in chain.sqlite
+    pub prune_depth:           Option<usize>,   // keep only headers for blocks this far below the tip
+    pub audit_capacity:        usize,           // rejections kept for GET /audit/rejections
+}
+
+impl NodeConfig {
+    /// PORT, CHAIN_FILE, NODE_KEY_FILE, API_KEY, API_KEY_FILE,
+    /// RATE_LIMIT_PER_MINUTE, IDEMPOTENCY_WINDOW_SECS, ALLOW_EMPTY_BLOCKS,
+    /// STORAGE and AUDIT_LOG_CAPACITY, with their defaults.
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+        let number = |name: &str| std::env::var(name).ok()
+            .map(|v| v.parse::<u64>().map_err(|_| format!("{} must be a whole number, got {:?}", name, v)))
+            .transpose();
+        Ok(NodeConfig {
+            // Railway injects PORT; locally it falls back to 3000
+            bind:                  format!("0.0.0.0:{}", var("PORT", "3000")),
+            chain_file:            var("CHAIN_FILE", "chain.json"),
+            node_key_file:         var("NODE_KEY_FILE", "node.key"),
+            api_key:               std::env::var("API_KEY").ok().filter(|k| !k.trim().is_empty()),
+            api_key_file:          var("API_KEY_FILE", "api.key"),
+            rate_limit_per_minute: match number("RATE_LIMIT_PER_MINUTE")? {
+                Some(n) => u32::try_from(n).map_err(|_| "RATE_LIMIT_PER_MINUTE is too large".to_string())?,
+                None    => ratelimit::DEFAULT_RATE_LIMIT_PER_MINUTE,
+            },
+            idempotency_window:    number("IDEMPOTENCY_WINDOW_SECS")?
+                .map_or(idempotency::DEFAULT_IDEMPOTENCY_WINDOW, Duration::from_secs),
+            difficulty:            None,
+            reward:                None,
+            peers:                 Vec::new(),
+            allow_empty_blocks:    match std::env::var("ALLOW_EMPTY_BLOCKS").as_deref() {
+                Ok("1" | "true")           => true,
+                Ok("0" | "false") | Err(_) => false,
+                Ok(other)                  => return Err(format!("ALLOW_EMPTY_BLOCKS must be true or false, got {:?}", other)),
+            },
+            storage:               match std::env::var("STORAGE") {
+                Ok(s)  => s.parse()?,
+                Err(_) => Storage::default(),
+            },
+            prune_depth:           None,
+            audit_capacity:        match number("AUDIT_LOG_CAPACITY")? {
+                Some(n) => usize::try_from(n).map_err(|_| "AUDIT_LOG_CAPACITY is too large".to_string())?,
+                None    => audit::DEFAULT_AUDIT_CAPACITY,
+            },
+        })
+    }
+}
+
+/// json or pretty; RUST_LOG picks the levels, info by default.
+pub fn init_logging(format: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let logs = tracing_subscriber::fmt().with_env_filter(filter);
+    match format {
+        "json"   => logs.json().init(),
+        "pretty" => logs.init(),
+        other    => return Err(format!("unknown log format {:?} (expected json or pretty)", other)),
+    }
+    Ok(())
+}
+
+/// Every route, with the extractor error handlers that keep failures in the envelope.
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg
+        .app_data(web::JsonConfig::default().error_handler(api::json_error))
+        .app_data(web::QueryConfig::default().error_handler(api::query_error))
+        .app_data(web::PathConfig::default().error_handler(api::path_error))
+        .route("/",                  web::get().to(explorer::index))
+        .route("/search",            web::get().to(api::search))
+        .route("/health",            web::get().to(api::health))
+        .route("/ready",             web::get().to(api::ready))
+        .route("/metrics",           web::get().to(api::metrics))
+        .route("/audit/rejections",  web::get().to(api::get_rejections))
+        .route("/openapi.json",      web::get().to(openapi::openapi_json))
+        .route("/docs",              web::get().to(openapi::swagger_ui))
+        .route("/wallet/new",        web::get().to(api::new_wallet))
+        .route("/wallet/restore",    web::post().to(api::restore_wallet))
+        .route("/multisig/address",  web::post().to(api::multisig_address))
+        .route("/message/verify",    web::post().to(api::verify_signed_message))
+        .route("/transaction",       web::post().to(api::submit_transaction))
+        .route("/transaction/raw",   web::post().to(api::submit_raw_transaction))
+        .route("/transaction/decode", web::post().to(api::decode_raw_transaction))
+        .route("/transaction/{txid}", web::get().to(api::get_transaction))
+        .route("/burn",              web::post().to(api::burn))
+        .route("/mine",              web::post().to(api::mine_block))
+        .route("/mine/cancel",       web::post().to(api::cancel_mining))
+        .route("/mining/template",   web::get().to(api::get_mining_template))
+        .route("/miner/start",       web::post().to(api::start_miner))
+        .route("/miner/stop",        web::post().to(api::stop_miner))
+        .route("/miner/status",      web::get().to(api::miner_status))
+        .route("/block",             web::post().to(api::submit_block))
+        .route("/chain",             web::get().to(api::get_chain))
+        .route("/chain/replace",     web::post().to(api::replace_chain))
+        .route("/chain/import",      web::post().to(api::import_chain))
+        .route("/chain/rollback",    web::post().to(api::rollback_chain))
+        .route("/chain/head",        web::get().to(api::get_chain_head))
+        .route("/chain/locator",     web::get().to(api::get_chain_locator))
+        .route("/headers",           web::get().to(api::get_headers))
+        .route("/block/{index}",     web::get().to(api::get_block))
+        .route("/block/hash/{hash}", web::get().to(api::get_block_by_hash))
+        .route("/mempool",           web::get().to(api::get_mempool))
+        .route("/mempool/stats",     web::get().to(api::get_mempool_stats))
+        .route("/mempool/{txid}",    web::get().to(api::get_mempool_transaction))
+        .route("/balance/{address}", web::get().to(api::get_balance))
+        .route("/richlist",          web::get().to(api::get_rich_list))
+        .route("/address/{address}/transactions", web::get().to(api::get_address_history))
+        .route("/address/{address}/stats", web::get().to(api::get_address_stats))
+        .route("/reward",            web::get().to(api::get_reward))
+        .route("/fee/estimate",      web::get().to(api::estimate_fee))
+        .route("/stats",             web::get().to(api::get_stats))
+        .route("/stats/blocks",      web::get().to(api::get_block_stats))
+        .route("/export/transactions.csv", web::get().to(api::export_transactions))
+        .route("/export/balances.json", web::get().to(api::export_balances))
+        .route("/supply",            web::get().to(api::get_supply))
+        .route("/nonce/{address}",   web::get().to(api::get_nonce))
+        .route("/proof/{txid}",      web::get().to(api::get_proof))
+        .route("/validate",          web::get().to(api::validate_chain))
+        .route("/peers",             web::post().to(api::register_peer))
+        .route("/peers",             web::get().to(api::list_peers))
+        .route("/peer/block",        web::post().to(api::peer_block))
+        .route("/peer/transaction",  web::post().to(api::peer_transaction))
+        .route("/webhooks",          web::post().to(api::register_webhook))
+        .route("/webhooks",          web::get().to(api::list_webhooks))
+        .route("/webhooks/{id}",     web::delete().to(api::delete_webhook));
+}
+
+/// Start the node and serve until Ctrl-C or SIGTERM, then shutdown().
+pub async fn run(config: NodeConfig) -> std::io::Result<()> {
+    let invalid = |e: String| Error::new(ErrorKind::InvalidData, e);
+    let difficulty = config.difficulty.unwrap_or(DEFAULT_DIFFICULTY);
+    check_difficulty(difficulty).map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))?;
+
+    // /ready answers 503 until the chain below is loaded and validated
+    let node_status = status::NodeStatus::new();
+
+    // reload the saved chain if there is one, otherwise start fresh
+    let store = open_store(&config).map_err(invalid)?;
+    let mut bc = if !store.is_empty().map_err(invalid)? {
+        let bc = Blockchain::from_store(store).map_err(invalid)?;
+        if config.difficulty.is_some() || config.reward.is_some_and(|r| r != bc.reward) {
+            warn!(chain_file = %config.chain_file, "using the saved chain's difficulty and reward; the configured ones only apply to a new chain");
+        }
+        bc
+    } else {
+        let mut bc = Blockchain::new(difficulty);
+        if let Some(reward) = config.reward {
+            bc.reward = reward;
+        }
+        bc.attach_store(store).map_err(invalid)?;
+        bc
+    };
+
+    bc.allow_empty_blocks = config.allow_empty_blocks;
+    bc.prune_depth = config.prune_depth;
+    let pruned = bc.prune_to_depth();
+    if pruned > 0 {
+        bc.persist().map_err(invalid)?;
+        info!(blocks = pruned, below = bc.pruned_below(), "pruned on startup");
+    }
+
+    // whatever was pending when the node stopped, or crashed, minus what the chain has since invalidated
+    let (mut restored, mut dropped) = bc.open_mempool_wal(&wal_path(&config.chain_file)).map_err(invalid)?;
+    // a snapshot from before the log existed goes into it, once
+    let snapshot = mempool_path(&config.chain_file);
+    if std::path::Path::new(&snapshot).exists() {
+        let (r, d) = bc.restore_mempool(&snapshot).map_err(invalid)?;
+        bc.log_mempool().map_err(invalid)?;
+        std::fs::remove_file(&snapshot)?;
+        (restored, dropped) = (restored + r, dropped + d);
+    }
+    if restored + dropped > 0 {
+        info!(restored, dropped, "mempool restored");
+    }
+    node_status.chain_loaded();
+
+    // the node's identity key signs everything it announces to peers
+    let identity = peers::load_or_create_identity(&config.node_key_file).map_err(invalid)?;
+
+    // peers hear about accepted transactions and blocks from a background task
+    let mut peer_set = peers::PeerSet::new(identity);
+    peer_set.restore(&peers::peers_path(&config.chain_file)).map_err(invalid)?;
+    let peers = Arc::new(Mutex::new(peer_set));
+    let gossip = gossip::Gossip::spawn(peers.clone());
+
+    // webhook registrations are kept next to the chain: chain.json → chain.webhooks.json
+    let registry = webhooks::WebhookRegistry::load(&webhooks::registry_path(&config.chain_file)).map_err(invalid)?;
+    let webhooks = webhooks::Webhooks::spawn(registry);
+
+    // admin endpoints want X-Api-Key: the configured key, otherwise the one in api_key_file
+    let api_key = match &config.api_key {
+        Some(key) => auth::ApiKey::new(key),
+        None      => auth::load_or_create_api_key(&config.api_key_file).map_err(invalid)?,
+    };
+
+    // from here on the chain lives on its own thread; everything else goes through the handle
+    let chain = ChainHandle::spawn(bc);
+    let miner = miner::AutoMiner::new(chain.mining_status().clone());
+    let state = web::Data::new(api::AppState {
+        chain,
+        chain_file:   config.chain_file,
+        peers,
+        gossip,
+        webhooks,
+        api_key,
+        // submits and mines per client IP per minute; 0 turns it off
+        rate_limiter: ratelimit::RateLimiter::new(config.rate_limit_per_minute),
+        // answers to POST /transaction(/raw) with an Idempotency-Key are replayed for this long
+        idempotency:  idempotency::IdempotencyStore::new(config.idempotency_window),
+        status:       node_status,
+        miner,
+        // the last audit_capacity transactions, blocks and chains turned away, for GET /audit/rejections
+        rejections:   audit::RejectionLog::new(config.audit_capacity),
+    });
+    sync::spawn_periodic(state.clone());
+
+    // configured peers are probed like POST /peers; one that's down is skipped, not fatal
+    let bootstrap = state.clone();
+    tokio::spawn(async move {
+        for url in config.peers {
+            match api::add_peer(&bootstrap, &url).await {
+                Ok(peer) => info!(url = %peer.url, "peer registered"),
+                Err(PeerError::AlreadyRegistered(_)) => {}
+                Err(e)   => warn!(%url, error = %e, "couldn't register peer"),
+            }
+        }
+    });
+
+    info!("running on http://{}", config.bind);
+
+    // signals are ours, not actix's: cancel any mine first, then let in-flight requests finish
+    let app_state = state.clone();
+    let server = HttpServer::new(move || App::new().app_data(app_state.clone()).configure(routes))
+        .disable_signals()
+        .bind(&config.bind)?
+        .run();
+    let handle = server.handle();
+    let stopping = state.clone();
+    tokio::spawn(async move {
+        let signal = shutdown_signal().await;
+        info!(signal, "shutting down: mining cancelled, finishing in-flight requests");
+        stopping.chain.cancel_mining().store(true, Ordering::Relaxed);
+        stopping.miner.stop().await;
+        handle.stop(true).await;
+    });
+    server.await?;
+
+    shutdown(&state).await;
+    Ok(())
+}
+
+// the configured backend; a node moving to sqlite brings its chain file along, once
+fn open_store(config: &NodeConfig) -> Result<Box<dyn ChainStore>, String> {
+    // chain.json if that's all there is when chain.json.zst is asked for, and the other way round
+    let exists = |path: &str| std::path::Path::new(path).exists();
+    let other = other_format(&config.chain_file);
+    let saved = match exists(&config.chain_file) || !exists(&other) {
+        true  => &config.chain_file,
+        false => &other,
+    };
+    match config.storage {
+        Storage::Json => {
+            let mut store = JsonStore::open(saved)?;
+            if *saved != config.chain_file {
+                store.move_to(&config.chain_file);
+                store.flush()?;
+                info!(from = %saved, to = %config.chain_file, "chain file converted, the old one is left in place");
+            }
+            Ok(Box::new(store))
+        }
+        Storage::Sqlite => {
+            let path = sqlite_path(&config.chain_file);
+            let mut store = SqliteStore::open(&path)?;
+            if store.is_empty()? && exists(saved) {
+                let blocks = store.import_from_json(saved)?;
+                info!(blocks, from = %saved, to = %path, "chain file imported into sqlite");
+            }
+            Ok(Box::new(store))
+        }
+    }
+}
+
+// Ctrl-C, or SIGTERM where there is one — what docker stop and systemd send
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+                _ = term.recv()             => "SIGTERM",
+            },
+            Err(e) => {
+                warn!(error = %e, "couldn't listen for SIGTERM, only Ctrl-C will stop the node");
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl-C"
+    }
+}
+
+/// What shutdown() wrote out.
+#[derive(Debug)]
+pub struct Flushed {
+    pub height:   usize,
+    pub mempool:  usize,
+    pub peers:    usize,
+    pub webhooks: usize,
+}
+
+/// Stop any mine in progress and write the chain, mempool log, peer list and
+/// webhook registry next to the chain file. Run once the server has stopped
+/// taking requests; a file that fails to write is logged and the rest still
+/// are. The next run() picks all four back up.
+pub async fn shutdown(state: &AppState) -> Flushed {
+    state.chain.cancel_mining().store(true, Ordering::Relaxed);
+    api::save_chain(state).await;
+
+    let written = |what: &str, result: Result<usize, String>| result.unwrap_or_else(|e| {
+        error!(error = %e, "could not save {}", what);
+        0
+    });
+    // the mempool log is compacted; a chain without one gets the old snapshot file
+    let path = mempool_path(&state.chain_file);
+    let (height, mempool) = state.chain.update(move |bc| {
+        let saved = bc.compact_mempool_wal().unwrap_or_else(|| bc.save_mempool(&path));
+        (bc.chain.len() - 1, saved)
+    }).await;
+    let flushed = Flushed {
+        height,
+        mempool:  written("mempool", mempool),
+        peers:    written("peers", state.peers.locked().save(&peers::peers_path(&state.chain_file))),
+        webhooks: written("webhooks", state.webhooks.registry.locked().flush()),
+    };
+    info!(chain_file = %state.chain_file, height = flushed.height, mempool = flushed.mempool,
+          peers = flushed.peers, webhooks = flushed.webhooks, "state flushed, shutting down");
+    flushed
+}
@@ -0,0 +1,111 @@
+// Node status — what GET /health and GET /ready report. Startup, every chain
+// save, every validation and every sync round record their outcome here.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::util::LockExt;
+
+/// The readiness part of the node's state, as GET /ready reports it.
+#[derive(Clone, Serialize, ToSchema)]
+pub struct Readiness {
+    pub status:                &'static str,  // "starting", "ready" or "degraded"
+    pub chain_loaded:          bool,
+    pub last_validation_error: Option<String>,
+    pub last_save_error:       Option<String>,
+    pub last_sync_error:       Option<String>,
+    pub secs_since_sync:       Option<u64>,
+}
+
+impl Readiness {
+    pub fn is_ready(&self) -> bool {
+        self.status == "ready"
+    }
+}
+
+#[derive(Default)]
+struct Health {
+    chain_loaded:          bool,
+    last_validation_error: Option<String>,
+    last_save_error:       Option<String>,
+    last_sync_error:       Option<String>,
+    last_sync:             Option<Instant>,
+}
+
+/// Starts out "starting"; ready once the chain is loaded and validated.
+/// A failed validation or a chain file that can't be written makes it
+/// "degraded" until the next success. Sync failures are reported but don't
+/// count against readiness — an unreachable peer isn't this node's problem.
+pub struct NodeStatus {
+    started: Instant,
+    health:  Mutex<Health>,
+    syncing: AtomicBool,    // a coalesced sync round is under way
+}
+
+impl NodeStatus {
+    pub fn new() -> Self {
+        NodeStatus { started: Instant::now(), health: Mutex::new(Health::default()), syncing: AtomicBool::new(false) }
+    }
+
+    pub fn uptime_secs(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+
+    /// The chain is in memory and passed validation.
+    pub fn chain_loaded(&self) {
+        let mut health = self.health.locked();
+        health.chain_loaded = true;
+        health.last_validation_error = None;
+    }
+
+    pub fn validated(&self, result: Result<(), String>) {
+        self.health.locked().last_validation_error = result.err();
+    }
+
+    pub fn saved(&self, result: Result<(), String>) {
+        self.health.locked().last_save_error = result.err();
+    }
+
+    /// One round of syncing with every peer; Err carries the last failure.
+    pub fn synced(&self, result: Result<(), String>) {
+        let mut health = self.health.locked();
+        health.last_sync_error = result.err();
+        health.last_sync = Some(Instant::now());
+    }
+
+    /// Claim the coalesced sync round; false if one is already running.
+    pub fn begin_sync(&self) -> bool {
+        !self.syncing.swap(true, Ordering::AcqRel)
+    }
+
+    pub fn end_sync(&self) {
+        self.syncing.store(false, Ordering::Release);
+    }
+
+    pub fn readiness(&self) -> Readiness {
+        let health = self.health.locked();
+        let status = if !health.chain_loaded {
+            "starting"
+        } else if health.last_validation_error.is_some() || health.last_save_error.is_some() {
+            "degraded"
+        } else {
+            "ready"
+        };
+        Readiness {
+            status,
+            chain_loaded:          health.chain_loaded,
+            last_validation_error: health.last_validation_error.clone(),
+            last_save_error:       health.last_save_error.clone(),
+            last_sync_error:       health.last_sync_error.clone(),
+            secs_since_sync:       health.last_sync.map(|t| t.elapsed().as_secs()),
+        }
+    }
+}
+
+impl Default for NodeStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,403 @@
+// Store — where a chain lives between runs. JsonStore is the original chain
+// file, rewritten whole on every save, zstd-compressed if its name ends in .zst. SqliteStore keeps one row per block,
+// so a save only appends what's new. Neither answers lookups: the node holds
+// the chain in memory and its indexes do that.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use crate::block::Block;
+use crate::blockchain::Blockchain;
+use crate::util::chain_stem;
+
+/// Blocks SqliteStore reads per query while iterating.
+pub const STORE_PAGE_BLOCKS: usize = 500;
+
+/// zstd level for compressed chain files — its default: fast, and most of the gain.
+pub const CHAIN_FILE_ZSTD_LEVEL: i32 = 3;
+// every zstd frame starts with these; a JSON file can't
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// A chain's blocks in height order, plus named settings (`meta`) as JSON.
+/// Errors are messages fit for the log, like the rest of the persistence code.
+pub trait ChainStore: Send {
+    /// Add `block` on top; its index has to be the store's current length.
+    fn append_block(&mut self, block: &Block) -> Result<(), String>;
+    /// Drop every block at `height` and above — how a reorg is written.
+    fn truncate(&mut self, height: usize) -> Result<(), String>;
+    /// Drop the transactions of every block below `height`, keeping the rest
+    /// of each block — how Blockchain::prune_below() is written.
+    fn prune_below(&mut self, height: usize) -> Result<(), String>;
+    fn get_block_by_index(&self, height: usize) -> Result<Option<Block>, String>;
+    fn get_block_by_hash(&self, hash: &str) -> Result<Option<Block>, String>;
+    fn tip(&self) -> Result<Option<Block>, String>;
+    fn len(&self) -> Result<usize, String>;
+    /// Every block from genesis up, read as it goes.
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<Block, String>> + '_>;
+    fn put_meta(&mut self, key: &str, value: Value) -> Result<(), String>;
+    fn get_meta(&self, key: &str) -> Result<Option<Value>, String>;
+    /// Make everything written so far durable. A no-op where every write already is.
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn is_empty(&self) -> Result<bool, String> {
+        Ok(self.len()? == 0)
+    }
+}
+
+/// Which ChainStore a node keeps its chain in.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Storage {
+    #[default]
+    Json,
+    Sqlite,
+}
+
+impl FromStr for Storage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "json"   => Ok(Storage::Json),
+            "sqlite" => Ok(Storage::Sqlite),
+            _        => Err(format!("storage must be json or sqlite, got {:?}", s)),
+        }
+    }
+}
+
+/// The database beside `chain_file`: chain.json → chain.sqlite
+pub fn sqlite_path(chain_file: &str) -> String {
+    format!("{}.sqlite", chain_stem(chain_file))
+}
+
+/// Chain files named *.zst are written compressed.
+pub fn is_compressed(path: &str) -> bool {
+    path.ends_with(".zst")
+}
+
+/// The same chain file in the other format: chain.json ↔ chain.json.zst
+pub fn other_format(path: &str) -> String {
+    match path.strip_suffix(".zst") {
+        Some(plain) => plain.to_string(),
+        None        => format!("{}.zst", path),
+    }
+}
+
+/// Serialize `value` into the file at `path` as it goes — through zstd if
+/// is_compressed(path), as pretty JSON otherwise — never holding all of it in memory.
+pub fn write_chain_file(path: &str, value: &impl Serialize) -> Result<(), String> {
+    let failed = |e: std::io::Error| format!("Could not write {}: {}", path, e);
+    let mut out = BufWriter::new(File::create(path).map_err(failed)?);
+    if is_compressed(path) {
+        let mut encoder = zstd::Encoder::new(&mut out, CHAIN_FILE_ZSTD_LEVEL).map_err(failed)?;
+        serde_json::to_writer(&mut encoder, value).map_err(|e| format!("Could not serialize chain: {}", e))?;
+        encoder.finish().map_err(failed)?;
+    } else {
+        serde_json::to_writer_pretty(&mut out, value).map_err(|e| format!("Could not serialize chain: {}", e))?;
+    }
+    out.flush().map_err(failed)
+}
+
+/// Read a file write_chain_file() wrote, whatever its name says: one that
+/// starts with zstd's magic bytes is decompressed on the way in.
+pub fn read_chain_file<T: DeserializeOwned>(path: &str) -> Result<T, String> {
+    let mut reader = File::open(path).map(BufReader::new)
+        .map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let compressed = reader.fill_buf()
+        .map_err(|e| format!("Could not read {}: {}", path, e))?
+        .starts_with(&ZSTD_MAGIC);
+    let parsed = match compressed {
+        true  => zstd::Decoder::with_buffer(reader)
+            .map_err(|e| format!("Could not read {}: {}", path, e))
+            .and_then(|decoder| serde_json::from_reader(decoder).map_err(|e| e.to_string())),
+        false => serde_json::from_reader(reader).map_err(|e| e.to_string()),
+    };
+    parsed.map_err(|e| format!("{} is not a valid chain file: {}", path, e))
+}
+
+/// The chain file save_to_file() writes, behind ChainStore. Meta entries are
+/// its top-level fields, so either one reads what the other wrote. Every
+/// flush rewrites the whole file, compressed if the path ends in .zst.
+pub struct JsonStore {
+    path:   String,
+    meta:   Map<String, Value>,
+    blocks: Vec<Block>,
+    dirty:  bool,  // changed since the last flush
+}
+
+// the file's layout: the settings, then the chain
+#[derive(Serialize)]
+struct JsonFile<'a> {
+    #[serde(flatten)]
+    meta:  &'a Map<String, Value>,
+    chain: &'a [Block],
+}
+
+impl JsonStore {
+    /// The store at `path`, empty if there's no file yet.
+    pub fn open(path: &str) -> Result<Self, String> {
+        let mut store = JsonStore { path: path.to_string(), meta: Map::new(), blocks: Vec::new(), dirty: false };
+        if !Path::new(path).exists() {
+            return Ok(store);
+        }
+        let mut meta: Map<String, Value> = read_chain_file(path)?;
+        let chain = meta.remove("chain").unwrap_or(Value::Array(vec![]));
+        store.blocks = serde_json::from_value(chain)
+            .map_err(|e| format!("{} is not a valid chain file: {}", path, e))?;
+        store.meta = meta;
+        Ok(store)
+    }
+
+    /// Write to `path` from the next flush on, in the format its name asks
+    /// for. Where the chain was read from is left alone.
+    pub fn move_to(&mut self, path: &str) {
+        self.path = path.to_string();
+        self.dirty = true;
+    }
+}
+
+impl ChainStore for JsonStore {
+    fn append_block(&mut self, block: &Block) -> Result<(), String> {
+        check_next(block, self.blocks.len())?;
+        self.blocks.push(block.clone());
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn truncate(&mut self, height: usize) -> Result<(), String> {
+        if height < self.blocks.len() {
+            self.blocks.truncate(height);
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    fn prune_below(&mut self, height: usize) -> Result<(), String> {
+        let end = height.min(self.blocks.len());
+        for block in self.blocks[..end].iter_mut().filter(|b| !b.transactions.is_empty()) {
+            block.transactions.clear();
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    fn get_block_by_index(&self, height: usize) -> Result<Option<Block>, String> {
+        Ok(self.blocks.get(height).cloned())
+    }
+
+    fn get_block_by_hash(&self, hash: &str) -> Result<Option<Block>, String> {
+        Ok(self.blocks.iter().find(|b| b.hash == hash).cloned())
+    }
+
+    fn tip(&self) -> Result<Option<Block>, String> {
+        Ok(self.blocks.last().cloned())
+    }
+
+    fn len(&self) -> Result<usize, String> {
+        Ok(self.blocks.len())
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<Block, String>> + '_> {
+        Box::new(self.blocks.iter().cloned().map(Ok))
+    }
+
+    fn put_meta(&mut self, key: &str, value: Value) -> Result<(), String> {
+        if self.meta.get(key) != Some(&value) {
+            self.meta.insert(key.to_string(), value);
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    fn get_meta(&self, key: &str) -> Result<Option<Value>, String> {
+        Ok(self.meta.get(key).cloned())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        if !self.dirty {
+            return Ok(());
+        }
+        write_chain_file(&self.path, &JsonFile { meta: &self.meta, chain: &self.blocks })?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    PRAGMA synchronous = NORMAL;
+    CREATE TABLE IF NOT EXISTS blocks (
+        height        INTEGER PRIMARY KEY,
+        hash          TEXT NOT NULL UNIQUE,
+        previous_hash TEXT NOT NULL,
+        timestamp     INTEGER NOT NULL,
+        body          TEXT NOT NULL  -- the block as JSON
+    );
+    -- lookup tables earlier versions wrote and nothing read
+    DROP TABLE IF EXISTS outputs;
+    DROP TABLE IF EXISTS transactions;
+    CREATE TABLE IF NOT EXISTS meta (
+        key   TEXT PRIMARY KEY,
+        value TEXT NOT NULL  -- JSON
+    );
+";
+
+/// A chain in SQLite, one row per block. Every write is a single statement,
+/// so a crash mid-save leaves whole blocks only.
+pub struct SqliteStore {
+    path: String,
+    conn: Connection,
+}
+
+impl SqliteStore {
+    /// Open (or create) the database at `path`.
+    pub fn open(path: &str) -> Result<Self, String> {
+        let conn = Connection::open(path).map_err(|e| format!("Could not open {}: {}", path, e))?;
+        let store = SqliteStore { path: path.to_string(), conn };
+        store.conn.execute_batch(SCHEMA).map_err(|e| store.failed(e))?;
+        Ok(store)
+    }
+
+    /// A store that lives and dies with the process — for tests and tools.
+    pub fn in_memory() -> Result<Self, String> {
+        Self::open(":memory:")
+    }
+
+    /// One-shot migration: copy the chain file at `path` into this store,
+    /// which has to be empty. The file is loaded and validated as a node
+    /// would; returns how many blocks were copied.
+    pub fn import_from_json(&mut self, path: &str) -> Result<usize, String> {
+        if !self.is_empty()? {
+            return Err(format!("{} already holds a chain — import into an empty database", self.path));
+        }
+        let bc = Blockchain::load_from_file(path)?;
+        bc.write_to_store(self)
+    }
+
+    // up to `limit` blocks from `from` on
+    fn blocks(&self, from: usize, limit: usize) -> Result<Vec<Block>, String> {
+        let mut stmt = self.conn.prepare_cached("SELECT body FROM blocks WHERE height >= ?1 ORDER BY height LIMIT ?2")
+            .map_err(|e| self.failed(e))?;
+        let bodies = stmt.query_map(params![from, limit], |row| row.get::<_, String>(0)).map_err(|e| self.failed(e))?;
+        bodies.map(|body| self.decode(&body.map_err(|e| self.failed(e))?)).collect()
+    }
+
+    fn block_where(&self, sql: &str, key: impl rusqlite::Params) -> Result<Option<Block>, String> {
+        let body: Option<String> = self.conn.query_row(sql, key, |row| row.get(0))
+            .optional()
+            .map_err(|e| self.failed(e))?;
+        body.map(|body| self.decode(&body)).transpose()
+    }
+
+    fn decode(&self, body: &str) -> Result<Block, String> {
+        serde_json::from_str(body).map_err(|e| format!("{} holds a block that doesn't parse: {}", self.path, e))
+    }
+
+    fn failed(&self, e: rusqlite::Error) -> String {
+        format!("{}: {}", self.path, e)
+    }
+}
+
+impl ChainStore for SqliteStore {
+    fn append_block(&mut self, block: &Block) -> Result<(), String> {
+        check_next(block, self.len()?)?;
+        let body = serde_json::to_string(block).map_err(|e| format!("Could not serialize block: {}", e))?;
+        self.conn.execute("INSERT INTO blocks (height, hash, previous_hash, timestamp, body) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![block.index, block.hash, block.previous_hash, block.timestamp, body])
+            .map(|_| ())
+            .map_err(|e| format!("{}: couldn't store block #{}: {}", self.path, block.index, e))
+    }
+
+    fn truncate(&mut self, height: usize) -> Result<(), String> {
+        self.conn.execute("DELETE FROM blocks WHERE height >= ?1", [height])
+            .map(|_| ())
+            .map_err(|e| format!("{}: couldn't drop blocks from #{}: {}", self.path, height, e))
+    }
+
+    fn prune_below(&mut self, height: usize) -> Result<(), String> {
+        self.conn.execute("UPDATE blocks SET body = json_set(body, '$.transactions', json('[]'))
+                           WHERE height < ?1 AND json_array_length(body, '$.transactions') > 0", [height])
+            .map(|_| ())
+            .map_err(|e| format!("{}: couldn't prune below #{}: {}", self.path, height, e))
+    }
+
+    fn get_block_by_index(&self, height: usize) -> Result<Option<Block>, String> {
+        self.block_where("SELECT body FROM blocks WHERE height = ?1", [height])
+    }
+
+    fn get_block_by_hash(&self, hash: &str) -> Result<Option<Block>, String> {
+        self.block_where("SELECT body FROM blocks WHERE hash = ?1", [hash])
+    }
+
+    fn tip(&self) -> Result<Option<Block>, String> {
+        self.block_where("SELECT body FROM blocks ORDER BY height DESC LIMIT 1", [])
+    }
+
+    fn len(&self) -> Result<usize, String> {
+        self.conn.query_row("SELECT COUNT(*) FROM blocks", [], |row| row.get(0)).map_err(|e| self.failed(e))
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<Block, String>> + '_> {
+        Box::new(Pages { store: self, next: 0, page: VecDeque::new(), done: false })
+    }
+
+    fn put_meta(&mut self, key: &str, value: Value) -> Result<(), String> {
+        self.conn.execute("INSERT INTO meta (key, value) VALUES (?1, ?2)
+                           ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![key, value.to_string()])
+            .map(|_| ())
+            .map_err(|e| self.failed(e))
+    }
+
+    fn get_meta(&self, key: &str) -> Result<Option<Value>, String> {
+        let text: Option<String> = self.conn.query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| row.get(0))
+            .optional()
+            .map_err(|e| self.failed(e))?;
+        text.map(|t| serde_json::from_str(&t).map_err(|e| format!("{}: meta {:?} isn't JSON: {}", self.path, key, e)))
+            .transpose()
+    }
+}
+
+// SqliteStore::iter — a page of blocks at a time
+struct Pages<'a> {
+    store: &'a SqliteStore,
+    next:  usize,
+    page:  VecDeque<Block>,
+    done:  bool,  // after an error or the last page
+}
+
+impl Iterator for Pages<'_> {
+    type Item = Result<Block, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.page.is_empty() && !self.done {
+            match self.store.blocks(self.next, STORE_PAGE_BLOCKS) {
+                Ok(blocks) => {
+                    self.done = blocks.len() < STORE_PAGE_BLOCKS;
+                    self.next += blocks.len();
+                    self.page = blocks.into();
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.page.pop_front().map(Ok)
+    }
+}
+
+// stores only ever grow by one block on top
+fn check_next(block: &Block, len: usize) -> Result<(), String> {
+    if block.index as usize != len {
+        return Err(format!("block #{} can't be stored at height {}", block.index, len));
+    }
+    Ok(())
+}
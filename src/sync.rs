@@ -0,0 +1,189 @@
+// Sync — catch up with a peer whose chain has more work than ours.
+// Headers first: the peer's block locator says which of our blocks it also has,
+// then its headers from just above the highest of those are checked for their
+// links, proof of work and total work. Only if they beat ours are the block
+// bodies downloaded, checked against those headers, and handed to replace_chain().
+
+use std::time::Duration;
+use actix_web::web;
+use serde::Deserialize;
+use thiserror::Error;
+use crate::api::{AppState, MAX_HEADER_LIMIT, MAX_PAGE_LIMIT, save_chain};
+use crate::audit::{Rejected, Source};
+use crate::block::{Block, BlockHeader, validate_headers};
+use crate::blockchain::{BlockRef, ReplaceOutcome};
+use crate::error::ChainError;
+use crate::peers::{PeerError, fetch_head, get_json};
+use crate::util::LockExt;
+use tracing::{info, warn};
+
+/// How often the background task checks every peer for a better chain.
+pub const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Most blocks one round asks a peer for — its head is only a claim, and a
+/// chain further ahead than this is caught up with over several rounds.
+pub const MAX_SYNC_BLOCKS: usize = 100_000;
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error(transparent)]
+    Peer(#[from] PeerError),
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+}
+
+// one page of a peer's GET /chain
+#[derive(Deserialize)]
+struct BlocksPage {
+    total_blocks: usize,
+    blocks:       Vec<Block>,
+}
+
+// one page of a peer's GET /headers
+#[derive(Deserialize)]
+struct HeadersPage {
+    total_blocks: usize,
+    headers:      Vec<BlockHeader>,
+}
+
+/// Adopt `url`'s chain if it has more work than ours. Ok(None) means we were
+/// already at least as good. The peer's record is updated either way.
+pub async fn sync_with_peer(state: &AppState, url: &str) -> Result<Option<ReplaceOutcome>, SyncError> {
+    let client = state.peers.locked().client();
+    let result = pull(state, &client, url).await;
+    let mut peers = state.peers.locked();
+    match &result {
+        Err(SyncError::Peer(e)) => peers.mark_failed(url, &format!("sync failed: {}", e)),
+        _                       => peers.mark_seen(url),
+    }
+    if let Ok(Some(outcome)) = &result {
+        info!(peer = %url, old_height = outcome.old_height, new_height = outcome.new_height, "synced");
+    }
+    result
+}
+
+/// sync_with_peer() against every registered peer, one after the other —
+/// each replacement raises the bar for the next.
+pub async fn sync_all(state: &AppState) {
+    let urls = state.peers.locked().urls();
+    let mut last_error = None;
+    for url in urls {
+        if let Err(e) = sync_with_peer(state, &url).await {
+            warn!(peer = %url, error = %e, "sync failed");
+            last_error = Some(format!("{}: {}", url, e));
+        }
+    }
+    state.status.synced(last_error.map_or(Ok(()), Err));
+}
+
+/// sync_all(), unless another call to this is still running — that round
+/// covers this one, so anonymous POST /block triggers don't stack up.
+pub async fn sync_all_coalesced(state: &AppState) {
+    if !state.status.begin_sync() {
+        return;
+    }
+    sync_all(state).await;
+    state.status.end_sync();
+}
+
+/// Run sync_all() every SYNC_INTERVAL, so a node that missed an announcement
+/// (it was down, or the peer couldn't reach it) still catches up.
+pub fn spawn_periodic(state: web::Data<AppState>) {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(SYNC_INTERVAL);
+        loop {
+            ticks.tick().await;
+            sync_all(&state).await;
+        }
+    });
+}
+
+async fn pull(state: &AppState, client: &reqwest::Client, url: &str) -> Result<Option<ReplaceOutcome>, SyncError> {
+    let head = fetch_head(client, url).await?;
+    let their_len = head.index as usize + 1;
+    let (our_work, our_len) = state.chain.query(|bc| (bc.total_work(), bc.chain.len())).await;
+    if (head.total_work, their_len) <= (our_work, our_len) {
+        return Ok(None);
+    }
+
+    // headers first, from just above the highest block their locator shares with us
+    let locator: Vec<BlockRef> = get_json(client, url, "/chain/locator").await?;
+    let shared = state.chain.query(move |bc| {
+        let height = bc.locate(&locator)?;
+        let prefix_work = bc.chain[..=height].iter().fold(0u128, |acc, b| acc.saturating_add(b.work()));
+        Some((height + 1, bc.chain[height].hash.clone(), prefix_work))
+    }).await;
+    let Some((start, parent_hash, prefix_work)) = shared else {
+        return Err(ChainError::GenesisMismatch.into());
+    };
+    let headers = fetch_headers(client, url, start, their_len.min(start.saturating_add(MAX_SYNC_BLOCKS))).await?;
+
+    // their /chain/head only claimed more work — the headers have to prove it
+    let their_work = prefix_work.saturating_add(validate_headers(&parent_hash, &headers)?);
+    if (their_work, start + headers.len()) <= (our_work, our_len) {
+        return Ok(None);
+    }
+
+    let bodies = fetch_blocks(client, url, start, start + headers.len()).await?;
+    if bodies.len() != headers.len() {
+        return Err(PeerError::NotANode {
+            url: url.to_string(),
+            reason: format!("sent {} blocks for {} headers", bodies.len(), headers.len()),
+        }.into());
+    }
+    for (body, header) in bodies.iter().zip(&headers) {
+        if body.merkle_root != Block::compute_merkle_root(&body.transactions) {
+            return Err(ChainError::MerkleMismatch { index: body.index }.into());
+        }
+        if body.header() != *header {
+            return Err(ChainError::HashMismatch { index: body.index }.into());
+        }
+    }
+
+    let tip = headers.last().map(|h| h.hash.clone());
+    let (outcome, adopted) = state.chain.update(move |bc| {
+        let candidate = bc.chain[..start.min(bc.chain.len())].iter().cloned().chain(bodies).collect();
+        bc.replace_chain(candidate).map(|outcome| {
+            let adopted = bc.blocks_after(outcome.fork_height - 1).to_vec();
+            (outcome, adopted)
+        })
+    }).await.inspect_err(|e| state.rejections.record(Source::Peer, Rejected::Chain, tip, e))?;
+    save_chain(state).await;
+    for block in &adopted {
+        state.webhooks.block_added(block);
+    }
+    Ok(Some(outcome))
+}
+
+/// Headers [from, to) of `url`'s chain, a page at a time. Stops early if the peer runs out.
+async fn fetch_headers(client: &reqwest::Client, url: &str, from: usize, to: usize) -> Result<Vec<BlockHeader>, PeerError> {
+    // sized for a page, not for however far ahead the peer says it is
+    let mut headers = Vec::with_capacity(to.saturating_sub(from).min(MAX_HEADER_LIMIT));
+    while from + headers.len() < to {
+        let next = from + headers.len();
+        let limit = (to - next).min(MAX_HEADER_LIMIT);
+        let page: HeadersPage = get_json(client, url, &format!("/headers?from={}&limit={}", next, limit)).await?;
+        if page.headers.is_empty() || next >= page.total_blocks {
+            break;
+        }
+        headers.extend(page.headers);
+    }
+    headers.truncate(to - from);
+    Ok(headers)
+}
+
+/// Blocks [from, to) of `url`'s chain, a page at a time. Stops early if the peer runs out.
+async fn fetch_blocks(client: &reqwest::Client, url: &str, from: usize, to: usize) -> Result<Vec<Block>, PeerError> {
+    let mut blocks = Vec::with_capacity(to.saturating_sub(from).min(MAX_PAGE_LIMIT));
+    while from + blocks.len() < to {
+        let next = from + blocks.len();
+        let limit = (to - next).min(MAX_PAGE_LIMIT);
+        let page: BlocksPage = get_json(client, url, &format!("/chain?from={}&limit={}", next, limit)).await?;
+        if page.blocks.is_empty() || next >= page.total_blocks {
+            break;
+        }
+        blocks.extend(page.blocks);
+    }
+    blocks.truncate(to - from);
+    Ok(blocks)
+}
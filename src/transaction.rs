@@ -1,51 +1,466 @@
-// Transaction — signed transfer of tokens between two addresses.
+// Transaction — signed transfer of tokens from one address to one or more others.
 // Amounts stored as u64 "nits" (1 token = 1000 nits) to avoid f64 precision errors.
 // Signature stored as hex string so it can be serialized to JSON.
 
 use sha2::{Sha256, Digest};
 use ed25519_dalek::{VerifyingKey, Signature};
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Serializer, Deserialize};
+use utoipa::ToSchema;
+use serde::ser::SerializeStruct;
+use utoipa::PartialSchema;
+use utoipa::openapi::{RefOr, schema::Schema};
+use crate::canonical::{CanonicalEncode, DOMAIN_TX_SIGN, DOMAIN_TXID};
 use crate::wallet::{Wallet, verify_signature};
+use crate::error::ChainError;
+use crate::util::short;
+use crate::multisig::{Multisig, CoSignature, is_multisig_address};
 
 pub const NITS_PER_TOKEN: u64 = 1000;
 
-#[derive(Serialize, Deserialize, Clone)]
+/// Coins sent here are gone for good. The all-zero key has no known secret,
+/// and validate() refuses anything claiming to come from it.
+pub const BURN_ADDRESS: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// First byte of every raw transaction, bumped if the layout ever changes.
+pub const RAW_VERSION: u8 = 1;
+/// Raw layout for transactions carrying a memo — RAW_VERSION plus the memo field.
+pub const RAW_VERSION_MEMO: u8 = 2;
+/// Raw layout for multi-output transactions — an output list, memo optional.
+pub const RAW_VERSION_MULTI: u8 = 3;
+/// RAW_VERSION_MULTI plus a lock height, for timelocked transactions.
+pub const RAW_VERSION_LOCKED: u8 = 4;
+/// Multisig spends — RAW_VERSION_MULTI with an optional lock and the key set and co-signatures.
+pub const RAW_VERSION_MULTISIG: u8 = 5;
+
+/// Most outputs one transaction may pay.
+pub const MAX_OUTPUTS: usize = 64;
+
+/// Longest memo a transaction may carry, in bytes.
+pub const MAX_MEMO_BYTES: usize = 256;
+/// Memo bytes aren't free storage — each one adds this much to the minimum fee.
+pub const MEMO_FEE_PER_BYTE: u64 = 1;
+
+/// One payment inside a transaction.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct Output {
+    pub to:     String,
+    pub amount: u64,  // in nits
+}
+
+impl CanonicalEncode for Output {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        self.to.encode(buf);
+        self.amount.encode(buf);
+    }
+}
+
+// Serialize is hand-written below so the JSON can carry a derived amount_tokens
+// next to the raw nits. Deserialize ignores it — nits are the source of truth.
+// A single output is written as plain to/amount, so older JSON still loads and
+// single-payment JSON looks exactly as it always did.
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "TransactionJson")]
 pub struct Transaction {
-    pub from:          String,
-    pub to:            String,
-    pub amount:        u64,             // in nits
-    pub signature_hex: Option<String>,  // hex string — serializes cleanly to JSON
+    pub from:             String,
+    pub outputs:          Vec<Output>,     // a single signature covers all of them
+    pub fee:              u64,             // in nits, paid to the miner
+    pub nonce:            u64,             // sender's tx count — stops replays
+    pub signature_hex:    Option<String>,  // hex string — serializes cleanly to JSON
+    pub memo:             Option<String>,  // free text, signed along with everything else
+    pub lock_until_block: Option<u32>,     // can't be mined into a block below this index
+    pub multisig:         Option<Multisig>,  // set when `from` is a multisig address
+}
+
+// What Transaction is read from — either to/amount or an outputs list.
+#[derive(Deserialize)]
+struct TransactionJson {
+    from:             String,
+    to:               Option<String>,
+    amount:           Option<u64>,
+    outputs:          Option<Vec<Output>>,
+    #[serde(default)]
+    fee:              u64,
+    #[serde(default)]
+    nonce:            u64,
+    signature_hex:    Option<String>,
+    #[serde(default)]
+    memo:             Option<String>,
+    #[serde(default)]
+    lock_until_block: Option<u32>,
+    #[serde(default)]
+    multisig:         Option<Multisig>,
+}
+
+impl TryFrom<TransactionJson> for Transaction {
+    type Error = String;
+
+    fn try_from(j: TransactionJson) -> Result<Self, String> {
+        let outputs = match (j.outputs, j.to) {
+            (Some(outputs), None) => outputs,
+            (None, Some(to))      => vec![Output { to, amount: j.amount.ok_or("missing field `amount`")? }],
+            (Some(_), Some(_))    => return Err("give either `to`/`amount` or `outputs`, not both".to_string()),
+            (None, None)          => return Err("missing field `to` (or `outputs`)".to_string()),
+        };
+        Ok(Transaction {
+            from: j.from, outputs, fee: j.fee, nonce: j.nonce,
+            signature_hex: j.signature_hex, memo: j.memo, lock_until_block: j.lock_until_block,
+            multisig: j.multisig,
+        })
+    }
+}
+
+/// Tokens → nits, refusing anything that can't be represented exactly.
+/// `what` names the field ("amount", "fee") in the error message.
+pub fn tokens_to_nits(tokens: f64, what: &str) -> Result<u64, String> {
+    if tokens.is_nan() {
+        return Err(format!("{} is not a number", what));
+    }
+    if tokens.is_infinite() {
+        return Err(format!("{} must be finite", what));
+    }
+    if tokens < 0.0 {
+        return Err(format!("{} cannot be negative (got {})", what, tokens));
+    }
+    let nits = tokens * NITS_PER_TOKEN as f64;
+    if nits >= u64::MAX as f64 {
+        return Err(format!("{} of {} tokens is too large", what, tokens));
+    }
+    let rounded = nits.round();
+    if (nits - rounded).abs() > 1e-6 {
+        return Err(format!("{} {} has more precision than 1 nit (0.001 tokens)", what, tokens));
+    }
+    Ok(rounded as u64)
+}
+
+/// Nits → a decimal string with every place ("1.500"), which parse_tokens reads back exactly.
+pub fn format_tokens(nits: u64) -> String {
+    let places = NITS_PER_TOKEN.ilog10() as usize;
+    format!("{}.{:0places$}", nits / NITS_PER_TOKEN, nits % NITS_PER_TOKEN)
+}
+
+/// Decimal string ("12", "0.25") → nits without ever going through f64.
+pub fn parse_tokens(text: &str, what: &str) -> Result<u64, String> {
+    let text = text.trim();
+    if text.starts_with('-') {
+        return Err(format!("{} cannot be negative (got {})", what, text));
+    }
+    let (whole, frac) = text.split_once('.').unwrap_or((text, ""));
+    if whole.is_empty() && frac.is_empty() {
+        return Err(format!("{} is empty", what));
+    }
+    let digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if !digits(whole) || !digits(frac) {
+        return Err(format!("{} {:?} is not a decimal number", what, text));
+    }
+    let places = NITS_PER_TOKEN.ilog10() as usize;
+    let frac = frac.trim_end_matches('0');
+    if frac.len() > places {
+        return Err(format!("{} {} has more precision than 1 nit (0.001 tokens)", what, text));
+    }
+    let too_large = || format!("{} of {} tokens is too large", what, text);
+    let whole: u64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| too_large())? };
+    let frac: u64 = format!("{:0<width$}", frac, width = places).parse().unwrap_or(0);
+    whole.checked_mul(NITS_PER_TOKEN)
+        .and_then(|n| n.checked_add(frac))
+        .ok_or_else(too_large)
 }
 
 impl Transaction {
-    pub fn new(from: String, to: String, amount_tokens: f64) -> Self {
-        let amount_nits = (amount_tokens * NITS_PER_TOKEN as f64).round() as u64;
-        Transaction { from, to, amount: amount_nits, signature_hex: None }
+    pub fn new(from: String, to: String, amount_tokens: f64) -> Result<Self, String> {
+        Self::new_with_fee(from, to, amount_tokens, 0.0)
+    }
+
+    /// Same as new() but attaches a fee (in tokens) that the miner collects.
+    pub fn new_with_fee(from: String, to: String, amount_tokens: f64, fee_tokens: f64) -> Result<Self, String> {
+        let mut txn = Self::new_multi(from, vec![(to, amount_tokens)])?;
+        txn.fee = tokens_to_nits(fee_tokens, "fee")?;
+        Ok(txn)
+    }
+
+    /// Pay several recipients under one signature. Amounts in tokens.
+    pub fn new_multi(from: String, outputs: Vec<(String, f64)>) -> Result<Self, String> {
+        let outputs = outputs.into_iter()
+            .map(|(to, tokens)| Ok(Output { to, amount: tokens_to_nits(tokens, "amount")? }))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Transaction {
+            from, outputs, fee: 0, nonce: 0, signature_hex: None, memo: None,
+            lock_until_block: None, multisig: None,
+        })
     }
 
     pub fn new_nits(from: String, to: String, amount_nits: u64) -> Self {
-        Transaction { from, to, amount: amount_nits, signature_hex: None }
+        Transaction {
+            from, outputs: vec![Output { to, amount: amount_nits }],
+            fee: 0, nonce: 0, signature_hex: None, memo: None, lock_until_block: None, multisig: None,
+        }
+    }
+
+    /// Everything paid out across all outputs, in nits. Saturates — validate() rejects overflow.
+    pub fn amount(&self) -> u64 {
+        self.outputs.iter().fold(0u64, |acc, o| acc.saturating_add(o.amount))
+    }
+
+    /// What this transaction pays `address`, summed over its outputs.
+    pub fn amount_to(&self, address: &str) -> u64 {
+        self.outputs.iter()
+            .filter(|o| o.to == address)
+            .fold(0u64, |acc, o| acc.saturating_add(o.amount))
+    }
+
+    /// The sole recipient of a single-output transaction, None otherwise.
+    pub fn single_recipient(&self) -> Option<&str> {
+        match self.outputs.as_slice() {
+            [only] => Some(&only.to),
+            _      => None,
+        }
+    }
+
+    pub fn pays(&self, address: &str) -> bool {
+        self.outputs.iter().any(|o| o.to == address)
     }
 
     pub fn amount_as_tokens(&self) -> f64 {
-        self.amount as f64 / NITS_PER_TOKEN as f64
+        self.amount() as f64 / NITS_PER_TOKEN as f64
     }
 
-    /// The exact bytes we sign — hash of (from + to + amount_nits).
-    /// Hashing first gives fixed 32 bytes regardless of address length.
+    pub fn fee_as_tokens(&self) -> f64 {
+        self.fee as f64 / NITS_PER_TOKEN as f64
+    }
+
+    /// What the sender is debited — every output plus the fee, None on overflow.
+    pub fn total_cost(&self) -> Option<u64> {
+        self.outputs.iter().try_fold(self.fee, |acc, o| acc.checked_add(o.amount))
+    }
+
+    /// Size of the raw encoding in bytes — what the mempool limit and fee rate count.
+    /// validate() refuses anything to_hex() can't encode, so for an accepted
+    /// transaction this is exact; one that can't be encoded never fits anywhere.
+    pub fn size(&self) -> usize {
+        self.to_hex().map_or(usize::MAX, |raw| raw.len() / 2)
+    }
+
+    /// Smallest fee that pays for the memo, 0 without one.
+    pub fn memo_fee(&self) -> u64 {
+        self.memo.as_ref().map_or(0, |m| m.len() as u64 * MEMO_FEE_PER_BYTE)
+    }
+
+    /// Whether this may go into the block at `height`.
+    pub fn is_unlocked_at(&self, height: u32) -> bool {
+        self.lock_until_block.is_none_or(|lock| height >= lock)
+    }
+
+    // every field a signature commits to, canonically encoded after `domain`
+    fn encode_unsigned(&self, domain: &str, buf: &mut Vec<u8>) {
+        domain.encode(buf);
+        self.from.encode(buf);
+        self.outputs.encode(buf);
+        self.fee.encode(buf);
+        self.nonce.encode(buf);
+        self.lock_until_block.encode(buf);
+        self.memo.encode(buf);
+    }
+
+    /// The exact bytes we sign — SHA-256 of the canonical encoding of from,
+    /// outputs, fee, nonce, lock and memo. Hashing first gives fixed 32 bytes
+    /// regardless of address length.
     pub fn message_to_sign(&self) -> Vec<u8> {
-        let data = format!("{}{}{}", self.from, self.to, self.amount);
-        let mut hasher = Sha256::new();
-        hasher.update(data.as_bytes());
-        hasher.finalize().to_vec()
+        let mut buf = Vec::with_capacity(256);
+        self.encode_unsigned(DOMAIN_TX_SIGN, &mut buf);
+        Sha256::digest(&buf).to_vec()
+    }
+
+    /// Transaction id — hex SHA-256 over every field including the signature.
+    /// Nothing stored; recomputed on demand so it can never go stale.
+    /// The block hash covers txids through the merkle root, so it covers the memo too.
+    pub fn id(&self) -> String {
+        let mut buf = Vec::with_capacity(256);
+        self.encode_unsigned(DOMAIN_TXID, &mut buf);
+        self.signature_hex.encode(&mut buf);
+        self.multisig.as_ref().map(|ms| &ms.signatures).encode(&mut buf);
+        hex::encode(Sha256::digest(&buf))
+    }
+
+    /// Canonical binary encoding, hex'd — what wallets broadcast to POST /transaction/raw.
+    /// Layout: version u8 | from (u16 len + bytes) | to (u16 len + bytes)
+    ///         | amount u64 | fee u64 | nonce u64 | has_sig u8 | sig 64 bytes (if has_sig)
+    /// Integers are little-endian. With a memo the version is RAW_VERSION_MEMO and
+    /// the memo (u16 len + bytes) sits between nonce and has_sig.
+    /// Anything but exactly one output is RAW_VERSION_MULTI:
+    ///   version u8 | from | count u16 | (to, amount u64) * count | fee u64 | nonce u64
+    ///   | has_memo u8 | memo (if has_memo) | has_sig u8 | sig (if has_sig)
+    /// A timelocked transaction is RAW_VERSION_LOCKED: that same layout with
+    /// lock_until_block u32 between the memo and has_sig.
+    /// A multisig one is RAW_VERSION_MULTISIG: the RAW_VERSION_MULTI layout, then
+    ///   has_lock u8 | lock u32 (if has_lock) | threshold u8 | key count u8 | keys
+    ///   | signature count u8 | (key, sig (u16 len + bytes)) * count | has_sig u8 (always 0)
+    /// so a partly signed transaction can be handed to the next co-signer as-is.
+    /// Errs rather than writing something from_hex() would read back differently:
+    /// a signature that isn't hex, or more keys or signatures than a u8 counts.
+    pub fn to_hex(&self) -> Result<String, ChainError> {
+        let put_str = |buf: &mut Vec<u8>, field: &str| {
+            buf.extend_from_slice(&(field.len() as u16).to_le_bytes());
+            buf.extend_from_slice(field.as_bytes());
+        };
+        let put_bytes = |buf: &mut Vec<u8>, field: &[u8]| {
+            buf.extend_from_slice(&(field.len() as u16).to_le_bytes());
+            buf.extend_from_slice(field);
+        };
+        let mut buf = Vec::new();
+        match self.outputs.as_slice() {
+            [only] if self.lock_until_block.is_none() && self.multisig.is_none() => {
+                buf.push(if self.memo.is_some() { RAW_VERSION_MEMO } else { RAW_VERSION });
+                put_str(&mut buf, &self.from);
+                put_str(&mut buf, &only.to);
+                buf.extend_from_slice(&only.amount.to_le_bytes());
+                buf.extend_from_slice(&self.fee.to_le_bytes());
+                buf.extend_from_slice(&self.nonce.to_le_bytes());
+                if let Some(memo) = &self.memo {
+                    put_str(&mut buf, memo);
+                }
+            }
+            many => {
+                buf.push(match (&self.multisig, self.lock_until_block) {
+                    (Some(_), _)    => RAW_VERSION_MULTISIG,
+                    (None, Some(_)) => RAW_VERSION_LOCKED,
+                    (None, None)    => RAW_VERSION_MULTI,
+                });
+                put_str(&mut buf, &self.from);
+                buf.extend_from_slice(&(many.len() as u16).to_le_bytes());
+                for o in many {
+                    put_str(&mut buf, &o.to);
+                    buf.extend_from_slice(&o.amount.to_le_bytes());
+                }
+                buf.extend_from_slice(&self.fee.to_le_bytes());
+                buf.extend_from_slice(&self.nonce.to_le_bytes());
+                match &self.memo {
+                    Some(memo) => { buf.push(1); put_str(&mut buf, memo); }
+                    None       => buf.push(0),
+                }
+                match (&self.multisig, self.lock_until_block) {
+                    (Some(ms), lock) => {
+                        match lock {
+                            Some(lock) => { buf.push(1); buf.extend_from_slice(&lock.to_le_bytes()); }
+                            None       => buf.push(0),
+                        }
+                        let count = |n: usize, what: &str| u8::try_from(n).map_err(|_| {
+                            ChainError::MalformedTransaction(format!("{} multisig {} don't fit the raw encoding", n, what))
+                        });
+                        buf.push(ms.threshold);
+                        buf.push(count(ms.keys.len(), "keys")?);
+                        for key in &ms.keys {
+                            put_str(&mut buf, key);
+                        }
+                        buf.push(count(ms.signatures.len(), "signatures")?);
+                        for sig in &ms.signatures {
+                            put_str(&mut buf, &sig.key);
+                            put_bytes(&mut buf, &decode_signature(&sig.signature_hex)?);
+                        }
+                    }
+                    (None, Some(lock)) => buf.extend_from_slice(&lock.to_le_bytes()),
+                    (None, None)       => {}
+                }
+            }
+        }
+        match self.signature_hex.as_deref() {
+            Some(sig) => { buf.push(1); buf.extend_from_slice(&decode_signature(sig)?); }
+            None      => buf.push(0),
+        }
+        Ok(hex::encode(buf))
+    }
+
+    /// Inverse of to_hex(). Doesn't check the signature — call validate() for that.
+    pub fn from_hex(raw: &str) -> Result<Self, ChainError> {
+        let bytes = hex::decode(raw.trim())
+            .map_err(|_| ChainError::MalformedTransaction("raw transaction is not valid hex".to_string()))?;
+        let mut r = RawReader { bytes: &bytes, pos: 0 };
+
+        let version = r.take(1, "version")?[0];
+        let from = r.string("from")?;
+        let (outputs, fee, nonce, memo, lock_until_block, multisig) = match version {
+            RAW_VERSION | RAW_VERSION_MEMO => {
+                let to = r.string("to")?;
+                let amount = r.u64("amount")?;
+                let fee = r.u64("fee")?;
+                let nonce = r.u64("nonce")?;
+                let memo = if version == RAW_VERSION_MEMO { Some(r.string("memo")?) } else { None };
+                (vec![Output { to, amount }], fee, nonce, memo, None, None)
+            }
+            RAW_VERSION_MULTI | RAW_VERSION_LOCKED | RAW_VERSION_MULTISIG => {
+                let count_bytes: [u8; 2] = r.take(2, "output count")?.try_into().expect("took 2 bytes");
+                let count = u16::from_le_bytes(count_bytes) as usize;
+                let mut outputs = Vec::with_capacity(count.min(MAX_OUTPUTS));
+                for _ in 0..count {
+                    let to = r.string("output to")?;
+                    let amount = r.u64("output amount")?;
+                    outputs.push(Output { to, amount });
+                }
+                let fee = r.u64("fee")?;
+                let nonce = r.u64("nonce")?;
+                let memo = match r.take(1, "memo flag")?[0] {
+                    0 => None,
+                    1 => Some(r.string("memo")?),
+                    f => return Err(ChainError::MalformedTransaction(format!("bad memo flag {}", f))),
+                };
+                let has_lock = match version {
+                    RAW_VERSION_LOCKED   => true,
+                    RAW_VERSION_MULTISIG => match r.take(1, "lock flag")?[0] {
+                        0 => false,
+                        1 => true,
+                        f => return Err(ChainError::MalformedTransaction(format!("bad lock flag {}", f))),
+                    },
+                    _ => false,
+                };
+                let lock = if has_lock {
+                    let b: [u8; 4] = r.take(4, "lock_until_block")?.try_into().expect("took 4 bytes");
+                    Some(u32::from_le_bytes(b))
+                } else {
+                    None
+                };
+                let multisig = if version == RAW_VERSION_MULTISIG {
+                    let threshold = r.take(1, "threshold")?[0];
+                    let key_count = r.take(1, "key count")?[0];
+                    let keys = (0..key_count)
+                        .map(|_| r.string("multisig key"))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let sig_count = r.take(1, "co-signature count")?[0];
+                    let mut signatures = Vec::with_capacity(sig_count as usize);
+                    for _ in 0..sig_count {
+                        let key = r.string("co-signer key")?;
+                        let len_bytes: [u8; 2] = r.take(2, "co-signature")?.try_into().expect("took 2 bytes");
+                        let sig = r.take(u16::from_le_bytes(len_bytes) as usize, "co-signature")?;
+                        signatures.push(CoSignature { key, signature_hex: hex::encode(sig) });
+                    }
+                    Some(Multisig { keys, threshold, signatures })
+                } else {
+                    None
+                };
+                (outputs, fee, nonce, memo, lock, multisig)
+            }
+            v => return Err(ChainError::MalformedTransaction(format!("unknown raw version {}", v))),
+        };
+        let signature_hex = match r.take(1, "signature flag")?[0] {
+            0 => None,
+            1 => Some(hex::encode(r.take(64, "signature")?)),
+            f => return Err(ChainError::MalformedTransaction(format!("bad signature flag {}", f))),
+        };
+        if r.pos != bytes.len() {
+            return Err(ChainError::MalformedTransaction(
+                format!("{} trailing bytes after transaction", bytes.len() - r.pos)));
+        }
+        Ok(Transaction { from, outputs, fee, nonce, signature_hex, memo, lock_until_block, multisig })
     }
 
     /// Sign with sender's wallet. Validates wallet matches self.from.
+    /// On a multisig transaction this is add_signature().
     pub fn sign(&mut self, wallet: &Wallet) -> Result<(), String> {
+        if self.multisig.is_some() {
+            return self.add_signature(wallet);
+        }
         if self.from != "NETWORK" && wallet.address() != self.from {
             return Err(format!(
                 "Wrong wallet — sender is {}... but wallet is {}...",
-                &self.from[..12], &wallet.address()[..12]
+                short(&self.from, 12), short(&wallet.address(), 12)
             ));
         }
         let msg = self.message_to_sign();
@@ -55,37 +470,82 @@ impl Transaction {
         Ok(())
     }
 
-    /// Full validation — returns descriptive Err so caller knows exactly why it failed.
-    pub fn validate(&self) -> Result<(), String> {
+    /// Co-sign a multisig transaction. Each key holder calls this in turn — passing
+    /// the partly signed transaction along as raw hex — until the threshold is met.
+    pub fn add_signature(&mut self, wallet: &Wallet) -> Result<(), String> {
+        let msg = self.message_to_sign();
+        let multisig = self.multisig.as_mut()
+            .ok_or("not a multisig transaction — use sign()")?;
+        if multisig.address() != self.from {
+            return Err("sender is not this key set's multisig address".to_string());
+        }
+        multisig.add_signature(wallet, &msg)
+    }
+
+    /// Full validation — returns a typed Err so caller knows exactly why it failed.
+    pub fn validate(&self) -> Result<(), ChainError> {
+        if let Some(memo) = &self.memo {
+            if memo.len() > MAX_MEMO_BYTES {
+                return Err(ChainError::MemoTooLong { len: memo.len(), max: MAX_MEMO_BYTES });
+            }
+        }
         if self.from == "NETWORK" { return Ok(()); }
 
-        if self.amount == 0 {
-            return Err("Amount cannot be zero".to_string());
+        if self.fee < self.memo_fee() {
+            return Err(ChainError::MemoFeeTooLow { need: self.memo_fee(), got: self.fee });
+        }
+
+        if self.outputs.is_empty() {
+            return Err(ChainError::NoOutputs);
+        }
+        if self.outputs.len() > MAX_OUTPUTS {
+            return Err(ChainError::TooManyOutputs { count: self.outputs.len(), max: MAX_OUTPUTS });
+        }
+        if self.outputs.iter().any(|o| o.amount == 0) {
+            return Err(ChainError::ZeroAmount);
+        }
+        for (i, o) in self.outputs.iter().enumerate() {
+            if self.outputs[..i].iter().any(|earlier| earlier.to == o.to) {
+                return Err(ChainError::DuplicateOutput(format!("{}...", short(&o.to, 12))));
+            }
+        }
+        if self.total_cost().is_none() {
+            return Err(ChainError::BalanceOverflow);
+        }
+
+        if self.from == BURN_ADDRESS {
+            return Err(ChainError::InvalidAddress("sender is the burn address — nothing can leave it".to_string()));
+        }
+        // Anything sent to a non-key is gone for good, so refuse it up front —
+        // unless it's the burn address, where that's the point, or a multisig address
+        for o in self.outputs.iter().filter(|o| o.to != BURN_ADDRESS && !is_multisig_address(&o.to)) {
+            parse_address(&o.to, "recipient")?;
+        }
+
+        let msg = self.message_to_sign();
+        if let Some(multisig) = &self.multisig {
+            if self.signature_hex.is_some() {
+                return Err(ChainError::InvalidMultisig("carries a single-key signature as well".to_string()));
+            }
+            multisig.check_shape()?;
+            if multisig.address() != self.from {
+                return Err(ChainError::InvalidMultisig("key set doesn't hash to the sender address".to_string()));
+            }
+            return multisig.verify(&msg);
         }
 
         let sig_hex = self.signature_hex.as_ref()
-            .ok_or("Transaction is unsigned — call sign() first")?;
+            .ok_or(ChainError::UnsignedTransaction)?;
 
         // Decode signature from hex back to bytes
-        let sig_bytes = hex::decode(sig_hex)
-            .map_err(|_| "Signature is not valid hex".to_string())?;
-        let sig_array: [u8; 64] = sig_bytes.try_into()
-            .map_err(|_| "Signature has wrong byte length".to_string())?;
-        let signature = Signature::from_bytes(&sig_array);
+        let signature = Signature::from_bytes(&decode_signature(sig_hex)?);
 
         // Decode sender's public key from their address (address IS the public key)
-        let key_bytes = hex::decode(&self.from)
-            .map_err(|_| "Sender address is not valid hex".to_string())?;
-        let key_array: [u8; 32] = key_bytes.try_into()
-            .map_err(|_| "Sender address has wrong byte length".to_string())?;
-        let verifying_key = VerifyingKey::from_bytes(&key_array)
-            .map_err(|_| "Sender address is not a valid ed25519 public key".to_string())?;
-
-        let msg = self.message_to_sign();
+        let verifying_key = parse_address(&self.from, "sender")?;
         if verify_signature(&verifying_key, &msg, &signature) {
             Ok(())
         } else {
-            Err("Signature invalid — transaction may have been tampered".to_string())
+            Err(ChainError::InvalidSignature)
         }
     }
 
@@ -94,10 +554,141 @@ impl Transaction {
 
     pub fn display(&self) -> String {
         let from_short = if self.from == "NETWORK" { "NETWORK".to_string() }
-                         else { format!("{}...", &self.from[..10]) };
-        let to_short = format!("{}...", &self.to[..10]);
-        format!("{} → {} : {} tokens [{}]",
-            from_short, to_short, self.amount_as_tokens(),
-            if self.signature_hex.is_some() { "✅ signed" } else { "❌ unsigned" })
+                         else { format!("{}...", short(&self.from, 10)) };
+        let to_short = match self.single_recipient() {
+            Some(to) => format!("{}...", short(to, 10)),
+            None     => format!("[{}]", self.outputs.iter()
+                .map(|o| format!("{}... {}", short(&o.to, 10), o.amount as f64 / NITS_PER_TOKEN as f64))
+                .collect::<Vec<_>>()
+                .join(", ")),
+        };
+        let memo = match &self.memo {
+            Some(m) if m.chars().count() > 24 => format!(" memo \"{}...\"", short(m, 24)),
+            Some(m)                           => format!(" memo \"{}\"", m),
+            None                              => String::new(),
+        };
+        let lock = self.lock_until_block.map(|l| format!(" 🔒 until #{}", l)).unwrap_or_default();
+        let status = match &self.multisig {
+            Some(ms) => format!("✍️ {}/{} signed", ms.signatures.len(), ms.threshold),
+            None if self.signature_hex.is_some() => "✅ signed".to_string(),
+            None => "❌ unsigned".to_string(),
+        };
+        format!("{} {} → {} : {} tokens (fee {}){}{} [{}]",
+            short(&self.id(), 12), from_short, to_short, self.amount_as_tokens(), self.fee_as_tokens(), memo, lock,
+            status)
+    }
+}
+
+impl Serialize for Transaction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let extra = self.memo.is_some() as usize + self.lock_until_block.is_some() as usize
+            + self.multisig.is_some() as usize;
+        let mut st = serializer.serialize_struct("Transaction", 7 + extra)?;
+        st.serialize_field("from", &self.from)?;
+        match self.single_recipient() {
+            Some(to) => st.serialize_field("to", to)?,
+            None     => st.serialize_field("outputs", &self.outputs)?,
+        }
+        st.serialize_field("amount", &self.amount())?;
+        st.serialize_field("amount_tokens", &self.amount_as_tokens())?;
+        st.serialize_field("fee", &self.fee)?;
+        st.serialize_field("nonce", &self.nonce)?;
+        st.serialize_field("signature_hex", &self.signature_hex)?;
+        if let Some(memo) = &self.memo {
+            st.serialize_field("memo", memo)?;
+        }
+        if let Some(lock) = self.lock_until_block {
+            st.serialize_field("lock_until_block", &lock)?;
+        }
+        if let Some(multisig) = &self.multisig {
+            st.serialize_field("multisig", multisig)?;
+        }
+        st.end()
+    }
+}
+
+// What the hand-written Serialize above produces, for the OpenAPI spec.
+// Exactly one of to / outputs is present; memo, lock_until_block and
+// multisig only when set.
+#[derive(ToSchema)]
+#[schema(as = Transaction)]
+#[allow(dead_code)]
+struct TransactionSchema {
+    from:             String,
+    to:               Option<String>,
+    outputs:          Option<Vec<Output>>,
+    /// total of all outputs, in nits
+    amount:           u64,
+    amount_tokens:    f64,
+    /// in nits
+    fee:              u64,
+    nonce:            u64,
+    signature_hex:    Option<String>,
+    memo:             Option<String>,
+    lock_until_block: Option<u32>,
+    multisig:         Option<Multisig>,
+}
+
+impl PartialSchema for Transaction {
+    fn schema() -> RefOr<Schema> {
+        TransactionSchema::schema()
+    }
+}
+
+impl ToSchema for Transaction {
+    fn name() -> std::borrow::Cow<'static, str> {
+        TransactionSchema::name()
+    }
+
+    fn schemas(schemas: &mut Vec<(String, RefOr<Schema>)>) {
+        TransactionSchema::schemas(schemas)
     }
-}
\ No newline at end of file
+}
+
+// Cursor over raw transaction bytes — every read names the field so a
+// truncated payload says exactly where it ran out.
+struct RawReader<'a> {
+    bytes: &'a [u8],
+    pos:   usize,
+}
+
+impl<'a> RawReader<'a> {
+    fn take(&mut self, n: usize, field: &str) -> Result<&'a [u8], ChainError> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.bytes.len())
+            .ok_or_else(|| ChainError::MalformedTransaction(format!("truncated at {}", field)))?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn u64(&mut self, field: &str) -> Result<u64, ChainError> {
+        let b: [u8; 8] = self.take(8, field)?.try_into().expect("took 8 bytes");
+        Ok(u64::from_le_bytes(b))
+    }
+
+    fn string(&mut self, field: &str) -> Result<String, ChainError> {
+        let len_bytes: [u8; 2] = self.take(2, field)?.try_into().expect("took 2 bytes");
+        let len = u16::from_le_bytes(len_bytes) as usize;
+        let raw = self.take(len, field)?;
+        String::from_utf8(raw.to_vec())
+            .map_err(|_| ChainError::MalformedTransaction(format!("{} is not valid UTF-8", field)))
+    }
+}
+
+/// A signature is the hex of 64 bytes.
+pub(crate) fn decode_signature(signature_hex: &str) -> Result<[u8; 64], ChainError> {
+    hex::decode(signature_hex)
+        .map_err(|_| ChainError::MalformedSignature("not valid hex".to_string()))?
+        .try_into()
+        .map_err(|_| ChainError::MalformedSignature("wrong byte length".to_string()))
+}
+
+/// An address is the hex of an ed25519 public key — 64 hex chars that decode to a curve point.
+pub(crate) fn parse_address(address: &str, role: &str) -> Result<VerifyingKey, ChainError> {
+    let key_bytes = hex::decode(address)
+        .map_err(|_| ChainError::InvalidAddress(format!("{} is not valid hex", role)))?;
+    let key_array: [u8; 32] = key_bytes.try_into()
+        .map_err(|_| ChainError::InvalidAddress(format!("{} has wrong byte length", role)))?;
+    VerifyingKey::from_bytes(&key_array)
+        .map_err(|_| ChainError::InvalidAddress(format!("{} is not a valid ed25519 public key", role)))
+}
@@ -0,0 +1,46 @@
+// Small helpers shared across modules.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use tracing::warn;
+use crate::multisig::is_multisig_address;
+
+/// First `max` characters of `s`, or all of it if shorter.
+/// Never panics — slices on a char boundary even for non-ASCII input.
+pub fn short(s: &str, max: usize) -> &str {
+    match s.char_indices().nth(max) {
+        Some((i, _)) => &s[..i],
+        None         => s,
+    }
+}
+
+/// What the files kept beside a chain file are named after: chain.json and
+/// chain.json.zst are both "chain".
+pub fn chain_stem(chain_file: &str) -> &str {
+    let name = chain_file.strip_suffix(".zst").unwrap_or(chain_file);
+    name.strip_suffix(".json").unwrap_or(name)
+}
+
+/// An address is a 64-char hex ed25519 public key, a multisig address, or the literal "NETWORK".
+pub fn is_valid_address(address: &str) -> bool {
+    address == "NETWORK"
+        || is_multisig_address(address)
+        || (address.len() == 64 && address.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Mutex::lock that survives poisoning. A handler that panics while holding a
+/// lock poisons it, and with lock().unwrap() every later request would panic
+/// too. Nothing behind our locks is left half-updated by a panic in a way the
+/// next reader can't cope with, so take the guard back and carry on.
+pub trait LockExt<T> {
+    fn locked(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockExt<T> for Mutex<T> {
+    fn locked(&self) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned: PoisonError<MutexGuard<'_, T>>| {
+            warn!("lock was poisoned by a panic, recovering it");
+            self.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
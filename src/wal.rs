@@ -0,0 +1,298 @@
+// Mempool WAL — a log of every transaction the mempool takes in and every
+// one that leaves it, fsynced before the node answers, so a crash loses
+// nothing a user was told was accepted. One record per line:
+//
+//   + <raw transaction hex> <check>
+//   - <txid> <check>
+//
+// where check is the first 8 hex chars of SHA-256 over the rest of the line.
+// A crash mid-append leaves a short or mangled last line; replay stops there.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use sha2::{Digest, Sha256};
+use crate::mempool::Mempool;
+use crate::transaction::Transaction;
+use crate::util::chain_stem;
+
+/// The log is rewritten down to what's pending once it grows past this many
+/// bytes and twice what the last rewrite left.
+pub const DEFAULT_WAL_COMPACT_BYTES: u64 = 1_000_000;
+
+/// The log beside `chain_file`: chain.json → chain.mempool.wal
+pub fn wal_path(chain_file: &str) -> String {
+    format!("{}.mempool.wal", chain_stem(chain_file))
+}
+
+/// What replay() got out of a log.
+pub struct Replayed {
+    pub pending:       Vec<Transaction>,  // added and not removed since, oldest first
+    pub skipped_bytes: u64,               // from the first bad record to the end
+}
+
+pub struct MempoolWal {
+    path:           String,
+    file:           File,
+    logged:         HashSet<String>,  // txids the log says are pending
+    size:           u64,
+    compacted_size: u64,              // what the last rewrite left
+    pub compact_bytes: u64,
+}
+
+impl MempoolWal {
+    /// The transactions the log at `path` still holds. Reading stops at the
+    /// first record that is cut short or fails its check, and everything from
+    /// there on is skipped, not an error. No file reads as an empty log.
+    pub fn replay(path: &str) -> Result<Replayed, String> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("Could not read {}: {}", path, e)),
+        };
+        let mut pending: Vec<Option<Transaction>> = Vec::new();
+        let mut position: HashMap<String, usize> = HashMap::new();  // txid → its slot in pending
+        let mut read = 0;
+        // only whole lines count; a last one without its newline was cut off
+        while let Some(len) = bytes[read..].iter().position(|&b| b == b'\n') {
+            let Some((sign, body)) = std::str::from_utf8(&bytes[read..read + len]).ok().and_then(parse_line) else {
+                break;
+            };
+            match sign {
+                '+' => {
+                    let Ok(txn) = Transaction::from_hex(body) else { break };
+                    position.insert(txn.id(), pending.len());
+                    pending.push(Some(txn));
+                }
+                _ => {
+                    if let Some(slot) = position.remove(body) {
+                        pending[slot] = None;
+                    }
+                }
+            }
+            read += len + 1;
+        }
+        Ok(Replayed {
+            pending:       pending.into_iter().flatten().collect(),
+            skipped_bytes: (bytes.len() - read) as u64,
+        })
+    }
+
+    /// Start the log at `path` afresh, holding exactly what `mempool` does.
+    /// It's written beside the old one and renamed over it, so a crash leaves
+    /// one or the other whole.
+    pub fn create(path: &str, mempool: &Mempool) -> Result<Self, String> {
+        let (file, size) = write_log(path, mempool)?;
+        Ok(MempoolWal {
+            path:           path.to_string(),
+            file,
+            logged:         mempool.txids().map(str::to_string).collect(),
+            size,
+            compacted_size: size,
+            compact_bytes:  DEFAULT_WAL_COMPACT_BYTES,
+        })
+    }
+
+    /// Log whatever entered or left `mempool` since the last call and fsync.
+    /// Returns the number of records written.
+    pub fn sync(&mut self, mempool: &Mempool) -> Result<usize, String> {
+        let removed: Vec<String> = self.logged.iter().filter(|txid| !mempool.contains(txid)).cloned().collect();
+        let added: Vec<(&str, &Transaction)> = mempool.txids()
+            .filter(|txid| !self.logged.contains(*txid))
+            .filter_map(|txid| Some((txid, mempool.get(txid)?)))
+            .collect();
+        if removed.is_empty() && added.is_empty() {
+            return Ok(0);
+        }
+        if self.size > self.compact_bytes.max(2 * self.compacted_size) {
+            self.rewrite(mempool)?;
+            return Ok(mempool.len());
+        }
+
+        let mut buf = String::new();
+        for txid in &removed {
+            push_line(&mut buf, '-', txid);
+        }
+        for (_, txn) in &added {
+            push_line(&mut buf, '+', &txn.to_hex().map_err(|e| e.to_string())?);
+        }
+        self.file.write_all(buf.as_bytes())
+            .and_then(|_| self.file.sync_data())
+            .map_err(|e| format!("Could not append to {}: {}", self.path, e))?;
+        self.size += buf.len() as u64;
+        for txid in removed {
+            self.logged.remove(&txid);
+        }
+        self.logged.extend(added.iter().map(|(txid, _)| txid.to_string()));
+        Ok(buf.lines().count())
+    }
+
+    /// Rewrite the log down to what `mempool` holds now; how many transactions that is.
+    pub fn compact(&mut self, mempool: &Mempool) -> Result<usize, String> {
+        self.rewrite(mempool)?;
+        Ok(mempool.len())
+    }
+
+    fn rewrite(&mut self, mempool: &Mempool) -> Result<(), String> {
+        let (file, size) = write_log(&self.path, mempool)?;
+        self.file = file;
+        self.logged = mempool.txids().map(str::to_string).collect();
+        self.size = size;
+        self.compacted_size = size;
+        Ok(())
+    }
+}
+
+// a log of just `mempool`: into path.tmp, fsynced, renamed over path, then opened to append
+fn write_log(path: &str, mempool: &Mempool) -> Result<(File, u64), String> {
+    let tmp = format!("{}.tmp", path);
+    let mut buf = String::new();
+    // fee order rather than arrival order, which replay doesn't need: it re-sorts by nonce
+    for txn in mempool.iter() {
+        push_line(&mut buf, '+', &txn.to_hex().map_err(|e| e.to_string())?);
+    }
+    let file = File::create(&tmp)
+        .and_then(|mut f| f.write_all(buf.as_bytes()).and_then(|_| f.sync_all()))
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .and_then(|_| OpenOptions::new().append(true).open(path))
+        .map_err(|e| format!("Could not write {}: {}", path, e))?;
+    Ok((file, buf.len() as u64))
+}
+
+fn check(sign: char, body: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update([sign as u8, b' ']);
+    hasher.update(body.as_bytes());
+    hex::encode(&hasher.finalize()[..4])
+}
+
+fn push_line(buf: &mut String, sign: char, body: &str) {
+    let sum = check(sign, body);
+    buf.push_str(&format!("{} {} {}\n", sign, body, sum));
+}
+
+// (sign, body) of a line whose check matches
+fn parse_line(line: &str) -> Option<(char, &str)> {
+    let (rest, sum) = line.rsplit_once(' ')?;
+    let (sign, body) = rest.split_once(' ')?;
+    let sign = match sign {
+        "+" => '+',
+        "-" => '-',
+        _   => return None,
+    };
+    (check(sign, body) == sum).then_some((sign, body))
+}
+
+#[cfg(test)]
+impl MempoolWal {
+    /// A log at `path` whose appends all fail, as on a full or read-only disk.
+    pub(crate) fn failing(path: &str, mempool: &Mempool) -> Self {
+        let mut wal = Self::create(path, mempool).unwrap();
+        wal.file = File::open(path).unwrap();
+        wal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::Wallet;
+
+    fn temp_log(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("mini-blockchain-{}-{}.wal", std::process::id(), name));
+        path.to_string_lossy().into_owned()
+    }
+
+    // a log of `count` transfers, one per line, and the transfers
+    fn write_transfers(path: &str, count: u64) -> (String, Vec<Transaction>) {
+        let sender = Wallet::new();
+        let to = Wallet::new().address();
+        let mut mempool = Mempool::new();
+        let mut wal = MempoolWal::create(path, &mempool).unwrap();
+        let mut txns = Vec::new();
+        for nonce in 0..count {
+            let txn = sender.sign_transaction(to.clone(), 1.0, 0.0, nonce).unwrap();
+            mempool.push(txn.clone());
+            wal.sync(&mempool).unwrap();
+            txns.push(txn);
+        }
+        (std::fs::read_to_string(path).unwrap(), txns)
+    }
+
+    fn ids(txns: &[Transaction]) -> Vec<String> {
+        txns.iter().map(Transaction::id).collect()
+    }
+
+    #[test]
+    fn truncated_last_line_is_skipped() {
+        let path = temp_log("truncated");
+        let (text, txns) = write_transfers(&path, 3);
+        std::fs::write(&path, &text[..text.len() - 20]).unwrap();
+        let replayed = MempoolWal::replay(&path).unwrap();
+        assert_eq!(ids(&replayed.pending), ids(&txns[..2]));
+        let last_line = text.lines().last().unwrap().len() as u64 + 1;
+        assert_eq!(replayed.skipped_bytes, last_line - 20);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn bad_checksum_stops_replay_mid_file() {
+        let path = temp_log("checksum");
+        let (text, txns) = write_transfers(&path, 4);
+        let mut lines: Vec<String> = text.lines().map(String::from).collect();
+        // flip the last hex digit of the second record's check
+        let flipped = if lines[1].ends_with('0') { '1' } else { '0' };
+        lines[1].pop();
+        lines[1].push(flipped);
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+        let replayed = MempoolWal::replay(&path).unwrap();
+        assert_eq!(ids(&replayed.pending), ids(&txns[..1]));
+        let rest: usize = lines[1..].iter().map(|l| l.len() + 1).sum();
+        assert_eq!(replayed.skipped_bytes, rest as u64);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn removals_drop_what_they_name() {
+        let path = temp_log("removals");
+        let sender = Wallet::new();
+        let to = Wallet::new().address();
+        let txns: Vec<Transaction> = (0..4)
+            .map(|nonce| sender.sign_transaction(to.clone(), 1.0, 0.0, nonce).unwrap())
+            .collect();
+        let mut mempool = Mempool::new();
+        let mut wal = MempoolWal::create(&path, &mempool).unwrap();
+        for txn in &txns {
+            mempool.push(txn.clone());
+        }
+        assert_eq!(wal.sync(&mempool).unwrap(), 4);
+        mempool.remove(&txns[0].id());
+        mempool.remove(&txns[2].id());
+        assert_eq!(wal.sync(&mempool).unwrap(), 2);
+        // and one back in after it was removed
+        mempool.push(txns[0].clone());
+        assert_eq!(wal.sync(&mempool).unwrap(), 1);
+
+        let replayed = MempoolWal::replay(&path).unwrap();
+        assert_eq!(replayed.skipped_bytes, 0);
+        // records written in one sync come in no particular order
+        let mut pending = ids(&replayed.pending);
+        pending.sort();
+        let mut expected = ids(&[txns[0].clone(), txns[1].clone(), txns[3].clone()]);
+        expected.sort();
+        assert_eq!(pending, expected);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn failed_append_is_an_error() {
+        let path = temp_log("failing");
+        let mut mempool = Mempool::new();
+        let mut wal = MempoolWal::failing(&path, &mempool);
+        mempool.push(Wallet::new().sign_transaction(Wallet::new().address(), 1.0, 0.0, 0).unwrap());
+        assert!(wal.sync(&mempool).is_err());
+        // nothing counted as logged, so the next sync tries again
+        assert!(wal.logged.is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,20 +1,34 @@
 use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer, Verifier};
+use bip39::Mnemonic;
+use rand::RngCore;
 use rand::rngs::OsRng;
+use sha2::{Sha256, Sha512, Digest};
 use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+use zeroize::{Zeroize, Zeroizing, ZeroizeOnDrop};
+use crate::transaction::Transaction;
+use crate::multisig::Multisig;
 
-// WalletInfo is what we send over the API — just the addresses, never the private key
-// The real Wallet struct holds the signing key (secret) and never gets serialized
-#[derive(Serialize, Deserialize)]
+/// Prepended to every signed message. Transaction digests start with an address,
+/// so a message signature can never verify as a transaction signature.
+pub const MESSAGE_PREFIX: &str = "MiniBlockchain Signed Message:\n";
+
+// WalletInfo is what we send over the API — just the address, never the private key.
+// The secret only leaves a Wallet through export_secret(), so every leak is greppable.
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct WalletInfo {
-    pub address: String,         // public key as hex — safe to share
-    pub private_key_hex: String, // hex of private key — shown ONCE on creation, never stored
+    pub address: String,  // public key as hex — safe to share
 }
 
+// The real Wallet struct holds the signing key (secret) and never gets serialized.
+// SigningKey wipes its bytes on drop, so dropping a Wallet leaves no key in memory.
 pub struct Wallet {
-    pub signing_key: SigningKey,
+    signing_key: SigningKey,
     pub verifying_key: VerifyingKey,
 }
 
+impl ZeroizeOnDrop for Wallet {}
+
 impl Wallet {
     /// Generates a brand new random wallet.
     /// OsRng = OS-level cryptographic randomness (safe for key generation).
@@ -28,33 +42,91 @@ impl Wallet {
     /// Recreate a wallet from a private key hex string.
     /// Used when the API receives a private key to sign a transaction.
     pub fn from_private_key_hex(hex_str: &str) -> Result<Self, String> {
-        let bytes = hex::decode(hex_str)
-            .map_err(|_| "Invalid private key hex".to_string())?;
-        let key_array: [u8; 32] = bytes.try_into()
+        let bytes = Zeroizing::new(hex::decode(hex_str)
+            .map_err(|_| "Invalid private key hex".to_string())?);
+        let mut key_array: [u8; 32] = bytes.as_slice().try_into()
             .map_err(|_| "Private key must be 32 bytes".to_string())?;
         let signing_key = SigningKey::from_bytes(&key_array);
+        key_array.zeroize();
         let verifying_key = signing_key.verifying_key();
         Ok(Wallet { signing_key, verifying_key })
     }
 
+    /// New wallet plus a 12-word BIP39 phrase that restores it via from_mnemonic().
+    pub fn generate_with_mnemonic() -> (Self, String) {
+        let mut entropy = Zeroizing::new([0u8; 16]);
+        OsRng.fill_bytes(entropy.as_mut());
+        let mnemonic = Mnemonic::from_entropy(entropy.as_ref())
+            .expect("16 bytes is valid BIP39 entropy");
+        let wallet = Self::from_bip39(&mnemonic);
+        (wallet, mnemonic.to_string())
+    }
+
+    /// Re-derive a wallet from its BIP39 phrase (12–24 English words).
+    /// Derivation: BIP39 seed (PBKDF2, empty passphrase) → SHA-512 → first 32 bytes
+    /// as the ed25519 secret. Not BIP32/SLIP-10 — phrases only round-trip with this node.
+    pub fn from_mnemonic(phrase: &str) -> Result<Self, String> {
+        let mnemonic = Mnemonic::parse(phrase)
+            .map_err(|e| format!("Invalid mnemonic: {}", e))?;
+        Ok(Self::from_bip39(&mnemonic))
+    }
+
+    fn from_bip39(mnemonic: &Mnemonic) -> Self {
+        let seed = Zeroizing::new(mnemonic.to_seed(""));
+        let mut digest = Zeroizing::new([0u8; 64]);
+        digest.copy_from_slice(&Sha512::digest(seed.as_ref()));
+        let mut key_array = [0u8; 32];
+        key_array.copy_from_slice(&digest[..32]);
+        let signing_key = SigningKey::from_bytes(&key_array);
+        key_array.zeroize();
+        let verifying_key = signing_key.verifying_key();
+        Wallet { signing_key, verifying_key }
+    }
+
     pub fn address(&self) -> String {
         hex::encode(self.verifying_key.as_bytes())
     }
 
-    pub fn private_key_hex(&self) -> String {
-        hex::encode(self.signing_key.as_bytes())
+    /// The private key as hex. Deliberately named — anything calling this
+    /// is handing the secret out. The returned string is wiped when dropped.
+    pub fn export_secret(&self) -> Zeroizing<String> {
+        Zeroizing::new(hex::encode(self.signing_key.as_bytes()))
     }
 
     pub fn sign(&self, message: &[u8]) -> Signature {
         self.signing_key.sign(message)
     }
 
+    /// Sign free text to prove ownership of this address. Hex signature over
+    /// SHA-256(MESSAGE_PREFIX + msg) — check it with verify_message().
+    pub fn sign_message(&self, msg: &str) -> String {
+        hex::encode(self.sign(&message_digest(msg)).to_bytes())
+    }
+
+    /// Build and sign a transfer from this wallet in one go — for offline signing.
+    /// Export the result with Transaction::to_hex() and POST it to /transaction/raw.
+    pub fn sign_transaction(&self, to: String, amount_tokens: f64, fee_tokens: f64, nonce: u64) -> Result<Transaction, String> {
+        let mut txn = Transaction::new_with_fee(self.address(), to, amount_tokens, fee_tokens)?;
+        txn.nonce = nonce;
+        txn.sign(self)?;
+        Ok(txn)
+    }
+
+    /// Address of the `m`-of-`keys` multisig — fund it like any other address,
+    /// spend from it with a Transaction carrying a Multisig and M co-signatures.
+    pub fn multisig_address(keys: &[String], m: u8) -> Result<String, String> {
+        Ok(Multisig::new(keys.to_vec(), m)?.address())
+    }
+
     /// Returns a WalletInfo struct safe to serialize and send as JSON
     pub fn to_info(&self) -> WalletInfo {
-        WalletInfo {
-            address: self.address(),
-            private_key_hex: self.private_key_hex(),
-        }
+        WalletInfo { address: self.address() }
+    }
+}
+
+impl Default for Wallet {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -64,4 +136,28 @@ pub fn verify_signature(
     signature: &Signature,
 ) -> bool {
     verifying_key.verify(message, signature).is_ok()
+}
+
+fn message_digest(msg: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(MESSAGE_PREFIX.as_bytes());
+    hasher.update(msg.as_bytes());
+    hasher.finalize().to_vec()
+}
+
+/// Ok(true) if `sig_hex` is `address_hex`'s signature over `msg` (see Wallet::sign_message).
+/// Err only when the address or signature can't be decoded at all.
+pub fn verify_message(address_hex: &str, msg: &str, sig_hex: &str) -> Result<bool, String> {
+    let key_bytes = hex::decode(address_hex)
+        .map_err(|_| "Address is not valid hex".to_string())?;
+    let key_array: [u8; 32] = key_bytes.try_into()
+        .map_err(|_| "Address must be 32 bytes".to_string())?;
+    let verifying_key = VerifyingKey::from_bytes(&key_array)
+        .map_err(|_| "Address is not a valid ed25519 public key".to_string())?;
+    let sig_bytes = hex::decode(sig_hex)
+        .map_err(|_| "Signature is not valid hex".to_string())?;
+    let sig_array: [u8; 64] = sig_bytes.try_into()
+        .map_err(|_| "Signature must be 64 bytes".to_string())?;
+    let signature = Signature::from_bytes(&sig_array);
+    Ok(verify_signature(&verifying_key, &message_digest(msg), &signature))
 }
\ No newline at end of file
@@ -0,0 +1,352 @@
+// Webhooks — POST chain events to URLs that asked for them.
+// Same shape as gossip: handlers drop events on a channel and return, one
+// background task fans each event out to the matching endpoints. The registry
+// lives in its own file next to the chain so registrations survive a restart.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use rand::RngCore;
+use rand::rngs::OsRng;
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+use serde_json::json;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use crate::block::Block;
+use crate::clock::{Clock, SystemClock};
+use crate::transaction::Transaction;
+use crate::util::{chain_stem, is_valid_address, short, LockExt};
+use tracing::{error, info, warn};
+
+/// Tries per event before a delivery counts as failed.
+pub const WEBHOOK_ATTEMPTS: u32 = 3;
+
+/// Failed deliveries in a row before an endpoint is disabled.
+pub const MAX_WEBHOOK_FAILURES: u32 = 5;
+
+/// How long an endpoint gets to answer.
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Wait before the first retry — doubles after each one.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum WebhookError {
+    #[error("Invalid webhook URL: {0}")]
+    InvalidUrl(String),
+    #[error("events must name at least one of \"block\", \"tx\"")]
+    NoEvents,
+    #[error("address_filter {0:?} is not a valid address")]
+    InvalidAddress(String),
+    #[error("no webhook with id {0}")]
+    NotFound(String),
+}
+
+impl WebhookError {
+    /// The API's error_code for this failure.
+    pub fn code(&self) -> &'static str {
+        match self {
+            WebhookError::InvalidUrl(_)     => "INVALID_URL",
+            WebhookError::NoEvents          => "NO_EVENTS",
+            WebhookError::InvalidAddress(_) => "INVALID_ADDRESS",
+            WebhookError::NotFound(_)       => "NOT_FOUND",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Block,
+    Tx,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct Webhook {
+    pub id:             String,
+    pub url:            String,
+    pub events:         Vec<EventKind>,
+    pub address_filter: Option<String>,  // tx events only where this is sender or recipient
+    pub enabled:        bool,            // false once it failed MAX_WEBHOOK_FAILURES times running
+    pub created_at:     u64,
+    pub last_delivery:  Option<u64>,     // last time it answered 2xx
+    pub last_error:     Option<String>,  // cleared by the next successful delivery
+    pub failures:       u32,             // failed deliveries since the last success
+}
+
+impl Webhook {
+    fn wants(&self, event: &ChainEvent) -> bool {
+        self.enabled
+            && self.events.contains(&event.kind())
+            && match (&self.address_filter, event) {
+                (Some(address), ChainEvent::Tx(tx)) => tx.touches(address),
+                _                                   => true,
+            }
+    }
+}
+
+/// A block joined the chain.
+#[derive(Serialize, Clone)]
+pub struct BlockEvent {
+    pub index:         u32,
+    pub hash:          String,
+    pub previous_hash: String,
+    pub timestamp:     u64,
+    pub miner:         String,
+    pub txids:         Vec<String>,
+}
+
+/// A transaction entered the mempool ("pending") or a block ("confirmed").
+#[derive(Serialize, Clone)]
+pub struct TxEvent {
+    pub txid:        String,
+    pub status:      &'static str,
+    pub block_index: Option<u32>,
+    pub transaction: Transaction,
+}
+
+impl TxEvent {
+    fn touches(&self, address: &str) -> bool {
+        self.transaction.from == address || self.transaction.pays(address)
+    }
+}
+
+/// What gets POSTed: { webhook_id, event, data }.
+#[derive(Serialize, Clone)]
+#[serde(tag = "event", content = "data", rename_all = "lowercase")]
+pub enum ChainEvent {
+    Block(BlockEvent),
+    Tx(TxEvent),
+}
+
+impl ChainEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            ChainEvent::Block(_) => EventKind::Block,
+            ChainEvent::Tx(_)    => EventKind::Tx,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            ChainEvent::Block(b) => format!("block #{}", b.index),
+            ChainEvent::Tx(t)    => format!("{} tx {}", t.status, short(&t.txid, 12)),
+        }
+    }
+}
+
+/// Registered endpoints, saved to `path` after every change.
+pub struct WebhookRegistry {
+    pub clock: Arc<dyn Clock>,
+    path:      String,
+    hooks:     Vec<Webhook>,
+}
+
+impl WebhookRegistry {
+    /// Read `path` if it exists, otherwise start empty.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let hooks = if std::path::Path::new(path).exists() {
+            let json = std::fs::read_to_string(path)
+                .map_err(|e| format!("Could not read {}: {}", path, e))?;
+            serde_json::from_str(&json)
+                .map_err(|e| format!("{} is not a valid webhook file: {}", path, e))?
+        } else {
+            Vec::new()
+        };
+        Ok(WebhookRegistry { clock: Arc::new(SystemClock), path: path.to_string(), hooks })
+    }
+
+    fn save(&self) {
+        if let Err(e) = self.flush() {
+            error!(path = %self.path, error = %e, "could not save webhooks");
+        }
+    }
+
+    /// Write the registry out now; returns how many endpoints it holds.
+    pub fn flush(&self) -> Result<usize, String> {
+        let json = serde_json::to_string_pretty(&self.hooks).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, json).map_err(|e| e.to_string())?;
+        Ok(self.hooks.len())
+    }
+
+    pub fn list(&self) -> &[Webhook] {
+        &self.hooks
+    }
+
+    pub fn register(&mut self, url: &str, events: Vec<EventKind>, address_filter: Option<String>) -> Result<Webhook, WebhookError> {
+        let url = check_url(url)?;
+        let events = events.iter().fold(Vec::new(), |mut kept, e| {
+            if !kept.contains(e) { kept.push(*e); }
+            kept
+        });
+        if events.is_empty() {
+            return Err(WebhookError::NoEvents);
+        }
+        if let Some(address) = &address_filter {
+            if !is_valid_address(address) {
+                return Err(WebhookError::InvalidAddress(address.clone()));
+            }
+        }
+        let mut id = [0u8; 8];
+        OsRng.fill_bytes(&mut id);
+        let hook = Webhook {
+            id: hex::encode(id),
+            url,
+            events,
+            address_filter,
+            enabled:       true,
+            created_at:    self.clock.now_secs(),
+            last_delivery: None,
+            last_error:    None,
+            failures:      0,
+        };
+        info!(webhook = %hook.id, url = %hook.url, "webhook registered");
+        self.hooks.push(hook.clone());
+        self.save();
+        Ok(hook)
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<Webhook, WebhookError> {
+        let at = self.hooks.iter().position(|h| h.id == id)
+            .ok_or_else(|| WebhookError::NotFound(id.to_string()))?;
+        let hook = self.hooks.remove(at);
+        self.save();
+        Ok(hook)
+    }
+
+    fn delivered(&mut self, id: &str) {
+        let now = self.clock.now_secs();
+        if let Some(hook) = self.hooks.iter_mut().find(|h| h.id == id) {
+            let changed = hook.failures > 0 || hook.last_error.is_some();
+            hook.last_delivery = Some(now);
+            hook.last_error = None;
+            hook.failures = 0;
+            if changed {
+                self.save();
+            }
+        }
+    }
+
+    fn failed(&mut self, id: &str, error: &str) {
+        if let Some(hook) = self.hooks.iter_mut().find(|h| h.id == id) {
+            hook.last_error = Some(error.to_string());
+            hook.failures += 1;
+            if hook.enabled && hook.failures >= MAX_WEBHOOK_FAILURES {
+                hook.enabled = false;
+                warn!(webhook = %hook.id, failures = hook.failures, "webhook disabled");
+            }
+            self.save();
+        }
+    }
+}
+
+/// Where the registry lives for a given chain file: chain.json → chain.webhooks.json.
+pub fn registry_path(chain_file: &str) -> String {
+    format!("{}.webhooks.json", chain_stem(chain_file))
+}
+
+// any http(s) URL — unlike peers, a path and query string are fine
+fn check_url(url: &str) -> Result<String, WebhookError> {
+    let url = url.trim();
+    let rest = url.strip_prefix("http://").or_else(|| url.strip_prefix("https://"));
+    match rest {
+        Some(rest) if !rest.is_empty() && !rest.starts_with('/') && !rest.contains(char::is_whitespace) => Ok(url.to_string()),
+        _ => Err(WebhookError::InvalidUrl(format!("{:?} must be an http:// or https:// URL", url))),
+    }
+}
+
+/// The sending half, kept in AppState next to the registry. Like Gossip,
+/// notifying never blocks and never fails.
+#[derive(Clone)]
+pub struct Webhooks {
+    pub registry: Arc<Mutex<WebhookRegistry>>,
+    tx:           mpsc::UnboundedSender<ChainEvent>,
+}
+
+impl Webhooks {
+    /// Start the dispatcher on the current tokio runtime.
+    pub fn spawn(registry: WebhookRegistry) -> Self {
+        let registry = Arc::new(Mutex::new(registry));
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .expect("HTTP client with a timeout always builds");
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(dispatch(rx, registry.clone(), client));
+        Webhooks { registry, tx }
+    }
+
+    pub fn notify(&self, event: ChainEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    /// A transaction was accepted into the mempool.
+    pub fn transaction_pending(&self, txn: &Transaction) {
+        self.notify(ChainEvent::Tx(TxEvent {
+            txid: txn.id(), status: "pending", block_index: None, transaction: txn.clone(),
+        }));
+    }
+
+    /// A block joined the chain: one block event, then a confirmed event per
+    /// transaction in it (the coinbase included — that's how a miner hears it got paid).
+    pub fn block_added(&self, block: &Block) {
+        self.notify(ChainEvent::Block(BlockEvent {
+            index:         block.index,
+            hash:          block.hash.clone(),
+            previous_hash: block.previous_hash.clone(),
+            timestamp:     block.timestamp,
+            miner:         block.miner.clone(),
+            txids:         block.transactions.iter().map(|t| t.id()).collect(),
+        }));
+        for txn in &block.transactions {
+            self.notify(ChainEvent::Tx(TxEvent {
+                txid: txn.id(), status: "confirmed", block_index: Some(block.index), transaction: txn.clone(),
+            }));
+        }
+    }
+}
+
+// every event goes to every enabled endpoint that wants it, each delivery on its own task
+async fn dispatch(mut rx: mpsc::UnboundedReceiver<ChainEvent>, registry: Arc<Mutex<WebhookRegistry>>, client: reqwest::Client) {
+    while let Some(event) = rx.recv().await {
+        let targets: Vec<(String, String)> = registry.locked().hooks.iter()
+            .filter(|h| h.wants(&event))
+            .map(|h| (h.id.clone(), h.url.clone()))
+            .collect();
+        if targets.is_empty() {
+            continue;
+        }
+        let payload = serde_json::to_value(&event).unwrap_or_default();
+        for (id, url) in targets {
+            let mut body = json!({ "webhook_id": id });
+            if let (Some(body), Some(payload)) = (body.as_object_mut(), payload.as_object()) {
+                body.extend(payload.clone());
+            }
+            tokio::spawn(deliver(client.clone(), id, url, event.describe(), body, registry.clone()));
+        }
+    }
+}
+
+/// POST one event to one endpoint. Anything but a 2xx is retried, up to
+/// WEBHOOK_ATTEMPTS times with the delay doubling in between.
+async fn deliver(client: reqwest::Client, id: String, url: String, what: String,
+                 body: serde_json::Value, registry: Arc<Mutex<WebhookRegistry>>) {
+    let mut delay = RETRY_DELAY;
+    let mut last_error = String::new();
+    for attempt in 1..=WEBHOOK_ATTEMPTS {
+        match client.post(&url).json(&body).send().await {
+            Ok(res) if res.status().is_success() => {
+                registry.locked().delivered(&id);
+                return;
+            }
+            Ok(res) => last_error = format!("HTTP {}", res.status()),
+            Err(e)  => last_error = e.to_string(),
+        }
+        if attempt < WEBHOOK_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+    warn!(webhook = %id, what = %what, error = %last_error, "webhook delivery failed");
+    registry.locked().failed(&id, &format!("delivery of {} failed: {}", what, last_error));
+}
@@ -0,0 +1,1181 @@
+mod common;
+
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use actix_web::test::{self, TestRequest};
+use actix_web::App;
+use serde_json::{json, Value};
+
+use mini_blockchain::block::Block;
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::error::ChainError;
+use mini_blockchain::ratelimit::RateLimiter;
+use mini_blockchain::server::routes;
+use mini_blockchain::status::NodeStatus;
+use mini_blockchain::transaction::{Transaction, BURN_ADDRESS};
+use mini_blockchain::wallet::Wallet;
+
+use common::{app_state, copy_chain, fund, test_state, API_KEY};
+
+fn get(uri: &str) -> TestRequest {
+    TestRequest::get().uri(uri)
+}
+
+// with the API key, as every mutating endpoint wants
+fn post(uri: &str, body: Value) -> TestRequest {
+    TestRequest::post().uri(uri).insert_header(("X-Api-Key", API_KEY)).set_json(body)
+}
+
+// the status and the response envelope
+macro_rules! call {
+    ($app:expr, $req:expr) => {{
+        let resp = test::call_service(&$app, $req.to_request()).await;
+        let status = resp.status().as_u16();
+        let body: Value = test::read_body_json(resp).await;
+        (status, body)
+    }};
+}
+
+#[actix_web::test]
+async fn validate_reports_an_invalid_chain_as_data() {
+    let state = test_state(Blockchain::with_prefix("0"), "api-validate");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let (status, body) = call!(app, get("/validate"));
+    assert_eq!(status, 200);
+    assert_eq!((body["ok"].clone(), body["data"]["valid"].clone()), (json!(true), json!(true)));
+    assert!(body["error_code"].is_null());
+
+    // an invalid chain is still a successful answer to "is it valid?"
+    state.chain.update(|bc| bc.chain[0].nonce += 1).await;
+    let (status, body) = call!(app, get("/validate"));
+    assert_eq!(status, 200);
+    assert_eq!((body["ok"].clone(), body["data"]["valid"].clone()), (json!(false), json!(false)));
+}
+
+#[actix_web::test]
+async fn errors_carry_a_status_and_a_code() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    bc.mine_pending_transactions(alice.address(), true).unwrap();
+    let state = test_state(bc, "api-codes");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let (status, body) = call!(app, post("/mine", json!({"miner_address": alice.address()})));
+    assert_eq!((status, body["error_code"].as_str()), (409, Some("MEMPOOL_EMPTY")));
+    assert_eq!(body["ok"], false);
+
+    let (status, body) = call!(app, post("/transaction/raw", json!({"raw": "zz"})));
+    assert_eq!((status, body["error_code"].as_str()), (400, Some("MALFORMED_TRANSACTION")));
+
+    let secret = alice.export_secret();
+    let (status, body) = call!(app, post("/transaction", json!({
+        "from": alice.address(), "to": Wallet::new().address(), "amount": 1e9, "private_key_hex": secret.as_str(),
+    })));
+    assert_eq!((status, body["error_code"].as_str()), (422, Some("INSUFFICIENT_FUNDS")));
+
+    let broken = TestRequest::post().uri("/transaction")
+        .insert_header(("content-type", "application/json"))
+        .set_payload("{nope");
+    let (status, body) = call!(app, broken);
+    assert_eq!((status, body["error_code"].as_str()), (400, Some("MALFORMED_JSON")));
+
+    let resp = test::call_service(&app, get("/block/abc").to_request()).await;
+    assert_eq!(resp.status(), 400);
+    let (status, body) = call!(app, get("/block/99"));
+    assert_eq!((status, body["error_code"].as_str()), (404, Some("NOT_FOUND")));
+    let (status, body) = call!(app, get(&format!("/block/{}", usize::MAX)));
+    assert_eq!((status, body["error_code"].as_str()), (404, Some("NOT_FOUND")));
+}
+
+#[actix_web::test]
+async fn mutating_endpoints_want_the_api_key() {
+    let state = test_state(Blockchain::with_prefix("0"), "api-key");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let body = json!({"miner_address": Wallet::new().address()});
+
+    let (status, res) = call!(app, TestRequest::post().uri("/mine").set_json(&body));
+    assert_eq!((status, res["error_code"].as_str()), (401, Some("API_KEY_REQUIRED")));
+    assert_eq!(res["ok"], false);
+
+    let wrong = TestRequest::post().uri("/mine").insert_header(("X-Api-Key", "nope")).set_json(&body);
+    let (status, res) = call!(app, wrong);
+    assert_eq!((status, res["error_code"].as_str()), (403, Some("BAD_API_KEY")));
+
+    // through to the handler, which has nothing to mine
+    let (status, res) = call!(app, post("/mine", body));
+    assert_eq!((status, res["error_code"].as_str()), (409, Some("MEMPOOL_EMPTY")));
+
+    // a missing key is reported before a body that doesn't parse
+    let broken = TestRequest::post().uri("/mine").insert_header(("content-type", "application/json")).set_payload("{");
+    let (status, _) = call!(app, broken);
+    assert_eq!(status, 401);
+
+    // reads stay open
+    let (status, _) = call!(app, get("/chain/head"));
+    assert_eq!(status, 200);
+}
+
+#[actix_web::test]
+async fn the_request_over_the_limit_gets_a_429() {
+    let status = NodeStatus::new();
+    status.chain_loaded();
+    let state = app_state(Blockchain::with_prefix("0"), "api-rate", RateLimiter::new(5), status);
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let raw = || post("/transaction/raw", json!({"raw": "00"}));
+
+    // the first five get as far as the decoder
+    for _ in 0..5 {
+        let (status, _) = call!(app, raw());
+        assert_eq!(status, 400);
+    }
+    let resp = test::call_service(&app, raw().to_request()).await;
+    assert_eq!(resp.status().as_u16(), 429);
+    assert!(resp.headers().get("retry-after").is_some());
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error_code"], "RATE_LIMITED");
+    assert!(body["data"]["retry_after_secs"].as_u64().unwrap() >= 1);
+
+    // reads don't spend tokens
+    for _ in 0..10 {
+        let (status, _) = call!(app, get("/chain/head"));
+        assert_eq!(status, 200);
+    }
+}
+
+#[actix_web::test]
+async fn ready_fails_while_health_stays_up() {
+    let state = app_state(Blockchain::with_prefix("0"), "api-ready", RateLimiter::disabled(), NodeStatus::new());
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    // nothing loaded yet
+    let (status, body) = call!(app, get("/ready"));
+    assert_eq!((status, body["error_code"].as_str()), (503, Some("NOT_READY")));
+    assert_eq!(body["data"]["status"], "starting");
+    assert_eq!(call!(app, get("/health")).0, 200);
+
+    state.status.chain_loaded();
+    assert_eq!(call!(app, get("/ready")).0, 200);
+
+    state.status.saved(Err("disk full".into()));
+    let (status, body) = call!(app, get("/ready"));
+    assert_eq!((status, body["error_code"].as_str()), (503, Some("NOT_READY")));
+    assert_eq!(body["data"]["status"], "degraded");
+    assert_eq!(body["data"]["last_save_error"], "disk full");
+    let (status, body) = call!(app, get("/health"));
+    assert_eq!((status, body["data"]["height"].as_u64()), (200, Some(0)));
+
+    state.status.saved(Ok(()));
+    assert_eq!(call!(app, get("/ready")).0, 200);
+
+    // a failing peer isn't this node's problem
+    state.status.synced(Err("peer down".into()));
+    assert_eq!(call!(app, get("/ready")).0, 200);
+
+    state.status.validated(Err("Block #3 hash mismatch".into()));
+    assert_eq!(call!(app, get("/ready")).0, 503);
+    assert_eq!(call!(app, get("/health")).0, 200);
+}
+
+#[actix_web::test]
+async fn reads_answer_while_a_block_is_being_mined() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.mining_threads = 1;
+    // the next block takes the tip's difficulty, and 64 bits won't turn up during a test
+    bc.chain[0].difficulty = 64;
+    let state = test_state(bc, "api-mining");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let mine = async {
+        call!(app, post("/mine", json!({"miner_address": Wallet::new().address(), "allow_empty": true})))
+    };
+    let read_then_cancel = async {
+        while !state.chain.mining_status().lock().unwrap().mining {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let balance = tokio::time::timeout(Duration::from_secs(5), async {
+            call!(app, get(&format!("/balance/{}", Wallet::new().address())))
+        }).await;
+        state.chain.cancel_mining().store(true, Ordering::Relaxed);
+        balance
+    };
+    let ((mined, body), balance) = tokio::join!(mine, read_then_cancel);
+    assert_eq!(balance.expect("GET /balance waited on the miner").0, 200);
+    assert_eq!((mined, body["error_code"].as_str()), (409, Some("MINING_CANCELLED")));
+    assert_eq!(state.chain.query(|bc| bc.chain.len()).await, 1);
+}
+
+#[actix_web::test]
+async fn mempool_shows_which_transactions_are_still_locked() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let mut txn = Transaction::new(alice.address(), Wallet::new().address(), 1.0).unwrap();
+    // one block too early for the next block
+    txn.lock_until_block = Some(bc.chain.len() as u32 + 1);
+    txn.sign(&alice).unwrap();
+    bc.add_transaction(txn).unwrap();
+    let state = test_state(bc, "api-locked");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let (_, body) = call!(app, get("/mempool"));
+    assert_eq!(body["data"]["transactions"][0]["locked"], true);
+    state.chain.update(|bc| bc.mine_pending_transactions(Wallet::new().address(), true).unwrap()).await;
+    let (_, body) = call!(app, get("/mempool"));
+    assert_eq!(body["data"]["transactions"][0]["locked"], false);
+}
+
+// what an external miner does with a template: add the coinbase, search nonces
+fn block_from_template(template: &Value) -> Block {
+    let index = template["index"].as_u64().unwrap();
+    let miner = template["miner"].as_str().unwrap().to_string();
+    let mut transactions: Vec<Transaction> = serde_json::from_value(template["transactions"].clone()).unwrap();
+    let mut coinbase = Transaction::new_nits("NETWORK".into(), miner.clone(), template["coinbase_amount"].as_u64().unwrap());
+    coinbase.nonce = index;
+    transactions.push(coinbase);
+    let previous_hash = template["previous_hash"].as_str().unwrap().to_string();
+    let mut block = Block::new(index as u32, transactions, previous_hash, miner, template["timestamp"].as_u64().unwrap());
+    block.difficulty = template["difficulty"].as_u64().unwrap() as u32;
+    block.hash = block.calculate_hash();
+    while !block.meets_difficulty() {
+        block.nonce += 1;
+        block.hash = block.calculate_hash();
+    }
+    block
+}
+
+#[actix_web::test]
+async fn an_external_miner_works_from_templates() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let miner = Wallet::new();
+    fund(&mut bc, &miner.address());
+    let state = test_state(bc, "api-template");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    for nonce in 0..3 {
+        let txn = miner.sign_transaction(Wallet::new().address(), 1.0, 0.5, nonce).unwrap();
+        let (status, _) = call!(app, post("/transaction/raw", json!({"raw": txn.to_hex().unwrap()})));
+        assert_eq!(status, 200);
+
+        let uri = format!("/mining/template?miner_address={}", miner.address());
+        let (status, template) = call!(app, get(&uri));
+        assert_eq!(status, 200);
+        let template = &template["data"];
+        assert_eq!(template["transactions"][0]["nonce"], nonce);
+        // the template only reads the mempool
+        assert_eq!(call!(app, get("/mempool")).1["data"]["count"], 1);
+
+        let block = block_from_template(template);
+        let (status, body) = call!(app, TestRequest::post().uri("/block").set_json(&block));
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["data"], template["index"]);
+    }
+    let (height, pending) = state.chain.query(|bc| (bc.chain.len() - 1, bc.mempool.len())).await;
+    assert_eq!((height, pending), (4, 0));
+    assert_eq!(call!(app, get("/validate")).1["data"]["valid"], true);
+}
+
+#[actix_web::test]
+async fn an_orphan_block_needs_its_own_work_before_we_sync_for_it() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    let state = test_state(bc, "api-orphan");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let uri = format!("/mining/template?miner_address={}&allow_empty=true", Wallet::new().address());
+    let template = call!(app, get(&uri)).1["data"].clone();
+    let mut block = block_from_template(&template);
+    block.previous_hash = "ab".repeat(32);
+
+    // no work behind it: turned away before it can send us to our peers
+    block.nonce += 1;
+    block.hash = block.calculate_hash();
+    while block.meets_difficulty() {
+        block.nonce += 1;
+        block.hash = block.calculate_hash();
+    }
+    let (status, body) = call!(app, TestRequest::post().uri("/block").set_json(&block));
+    assert_eq!((status, body["error_code"].as_str()), (422, Some("DIFFICULTY_NOT_MET")));
+
+    while !block.meets_difficulty() {
+        block.nonce += 1;
+        block.hash = block.calculate_hash();
+    }
+    let (status, body) = call!(app, TestRequest::post().uri("/block").set_json(&block));
+    assert_eq!((status, body["error_code"].as_str()), (409, Some("UNKNOWN_PARENT")));
+}
+
+#[actix_web::test]
+async fn a_panic_mid_request_doesnt_take_the_next_one_down() {
+    let state = test_state(Blockchain::with_prefix("0"), "api-panic");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    // on the chain thread
+    let chain = state.clone();
+    let panicked = actix_web::rt::spawn(async move {
+        chain.chain.query(|_| -> usize { panic!("boom on the chain thread") }).await
+    }).await;
+    assert!(panicked.is_err());
+    let (status, _) = call!(app, get(&format!("/balance/{}", Wallet::new().address())));
+    assert_eq!(status, 200);
+
+    // and while holding one of the shared locks, poisoning it
+    let peers = state.peers.clone();
+    let panicked = std::thread::spawn(move || {
+        let _held = peers.lock().unwrap();
+        panic!("boom with the peer set locked");
+    }).join();
+    assert!(panicked.is_err() && state.peers.is_poisoned());
+    let (status, body) = call!(app, get("/peers"));
+    assert_eq!((status, body["data"].as_array().map(Vec::len)), (200, Some(0)));
+    let (status, _) = call!(app, get("/chain/head"));
+    assert_eq!(status, 200);
+}
+
+#[actix_web::test]
+async fn a_change_that_panics_halfway_leaves_the_indexes_matching_the_chain() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new().address();
+    fund(&mut bc, &alice);
+    bc.mine_pending_transactions(alice.clone(), true).unwrap();
+    let state = test_state(bc, "api-panic-update");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let owner = alice.clone();
+    let (tip, expected) = state.chain.query(move |bc| {
+        let tip = bc.chain.last().unwrap();
+        let reward = tip.transactions.last().unwrap().amount_to(&owner);
+        (tip.hash.clone(), bc.get_balance_nits(&owner).unwrap() - reward)
+    }).await;
+
+    // the tip comes off, but its balance change never gets undone
+    let chain = state.clone();
+    let panicked = actix_web::rt::spawn(async move {
+        chain.chain.update(|bc| {
+            bc.chain.pop();
+            panic!("boom halfway through a change")
+        }).await
+    }).await;
+    assert!(panicked.is_err());
+
+    let balance = state.chain.query(move |bc| bc.get_balance_nits(&alice).unwrap()).await;
+    assert_eq!(balance, expected);
+    let (status, _) = call!(app, get(&format!("/block/hash/{}", tip)));
+    assert_eq!(status, 404);
+    assert_eq!(call!(app, get("/validate")).1["data"]["valid"], true);
+}
+
+#[actix_web::test]
+async fn a_hundred_reads_during_a_mine() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.mining_threads = 1;
+    // as in reads_answer_while_a_block_is_being_mined: a block that won't be found
+    bc.chain[0].difficulty = 64;
+    let state = test_state(bc, "api-reads");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let mine = async {
+        call!(app, post("/mine", json!({"miner_address": Wallet::new().address(), "allow_empty": true})))
+    };
+    let reads = async {
+        while !state.chain.mining_status().lock().unwrap().mining {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let mut readers = tokio::task::JoinSet::new();
+        for _ in 0..100 {
+            let state = state.clone();
+            let address = Wallet::new().address();
+            readers.spawn(async move { state.chain.query(move |bc| bc.get_balance(&address)).await });
+        }
+        let balances = tokio::time::timeout(Duration::from_secs(10), readers.join_all()).await;
+        state.chain.cancel_mining().store(true, Ordering::Relaxed);
+        balances
+    };
+    let ((mined, _), balances) = tokio::join!(mine, reads);
+    let balances = balances.expect("the reads waited on the miner");
+    assert_eq!(balances.len(), 100);
+    assert!(balances.iter().all(|b| b.as_ref().is_ok_and(|&b| b == 0.0)));
+    assert_eq!(mined, 409);
+}
+
+#[actix_web::test]
+async fn refused_transactions_are_on_the_audit_log() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let state = test_state(bc, "api-audit");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let replay = alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 5).unwrap();
+    let (status, _) = call!(app, post("/transaction/raw", json!({"raw": replay.to_hex().unwrap()})));
+    assert_eq!(status, 422);
+
+    let (status, body) = call!(app, get("/audit/rejections").insert_header(("X-Api-Key", API_KEY)));
+    assert_eq!(status, 200);
+    let entry = &body["data"]["rejections"][0];
+    assert_eq!((entry["source"].as_str(), entry["rejected"].as_str()), (Some("api"), Some("transaction")));
+    assert_eq!(entry["id"], replay.id());
+    assert_eq!(entry["error_code"], "BAD_NONCE");
+    assert_eq!(body["data"]["by_reason"]["BAD_NONCE"], 1);
+
+    let resp = test::call_service(&app, get("/metrics").to_request()).await;
+    let metrics = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(metrics.contains("mini_blockchain_rejections_total{reason=\"BAD_NONCE\"} 1"), "{}", metrics);
+}
+
+#[actix_web::test]
+async fn head_shows_enough_to_find_a_fork() {
+    let mut bc = Blockchain::with_prefix("0");
+    while bc.chain.len() < 6 {
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    let (genesis, tip, work) = (bc.chain[0].hash.clone(), bc.chain[5].hash.clone(), bc.total_work());
+    let below: Vec<(usize, String)> = [4, 3, 1].iter().map(|&h| (h, bc.chain[h].hash.clone())).collect();
+    let state = test_state(bc, "api-head");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let (_, body) = call!(app, get("/chain/head"));
+    let head = &body["data"];
+    assert_eq!((head["index"].as_u64(), head["hash"].as_str()), (Some(5), Some(tip.as_str())));
+    assert_eq!(head["genesis_hash"], genesis);
+    assert_eq!(head["total_work"], json!(work));
+    // 1, 2 and 4 below the tip; the deeper ones aren't there yet
+    let recent: Vec<(usize, String)> = head["recent_blocks"].as_array().unwrap().iter()
+        .map(|b| (b["height"].as_u64().unwrap() as usize, b["hash"].as_str().unwrap().to_string()))
+        .collect();
+    assert_eq!(recent, below);
+
+    let (_, body) = call!(app, get("/chain/locator"));
+    let heights: Vec<u64> = body["data"].as_array().unwrap().iter().map(|b| b["height"].as_u64().unwrap()).collect();
+    assert_eq!(heights, [5, 4, 3, 2, 1, 0]);
+}
+
+#[actix_web::test]
+async fn nonce_is_what_to_sign_with_next() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let state = test_state(bc, "api-nonce");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let uri = format!("/nonce/{}", alice.address());
+
+    let (status, body) = call!(app, get(&uri));
+    assert_eq!((status, body["data"].clone()), (200, json!(0)));
+    let txn = alice.sign_transaction(Wallet::new().address(), 1.0, 0.0, 0).unwrap();
+    let (status, _) = call!(app, post("/transaction/raw", json!({"raw": txn.to_hex().unwrap()})));
+    assert_eq!(status, 200);
+    assert_eq!(call!(app, get(&uri)).1["data"], 1);
+
+    // a second copy of the same signed transfer gets nowhere
+    let (status, body) = call!(app, post("/transaction/raw", json!({"raw": txn.to_hex().unwrap()})));
+    assert_eq!((status, body["error_code"].as_str()), (422, Some("ALREADY_IN_MEMPOOL")));
+    let skipped = alice.sign_transaction(Wallet::new().address(), 1.0, 0.0, 5).unwrap();
+    let (status, body) = call!(app, post("/transaction/raw", json!({"raw": skipped.to_hex().unwrap()})));
+    assert_eq!((status, body["error_code"].as_str()), (422, Some("BAD_NONCE")));
+    assert_eq!(call!(app, get(&uri)).1["data"], 1);
+}
+
+#[actix_web::test]
+async fn mempool_lists_pending_transfers_by_fee() {
+    let mut bc = Blockchain::with_prefix("0");
+    let (alice, carol) = (Wallet::new(), Wallet::new());
+    fund(&mut bc, &alice.address());
+    fund(&mut bc, &carol.address());
+    let bob = Wallet::new().address();
+    let cheap = bc.add_transaction(alice.sign_transaction(bob.clone(), 1.5, 0.1, 0).unwrap()).unwrap();
+    let dear = bc.add_transaction(carol.sign_transaction(bob.clone(), 2.0, 0.5, 0).unwrap()).unwrap();
+    let state = test_state(bc, "api-mempool");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let (status, body) = call!(app, get("/mempool"));
+    assert_eq!(status, 200);
+    assert_eq!(body["data"]["count"], 2);
+    let listed = &body["data"]["transactions"];
+    assert_eq!((listed[0]["txid"].as_str(), listed[1]["txid"].as_str()), (Some(dear.as_str()), Some(cheap.as_str())));
+    assert_eq!(listed[1]["from"], alice.address());
+    assert_eq!(listed[1]["to"], bob);
+    assert_eq!((listed[1]["amount"].clone(), listed[1]["fee"].clone()), (json!(1.5), json!(0.1)));
+    assert_eq!(listed[1]["signed"], true);
+
+    let (status, body) = call!(app, get(&format!("/mempool/{}", cheap)));
+    assert_eq!((status, body["data"]["txid"].as_str()), (200, Some(cheap.as_str())));
+    let (status, body) = call!(app, get(&format!("/mempool/{}", "ab".repeat(32))));
+    assert_eq!((status, body["error_code"].as_str()), (404, Some("NOT_FOUND")));
+
+    // mined, it's gone from both
+    state.chain.update(|bc| bc.mine_pending_transactions(Wallet::new().address(), false).unwrap()).await;
+    assert_eq!(call!(app, get("/mempool")).1["data"]["count"], 0);
+    assert_eq!(call!(app, get(&format!("/mempool/{}", cheap))).0, 404);
+}
+
+#[actix_web::test]
+async fn blocks_are_found_by_height_and_by_hash() {
+    let mut bc = Blockchain::with_prefix("0");
+    for _ in 0..3 {
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    let hashes: Vec<String> = bc.chain.iter().map(|b| b.hash.clone()).collect();
+    let state = test_state(bc, "api-blocks");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    for (height, hash) in hashes.iter().enumerate() {
+        let (status, body) = call!(app, get(&format!("/block/{}", height)));
+        assert_eq!((status, body["data"]["hash"].as_str()), (200, Some(hash.as_str())));
+        let (status, body) = call!(app, get(&format!("/block/hash/{}", hash)));
+        assert_eq!((status, body["data"]["index"].as_u64()), (200, Some(height as u64)));
+    }
+    let (status, body) = call!(app, get("/block/4"));
+    assert_eq!((status, body["error_code"].as_str()), (404, Some("NOT_FOUND")));
+    let (status, _) = call!(app, get(&format!("/block/hash/{}", "0".repeat(64))));
+    assert_eq!(status, 404);
+
+    // the hash index drops what a rollback takes off
+    state.chain.update(|bc| bc.rollback(1).map(|_| ())).await.unwrap();
+    assert_eq!(call!(app, get(&format!("/block/hash/{}", hashes[3]))).0, 404);
+    assert_eq!(call!(app, get(&format!("/block/hash/{}", hashes[2]))).0, 200);
+}
+
+#[actix_web::test]
+async fn a_transaction_is_pending_then_confirmed_deeper_and_deeper() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let txid = bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 0).unwrap()).unwrap();
+    let state = test_state(bc, "api-txstatus");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let uri = format!("/transaction/{}", txid);
+
+    let (status, body) = call!(app, get(&uri));
+    assert_eq!(status, 200);
+    assert_eq!((body["data"]["block_index"].clone(), body["data"]["confirmations"].clone()), (Value::Null, json!(0)));
+    assert_eq!(body["data"]["transaction"]["from"], alice.address());
+
+    let mine = || state.chain.update(|bc| bc.mine_pending_transactions(Wallet::new().address(), true).unwrap());
+    mine().await;
+    let height = state.chain.query(|bc| bc.chain.len() - 1).await;
+    let (_, body) = call!(app, get(&uri));
+    assert_eq!((body["data"]["block_index"].clone(), body["data"]["confirmations"].clone()), (json!(height), json!(1)));
+    mine().await;
+    mine().await;
+    assert_eq!(call!(app, get(&uri)).1["data"]["confirmations"], 3);
+
+    // a reloaded chain has rebuilt its txid index
+    let reloaded = state.chain.query(|bc| copy_chain(bc, "api-txstatus-copy")).await;
+    assert_eq!(reloaded.find_transaction(&txid).unwrap().block_index, Some(height));
+
+    let (status, body) = call!(app, get(&format!("/transaction/{}", "ab".repeat(32))));
+    assert_eq!((status, body["error_code"].as_str()), (404, Some("NOT_FOUND")));
+}
+
+#[actix_web::test]
+async fn the_chain_comes_a_page_at_a_time() {
+    let mut bc = Blockchain::with_prefix("0");
+    for _ in 0..59 {
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    let tip = bc.chain.last().unwrap().clone();
+    let state = test_state(bc, "api-paging");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    // the default page is 50 blocks
+    let (status, body) = call!(app, get("/chain"));
+    assert_eq!(status, 200);
+    assert_eq!((body["data"]["total_blocks"].clone(), body["data"]["from"].clone(), body["data"]["limit"].clone()), (json!(60), json!(0), json!(50)));
+    assert_eq!(body["data"]["blocks"].as_array().unwrap().len(), 50);
+
+    let (_, body) = call!(app, get("/chain?from=55&limit=10"));
+    let blocks = body["data"]["blocks"].as_array().unwrap();
+    assert_eq!(blocks.iter().map(|b| b["index"].as_u64().unwrap()).collect::<Vec<_>>(), vec![55, 56, 57, 58, 59]);
+    // past the end is an empty page, and the limit is capped
+    assert_eq!(call!(app, get("/chain?from=100")).1["data"]["blocks"], json!([]));
+    assert_eq!(call!(app, get("/chain?limit=100000")).1["data"]["limit"], 500);
+
+    let (status, body) = call!(app, get("/chain/head"));
+    assert_eq!(status, 200);
+    let head = &body["data"];
+    assert_eq!((head["index"].as_u64(), head["hash"].as_str()), (Some(59), Some(tip.hash.as_str())));
+    assert_eq!((head["timestamp"].as_u64(), head["transaction_count"].as_u64()), (Some(tip.timestamp), Some(1)));
+}
+
+#[actix_web::test]
+async fn address_history_is_newest_first_with_directions() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let (alice, bob) = (Wallet::new(), Wallet::new());
+    let miner = Wallet::new().address();
+    fund(&mut bc, &alice.address());
+    let mut sent = vec![];
+    for (from, to, nonce) in [(&alice, bob.address(), 0), (&alice, alice.address(), 1)] {
+        sent.push(bc.add_transaction(from.sign_transaction(to, 2.0, 0.0, nonce).unwrap()).unwrap());
+        bc.mine_pending_transactions(miner.clone(), false).unwrap();
+    }
+    sent.push(bc.add_transaction(bob.sign_transaction(alice.address(), 0.5, 0.0, 0).unwrap()).unwrap());
+    bc.mine_pending_transactions(miner, false).unwrap();
+    let tip = bc.chain.len() - 1;
+    let state = test_state(bc, "api-history");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let history = |uri: String| {
+        let req = get(&uri).to_request();
+        let app = &app;
+        async move { test::read_body_json::<Value, _>(test::call_service(app, req).await).await["data"].clone() }
+    };
+
+    let all = history(format!("/address/{}/transactions", alice.address())).await;
+    let seen: Vec<(&str, &str, u64)> = all.as_array().unwrap().iter()
+        .map(|e| (e["txid"].as_str().unwrap(), e["direction"].as_str().unwrap(), e["block_index"].as_u64().unwrap()))
+        .collect();
+    // her three transfers, newest first, then the reward that funded her
+    assert_eq!(seen.len(), 4);
+    assert_eq!(&seen[..3], &[(sent[2].as_str(), "in", tip as u64), (sent[1].as_str(), "self", tip as u64 - 1),
+                             (sent[0].as_str(), "out", tip as u64 - 2)]);
+    assert_eq!((seen[3].1, seen[3].2), ("in", 1));
+    assert_eq!((all[0]["amount"].clone(), all[0]["confirmations"].clone()), (json!(0.5), json!(1)));
+    assert_eq!(all[2]["confirmations"], 3);
+
+    let page = history(format!("/address/{}/transactions?limit=1&before_block={}", alice.address(), tip)).await;
+    assert_eq!(page.as_array().unwrap().len(), 1);
+    assert_eq!(page[0]["txid"].as_str(), Some(sent[1].as_str()));
+    let bobs = history(format!("/address/{}/transactions", bob.address())).await;
+    assert_eq!(bobs.as_array().unwrap().iter().map(|e| e["direction"].as_str().unwrap()).collect::<Vec<_>>(), ["out", "in"]);
+    assert_eq!(history(format!("/address/{}/transactions", Wallet::new().address())).await, json!([]));
+}
+
+#[actix_web::test]
+async fn decode_shows_a_raw_transaction_without_sending_it() {
+    let state = test_state(Blockchain::with_prefix("0"), "api-decode");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let (alice, bob) = (Wallet::new(), Wallet::new());
+    let txn = alice.sign_transaction(bob.address(), 2.5, 0.2, 0).unwrap();
+    let raw = txn.to_hex().unwrap();
+
+    let (status, body) = call!(app, post("/transaction/decode", json!({"raw": raw})));
+    assert_eq!(status, 200);
+    let decoded = &body["data"];
+    assert_eq!(decoded["txid"].as_str(), Some(txn.id().as_str()));
+    assert_eq!((decoded["signature_valid"].clone(), decoded["validation_error"].clone()), (json!(true), Value::Null));
+    assert_eq!(decoded["transaction"]["from"].as_str(), Some(alice.address().as_str()));
+    assert_eq!(decoded["transaction"]["fee"], 200);
+    // only looked at, not queued
+    let (_, mempool) = call!(app, get("/mempool"));
+    assert_eq!(mempool["data"]["count"], 0);
+
+    // a scrambled signature still decodes, and says so
+    let mut bytes = hex::decode(&raw).unwrap();
+    *bytes.last_mut().unwrap() ^= 0xff;
+    let (status, body) = call!(app, post("/transaction/decode", json!({"raw": hex::encode(&bytes)})));
+    assert_eq!((status, body["data"]["signature_valid"].as_bool()), (200, Some(false)));
+    assert!(body["data"]["validation_error"].is_string());
+
+    // what isn't a transaction at all is a 400 that says what's wrong with it
+    let mut bad_flag = hex::decode(&raw).unwrap();
+    let flag = bad_flag.len() - 65;
+    bad_flag[flag] = 7;
+    for broken in ["zz".to_string(), raw[..raw.len() - 10].to_string(), hex::encode(bad_flag), String::new()] {
+        let (status, body) = call!(app, post("/transaction/decode", json!({"raw": broken})));
+        assert_eq!((status, body["error_code"].as_str()), (400, Some("MALFORMED_TRANSACTION")), "{:?}", broken);
+        assert!(!body["message"].as_str().unwrap().is_empty());
+    }
+}
+
+#[actix_web::test]
+async fn reward_is_what_the_next_block_pays() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.halving_interval = 2;
+    bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    let state = test_state(bc, "api-reward");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let (status, body) = call!(app, get("/reward"));
+    assert_eq!(status, 200);
+    assert_eq!(body["data"], json!({"next_height": 2, "reward": 25.0, "halving_interval": 2}));
+}
+
+#[actix_web::test]
+async fn supply_reports_issuance_against_the_cap() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.max_supply_nits = Some(bc.reward * 4);
+    bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    let reward = bc.reward as f64 / 1000.0;
+    let state = test_state(bc, "api-supply");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let (status, body) = call!(app, get("/supply"));
+    assert_eq!(status, 200);
+    assert_eq!((body["data"]["circulating"].as_f64(), body["data"]["max_supply"].as_f64()), (Some(reward), Some(reward * 4.0)));
+    assert_eq!(body["data"]["percent_issued"].as_f64(), Some(25.0));
+
+    // without a cap there's nothing to be a percentage of
+    let state = test_state(Blockchain::with_prefix("0"), "api-supply-uncapped");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let (_, body) = call!(app, get("/supply"));
+    assert_eq!((body["data"]["max_supply"].clone(), body["data"]["percent_issued"].clone()), (Value::Null, Value::Null));
+}
+
+#[actix_web::test]
+async fn amounts_can_be_decimal_strings_and_bad_ones_say_why() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let state = test_state(bc, "api-amounts");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let secret = alice.export_secret();
+    let send = |amount: Value, fee: Value| post("/transaction", json!({
+        "from": alice.address(), "to": Wallet::new().address(), "amount": amount, "fee": fee,
+        "private_key_hex": secret.as_str(),
+    }));
+
+    let (status, body) = call!(app, send(json!("2.5"), json!("0.01")));
+    assert_eq!(status, 200, "{}", body);
+    let txid = body["data"].as_str().unwrap().to_string();
+    let (_, body) = call!(app, get(&format!("/transaction/{}", txid)));
+    assert_eq!(body["data"]["transaction"]["amount"], 2500);
+    assert_eq!(body["data"]["transaction"]["fee"], 10);
+
+    for (amount, fee, why) in [
+        (json!(-1.0), json!(0), "negative"), (json!("-1"), json!(0), "negative"),
+        (json!(0.0004), json!(0), "precision"), (json!("1.0001"), json!(0), "precision"),
+        (json!("lots"), json!(0), "not a decimal"), (json!(1e17), json!(0), "too large"),
+        (json!(1.0), json!("-0.5"), "fee"),
+    ] {
+        let (status, body) = call!(app, send(amount.clone(), fee));
+        assert_eq!((status, body["error_code"].as_str()), (400, Some("INVALID_AMOUNT")), "{}", amount);
+        assert!(body["message"].as_str().unwrap().contains(why), "{}: {}", amount, body["message"]);
+    }
+}
+
+#[actix_web::test]
+async fn fee_estimate_falls_back_to_the_relay_minimum() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.min_relay_fee = 7;
+    let state = test_state(bc, "api-fee");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let (status, body) = call!(app, get("/fee/estimate"));
+    assert_eq!(status, 200);
+    assert_eq!(body["data"], json!({"target_blocks": 1, "fee": 0.007, "fee_nits": 7}));
+    let (_, body) = call!(app, get("/fee/estimate?blocks=0"));
+    assert_eq!(body["data"]["target_blocks"], 1);
+    let (_, body) = call!(app, get("/fee/estimate?blocks=6"));
+    assert_eq!((body["data"]["target_blocks"].clone(), body["data"]["fee_nits"].clone()), (json!(6), json!(7)));
+}
+
+#[actix_web::test]
+async fn balance_splits_confirmed_pending_and_spendable() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let (alice, bob, carol) = (Wallet::new(), Wallet::new(), Wallet::new());
+    fund(&mut bc, &alice.address());
+    let funded = bc.get_balance(&alice.address()).unwrap();
+    bc.add_transaction(alice.sign_transaction(bob.address(), 2.0, 0.5, 0).unwrap()).unwrap();
+    // carol has nothing, and her transfer only got in by going round the admission checks
+    bc.mempool.push(carol.sign_transaction(bob.address(), 1.0, 0.0, 0).unwrap());
+    let state = test_state(bc, "api-balance");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let balance = |who: &Wallet| get(&format!("/balance/{}", who.address()));
+
+    let (status, body) = call!(app, balance(&alice));
+    assert_eq!(status, 200);
+    assert_eq!(body["data"], json!({
+        "confirmed": funded, "immature": 0.0, "pending": funded - 2.5, "spendable": funded - 2.5, "overdrawn": false,
+    }));
+    let (_, body) = call!(app, balance(&bob));
+    assert_eq!(body["data"], json!({
+        "confirmed": 0.0, "immature": 0.0, "pending": 3.0, "spendable": 0.0, "overdrawn": false,
+    }));
+    let (_, body) = call!(app, balance(&carol));
+    assert_eq!(body["data"], json!({
+        "confirmed": 0.0, "immature": 0.0, "pending": 0.0, "spendable": 0.0, "overdrawn": true,
+    }));
+
+    let (status, body) = call!(app, get("/balance/not-an-address"));
+    assert_eq!((status, body["error_code"].as_str()), (422, Some("INVALID_ADDRESS")));
+}
+
+#[actix_web::test]
+async fn richlist_is_largest_first_with_ties_by_address() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let reward = bc.reward;
+    let (alice, bob, carol, emptied) = (Wallet::new(), Wallet::new(), Wallet::new(), Wallet::new());
+    for miner in [&carol, &carol, &alice, &bob] {
+        bc.mine_pending_transactions(miner.address(), true).unwrap();
+    }
+    bc.add_transaction(carol.sign_transaction(emptied.address(), 1.0, 0.0, 0).unwrap()).unwrap();
+    bc.add_transaction(carol.sign_transaction(BURN_ADDRESS.to_string(), 1.0, 0.0, 1).unwrap()).unwrap();
+    bc.mine_pending_transactions(carol.address(), false).unwrap();
+    bc.add_transaction(emptied.sign_transaction(carol.address(), 1.0, 0.0, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(carol.address(), false).unwrap();
+
+    // no NETWORK, no burn address, nobody at zero; alice and bob tie
+    let mut tied = [alice.address(), bob.address()];
+    tied.sort();
+    let expected = vec![(carol.address(), 4 * reward - 1000), (tied[0].clone(), reward), (tied[1].clone(), reward)];
+    assert_eq!(bc.top_holders(10), expected);
+    assert_eq!(bc.top_holders(2), expected[..2]);
+    let supply = bc.total_supply() as f64;
+    let state = test_state(bc, "api-richlist");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let (status, body) = call!(app, get("/richlist"));
+    assert_eq!(status, 200);
+    let listed: Vec<&str> = body["data"].as_array().unwrap().iter().map(|h| h["address"].as_str().unwrap()).collect();
+    assert_eq!(listed, expected.iter().map(|(a, _)| a.as_str()).collect::<Vec<_>>());
+    assert_eq!(body["data"][1]["balance"].as_f64(), Some(reward as f64 / 1000.0));
+    assert_eq!(body["data"][1]["percent_of_supply"].as_f64(), Some(reward as f64 / supply * 100.0));
+    let (_, body) = call!(app, get("/richlist?limit=1"));
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert_eq!(call!(app, get("/richlist")).1, call!(app, get("/richlist")).1);
+}
+
+#[actix_web::test]
+async fn chain_replace_adopts_a_heavier_chain_and_refuses_the_rest() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.retarget_interval = 0;
+    bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    let mut theirs = copy_chain(&bc, "api-replace-theirs.json");
+    for _ in 0..3 {
+        theirs.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    let state = test_state(bc, "api-replace");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let offer = |chain: &[Block]| post("/chain/replace", serde_json::to_value(chain).unwrap());
+
+    let mut tampered = theirs.chain.clone();
+    tampered[3].nonce += 1;
+    let (status, body) = call!(app, offer(&tampered));
+    assert_eq!((status, body["error_code"].as_str()), (422, Some("HASH_MISMATCH")));
+
+    let (status, body) = call!(app, offer(&theirs.chain));
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!((body["data"]["fork_height"].clone(), body["data"]["new_height"].clone()), (json!(2), json!(4)));
+    let (_, head) = call!(app, get("/chain/head"));
+    assert_eq!(head["data"]["hash"], theirs.chain[4].hash);
+
+    let (status, body) = call!(app, offer(&theirs.chain));
+    assert_eq!((status, body["error_code"].as_str()), (409, Some("CHAIN_NOT_BETTER")));
+    let mut stranger = Blockchain::with_prefix("00");
+    while stranger.chain.len() < 8 {
+        stranger.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    let (status, body) = call!(app, offer(&stranger.chain));
+    assert_eq!((status, body["error_code"].as_str()), (422, Some("GENESIS_MISMATCH")));
+}
+
+#[actix_web::test]
+async fn rollback_pops_the_tip_and_requeues_its_transfers() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let (alice, bob) = (Wallet::new(), Wallet::new());
+    fund(&mut bc, &alice.address());
+    let txid = bc.add_transaction(alice.sign_transaction(bob.address(), 2.0, 0.0, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(Wallet::new().address(), false).unwrap();
+    bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    let height = bc.chain.len() - 1;
+    let popped: Vec<String> = bc.chain[height - 1..].iter().map(|b| b.hash.clone()).collect();
+    let state = test_state(bc, "api-rollback");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    for blocks in [0, height + 1] {
+        let (status, body) = call!(app, post("/chain/rollback", json!({"blocks": blocks})));
+        assert_eq!((status, body["error_code"].as_str()), (400, Some("INVALID_ROLLBACK")), "{}", blocks);
+    }
+    let (status, body) = call!(app, post("/chain/rollback", json!({"blocks": 2})));
+    assert_eq!(status, 200);
+    assert_eq!(body["data"], json!(popped));
+
+    // straight away the balance is the shorter chain's, and the transfer is pending again
+    let (_, body) = call!(app, get(&format!("/balance/{}", bob.address())));
+    assert_eq!((body["data"]["confirmed"].as_f64(), body["data"]["pending"].as_f64()), (Some(0.0), Some(2.0)));
+    let (_, body) = call!(app, get(&format!("/transaction/{}", txid)));
+    assert_eq!(body["data"]["confirmations"], 0);
+    let (_, head) = call!(app, get("/chain/head"));
+    assert_eq!(head["data"]["index"], height - 2);
+
+    // genesis stays
+    let (status, _) = call!(app, post("/chain/rollback", json!({"blocks": height - 2})));
+    assert_eq!(status, 200);
+    let (status, _) = call!(app, post("/chain/rollback", json!({"blocks": 1})));
+    assert_eq!(status, 400);
+}
+
+#[actix_web::test]
+async fn a_new_wallet_comes_with_its_key_once() {
+    let state = test_state(Blockchain::with_prefix("0"), "api-new-wallet");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let (status, body) = call!(app, get("/wallet/new"));
+    assert_eq!(status, 200);
+    let (address, secret) = (body["data"]["address"].as_str().unwrap(), body["data"]["private_key_hex"].as_str().unwrap());
+    assert_eq!(Wallet::from_private_key_hex(secret).unwrap().address(), address);
+    let (_, again) = call!(app, get("/wallet/new"));
+    assert_ne!(again["data"]["address"].as_str(), Some(address));
+}
+
+#[actix_web::test]
+async fn burned_tokens_leave_the_effective_supply() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let funded = bc.get_balance(&alice.address()).unwrap();
+    let issued = bc.total_supply() as f64 / 1000.0;
+    // nobody holds the burn address's key, so nothing signed from it gets in
+    let mut from_burn = Transaction::new(BURN_ADDRESS.to_string(), alice.address(), 1.0).unwrap();
+    from_burn.signature_hex = Some("00".repeat(64));
+    assert!(matches!(bc.add_transaction(from_burn), Err(ChainError::InvalidAddress(why)) if why.contains("burn address")));
+    let state = test_state(bc, "api-burn");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let (status, body) = call!(app, post("/burn", json!({
+        "from": alice.address(), "amount": "3", "private_key_hex": alice.export_secret().as_str(),
+    })));
+    assert_eq!(status, 200, "{}", body);
+    let (_, pending) = call!(app, get(&format!("/transaction/{}", body["data"].as_str().unwrap())));
+    assert_eq!(pending["data"]["transaction"]["to"], BURN_ADDRESS);
+    state.chain.update(|bc| bc.mine_pending_transactions(Wallet::new().address(), false).unwrap()).await;
+
+    let (_, body) = call!(app, get(&format!("/balance/{}", alice.address())));
+    assert_eq!(body["data"]["confirmed"].as_f64(), Some(funded - 3.0));
+    let (_, body) = call!(app, get("/supply"));
+    let reward = state.chain.query(|bc| bc.reward).await as f64 / 1000.0;
+    assert_eq!(body["data"]["total_burned"].as_f64(), Some(3.0));
+    assert_eq!(body["data"]["effective_supply"].as_f64(), Some(issued + reward - 3.0));
+    let (_, body) = call!(app, get("/stats"));
+    assert_eq!(body["data"]["total_burned"].as_f64(), Some(3.0));
+}
+
+#[actix_web::test]
+async fn memos_come_through_the_submit_endpoints() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let state = test_state(bc, "api-memo");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let secret = alice.export_secret();
+    let memo_of = |txid: &Value| get(&format!("/transaction/{}", txid.as_str().unwrap()));
+
+    let (status, sent) = call!(app, post("/transaction", json!({
+        "from": alice.address(), "to": Wallet::new().address(), "amount": 1, "fee": "0.01",
+        "memo": "invoice 42", "private_key_hex": secret.as_str(),
+    })));
+    assert_eq!(status, 200, "{}", sent);
+    assert_eq!(call!(app, memo_of(&sent["data"])).1["data"]["transaction"]["memo"], "invoice 42");
+    let (status, burned) = call!(app, post("/burn", json!({
+        "from": alice.address(), "amount": 1, "fee": "0.01", "memo": "gone", "private_key_hex": secret.as_str(),
+    })));
+    assert_eq!(status, 200, "{}", burned);
+    assert_eq!(call!(app, memo_of(&burned["data"])).1["data"]["transaction"]["memo"], "gone");
+
+    // the memo's bytes have to be paid for
+    let (status, body) = call!(app, post("/transaction", json!({
+        "from": alice.address(), "to": Wallet::new().address(), "amount": 1,
+        "memo": "invoice 43", "private_key_hex": secret.as_str(),
+    })));
+    assert_eq!((status, body["error_code"].as_str()), (422, Some("MEMO_FEE_TOO_LOW")));
+}
+
+#[actix_web::test]
+async fn transaction_endpoint_takes_a_list_of_outputs() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let state = test_state(bc, "api-outputs");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let (bob, carol) = (Wallet::new().address(), Wallet::new().address());
+    let secret = alice.export_secret();
+    let send = |extra: Value| {
+        let mut body = json!({"from": alice.address(), "private_key_hex": secret.as_str()});
+        body.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        post("/transaction", body)
+    };
+
+    let (status, body) = call!(app, send(json!({"outputs": [{"to": bob, "amount": 1}, {"to": carol, "amount": "2.5"}]})));
+    assert_eq!(status, 200, "{}", body);
+    let (_, body) = call!(app, get(&format!("/transaction/{}", body["data"].as_str().unwrap())));
+    assert_eq!(body["data"]["transaction"]["outputs"], json!([{"to": bob, "amount": 1000}, {"to": carol, "amount": 2500}]));
+
+    for bad in [
+        json!({"to": bob, "amount": 1, "outputs": [{"to": carol, "amount": 1}]}),
+        json!({"to": bob}),
+        json!({}),
+    ] {
+        let (status, body) = call!(app, send(bad.clone()));
+        assert_eq!((status, body["error_code"].as_str()), (400, Some("INVALID_REQUEST")), "{}", bad);
+    }
+    let (status, body) = call!(app, send(json!({"outputs": []})));
+    assert_eq!((status, body["error_code"].as_str()), (422, Some("NO_OUTPUTS")));
+}
+
+#[actix_web::test]
+async fn a_block_on_an_old_tip_is_stale() {
+    let state = test_state(Blockchain::with_prefix("0"), "api-stale");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let uri = format!("/mining/template?miner_address={}&allow_empty=true", Wallet::new().address());
+    let template = call!(app, get(&uri)).1["data"].clone();
+    let block = block_from_template(&template);
+    // someone else's block lands first
+    state.chain.update(|bc| bc.mine_pending_transactions(Wallet::new().address(), true).unwrap()).await;
+
+    let (status, body) = call!(app, TestRequest::post().uri("/block").set_json(&block));
+    assert_eq!((status, body["error_code"].as_str()), (409, Some("STALE_TIP")));
+}
+
+#[actix_web::test]
+async fn the_openapi_spec_covers_every_route() {
+    let state = test_state(Blockchain::with_prefix("0"), "api-openapi");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let resp = test::call_service(&app, get("/openapi.json").to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let spec: Value = test::read_body_json(resp).await;
+
+    // every route server.rs registers, bar the pages that are for people
+    let server = include_str!("../src/server.rs");
+    let mut routes_seen = 0;
+    for line in server.lines().map(str::trim).filter(|l| l.starts_with(".route(\"")) {
+        let path = line.split('"').nth(1).unwrap();
+        let method = line.split("web::").nth(1).unwrap().split('(').next().unwrap();
+        if ["/", "/openapi.json", "/docs"].contains(&path) {
+            continue;
+        }
+        routes_seen += 1;
+        let operation = &spec["paths"][path][method];
+        assert!(operation.is_object(), "{} {} isn't in the spec", method, path);
+        let answers = operation["responses"].as_object().unwrap();
+        assert!(answers.keys().any(|code| code.starts_with('2') || code.starts_with('3')), "{} {} never succeeds", method, path);
+        assert!(answers.keys().all(|code| code.starts_with('2') || code.starts_with('3') || answers[code]["content"].is_object()),
+            "{} {} has an error without a body", method, path);
+    }
+    assert!(routes_seen > 50, "{}", routes_seen);
+
+    // the envelope and the types in it are schemas, not prose
+    let schemas = &spec["components"]["schemas"];
+    for name in ["ApiError", "Block", "Transaction", "TransactionRequest", "HistoryEntry", "ChainStats"] {
+        assert!(schemas[name].is_object(), "no {} schema", name);
+    }
+    assert!(spec["paths"]["/transaction/{txid}"]["get"]["parameters"][0]["name"] == "txid");
+    assert_eq!(spec["paths"]["/mine"]["post"]["security"][0]["api_key"], json!([]));
+
+    let resp = test::call_service(&app, get("/docs").to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert!(String::from_utf8(test::read_body(resp).await.to_vec()).unwrap().contains("/openapi.json"));
+}
+
+#[actix_web::test]
+async fn the_auto_miner_mines_until_stopped() {
+    let state = test_state(Blockchain::with_prefix("0"), "api-auto-miner");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+    let miner = Wallet::new().address();
+
+    let (status, body) = call!(app, post("/miner/start", json!({"address": "nobody"})));
+    assert_eq!((status, body["error_code"].as_str()), (400, Some("INVALID_ADDRESS")));
+    let (status, body) = call!(app, post("/miner/start", json!({"address": miner, "interval_secs": 0})));
+    assert_eq!((status, body["error_code"].as_str()), (400, Some("INVALID_INTERVAL")));
+
+    // an empty mempool gives it nothing to do
+    let (status, body) = call!(app, post("/miner/start", json!({"address": miner, "interval_secs": 1})));
+    assert_eq!((status, body["data"]["status"].as_str()), (200, Some("running")));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let (_, body) = call!(app, get("/miner/status"));
+    assert_eq!((body["data"]["blocks_mined"].as_u64(), body["data"]["address"].as_str()), (Some(0), Some(miner.as_str())));
+    let (status, body) = call!(app, post("/miner/start", json!({"address": Wallet::new().address()})));
+    assert_eq!((status, body["error_code"].as_str()), (409, Some("MINER_RUNNING")));
+    call!(app, post("/miner/stop", json!({})));
+
+    // with allow_empty it mines every round
+    let (status, _) = call!(app, post("/miner/start", json!({"address": miner, "interval_secs": 1, "allow_empty": true})));
+    assert_eq!(status, 200);
+    let mut mined = 0;
+    for _ in 0..100 {
+        let (_, body) = call!(app, get("/miner/status"));
+        mined = body["data"]["blocks_mined"].as_u64().unwrap();
+        if mined > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(mined > 0, "the miner never mined a block");
+
+    let (status, body) = call!(app, post("/miner/stop", json!({})));
+    assert_eq!((status, body["data"]["status"].as_str()), (200, Some("idle")));
+    let height = state.chain.query(|bc| bc.chain.len()).await;
+    assert!(state.chain.query(move |bc| bc.get_balance_nits(&miner).unwrap()).await > 0);
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(state.chain.query(|bc| bc.chain.len()).await, height, "mined after it was stopped");
+    // stopping an idle miner is fine
+    let (status, _) = call!(app, post("/miner/stop", json!({})));
+    assert_eq!(status, 200);
+}
+
+#[actix_web::test]
+async fn the_explorer_page_and_its_search() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    let pending = alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 1).unwrap();
+    bc.add_transaction(pending.clone()).unwrap();
+    let (hash, mined) = (bc.chain[1].hash.clone(), bc.chain[2].transactions[0].id());
+    let state = test_state(bc, "api-explorer");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let resp = test::call_service(&app, get("/").to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let header = |name: &str| resp.headers().get(name).unwrap().to_str().unwrap().to_string();
+    assert!(header("content-type").starts_with("text/html"));
+    assert_eq!(header("cache-control"), "no-cache");
+    let page = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(page.contains("/search?q=") && page.contains("/chain/head"));
+
+    for (q, kind, location) in [
+        ("1".to_string(),                  "block",       "/block/1".to_string()),
+        (hash.to_uppercase(),              "block",       format!("/block/hash/{}", hash)),
+        (mined.clone(),                    "transaction", format!("/transaction/{}", mined)),
+        (pending.id(),                     "transaction", format!("/transaction/{}", pending.id())),
+        (format!(" {} ", alice.address()), "address",     format!("/address/{}/transactions", alice.address())),
+    ] {
+        let resp = test::call_service(&app, get(&format!("/search?q={}", q.replace(' ', "%20"))).to_request()).await;
+        assert_eq!(resp.status(), 303, "{}", q);
+        assert_eq!(resp.headers().get("location").unwrap(), location.as_str());
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!((body["data"]["kind"].as_str(), body["data"]["location"].as_str()), (Some(kind), Some(location.as_str())));
+        // and the redirect lands somewhere that answers
+        let (status, _) = call!(app, get(&location));
+        assert_eq!(status, 200, "{}", location);
+    }
+
+    let (status, body) = call!(app, get("/search?q=%20"));
+    assert_eq!((status, body["error_code"].as_str()), (400, Some("EMPTY_QUERY")));
+    let (status, body) = call!(app, get("/search?q=999"));
+    assert_eq!((status, body["error_code"].as_str()), (404, Some("NOT_FOUND")));
+}
+
+#[actix_web::test]
+async fn block_stats_chart_the_last_blocks() {
+    let mut bc = Blockchain::with_prefix("0");
+    for _ in 0..4 {
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    let state = test_state(bc, "api-block-stats");
+    let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+    let (status, body) = call!(app, get("/stats/blocks?last=2"));
+    assert_eq!(status, 200);
+    let series = body["data"].as_array().unwrap();
+    assert_eq!(series.iter().map(|t| t["index"].as_u64().unwrap()).collect::<Vec<_>>(), [3, 4]);
+    for field in ["timestamp", "secs_since_previous", "clamped", "difficulty", "transaction_count", "nonce"] {
+        assert!(!series[1][field].is_null(), "{}", field);
+    }
+    // the whole chain, from genesis, when it's shorter than asked for
+    let (_, body) = call!(app, get("/stats/blocks"));
+    let series = body["data"].as_array().unwrap();
+    assert_eq!(series.len(), 5);
+    assert!(series[0]["secs_since_previous"].is_null());
+}
@@ -0,0 +1,394 @@
+mod common;
+
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use mini_blockchain::block::Block;
+use mini_blockchain::blockchain::{Blockchain, MIN_DIFFICULTY_BITS, MIN_PRUNE_DEPTH, STATS_WINDOW};
+use mini_blockchain::clock::{FixedClock, SteppingClock};
+use mini_blockchain::error::ChainError;
+use mini_blockchain::transaction::Transaction;
+use mini_blockchain::util::short;
+use mini_blockchain::wallet::Wallet;
+
+use common::{copy_chain, fund, temp_path};
+
+#[test]
+fn mine_confirm_and_reload() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    fund(&mut bc, &alice.address());
+    for nonce in 0..3 {
+        bc.add_transaction(alice.sign_transaction(bob.address(), 1.5, 0.1, nonce).unwrap()).unwrap();
+    }
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    assert!(bc.mempool.is_empty());
+    assert_eq!(bc.chain.last().unwrap().transactions.len(), 4);
+    assert_eq!(bc.get_balance(&bob.address()).unwrap(), 4.5);
+    bc.validate().unwrap();
+
+    let path = temp_path("mine-flow.json");
+    bc.save_to_file(&path).unwrap();
+    let loaded = Blockchain::load_from_file(&path).unwrap();
+    assert_eq!(loaded.chain.last().unwrap().hash, bc.chain.last().unwrap().hash);
+    assert_eq!(loaded.get_balance(&bob.address()).unwrap(), 4.5);
+    assert_eq!(loaded.next_nonce(&alice.address()), 3);
+    let _ = std::fs::remove_file(&path);
+}
+
+/// A chain whose tip re-includes a transaction an earlier block already confirmed.
+fn chain_with_replay() -> Vec<Block> {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.retarget_interval = 0;
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    fund(&mut bc, &alice.address());
+    bc.add_transaction(alice.sign_transaction(bob.address(), 1.0, 0.0, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    let tip = bc.chain.last().unwrap().clone();
+
+    let mut replay = Block::new(tip.index + 1, tip.transactions.clone(), tip.hash.clone(),
+        alice.address(), tip.timestamp + 1);
+    replay.mine(tip.difficulty).unwrap();
+    let mut blocks = bc.chain.clone();
+    blocks.push(replay);
+    blocks
+}
+
+fn is_replay(e: &ChainError) -> bool {
+    matches!(e, ChainError::InvalidTransaction { source, .. }
+        if matches!(**source, ChainError::AlreadyConfirmed { .. } | ChainError::BadNonce { .. }))
+}
+
+#[test]
+fn whole_chain_checks_refuse_a_replayed_transaction() {
+    let blocks = chain_with_replay();
+    let honest = blocks[..blocks.len() - 1].to_vec();
+
+    let err = Blockchain::from_blocks(blocks.clone()).err().unwrap();
+    assert!(err.contains("already confirmed"), "{}", err);
+
+    let mut bc = Blockchain::from_blocks(honest.clone()).unwrap();
+    let err = bc.import_chain(blocks.clone(), true).err().unwrap();
+    assert!(is_replay(&err), "{}", err);
+    assert_eq!(bc.chain.len(), honest.len());
+
+    let err = bc.replace_chain(blocks.clone()).err().unwrap();
+    assert!(is_replay(&err), "{}", err);
+
+    // a chain file someone edited by hand
+    let path = temp_path("replayed-chain.json");
+    bc.chain = blocks;
+    bc.save_to_file(&path).unwrap();
+    assert!(Blockchain::load_from_file(&path).is_err());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn malformed_addresses_get_an_error_not_a_zero() {
+    let bc = Blockchain::with_prefix("0");
+    for address in ["", "abcde", &"g".repeat(64), &"ab".repeat(33), "é"] {
+        assert!(matches!(bc.get_balance(address), Err(ChainError::InvalidAddress(_))), "{:?}", address);
+    }
+    assert_eq!(bc.get_balance(&Wallet::new().address()).unwrap(), 0.0);
+    assert_eq!(bc.get_balance("NETWORK").unwrap(), 0.0);
+}
+
+#[test]
+fn short_strings_shorten_safely() {
+    assert_eq!(short("", 12), "");
+    assert_eq!(short("abcde", 12), "abcde");
+    assert_eq!(short("abcdef", 3), "abc");
+    // by character, never through the middle of one
+    assert_eq!(short("ééééé", 2), "éé");
+
+    let mut txn = Transaction::new_nits("abc".into(), "de".into(), 1);
+    txn.signature_hex = Some("f".into());
+    assert!(txn.display().contains("abc"));
+    assert!(txn.validate().is_err());
+    assert!(txn.sign(&Wallet::new()).is_err());
+}
+
+#[test]
+fn balance_index_matches_a_full_replay() {
+    let mut rng = StdRng::seed_from_u64(35);
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    // blocks come much faster than the target here; keep the difficulty where it is
+    bc.retarget_interval = u32::MAX;
+    let wallets: Vec<Wallet> = (0..20).map(|_| Wallet::new()).collect();
+    for w in &wallets {
+        fund(&mut bc, &w.address());
+    }
+
+    let mut sent = 0;
+    while sent < 500 {
+        let from = &wallets[rng.gen_range(0..20)];
+        let to = wallets[rng.gen_range(0..20)].address();
+        let amount = rng.gen_range(1..3000) as f64 / 1000.0;
+        let txn = from.sign_transaction(to, amount, 0.001, bc.next_nonce(&from.address())).unwrap();
+        // some overdraw what's left after their pending ones; that's fine
+        if bc.add_transaction(txn).is_ok() {
+            sent += 1;
+        }
+        if rng.gen_bool(0.05) || bc.mempool.len() >= 100 {
+            let miner = wallets[rng.gen_range(0..20)].address();
+            bc.mine_pending_transactions(miner, true).unwrap();
+        }
+    }
+    while !bc.mempool.is_empty() {
+        bc.mine_pending_transactions(wallets[0].address(), false).unwrap();
+    }
+
+    let replayed = bc.recompute_balances().unwrap();
+    let reloaded = copy_chain(&bc, "balance-index");
+    for w in &wallets {
+        let address = w.address();
+        let indexed = bc.get_balance_nits(&address).unwrap();
+        assert_eq!(indexed, replayed[&address]);
+        assert_eq!(reloaded.get_balance_nits(&address).unwrap(), indexed);
+    }
+}
+
+const START: u64 = 1_700_000_000;
+
+// eleven blocks by the same miner, from START, against a clock stepping `step` seconds a read
+fn stepping_chain(step: u64) -> Blockchain {
+    let mut bc = Blockchain::with_clock(8, Arc::new(SteppingClock::new(START, step)));
+    while bc.chain.len() < 11 {
+        bc.mine_pending_transactions("ab".repeat(32), true).unwrap();
+    }
+    bc
+}
+
+#[test]
+fn same_clock_same_chain() {
+    let hashes = |bc: &Blockchain| bc.chain.iter().map(|b| b.hash.clone()).collect::<Vec<_>>();
+    assert_eq!(hashes(&stepping_chain(10)), hashes(&stepping_chain(10)));
+    assert_ne!(hashes(&stepping_chain(10)), hashes(&stepping_chain(11)));
+
+    let at = |secs| Blockchain::with_clock(8, Arc::new(FixedClock(secs))).chain[0].clone();
+    assert_eq!(at(START).timestamp, START);
+    assert_eq!(at(START).hash, at(START).hash);
+    assert_ne!(at(START).hash, at(START + 1).hash);
+}
+
+#[test]
+fn retargeting_follows_the_block_times() {
+    // the target is 10s, checked every 10 blocks, and moves at most 2 bits at a
+    // time; #10 is retargeted on how long #1..=#9 took, against 90s
+    for (step, bits) in [(1, 10), (5, 8), (100, 6)] {
+        let bc = stepping_chain(step);
+        let took = bc.chain[9].timestamp - bc.chain[0].timestamp;
+        assert_eq!(bc.chain[9].difficulty, 8);
+        assert_eq!(bc.chain[10].difficulty, bits, "#1..=#9 took {}s", took);
+        assert_eq!(stepping_chain(step).chain[10].hash, bc.chain[10].hash);
+    }
+}
+
+#[test]
+fn one_tampered_block_only_costs_its_own_balances() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let (alice, bob, carol) = (Wallet::new(), Wallet::new().address(), Wallet::new().address());
+    fund(&mut bc, &alice.address());
+    bc.add_transaction(alice.sign_transaction(bob.clone(), 5.0, 0.0, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(carol.clone(), false).unwrap();
+    bc.mine_pending_transactions(carol.clone(), true).unwrap();
+    assert_eq!(bc.get_balance_strict(&bob).unwrap(), 5.0);
+
+    // bump the transfer in block #2 and don't bother re-mining
+    bc.chain[2].transactions[0].outputs[0].amount *= 8;
+    // below the checkpoint, so it takes a full pass to notice; after that the
+    // strict lookup refuses, for everyone
+    bc.validate_full().unwrap_err();
+    let err = bc.get_balance_strict(&carol).unwrap_err().to_string();
+    assert!(err.starts_with("Block #2"), "{}", err);
+    // the plain lookup still answers, from the index as it was
+    assert_eq!(bc.get_balance(&alice.address()).unwrap(), 45.0);
+    assert_eq!(bc.get_balance(&carol).unwrap(), 100.0);
+
+    // reindexing (here, through a rollback) leaves the broken block out
+    bc.rollback(1).unwrap();
+    assert_eq!(bc.get_balance(&alice.address()).unwrap(), 50.0);
+    assert_eq!(bc.get_balance(&bob).unwrap(), 0.0);
+    assert_eq!(bc.get_balance(&carol).unwrap(), 0.0);
+    assert!(bc.get_balance_strict(&carol).is_err());
+}
+
+#[test]
+fn address_stats_add_up() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    let (bob, carol) = (Wallet::new().address(), Wallet::new().address());
+    fund(&mut bc, &alice.address());
+    bc.add_transaction(alice.sign_transaction(bob.clone(), 5.0, 0.5, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(carol.clone(), false).unwrap();
+    // to herself: only the fee leaves
+    bc.add_transaction(alice.sign_transaction(alice.address(), 1.0, 0.1, 1).unwrap()).unwrap();
+    bc.mine_pending_transactions(bob.clone(), false).unwrap();
+
+    let stats = |bc: &Blockchain, address: &str| bc.address_stats(address).unwrap();
+    let alice_stats = stats(&bc, &alice.address());
+    assert_eq!((alice_stats.total_received, alice_stats.total_sent, alice_stats.total_fees), (50.0, 5.0, 0.6));
+    assert_eq!((alice_stats.first_seen_block, alice_stats.last_active_block), (Some(1), Some(3)));
+    assert_eq!(alice_stats.transaction_count, 3);
+    let bob_stats = stats(&bc, &bob);
+    assert_eq!((bob_stats.total_received, bob_stats.transaction_count), (55.1, 2));
+    assert_eq!((bob_stats.first_seen_block, bob_stats.last_active_block), (Some(2), Some(3)));
+    let carol_stats = stats(&bc, &carol);
+    assert_eq!((carol_stats.total_received, carol_stats.blocks_mined, carol_stats.has_mined), (50.5, 1, true));
+    for address in [alice.address(), bob.clone(), carol.clone()] {
+        let s = stats(&bc, &address);
+        assert!((s.total_received - s.total_sent - s.total_fees - bc.get_balance(&address).unwrap()).abs() < 1e-9);
+    }
+
+    // never on chain: zeroes, not an error
+    let unseen = stats(&bc, &Wallet::new().address());
+    assert_eq!((unseen.total_received, unseen.transaction_count, unseen.has_mined), (0.0, 0, false));
+    assert_eq!((unseen.first_seen_block, unseen.last_active_block), (None, None));
+    assert!(matches!(bc.address_stats("zz"), Err(ChainError::InvalidAddress(_))));
+
+    // a reload rebuilds the same numbers from scratch
+    let json = |bc: &Blockchain| [alice.address(), bob.clone(), carol.clone()]
+        .map(|address| serde_json::to_value(stats(bc, &address)).unwrap());
+    assert_eq!(json(&copy_chain(&bc, "address-stats")), json(&bc));
+}
+
+#[test]
+fn a_tampered_chain_file_is_refused() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.5, 0.1, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    let path = temp_path("tampered.json");
+    bc.save_to_file(&path).unwrap();
+
+    let mut file: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let tip = bc.chain.len() - 1;
+    let transfer = &mut file["chain"][tip]["transactions"][0];
+    // signatures go to disk as hex
+    assert_eq!(transfer["signature_hex"].as_str().unwrap().len(), 128);
+    transfer["amount"] = serde_json::json!(1_000_000);
+    std::fs::write(&path, serde_json::to_string(&file).unwrap()).unwrap();
+
+    let err = Blockchain::load_from_file(&path).err().unwrap();
+    assert!(err.starts_with("Refusing to load"), "{}", err);
+    assert!(err.contains(&format!("Block #{}", tip)), "{}", err);
+    std::fs::write(&path, "{\"chain\": ").unwrap();
+    assert!(Blockchain::load_from_file(&path).err().unwrap().contains("not a valid chain file"));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn each_block_has_to_meet_the_difficulty_in_force_for_it() {
+    // far slower than the target, already at the floor: it stays there
+    let mut slow = Blockchain::with_clock(MIN_DIFFICULTY_BITS, Arc::new(SteppingClock::new(START, 1_000)));
+    while slow.chain.len() < 11 {
+        slow.mine_pending_transactions("ab".repeat(32), true).unwrap();
+    }
+    assert!(slow.chain.iter().all(|b| b.difficulty == MIN_DIFFICULTY_BITS));
+
+    // #10 was retargeted up; the same block at the old difficulty is refused
+    let mut bc = stepping_chain(1);
+    assert_eq!(bc.chain[10].difficulty, 10);
+    let mut easy = bc.chain[10].clone();
+    easy.mine(8).unwrap();
+    bc.chain[10] = easy;
+    assert_eq!(bc.validate_full(), Err(ChainError::WrongDifficulty { index: 10, found: 8, expected: 10 }));
+    // and with the interval off nothing moves
+    let mut flat = Blockchain::with_clock(8, Arc::new(SteppingClock::new(START, 1)));
+    flat.retarget_interval = 0;
+    while flat.chain.len() < 11 {
+        flat.mine_pending_transactions("ab".repeat(32), true).unwrap();
+    }
+    assert!(flat.chain.iter().all(|b| b.difficulty == 8));
+}
+
+#[test]
+fn stats_add_up_the_chain() {
+    let mut bc = Blockchain::with_clock(4, Arc::new(SteppingClock::new(START, 10)));
+    bc.retarget_interval = u32::MAX;
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    bc.mine_pending_transactions(alice.address(), true).unwrap();
+    for nonce in 0..3 {
+        bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, nonce).unwrap()).unwrap();
+        bc.mine_pending_transactions(alice.address(), false).unwrap();
+    }
+    while bc.chain.len() < STATS_WINDOW + 5 {
+        bc.mine_pending_transactions(alice.address(), true).unwrap();
+    }
+    bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 3).unwrap()).unwrap();
+
+    let mined = bc.chain.len() - 1;
+    let stats = bc.stats();
+    assert_eq!((stats.block_count, stats.total_transactions, stats.empty_blocks), (mined + 1, 3, mined as u64 - 3));
+    assert_eq!(stats.total_fees, 0.3);
+    assert_eq!(stats.total_issued, (bc.reward * mined as u64) as f64 / 1000.0);
+    assert_eq!(stats.avg_transactions_per_block, 3.0 / mined as f64);
+    assert_eq!((stats.difficulty, stats.mempool_size), (4, 1));
+    // only the last STATS_WINDOW blocks count towards the interval
+    let window = &bc.chain[bc.chain.len() - STATS_WINDOW..];
+    let interval = (window[STATS_WINDOW - 1].timestamp - window[0].timestamp) as f64 / (STATS_WINDOW - 1) as f64;
+    assert_eq!(stats.avg_block_interval_secs, Some(interval));
+    assert!(stats.hashrate_measured);
+
+    // blocks that came from elsewhere only leave the difficulty to go on
+    for block in bc.chain.iter_mut() {
+        block.effort = None;
+    }
+    let stats = bc.stats();
+    assert!(!stats.hashrate_measured);
+    assert_eq!(stats.estimated_hashrate, Some(16.0 / interval));
+
+    // a lone genesis has no interval to speak of
+    let fresh = Blockchain::with_prefix("0").stats();
+    assert_eq!((fresh.block_count, fresh.avg_block_interval_secs, fresh.estimated_hashrate), (1, None, None));
+}
+
+#[test]
+fn block_times_come_from_the_headers() {
+    let mut bc = Blockchain::with_clock(4, Arc::new(SteppingClock::new(START, 10)));
+    bc.retarget_interval = u32::MAX;
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    bc.mine_pending_transactions(alice.address(), true).unwrap();
+    bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    while bc.chain.len() < MIN_PRUNE_DEPTH + 8 {
+        bc.mine_pending_transactions(alice.address(), true).unwrap();
+    }
+
+    // oldest first, genesis with no gap
+    let all = bc.block_time_series(usize::MAX);
+    assert_eq!(all.len(), bc.chain.len());
+    assert_eq!((all[0].secs_since_previous, all[0].clamped), (None, false));
+    for (timing, pair) in all[1..].iter().zip(bc.chain.windows(2)) {
+        assert_eq!(timing.secs_since_previous, Some(pair[1].timestamp - pair[0].timestamp));
+        assert_eq!((timing.index, timing.difficulty, timing.nonce), (pair[1].index, pair[1].difficulty, pair[1].nonce));
+        assert!(!timing.clamped);
+    }
+    assert_eq!(all[2].transaction_count, Some(2));
+    let last = bc.block_time_series(3);
+    assert_eq!(last.iter().map(|t| t.index as usize).collect::<Vec<_>>(), (bc.chain.len() - 3..bc.chain.len()).collect::<Vec<_>>());
+
+    // below the prune point only the transaction count is gone
+    bc.prune_below(4).unwrap();
+    let pruned = bc.block_time_series(usize::MAX);
+    assert_eq!(pruned.len(), bc.chain.len());
+    assert_eq!((pruned[2].transaction_count, pruned[2].secs_since_previous), (None, all[2].secs_since_previous));
+    assert_eq!(pruned[4].transaction_count, Some(1));
+
+    // a block stamped before its parent counts as no time at all
+    let stamp = bc.chain[6].timestamp;
+    bc.chain[7].timestamp = stamp - 5;
+    let skewed = bc.block_time_series(usize::MAX);
+    assert_eq!((skewed[7].secs_since_previous, skewed[7].clamped), (Some(0), true));
+    assert_eq!((skewed[8].secs_since_previous, skewed[8].clamped), (Some(bc.chain[8].timestamp - stamp + 5), false));
+}
@@ -0,0 +1,81 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::web;
+use mini_blockchain::actor::ChainHandle;
+use mini_blockchain::api::AppState;
+use mini_blockchain::audit::RejectionLog;
+use mini_blockchain::auth::ApiKey;
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::gossip::Gossip;
+use mini_blockchain::idempotency::IdempotencyStore;
+use mini_blockchain::miner::AutoMiner;
+use mini_blockchain::peers::PeerSet;
+use mini_blockchain::ratelimit::RateLimiter;
+use mini_blockchain::status::NodeStatus;
+use mini_blockchain::wallet::Wallet;
+use mini_blockchain::webhooks::{registry_path, WebhookRegistry, Webhooks};
+
+/// Mines enough blocks to `addr` for its first reward to mature — call before
+/// filling the mempool, since these blocks would sweep it.
+pub fn fund(bc: &mut Blockchain, addr: &str) {
+    for _ in 0..bc.coinbase_maturity.max(1) {
+        bc.mine_pending_transactions(addr.to_string(), true).unwrap();
+    }
+}
+
+/// A path under the temp dir no other test — or test run — will use.
+pub fn temp_path(name: &str) -> String {
+    let path: PathBuf = std::env::temp_dir().join(format!("mini-blockchain-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().into_owned()
+}
+
+/// A copy of `bc` through its own chain file, the way a second node would load it.
+pub fn copy_chain(bc: &Blockchain, name: &str) -> Blockchain {
+    let path = temp_path(name);
+    bc.save_to_file(&path).unwrap();
+    let copy = Blockchain::load_from_file(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    copy
+}
+
+/// The API key every test_state() node takes.
+pub const API_KEY: &str = "sekrit";
+
+/// A node's shared state around `bc`, as server::run() would build it, with
+/// its files under a temp dir of its own and no rate limit.
+pub fn test_state(bc: Blockchain, name: &str) -> web::Data<AppState> {
+    let status = NodeStatus::new();
+    status.chain_loaded();
+    app_state(bc, name, RateLimiter::disabled(), status)
+}
+
+/// test_state() with the rate limiter and startup status up to the caller.
+pub fn app_state(bc: Blockchain, name: &str, rate_limiter: RateLimiter, status: NodeStatus) -> web::Data<AppState> {
+    let dir = std::env::temp_dir().join(format!("mini-blockchain-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let chain_file = dir.join("chain.json").to_string_lossy().into_owned();
+    let peers = Arc::new(Mutex::new(PeerSet::new(Wallet::new())));
+    let registry = WebhookRegistry::load(&registry_path(&chain_file)).unwrap();
+    let chain = ChainHandle::spawn(bc);
+    let miner = AutoMiner::new(chain.mining_status().clone());
+    web::Data::new(AppState {
+        chain,
+        chain_file,
+        gossip:      Gossip::spawn(peers.clone()),
+        peers,
+        webhooks:    Webhooks::spawn(registry),
+        api_key:     ApiKey::new(API_KEY),
+        rate_limiter,
+        idempotency: IdempotencyStore::new(Duration::from_secs(600)),
+        status,
+        miner,
+        rejections:  RejectionLog::default(),
+    })
+}
@@ -0,0 +1,278 @@
+mod common;
+
+use std::future::Future;
+use std::time::Duration;
+
+use actix_web::{web, App, HttpServer};
+use serde_json::{json, Value};
+
+use mini_blockchain::api::{add_peer, AppState};
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::peers::{PeerError, PeerSet, NODE_KEY_HEADER, NODE_SIGNATURE_HEADER};
+use mini_blockchain::server::routes;
+use mini_blockchain::sync;
+use mini_blockchain::util::LockExt;
+use mini_blockchain::wallet::Wallet;
+
+use common::{copy_chain, fund, test_state, API_KEY};
+
+// a node serving `bc` on a free local port
+struct Node {
+    state: web::Data<AppState>,
+    url:   String,
+}
+
+fn start(bc: Blockchain, name: &str) -> Node {
+    let state = test_state(bc, name);
+    let app_state = state.clone();
+    let server = HttpServer::new(move || App::new().app_data(app_state.clone()).configure(routes))
+        .workers(1)
+        .disable_signals()
+        .bind("127.0.0.1:0")
+        .unwrap();
+    let url = format!("http://{}", server.addrs()[0]);
+    actix_web::rt::spawn(server.run());
+    Node { state, url }
+}
+
+// each registers the other, as two operators pointing their nodes at each other would
+async fn link(a: &Node, b: &Node) {
+    add_peer(&a.state, &b.url).await.unwrap();
+    add_peer(&b.state, &a.url).await.unwrap();
+}
+
+async fn post(node: &Node, path: &str, body: Value) -> Value {
+    reqwest::Client::new().post(format!("{}{}", node.url, path))
+        .header("X-Api-Key", API_KEY)
+        .json(&body)
+        .send().await.unwrap()
+        .json().await.unwrap()
+}
+
+async fn get(node: &Node, path: &str) -> Value {
+    reqwest::get(format!("{}{}", node.url, path)).await.unwrap().json().await.unwrap()
+}
+
+// gossip is fire-and-forget, so poll for what it should lead to
+async fn eventually<F: Future<Output = bool>>(what: &str, mut check: impl FnMut() -> F) {
+    for _ in 0..100 {
+        if check().await {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("gave up waiting for {}", what);
+}
+
+#[actix_web::test]
+async fn transaction_submitted_to_one_node_reaches_the_other() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let b_chain = copy_chain(&bc, "gossip-tx-b");
+    let (a, b) = (start(bc, "gossip-tx-a"), start(b_chain, "gossip-tx-b"));
+    link(&a, &b).await;
+
+    let txn = alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 0).unwrap();
+    let txid = txn.id();
+    let res = post(&a, "/transaction/raw", json!({"raw": txn.to_hex().unwrap()})).await;
+    assert_eq!(res["data"], txid.as_str());
+
+    eventually("B's mempool", || async {
+        get(&b, "/mempool").await["data"]["transactions"][0]["txid"] == txid.as_str()
+    }).await;
+    // B's announcement back to A is a duplicate there, and that's where it stops
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    for node in [&a, &b] {
+        assert_eq!(get(node, "/mempool").await["data"]["count"], 1);
+    }
+}
+
+#[actix_web::test]
+async fn failed_deliveries_are_recorded_against_the_peer() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let a = start(bc, "gossip-dead");
+    // a port nothing listens on any more
+    let dead = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    };
+    a.state.peers.locked().insert(dead.clone(), Wallet::new().address()).unwrap();
+
+    let txn = alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 0).unwrap();
+    post(&a, "/transaction/raw", json!({"raw": txn.to_hex().unwrap()})).await;
+    eventually("the failure to be recorded", || async {
+        a.state.peers.locked().list().iter().any(|p| p.url == dead && p.failures > 0 && p.last_error.is_some())
+    }).await;
+}
+
+#[actix_web::test]
+async fn three_nodes_follow_the_one_that_mines() {
+    let bc = Blockchain::with_prefix("0");
+    let (b_chain, c_chain) = (copy_chain(&bc, "gossip-mine-b"), copy_chain(&bc, "gossip-mine-c"));
+    let a = start(bc, "gossip-mine-a");
+    let b = start(b_chain, "gossip-mine-b");
+    let c = start(c_chain, "gossip-mine-c");
+    link(&a, &b).await;
+    let mine = || post(&a, "/mine", json!({"miner_address": Wallet::new().address(), "allow_empty": true}));
+
+    mine().await;
+    mine().await;
+    eventually("B to take A's blocks", || async { get(&b, "/chain/head").await["data"]["index"] == 2 }).await;
+
+    // C only knows B, and has missed both blocks: the next one it hears of
+    // doesn't connect, so it syncs the rest from B
+    link(&b, &c).await;
+    mine().await;
+    let head = get(&a, "/chain/head").await["data"].clone();
+    assert_eq!(head["index"], 3);
+    for node in [&b, &c] {
+        eventually("every node at A's tip", || async {
+            let theirs = get(node, "/chain/head").await["data"].clone();
+            theirs["index"] == head["index"] && theirs["hash"] == head["hash"]
+        }).await;
+    }
+}
+
+#[actix_web::test]
+async fn a_peer_claiming_billions_of_blocks_is_synced_page_by_page() {
+    let mut bc = Blockchain::with_prefix("0");
+    for _ in 0..3 {
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    let b = start(copy_chain(&Blockchain::with_prefix("0"), "gossip-liar-b"), "gossip-liar-b");
+    let head = json!({"ok": true, "message": "chain head", "data": {
+        "index": u32::MAX, "hash": bc.chain[3].hash, "genesis_hash": bc.chain[0].hash,
+        "node_id": "liar", "total_work": u64::MAX,
+    }});
+    // a real node's routes, behind a /chain/head that lies about how far ahead it is
+    let state = test_state(bc, "gossip-liar-a");
+    let server = HttpServer::new(move || {
+        let head = head.clone();
+        App::new().app_data(state.clone())
+            .route("/chain/head", web::get().to(move || {
+                let head = head.clone();
+                async move { actix_web::HttpResponse::Ok().json(head) }
+            }))
+            .configure(routes)
+    }).workers(1).disable_signals().bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", server.addrs()[0]);
+    actix_web::rt::spawn(server.run());
+
+    let outcome = sync::sync_with_peer(&b.state, &url).await.unwrap().unwrap();
+    assert_eq!(outcome.new_height, 3);
+}
+
+fn bad_announcements(peers: &PeerSet, url: &str) -> u64 {
+    peers.list().iter().find(|p| p.url == url).unwrap().bad_announcements
+}
+
+#[test]
+fn only_announcements_signed_by_a_registered_peer_get_through() {
+    let mut peers = PeerSet::new(Wallet::new());
+    let friend = PeerSet::new(Wallet::new());
+    let url = "http://127.0.0.1:9".to_string();
+    peers.insert(url.clone(), friend.node_id()).unwrap();
+    let body = br#"{"raw":"00"}"#;
+    let (key, signature) = friend.sign_announcement(body);
+
+    assert_eq!(peers.verify_announcement(Some(&key), Some(&signature), body), Ok(url.clone()));
+
+    // nothing to charge these to
+    assert_eq!(peers.verify_announcement(None, None, body), Err(PeerError::Unsigned));
+    assert_eq!(peers.verify_announcement(Some(&key), None, body), Err(PeerError::Unsigned));
+    let (stranger, theirs) = PeerSet::new(Wallet::new()).sign_announcement(body);
+    assert_eq!(peers.verify_announcement(Some(&stranger), Some(&theirs), body), Err(PeerError::UnknownNode(stranger)));
+    assert_eq!(bad_announcements(&peers, &url), 0);
+
+    // the peer's key with a signature that doesn't check out counts against it
+    let forged = [
+        PeerSet::new(Wallet::new()).sign_announcement(body).1,  // someone else's key
+        friend.sign_announcement(b"{}").1,                     // another body
+        "zz".repeat(64),                                       // not hex
+        signature[..64].to_string(),                           // too short
+    ];
+    for (n, bad) in forged.iter().enumerate() {
+        assert_eq!(peers.verify_announcement(Some(&key), Some(bad), body), Err(PeerError::BadSignature(url.clone())));
+        assert_eq!(bad_announcements(&peers, &url), n as u64 + 1);
+    }
+    assert_eq!(peers.verify_announcement(Some(&key), Some(&signature), body), Ok(url.clone()));
+}
+
+#[actix_web::test]
+async fn badly_signed_announcements_are_refused_before_the_body_is_read() {
+    let a = start(Blockchain::with_prefix("0"), "gossip-signed");
+    let friend = PeerSet::new(Wallet::new());
+    let friend_url = "http://127.0.0.1:9".to_string();
+    a.state.peers.locked().insert(friend_url.clone(), friend.node_id()).unwrap();
+    // not even JSON — a refusal has to come from the headers alone
+    let body = b"not json".to_vec();
+    let (key, signature) = friend.sign_announcement(&body);
+    let (stranger, theirs) = PeerSet::new(Wallet::new()).sign_announcement(&body);
+
+    let announce = |headers: Vec<(&'static str, String)>| {
+        let mut req = reqwest::Client::new().post(format!("{}/peer/block", a.url)).body(body.clone());
+        for (name, value) in headers {
+            req = req.header(name, value);
+        }
+        async move {
+            let res = req.send().await.unwrap();
+            let status = res.status().as_u16();
+            let json: Value = res.json().await.unwrap();
+            (status, json["error_code"].as_str().unwrap_or_default().to_string())
+        }
+    };
+    assert_eq!(announce(vec![]).await, (401, "UNSIGNED_ANNOUNCEMENT".to_string()));
+    assert_eq!(announce(vec![(NODE_KEY_HEADER, stranger), (NODE_SIGNATURE_HEADER, theirs.clone())]).await,
+               (403, "UNKNOWN_NODE".to_string()));
+    assert_eq!(bad_announcements(&a.state.peers.locked(), &friend_url), 0);
+    assert_eq!(announce(vec![(NODE_KEY_HEADER, key.clone()), (NODE_SIGNATURE_HEADER, theirs)]).await,
+               (403, "BAD_SIGNATURE".to_string()));
+    assert_eq!(bad_announcements(&a.state.peers.locked(), &friend_url), 1);
+
+    // signed properly, it gets as far as parsing
+    assert_eq!(announce(vec![(NODE_KEY_HEADER, key), (NODE_SIGNATURE_HEADER, signature)]).await,
+               (400, "MALFORMED_JSON".to_string()));
+    assert_eq!(bad_announcements(&a.state.peers.locked(), &friend_url), 1);
+}
+
+#[actix_web::test]
+async fn a_peer_is_only_registered_once_it_checks_out() {
+    let bc = Blockchain::with_prefix("0");
+    let b = start(copy_chain(&bc, "peers-reg-b.json"), "peers-reg-b");
+    let a = start(bc, "peers-reg-a");
+    let stranger = start(Blockchain::with_prefix("00"), "peers-reg-stranger");
+    let register = |url: String| {
+        let to = format!("{}/peers", a.url);
+        async move {
+            let res = reqwest::Client::new().post(to).header("X-Api-Key", API_KEY)
+                .json(&json!({"url": url})).send().await.unwrap();
+            (res.status().as_u16(), res.json::<Value>().await.unwrap())
+        }
+    };
+
+    // a trailing slash is the same node
+    let (status, body) = register(format!("{}/", b.url)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["data"]["url"], b.url);
+    assert_eq!(body["data"]["node_key"], b.state.peers.locked().node_id());
+    let listed = get(&a, "/peers").await;
+    assert_eq!(listed["data"].as_array().unwrap().len(), 1);
+    assert_eq!(listed["data"][0]["failures"], 0);
+
+    let refused = [
+        (b.url.clone(), 409, "ALREADY_REGISTERED"),
+        (a.url.clone(), 409, "SELF_REGISTRATION"),
+        (stranger.url.clone(), 422, "GENESIS_MISMATCH"),
+        (format!("{}/nothing-here", b.url), 502, "NOT_A_NODE"),
+        ("http://127.0.0.1:1".to_string(), 502, "PEER_UNREACHABLE"),
+        ("ftp://example.com".to_string(), 400, "INVALID_URL"),
+    ];
+    for (url, code, error) in refused {
+        let (status, body) = register(url.clone()).await;
+        assert_eq!((status, body["error_code"].as_str()), (code, Some(error)), "{}", url);
+    }
+    assert_eq!(get(&a, "/peers").await["data"].as_array().unwrap().len(), 1);
+}
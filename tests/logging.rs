@@ -0,0 +1,80 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+use tracing::Level;
+
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::wallet::Wallet;
+
+// a writer the subscriber formats into, so the test can read back what was logged
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// every JSON event logged on this thread while `f` runs
+fn logged(f: impl FnOnce()) -> Vec<Value> {
+    let out = Captured::default();
+    let writer = out.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_max_level(Level::DEBUG)
+        .with_writer(move || writer.clone())
+        .finish();
+    tracing::subscriber::with_default(subscriber, f);
+    let text = String::from_utf8(out.0.lock().unwrap().clone()).unwrap();
+    text.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+}
+
+fn find<'a>(events: &'a [Value], message: &str) -> &'a Value {
+    events.iter()
+        .find(|e| e["fields"]["message"] == message)
+        .unwrap_or_else(|| panic!("no {:?} event in {:#?}", message, events))
+}
+
+#[test]
+fn chain_work_is_logged_as_leveled_events() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    let events = logged(|| {
+        bc.mine_pending_transactions(alice.address(), true).unwrap();
+        bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 0).unwrap()).unwrap();
+    });
+    let added = find(&events, "mempool add");
+    assert_eq!(added["level"], "DEBUG");
+    let confirmed = find(&events, "block confirmed");
+    assert_eq!((confirmed["level"].as_str(), confirmed["fields"]["block"].as_u64()), (Some("INFO"), Some(1)));
+    assert_eq!(confirmed["span"]["name"], "mine_pending_transactions");
+    assert_eq!(confirmed["span"]["block"], 1);
+
+    bc.chain[1].nonce += 1;
+    let events = logged(|| assert!(bc.validate().is_err()));
+    assert_eq!(find(&events, "chain failed validation")["level"], "WARN");
+}
+
+#[test]
+fn library_code_never_prints() {
+    let src = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+    for entry in std::fs::read_dir(src).unwrap() {
+        let path = entry.unwrap().path();
+        // main.rs and src/bin/ are the programs, they talk to their user
+        if path.extension().is_none_or(|e| e != "rs") || path.ends_with("main.rs") {
+            continue;
+        }
+        let code = std::fs::read_to_string(&path).unwrap();
+        for call in ["println!", "print!", "eprintln!", "eprint!", "dbg!"] {
+            assert!(!code.contains(call), "{} calls {}", path.display(), call);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use mini_blockchain::block::Block;
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::error::ChainError;
+use mini_blockchain::merkle::{hash_pair, merkle_proof, merkle_root, verify_merkle_proof, EMPTY_MERKLE_ROOT};
+use mini_blockchain::wallet::Wallet;
+
+fn leaves(n: usize) -> Vec<String> {
+    (0..n).map(|i| format!("{:064x}", i + 1)).collect()
+}
+
+#[test]
+fn no_transactions() {
+    assert_eq!(merkle_root(&[]), EMPTY_MERKLE_ROOT);
+    assert_eq!(merkle_proof(&[], 0), None);
+}
+
+#[test]
+fn one_transaction_is_its_own_root() {
+    let ids = leaves(1);
+    assert_eq!(merkle_root(&ids), ids[0]);
+    let proof = merkle_proof(&ids, 0).unwrap();
+    assert!(proof.is_empty());
+    assert!(verify_merkle_proof(&ids[0], &ids[0], &proof));
+}
+
+#[test]
+fn two_transactions() {
+    let ids = leaves(2);
+    let root = hash_pair(&ids[0], &ids[1]);
+    assert_eq!(merkle_root(&ids), root);
+    assert_eq!(merkle_proof(&ids, 0).unwrap(), vec![(ids[1].clone(), false)]);
+    assert_eq!(merkle_proof(&ids, 1).unwrap(), vec![(ids[0].clone(), true)]);
+    for (i, id) in ids.iter().enumerate() {
+        assert!(verify_merkle_proof(&root, id, &merkle_proof(&ids, i).unwrap()));
+    }
+}
+
+#[test]
+fn odd_count_duplicates_the_last_leaf() {
+    let ids = leaves(3);
+    let root = hash_pair(&hash_pair(&ids[0], &ids[1]), &hash_pair(&ids[2], &ids[2]));
+    assert_eq!(merkle_root(&ids), root);
+    // the lone leaf is paired with itself, so its own hash is its sibling
+    let proof = merkle_proof(&ids, 2).unwrap();
+    assert_eq!(proof, vec![(ids[2].clone(), false), (hash_pair(&ids[0], &ids[1]), true)]);
+    assert!(verify_merkle_proof(&root, &ids[2], &proof));
+    // and duplicating it by hand gives the same tree
+    let mut padded = ids.clone();
+    padded.push(ids[2].clone());
+    assert_eq!(merkle_root(&padded), root);
+}
+
+#[test]
+fn odd_levels_higher_up_duplicate_too() {
+    // 5 leaves: 3 nodes on the level above, so its last node is paired with itself
+    let ids = leaves(5);
+    let l1 = [hash_pair(&ids[0], &ids[1]), hash_pair(&ids[2], &ids[3]), hash_pair(&ids[4], &ids[4])];
+    let l2 = [hash_pair(&l1[0], &l1[1]), hash_pair(&l1[2], &l1[2])];
+    assert_eq!(merkle_root(&ids), hash_pair(&l2[0], &l2[1]));
+}
+
+#[test]
+fn every_leaf_proves_for_many_sizes() {
+    for n in 1..=33 {
+        let ids = leaves(n);
+        let root = merkle_root(&ids);
+        for (i, id) in ids.iter().enumerate() {
+            let proof = merkle_proof(&ids, i).unwrap();
+            assert_eq!(proof.len(), n.next_power_of_two().trailing_zeros() as usize, "n={} i={}", n, i);
+            assert!(verify_merkle_proof(&root, id, &proof), "n={} i={}", n, i);
+        }
+        assert_eq!(merkle_proof(&ids, n), None);
+    }
+}
+
+#[test]
+fn proof_fails_for_the_wrong_leaf_or_root() {
+    let ids = leaves(6);
+    let root = merkle_root(&ids);
+    let proof = merkle_proof(&ids, 3).unwrap();
+    assert!(!verify_merkle_proof(&root, &ids[4], &proof));
+    assert!(!verify_merkle_proof(&merkle_root(&leaves(7)), &ids[3], &proof));
+    let mut flipped = proof.clone();
+    flipped[0].1 = !flipped[0].1;
+    assert!(!verify_merkle_proof(&root, &ids[3], &flipped));
+}
+
+#[test]
+fn block_proofs_check_against_the_header_root() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 0;
+    let alice = Wallet::new();
+    bc.mine_pending_transactions(alice.address(), true).unwrap();
+    for nonce in 0..4 {
+        bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.0, nonce).unwrap()).unwrap();
+    }
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    // four transfers and the coinbase: an odd count
+    let block = bc.chain.last().unwrap();
+    assert_eq!(block.transactions.len(), 5);
+    for txn in &block.transactions {
+        let proof = block.merkle_proof(&txn.id()).unwrap();
+        assert!(verify_merkle_proof(&block.header().merkle_root, &txn.id(), &proof));
+    }
+    assert!(block.merkle_proof(&"ab".repeat(32)).is_none());
+}
+
+#[test]
+fn editing_a_confirmed_transaction_breaks_its_block() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 0;
+    let alice = Wallet::new();
+    assert!(bc.chain[0].transactions.is_empty());
+    assert_eq!(bc.chain[0].merkle_root, EMPTY_MERKLE_ROOT);
+    bc.mine_pending_transactions(alice.address(), true).unwrap();
+    bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.0, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    let tip = bc.chain.len() - 1;
+
+    // the header's root no longer matches its transactions
+    bc.chain[tip].transactions[0].outputs[0].amount += 1;
+    assert_eq!(bc.validate(), Err(ChainError::MerkleMismatch { index: tip as u32 }));
+
+    // and fixing the root up changes what the block hashes to
+    bc.chain[tip].merkle_root = Block::compute_merkle_root(&bc.chain[tip].transactions);
+    assert_eq!(bc.validate(), Err(ChainError::HashMismatch { index: tip as u32 }));
+}
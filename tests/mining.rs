@@ -0,0 +1,344 @@
+mod common;
+
+use std::sync::atomic::AtomicBool;
+
+use mini_blockchain::block::{difficulty_from_prefix, target, Block, MineAborted, PROGRESS_INTERVAL};
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::error::ChainError;
+use mini_blockchain::wallet::Wallet;
+
+use common::{copy_chain, fund};
+
+// "000"
+const BITS: u32 = 12;
+
+// block #1 of a copy of `bc`, with its proof of work wiped
+fn unmined_next_block(bc: &Blockchain, name: &str) -> Block {
+    let mut copy = copy_chain(bc, name);
+    copy.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    let mut block = copy.chain.pop().unwrap();
+    block.nonce = 0;
+    block.hash = String::new();
+    block
+}
+
+#[test]
+fn parallel_and_serial_mining_validate_the_same() {
+    let bc = Blockchain::new(BITS);
+    let unmined = unmined_next_block(&bc, "parallel");
+
+    let mut serial = unmined.clone();
+    serial.mine(BITS).unwrap();
+    for threads in [1, 2, 4, 8] {
+        let mut parallel = unmined.clone();
+        parallel.mine_parallel(BITS, threads, &AtomicBool::new(false)).unwrap();
+        assert!(parallel.is_intact() && parallel.meets_difficulty());
+        // one worker walks the nonces in order, just like mine()
+        if threads == 1 {
+            assert_eq!((parallel.nonce, &parallel.hash), (serial.nonce, &serial.hash));
+        }
+
+        let mut chain = copy_chain(&bc, &format!("parallel-{}", threads));
+        chain.chain.push(parallel);
+        chain.validate_full().unwrap();
+    }
+}
+
+#[test]
+fn parallel_mining_stops_when_cancelled() {
+    let bc = Blockchain::new(BITS);
+    let mut block = unmined_next_block(&bc, "cancelled");
+    assert_eq!(block.mine_parallel(BITS, 4, &AtomicBool::new(true)), Err(MineAborted::Cancelled));
+    assert!(block.hash.is_empty());
+}
+
+#[test]
+fn mining_threads_default_to_the_cores() {
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    assert_eq!(Blockchain::new(BITS).mining_threads, cores);
+}
+
+#[test]
+fn overflow_waits_for_the_next_block() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.max_transactions_per_block = 3;
+    let alice = Wallet::new();
+    let carol = Wallet::new();
+    let bob = Wallet::new().address();
+    fund(&mut bc, &alice.address());
+    fund(&mut bc, &carol.address());
+    // alice's nonces have to go in order, so her cheap first one holds back the rest
+    for (nonce, fee) in [(0, 0.0), (1, 0.5), (2, 0.5), (3, 0.5), (4, 0.5)] {
+        bc.add_transaction(alice.sign_transaction(bob.clone(), 1.0, fee, nonce).unwrap()).unwrap();
+    }
+    bc.add_transaction(carol.sign_transaction(bob.clone(), 1.0, 0.1, 0).unwrap()).unwrap();
+
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    let first = bc.chain.last().unwrap();
+    let picked: Vec<(String, u64)> = first.transactions.iter()
+        .filter(|t| t.from != "NETWORK")
+        .map(|t| (t.from.clone(), t.nonce))
+        .collect();
+    assert_eq!(picked, [(carol.address(), 0), (alice.address(), 0), (alice.address(), 1)]);
+    assert_eq!(bc.mempool.len(), 3);
+
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    let nonces: Vec<u64> = bc.chain.last().unwrap().transactions.iter()
+        .filter(|t| t.from == alice.address())
+        .map(|t| t.nonce)
+        .collect();
+    assert_eq!(nonces, [2, 3, 4]);
+    assert!(bc.mempool.is_empty());
+    bc.validate_full().unwrap();
+
+    // a block over the limit is invalid, however it got there
+    bc.max_transactions_per_block = 2;
+    assert!(matches!(bc.validate_full(), Err(ChainError::TooManyTransactions { count: 3, max: 2, .. })));
+}
+
+#[test]
+fn tiny_nonce_space_rolls_the_extra_nonce() {
+    let bc = Blockchain::new(BITS);
+    let mut block = unmined_next_block(&bc, "extra-nonce");
+    block.mine_with_nonce_space(BITS, 2).unwrap();
+    assert!(block.extra_nonce > 0 && block.nonce < 2);
+    assert!(block.is_intact() && block.meets_difficulty());
+    assert_eq!(block.header().calculate_hash(), block.hash);
+
+    // it's hashed: change it and the block no longer checks out
+    let mut bumped = block.clone();
+    bumped.extra_nonce += 1;
+    assert!(!bumped.is_intact());
+    let mut chain = copy_chain(&bc, "extra-nonce-bumped");
+    chain.chain.push(bumped);
+    assert!(matches!(chain.validate_full(), Err(ChainError::HashMismatch { index: 1 })));
+}
+
+#[test]
+fn parallel_miners_roll_over_a_nonce_space_smaller_than_their_count() {
+    let mut bc = Blockchain::new(BITS);
+    bc.nonce_space = 3;
+    bc.mining_threads = 4;
+    while bc.chain.len() < 6 {
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    assert!(bc.chain[1..].iter().all(|b| b.nonce < 3));
+    assert!(bc.chain[1..].iter().any(|b| b.extra_nonce > 0));
+    bc.validate_full().unwrap();
+}
+
+#[test]
+fn blocks_from_before_extra_nonce_still_load() {
+    let bc = Blockchain::new(BITS);
+    let mut json = serde_json::to_value(&bc.chain[0]).unwrap();
+    json.as_object_mut().unwrap().remove("extra_nonce");
+    let genesis: Block = serde_json::from_value(json).unwrap();
+    assert_eq!(genesis.extra_nonce, 0);
+    assert_eq!(genesis.calculate_hash(), bc.chain[0].hash);
+}
+
+#[test]
+fn a_mine_that_cant_finish_can_be_stopped() {
+    let bc = Blockchain::new(BITS);
+    let unmined = unmined_next_block(&bc, "stoppable");
+
+    let mut block = unmined.clone();
+    assert_eq!(block.mine_with_cancel(BITS, &AtomicBool::new(true)), Err(MineAborted::Cancelled));
+    assert_eq!(block.mine_until(256, 1_000), Err("No valid hash within 1000 nonces".to_string()));
+    assert!(block.hash.is_empty());
+    // a difficulty no hash can meet is refused before any hashing
+    for bits in [0, 257] {
+        assert!(matches!(block.clone().mine(bits), Err(MineAborted::InvalidDifficulty(_))), "{} bits", bits);
+    }
+    assert!(matches!(difficulty_from_prefix("zz"), Err(MineAborted::InvalidDifficulty(_))));
+
+    // and through the chain: cancel_mining stops a search at 64 bits
+    let mut bc = Blockchain::new(BITS);
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.0, 0).unwrap()).unwrap();
+    let tip = bc.chain.len() - 1;
+    bc.chain[tip].difficulty = 64;
+    let cancel = bc.cancel_mining.clone();
+    let stopper = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+    });
+    let err = bc.mine_pending_transactions(alice.address(), false).unwrap_err();
+    stopper.join().unwrap();
+    assert_eq!(err, ChainError::MiningAborted(MineAborted::Cancelled));
+    assert_eq!((bc.chain.len(), bc.mempool.len()), (tip + 1, 1));
+}
+
+#[test]
+fn the_mempool_comes_out_best_fee_first_but_in_nonce_order() {
+    let mut bc = Blockchain::with_prefix("0");
+    let (alice, carol, dave) = (Wallet::new(), Wallet::new(), Wallet::new());
+    for who in [&alice, &carol, &dave] {
+        fund(&mut bc, &who.address());
+    }
+    let bob = Wallet::new().address();
+    let mut send = |from: &Wallet, fee: f64, nonce: u64| {
+        bc.add_transaction(from.sign_transaction(bob.clone(), 1.0, fee, nonce).unwrap()).unwrap()
+    };
+    // alice's big fee sits behind her small one; carol and dave tie, carol got there first
+    let alice_0 = send(&alice, 0.01, 0);
+    let alice_1 = send(&alice, 0.9, 1);
+    let carol_0 = send(&carol, 0.3, 0);
+    let dave_0 = send(&dave, 0.3, 0);
+    let carol_1 = send(&carol, 0.05, 1);
+
+    let order: Vec<String> = bc.mempool.mining_order().iter().map(|t| t.id()).collect();
+    assert_eq!(order, [carol_0, dave_0, carol_1, alice_0, alice_1]);
+
+    // and that's what a block takes
+    bc.max_transactions_per_block = 3;
+    bc.mine_pending_transactions(Wallet::new().address(), false).unwrap();
+    let mined: Vec<String> = bc.chain.last().unwrap().transactions.iter()
+        .filter(|t| t.from != "NETWORK")
+        .map(|t| t.id())
+        .collect();
+    assert_eq!(mined, order[..3]);
+    let left: Vec<String> = bc.mempool.mining_order().iter().map(|t| t.id()).collect();
+    assert_eq!(left, order[3..]);
+}
+
+#[test]
+fn fee_estimates_follow_the_competition() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.min_relay_fee = 5;
+    bc.max_transactions_per_block = 2;
+    assert_eq!(bc.estimate_fee(1), 5);
+
+    let senders = [Wallet::new(), Wallet::new(), Wallet::new()];
+    for who in &senders {
+        fund(&mut bc, &who.address());
+    }
+    for (who, fee) in senders.iter().zip([0.1, 0.3, 0.2]) {
+        bc.add_transaction(who.sign_transaction(Wallet::new().address(), 1.0, fee, 0).unwrap()).unwrap();
+    }
+    // three waiting for two slots: outbid the second best to make the next block
+    assert_eq!(bc.estimate_fee(1), 201);
+    // two blocks hold them all, and nothing recent was full
+    assert_eq!(bc.estimate_fee(2), 5);
+
+    // a full block sets the going rate
+    bc.mine_pending_transactions(Wallet::new().address(), false).unwrap();
+    assert_eq!(bc.mempool.len(), 1);
+    assert_eq!(bc.estimate_fee(1), 300);
+    assert_eq!(bc.estimate_fee(0), bc.estimate_fee(1));
+}
+
+#[test]
+fn empty_blocks_only_when_asked_for() {
+    let mut bc = Blockchain::with_prefix("0");
+    let miner = Wallet::new().address();
+    assert_eq!(bc.mine_pending_transactions(miner.clone(), false), Err(ChainError::EmptyMempool));
+    assert_eq!(bc.chain.len(), 1);
+
+    // forced, the block is just its coinbase
+    bc.mine_pending_transactions(miner.clone(), true).unwrap();
+    let block = bc.chain.last().unwrap();
+    assert_eq!(block.transactions.len(), 1);
+    assert_eq!((block.transactions[0].from.as_str(), block.transactions[0].single_recipient()), ("NETWORK", Some(miner.as_str())));
+
+    // or the chain mines them every time
+    bc.allow_empty_blocks = true;
+    bc.mine_pending_transactions(miner.clone(), false).unwrap();
+    assert_eq!(bc.get_balance_nits(&miner).unwrap(), bc.reward * 2);
+
+    // coinbase-only blocks are valid either way
+    bc.validate_full().unwrap();
+    bc.allow_empty_blocks = false;
+    bc.validate_full().unwrap();
+    let stats = bc.stats();
+    assert_eq!((stats.empty_blocks, stats.avg_transactions_per_block), (2, 0.0));
+    assert_eq!(Blockchain::with_prefix("0").stats().avg_transactions_per_block, 0.0);
+}
+
+#[test]
+fn the_prepared_input_hashes_like_the_whole_header() {
+    let mut block = unmined_next_block(&Blockchain::new(BITS), "prepared");
+    let input = block.prepare_mining_input();
+    for nonce in [0, 1, 7_777, u64::MAX] {
+        block.nonce = nonce;
+        assert_eq!(hex::encode(input.hash(nonce)), block.calculate_hash());
+    }
+
+    // anything but the nonce needs a fresh one
+    block.nonce = 0;
+    for change in [|b: &mut Block| b.extra_nonce += 1, |b: &mut Block| b.timestamp += 1, |b: &mut Block| b.difficulty += 1] {
+        let mut changed = block.clone();
+        change(&mut changed);
+        assert_ne!(hex::encode(input.hash(0)), changed.calculate_hash());
+        assert_eq!(hex::encode(changed.prepare_mining_input().hash(0)), changed.calculate_hash());
+    }
+
+    // and what mine() settles on is what the input gives for its nonce
+    block.mine(BITS).unwrap();
+    assert_eq!(hex::encode(block.prepare_mining_input().hash(block.nonce)), block.hash);
+}
+
+#[test]
+fn difficulty_is_a_count_of_bits_compared_as_a_number() {
+    assert_eq!(target(12)[..3], [0x00, 0x0f, 0xff]);
+    assert_eq!(target(5)[..2], [0x07, 0xff]);
+    assert_eq!(target(16)[..3], [0x00, 0x00, 0xff]);
+    // the old prefixes are four bits a zero
+    assert_eq!((difficulty_from_prefix("0"), difficulty_from_prefix("0000")), (Ok(4), Ok(16)));
+    assert!(difficulty_from_prefix("").is_err());
+    assert_eq!(Blockchain::with_prefix("000").difficulty, Blockchain::new(12).difficulty);
+    assert_eq!(Blockchain::with_prefix("000").chain[0].difficulty, 12);
+
+    // 5 bits falls between two prefixes: a hash starting 0x08 has the zero but not the bits
+    let mut block = unmined_next_block(&Blockchain::new(5), "bits");
+    block.difficulty = 5;
+    block.hash = loop {
+        let hash = block.calculate_hash();
+        if hash.starts_with("08") {
+            break hash;
+        }
+        block.nonce += 1;
+    };
+    assert!(block.is_intact() && !block.meets_difficulty());
+    block.mine(5).unwrap();
+    assert!(block.meets_difficulty());
+    assert!(u8::from_str_radix(&block.hash[..2], 16).unwrap() <= 0x07);
+}
+
+#[test]
+fn a_long_mine_reports_progress_and_the_block_keeps_its_effort() {
+    const SLOW: u32 = 16;
+    let mut unmined = unmined_next_block(&Blockchain::new(BITS), "progress");
+    // a block that takes long enough to hear from
+    loop {
+        let mut probe = unmined.clone();
+        probe.mine(SLOW).unwrap();
+        if probe.nonce >= PROGRESS_INTERVAL * 2 {
+            break;
+        }
+        unmined.timestamp += 1;
+    }
+
+    let mut heard = Vec::new();
+    let mut block = unmined.clone();
+    block.mine_with_progress(SLOW, |attempts, elapsed| heard.push((attempts, elapsed))).unwrap();
+    let effort = block.effort.unwrap();
+    assert_eq!(effort.attempts, block.nonce + 1);
+    assert_eq!(heard.len() as u64, block.nonce / PROGRESS_INTERVAL);
+    assert!(heard.iter().enumerate().all(|(i, &(attempts, _))| attempts == (i as u64 + 1) * PROGRESS_INTERVAL));
+    assert!(heard.windows(2).all(|w| w[0].1 <= w[1].1));
+    assert!(effort.secs >= heard.last().unwrap().1.as_secs_f64());
+    // it isn't part of the header
+    let hash = block.hash.clone();
+    block.effort = None;
+    assert!(block.is_intact() && block.hash == hash);
+
+    // the chain's own mining leaves its last search in mining_status
+    let mut bc = Blockchain::new(BITS);
+    bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    let status = bc.mining_status.lock().unwrap().clone();
+    let effort = bc.chain.last().unwrap().effort.unwrap();
+    assert!(!status.mining && status.started_at.is_some());
+    assert_eq!(status.attempts, effort.attempts);
+}
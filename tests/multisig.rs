@@ -0,0 +1,88 @@
+use mini_blockchain::error::ChainError;
+use mini_blockchain::multisig::{CoSignature, Multisig};
+use mini_blockchain::transaction::Transaction;
+use mini_blockchain::wallet::Wallet;
+
+/// A 2-of-3 key set — alice, bob and carol — and an unsigned spend from it.
+fn two_of_three() -> ([Wallet; 3], Transaction) {
+    let wallets = [Wallet::new(), Wallet::new(), Wallet::new()];
+    let multisig = Multisig::new(wallets.iter().map(Wallet::address).collect(), 2).unwrap();
+    let mut txn = Transaction::new_with_fee(multisig.address(), Wallet::new().address(), 1.0, 0.1).unwrap();
+    txn.multisig = Some(multisig);
+    (wallets, txn)
+}
+
+#[test]
+fn alice_and_bob_together_can_spend() {
+    let ([alice, bob, _], mut txn) = two_of_three();
+    txn.add_signature(&alice).unwrap();
+    txn.add_signature(&bob).unwrap();
+    txn.validate().unwrap();
+}
+
+#[test]
+fn carol_alone_cant() {
+    let ([_, _, carol], mut txn) = two_of_three();
+    txn.add_signature(&carol).unwrap();
+    assert!(matches!(txn.validate(), Err(ChainError::NotEnoughSignatures { have: 1, need: 2 })));
+    // nor can an outsider make up the difference
+    assert!(txn.add_signature(&Wallet::new()).is_err());
+}
+
+#[test]
+fn the_same_signer_twice_counts_once() {
+    let ([alice, ..], mut txn) = two_of_three();
+    txn.add_signature(&alice).unwrap();
+    txn.add_signature(&alice).unwrap();
+    assert_eq!(txn.multisig.as_ref().unwrap().signatures.len(), 1);
+    assert!(matches!(txn.validate(), Err(ChainError::NotEnoughSignatures { have: 1, need: 2 })));
+
+    // a copy of the signature slipped in by hand doesn't count either
+    let multisig = txn.multisig.as_mut().unwrap();
+    multisig.signatures.push(multisig.signatures[0].clone());
+    assert!(matches!(txn.validate(), Err(ChainError::InvalidMultisig(_))));
+}
+
+#[test]
+fn a_partly_signed_transaction_survives_raw_hex() {
+    let ([alice, bob, _], mut txn) = two_of_three();
+    txn.add_signature(&alice).unwrap();
+    let raw = txn.to_hex().unwrap();
+    let mut passed_on = Transaction::from_hex(&raw).unwrap();
+    assert_eq!(passed_on.multisig, txn.multisig);
+    assert_eq!(passed_on.id(), txn.id());
+
+    passed_on.add_signature(&bob).unwrap();
+    passed_on.validate().unwrap();
+    assert_eq!(Transaction::from_hex(&passed_on.to_hex().unwrap()).unwrap().id(), passed_on.id());
+}
+
+#[test]
+fn a_malformed_co_signature_fails_to_encode() {
+    let ([alice, ..], mut txn) = two_of_three();
+    txn.add_signature(&alice).unwrap();
+    let multisig = txn.multisig.as_mut().unwrap();
+    multisig.signatures[0].signature_hex = "not hex".to_string();
+    assert!(matches!(txn.to_hex(), Err(ChainError::MalformedSignature(_))));
+    assert!(matches!(txn.validate(), Err(ChainError::MalformedSignature(_))));
+
+    // more co-signatures than a u8 counts can't be written either
+    let multisig = txn.multisig.as_mut().unwrap();
+    multisig.signatures = vec![CoSignature { key: alice.address(), signature_hex: "00".repeat(64) }; 256];
+    assert!(matches!(txn.to_hex(), Err(ChainError::MalformedTransaction(_))));
+    assert!(matches!(txn.validate(), Err(ChainError::InvalidMultisig(_))));
+}
+
+#[test]
+fn the_address_is_a_domain_tagged_hash_of_threshold_and_sorted_keys() {
+    let keys = vec![
+        "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a".to_string(),
+        "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c".to_string(),
+    ];
+    let multisig = Multisig::new(keys.clone(), 2).unwrap();
+    assert_eq!(multisig.address(), "ms2005f45db1507e561c05e66710d119d2f9a612ef1643346e51ef0460a440780e");
+    // key order doesn't matter, the threshold does
+    let reversed: Vec<String> = keys.iter().rev().cloned().collect();
+    assert_eq!(Multisig::new(reversed, 2).unwrap().address(), multisig.address());
+    assert_ne!(Multisig::new(keys, 1).unwrap().address(), multisig.address());
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::wallet::Wallet;
+
+use common::temp_path;
+
+const NODE: &str = env!("CARGO_BIN_EXE_node");
+
+// a fresh data dir, and a config file beside it holding `toml`
+fn setup(name: &str, toml: &str) -> (String, String) {
+    let dir = temp_path(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    let config = format!("{}.toml", dir);
+    std::fs::write(&config, toml).unwrap();
+    (dir, config)
+}
+
+// what the node says when it refuses to start
+fn refused(args: &[&str]) -> String {
+    let out = Command::new(NODE).args(args).env_remove("LOG_FORMAT").output().unwrap();
+    assert!(!out.status.success(), "node started with {:?}", args);
+    String::from_utf8_lossy(&out.stderr).into_owned()
+}
+
+async fn data(client: &reqwest::Client, url: String) -> Value {
+    client.get(url).send().await.unwrap().json::<Value>().await.unwrap()["data"].clone()
+}
+
+#[test]
+fn bad_settings_stop_the_node_before_it_starts() {
+    let (dir, config) = setup("node-refused", "difficulty_bits = 6\ndifficulty = \"00\"\n");
+
+    let err = refused(&["--difficulty", "zz", "--data-dir", &dir]);
+    assert!(err.starts_with("error: difficulty \"zz\" is not usable"), "{}", err);
+    let err = refused(&["--difficulty-bits", "300", "--data-dir", &dir]);
+    assert!(err.starts_with("error: difficulty 300 bits is not usable"), "{}", err);
+    let err = refused(&["--config", &config, "--data-dir", &dir]);
+    assert!(err.contains("not both"), "{}", err);
+    let err = refused(&["--prune-depth", "3", "--data-dir", &dir]);
+    assert!(err.contains("prune depth 3 is too shallow"), "{}", err);
+
+    std::fs::write(&config, "difficulty_bits = 6\nminers = 4\n").unwrap();
+    let err = refused(&["--config", &config, "--data-dir", &dir]);
+    assert!(err.contains("is not a valid config"), "{}", err);
+    // nothing got as far as a chain file
+    assert!(!Path::new(&dir).join("chain.json").exists());
+    let _ = std::fs::remove_dir_all(dir);
+    let _ = std::fs::remove_file(config);
+}
+
+#[actix_web::test]
+async fn flags_beat_the_config_file_and_ctrl_c_saves_the_chain() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let (dir, config) = setup("node-run", &format!(
+        "bind = \"127.0.0.1:1\"\ndifficulty_bits = 6\nreward = 7\ndata_dir = {:?}\n", temp_path("node-run-unused")));
+    let node = Command::new(NODE)
+        .args(["--config", &config, "--bind", &format!("127.0.0.1:{}", port), "--reward", "3", "--data-dir", &dir])
+        .env_remove("LOG_FORMAT")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    let mut up = false;
+    for _ in 0..100 {
+        if client.get(format!("{}/health", base)).send().await.is_ok() {
+            up = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(up, "the node never answered on the flag's address");
+
+    // the flags won, the file filled in the rest
+    assert_eq!(data(&client, format!("{}/reward", base)).await["reward"].as_f64(), Some(3.0));
+    assert_eq!(data(&client, format!("{}/stats", base)).await["difficulty"].as_u64(), Some(6));
+    assert!(Path::new(&dir).join("node.key").exists());
+    assert!(!Path::new(&temp_path("node-run-unused")).exists());
+
+    // a block mined just before Ctrl-C is in the chain file afterwards
+    let key = std::fs::read_to_string(Path::new(&dir).join("api.key")).unwrap();
+    let mined = client.post(format!("{}/mine", base))
+        .header("X-Api-Key", key.trim())
+        .json(&json!({"miner_address": Wallet::new().address(), "allow_empty": true}))
+        .send().await.unwrap();
+    assert_eq!(mined.status(), 200);
+    let tip = data(&client, format!("{}/chain/head", base)).await["hash"].clone();
+    Command::new("kill").args(["-INT", &node.id().to_string()]).status().unwrap();
+    let out = tokio::task::spawn_blocking(move || node.wait_with_output()).await.unwrap().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("state flushed"));
+    let saved = Blockchain::load_from_file(&Path::new(&dir).join("chain.json").to_string_lossy()).unwrap();
+    assert_eq!((saved.chain.len(), json!(saved.chain[1].hash)), (2, tip));
+    let _ = std::fs::remove_dir_all(dir);
+    let _ = std::fs::remove_file(config);
+}
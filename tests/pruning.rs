@@ -0,0 +1,116 @@
+mod common;
+
+use mini_blockchain::blockchain::{Blockchain, MIN_PRUNE_DEPTH};
+use mini_blockchain::error::ChainError;
+use mini_blockchain::store::SqliteStore;
+use mini_blockchain::wallet::Wallet;
+
+use common::{fund, temp_path};
+
+// alice pays bob in each of `blocks` blocks after her funding
+fn paid_chain(blocks: u64) -> (Blockchain, Wallet, Wallet) {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.retarget_interval = 0;
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    fund(&mut bc, &alice.address());
+    for nonce in 0..blocks {
+        bc.add_transaction(alice.sign_transaction(bob.address(), 0.1, 0.01, nonce).unwrap()).unwrap();
+        bc.mine_pending_transactions(alice.address(), false).unwrap();
+    }
+    (bc, alice, bob)
+}
+
+#[test]
+fn lookups_below_the_prune_point_say_pruned() {
+    let (mut bc, _, bob) = paid_chain(30);
+    let first_payment = bc.chain[2].transactions.iter().find(|t| t.from != "NETWORK").unwrap().id();
+    bc.prune_below(10).unwrap();
+    assert_eq!(bc.pruned_below(), 10);
+
+    assert!(matches!(bc.address_history(&bob.address(), 100, Some(10)), Err(ChainError::Pruned { below: 10 })));
+    assert!(matches!(bc.address_history(&bob.address(), 100, Some(5)), Err(ChainError::Pruned { below: 10 })));
+    assert!(matches!(bc.check_unpruned(3, 12), Err(ChainError::Pruned { below: 10 })));
+    bc.check_unpruned(10, 12).unwrap();
+    assert!(bc.find_transaction(&first_payment).is_none());
+    assert!(bc.chain[..10].iter().all(|b| b.transactions.is_empty()));
+
+    // what's above the prune point is still there, and only that
+    let history = bc.address_history(&bob.address(), 100, None).unwrap();
+    assert!(!history.is_empty());
+    assert!(history.iter().all(|entry| entry.block_index >= 10));
+
+    // someone who was only active below it has nothing left to show
+    let early = Wallet::new();
+    let (mut bc, alice, _) = paid_chain(0);
+    bc.add_transaction(alice.sign_transaction(early.address(), 0.1, 0.01, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    for _ in 0..30 {
+        bc.mine_pending_transactions(alice.address(), true).unwrap();
+    }
+    bc.prune_below(10).unwrap();
+    assert!(matches!(bc.address_history(&early.address(), 100, None), Err(ChainError::Pruned { below: 10 })));
+    assert_eq!(bc.get_balance_nits(&early.address()).unwrap(), 100);
+}
+
+#[test]
+fn pruning_never_reaches_into_the_last_blocks() {
+    let (mut bc, alice, bob) = paid_chain(30);
+    let len = bc.chain.len();
+    let limit = len - MIN_PRUNE_DEPTH;
+    let balances = (bc.get_balance_nits(&alice.address()).unwrap(), bc.get_balance_nits(&bob.address()).unwrap());
+
+    assert!(matches!(bc.prune_below(limit + 1), Err(ChainError::InvalidPrune(_))));
+    assert_eq!(bc.pruned_below(), 0);
+    assert_eq!(bc.prune_below(limit).unwrap(), limit);
+    assert_eq!(bc.prune_below(limit).unwrap(), 0);
+
+    // a maturity deeper than the minimum keeps that many
+    let (mut deep, _, _) = paid_chain(30);
+    deep.coinbase_maturity = MIN_PRUNE_DEPTH as u32 + 5;
+    assert!(matches!(deep.prune_below(len - MIN_PRUNE_DEPTH), Err(ChainError::InvalidPrune(_))));
+
+    // prune_to_depth() rounds a shallow depth up to the minimum, a deep one is kept
+    let (mut shallow, _, _) = paid_chain(30);
+    shallow.prune_depth = Some(3);
+    assert_eq!(shallow.prune_to_depth(), limit);
+    assert_eq!(shallow.pruned_below(), limit);
+    let (mut deep, _, _) = paid_chain(30);
+    deep.prune_depth = Some(MIN_PRUNE_DEPTH + 4);
+    deep.prune_to_depth();
+    assert_eq!(deep.pruned_below(), limit - 4);
+
+    assert_eq!(bc.get_balance_nits(&alice.address()).unwrap(), balances.0);
+    assert_eq!(bc.get_balance_nits(&bob.address()).unwrap(), balances.1);
+    bc.validate_full().unwrap();
+}
+
+#[test]
+fn a_pruned_chain_reloads_from_its_store() {
+    let (mut bc, alice, bob) = paid_chain(30);
+    let path = temp_path("pruned.sqlite");
+    bc.attach_store(Box::new(SqliteStore::open(&path).unwrap())).unwrap();
+    bc.prune_below(12).unwrap();
+    // one more block on top, pruned a little further
+    bc.add_transaction(alice.sign_transaction(bob.address(), 0.1, 0.01, 30).unwrap()).unwrap();
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    bc.prune_below(13).unwrap();
+    assert_eq!(bc.persist().unwrap(), 1);
+
+    let loaded = Blockchain::from_store(Box::new(SqliteStore::open(&path).unwrap())).unwrap();
+    assert_eq!(loaded.pruned_below(), 13);
+    assert_eq!(loaded.chain.len(), bc.chain.len());
+    assert_eq!(loaded.chain.last().unwrap().hash, bc.chain.last().unwrap().hash);
+    assert!(loaded.chain[..13].iter().all(|b| b.transactions.is_empty()));
+    for who in [alice.address(), bob.address()] {
+        assert_eq!(loaded.get_balance_nits(&who).unwrap(), bc.get_balance_nits(&who).unwrap());
+        assert_eq!(loaded.next_nonce(&who), bc.next_nonce(&who));
+    }
+    assert!(matches!(loaded.address_history(&bob.address(), 10, Some(13)), Err(ChainError::Pruned { below: 13 })));
+    loaded.validate_full().unwrap();
+    drop(loaded);
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+    }
+}
@@ -0,0 +1,260 @@
+mod common;
+
+use std::sync::Arc;
+
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::clock::SteppingClock;
+use mini_blockchain::error::ChainError;
+use mini_blockchain::wallet::Wallet;
+
+use common::{copy_chain, fund};
+
+/// Two nodes that agree up to `base` and then each mine their own blocks: `ours`
+/// confirms a payment from dave to bob, `theirs` — one block longer — one from
+/// alice to carol.
+struct Fork {
+    ours:   Blockchain,
+    theirs: Blockchain,
+    base:   usize,
+    alice:  Wallet,
+    bob:    String,
+    carol:  String,
+}
+
+fn fork(name: &str) -> Fork {
+    let alice = Wallet::new();
+    let dave = Wallet::new();
+    let mut ours = Blockchain::with_prefix("0");
+    ours.retarget_interval = 0;
+    fund(&mut ours, &dave.address());
+    fund(&mut ours, &alice.address());
+    for _ in 0..5 {
+        ours.mine_pending_transactions(alice.address(), true).unwrap();
+    }
+    let mut theirs = copy_chain(&ours, name);
+    let base = ours.chain.len() - 1;
+
+    let bob = Wallet::new().address();
+    ours.add_transaction(dave.sign_transaction(bob.clone(), 1.0, 0.1, ours.next_nonce(&dave.address())).unwrap()).unwrap();
+    for _ in 0..2 {
+        ours.mine_pending_transactions(alice.address(), true).unwrap();
+    }
+    // a different miner, so theirs really differs from ours even within the same second
+    let carol = Wallet::new().address();
+    theirs.add_transaction(alice.sign_transaction(carol.clone(), 2.0, 0.1, theirs.next_nonce(&alice.address())).unwrap()).unwrap();
+    for _ in 0..3 {
+        theirs.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    Fork { ours, theirs, base, alice, bob, carol }
+}
+
+fn hashes(bc: &Blockchain) -> Vec<String> {
+    bc.chain.iter().map(|b| b.hash.clone()).collect()
+}
+
+fn json<T: serde::Serialize>(value: T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap()
+}
+
+// the part of stats() the indexes keep — not the mempool or this run's hashrate
+fn totals(bc: &Blockchain) -> Vec<serde_json::Value> {
+    let stats = json(bc.stats());
+    ["block_count", "total_transactions", "empty_blocks", "total_issued", "total_fees", "total_burned"]
+        .iter()
+        .map(|k| stats[k].clone())
+        .collect()
+}
+
+/// Everything the indexes answer for `addresses` matches a chain that built
+/// them from scratch.
+fn assert_indexes_fresh(bc: &Blockchain, addresses: &[&str], name: &str) {
+    let fresh = copy_chain(bc, name);
+    assert_eq!(totals(bc), totals(&fresh));
+    assert_eq!(bc.balance_snapshot(), fresh.balance_snapshot());
+    for &address in addresses {
+        assert_eq!(json(bc.address_stats(address).unwrap()), json(fresh.address_stats(address).unwrap()), "{}", address);
+        assert_eq!(json(bc.address_history(address, 1000, None).unwrap()),
+                   json(fresh.address_history(address, 1000, None).unwrap()), "{}", address);
+    }
+    for block in &bc.chain {
+        assert_eq!(bc.get_block_by_hash(&block.hash).map(|b| b.index), Some(block.index));
+    }
+}
+
+#[test]
+fn reorg_attaches_from_the_fork_point() {
+    let Fork { mut ours, theirs, base, alice, bob, carol } = fork("reorg-fork.json");
+    let abandoned_tip = ours.chain.last().unwrap().hash.clone();
+    let headers: Vec<_> = theirs.chain.iter().map(|b| b.header()).collect();
+    assert_eq!(ours.find_fork_point(&headers), Some(base as u32));
+    assert_eq!(ours.blocks_after(base).len(), 2);
+
+    // garbage below the fork doesn't matter: our blocks stand in for theirs
+    let mut candidate = theirs.chain.clone();
+    candidate[2].nonce += 1;
+    let out = ours.replace_chain(candidate).unwrap();
+    assert_eq!((out.fork_height, out.blocks_abandoned, out.returned_to_mempool, out.new_height),
+               (base + 1, 2, 1, base + 3));
+    assert_eq!(hashes(&ours), hashes(&theirs));
+    assert!(ours.get_block_by_hash(&abandoned_tip).is_none());
+    assert_eq!(ours.get_balance(&bob).unwrap(), 0.0);
+    assert_eq!(ours.get_balance(&carol).unwrap(), 2.0);
+    assert_eq!(ours.mempool.len(), 1);
+    ours.validate_full().unwrap();
+    let miners: Vec<String> = ours.chain.iter().map(|b| b.miner.clone()).collect();
+    let mut addresses = vec![alice.address(), bob, carol, "NETWORK".to_string()];
+    addresses.extend(miners);
+    assert_indexes_fresh(&ours, &addresses.iter().map(String::as_str).collect::<Vec<_>>(), "reorg-fresh.json");
+}
+
+#[test]
+fn failed_candidate_leaves_our_chain_intact() {
+    let Fork { mut ours, theirs, base, alice, bob, carol } = fork("reorg-failed.json");
+    let before = hashes(&ours);
+    let stats = totals(&ours);
+    let alice_stats = json(ours.address_stats(&alice.address()).unwrap());
+
+    // a bad block in their suffix: it fails after ours were detached
+    let mut bad = theirs.chain.clone();
+    bad[base + 3].nonce += 1;
+    assert!(ours.replace_chain(bad).is_err());
+    assert_eq!(hashes(&ours), before);
+    assert_eq!(totals(&ours), stats);
+    assert_eq!(json(ours.address_stats(&alice.address()).unwrap()), alice_stats);
+    assert_eq!(ours.get_balance(&bob).unwrap(), 1.0);
+    assert_eq!(ours.get_balance(&carol).unwrap(), 0.0);
+    ours.validate_full().unwrap();
+    assert_indexes_fresh(&ours, &[&alice.address(), &bob, &carol, "NETWORK"], "reorg-failed-fresh.json");
+
+    // and the next block still goes on top of ours
+    ours.mine_pending_transactions(alice.address(), true).unwrap();
+    ours.validate().unwrap();
+}
+
+#[test]
+fn a_refused_candidate_says_why() {
+    let Fork { mut ours, theirs, base, .. } = fork("reorg-refused.json");
+    let before = hashes(&ours);
+
+    // no more work than ours: the same chain, or a piece of it
+    for candidate in [ours.chain.clone(), ours.chain[..base + 1].to_vec()] {
+        assert!(matches!(ours.replace_chain(candidate), Err(ChainError::ChainNotBetter { .. })));
+    }
+    // plenty of work, but from a genesis of its own
+    let mut stranger = Blockchain::with_clock(4, Arc::new(SteppingClock::new(1_600_000_000, 1)));
+    stranger.retarget_interval = 0;
+    while stranger.chain.len() < theirs.chain.len() + 5 {
+        stranger.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    assert!(matches!(ours.replace_chain(stranger.chain.clone()), Err(ChainError::GenesisMismatch)));
+    // from our genesis, but one of its blocks doesn't hash to what it says
+    let mut tampered = theirs.chain.clone();
+    tampered[base + 2].nonce += 1;
+    let index = tampered[base + 2].index;
+    assert!(matches!(ours.replace_chain(tampered), Err(ChainError::HashMismatch { index: i }) if i == index));
+
+    assert_eq!(hashes(&ours), before);
+    ours.replace_chain(theirs.chain.clone()).unwrap();
+    assert_eq!(hashes(&ours), hashes(&theirs));
+}
+
+#[test]
+fn misindexed_candidate_is_rejected() {
+    let Fork { mut ours, .. } = fork("reorg-misindexed.json");
+    let before = hashes(&ours);
+    // our own block 5 at position 1, then a copy claiming far more work
+    let mut heavy = ours.chain[5].clone();
+    heavy.difficulty = 200;
+    let candidate = vec![ours.chain[0].clone(), ours.chain[5].clone(), heavy];
+    assert!(ours.replace_chain(candidate).is_err());
+    assert_eq!(hashes(&ours), before);
+    ours.validate_full().unwrap();
+}
+
+#[test]
+fn fork_below_the_prune_point_replaces_the_whole_chain() {
+    let Fork { mut ours, mut theirs, base, alice, bob, carol } = fork("reorg-pruned.json");
+    // deep enough on both sides that the fork falls below what we can prune
+    for _ in 0..22 {
+        ours.mine_pending_transactions(alice.address(), true).unwrap();
+    }
+    for _ in 0..25 {
+        theirs.mine_pending_transactions(carol.clone(), true).unwrap();
+    }
+    ours.prune_below(base + 2).unwrap();
+    assert_eq!(ours.pruned_below(), base + 2);
+
+    let out = ours.replace_chain(theirs.chain.clone()).unwrap();
+    assert_eq!((out.fork_height, out.blocks_abandoned), (base + 1, 24));
+    assert_eq!(hashes(&ours), hashes(&theirs));
+    assert_eq!(ours.pruned_below(), 0);
+    assert_eq!(ours.get_balance(&bob).unwrap(), 0.0);
+    assert!(ours.get_balance(&carol).unwrap() > 2.0);
+    ours.validate_full().unwrap();
+    assert_indexes_fresh(&ours, &[&alice.address(), &bob, &carol], "reorg-pruned-fresh.json");
+}
+
+#[test]
+fn locators_find_the_fork_point() {
+    let Fork { ours, theirs, base, .. } = fork("reorg-locator");
+    assert_eq!(ours.locate(&theirs.block_locator()), Some(base));
+    assert_eq!(theirs.locate(&ours.block_locator()), Some(base));
+    assert_eq!(ours.locate(&ours.block_locator()), Some(ours.chain.len() - 1));
+    // a different genesis shares nothing
+    assert_eq!(ours.locate(&Blockchain::with_prefix("00").block_locator()), None);
+}
+
+#[test]
+fn locator_is_dense_at_the_tip_then_doubles_back() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.retarget_interval = 0;
+    while bc.chain.len() < 101 {
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    let heights: Vec<usize> = bc.block_locator().iter().map(|b| b.height).collect();
+    assert_eq!(heights, [100, 99, 98, 97, 96, 95, 94, 93, 92, 91, 89, 85, 77, 61, 29, 0]);
+    assert!(bc.block_locator().iter().all(|b| b.hash == bc.chain[b.height].hash));
+    assert_eq!(Blockchain::with_prefix("0").block_locator().len(), 1);
+
+    // a fork below the dense part comes out at the locator entry under it:
+    // forked at #30, a tip at #89 lists 89..=80, 78, 74, 66, 50, 18, 0
+    let mut deep = copy_chain(&bc, "reorg-locator-deep");
+    deep.rollback(70).unwrap();
+    while deep.chain.len() < 90 {
+        deep.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    assert_eq!(bc.locate(&deep.block_locator()), Some(18));
+}
+
+// from the same genesis at START, retargeting every four blocks against a clock
+// stepping `step` seconds a read
+fn paced_chain(step: u64, len: usize) -> Blockchain {
+    let mut bc = Blockchain::with_clock(8, Arc::new(SteppingClock::new(1_700_000_000, step)));
+    bc.retarget_interval = 4;
+    while bc.chain.len() < len {
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    bc
+}
+
+#[test]
+fn the_heavier_chain_wins_not_the_longer_one() {
+    // slow blocks drag the difficulty down, fast ones push it up
+    let mut long = paced_chain(1000, 12);
+    let mut heavy = paced_chain(1, 6);
+    assert_eq!(long.chain[0].hash, heavy.chain[0].hash);
+    let bits = |bc: &Blockchain| bc.chain.iter().map(|b| b.difficulty).collect::<Vec<_>>();
+    assert_eq!(bits(&long), [8, 8, 8, 8, 6, 6, 6, 6, 4, 4, 4, 4]);
+    assert_eq!(bits(&heavy), [8, 8, 8, 8, 10, 10]);
+    assert_eq!(long.chain[4].work(), 64);
+    assert_eq!(long.total_work(), 4 * 256 + 4 * 64 + 4 * 16);
+    assert_eq!(heavy.total_work(), 4 * 256 + 2 * 1024);
+    assert_eq!(heavy.stats().total_work, heavy.total_work());
+
+    assert!(matches!(heavy.replace_chain(long.chain.clone()),
+        Err(ChainError::ChainNotBetter { ours_work: 3072, theirs_work: 1344 })));
+    let out = long.replace_chain(heavy.chain.clone()).unwrap();
+    assert_eq!((out.old_height, out.new_height, out.fork_height), (11, 5, 1));
+    assert_eq!(hashes(&long), hashes(&heavy));
+    long.validate_full().unwrap();
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use mini_blockchain::blockchain::{mempool_path, Blockchain};
+use mini_blockchain::peers::{peers_path, PeerSet};
+use mini_blockchain::server::shutdown;
+use mini_blockchain::wallet::Wallet;
+
+use common::{fund, test_state};
+
+#[actix_web::test]
+async fn a_restart_picks_up_what_shutdown_wrote() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    let bob = Wallet::new().address();
+    fund(&mut bc, &alice.address());
+    let first = bc.add_transaction(alice.sign_transaction(bob.clone(), 1.0, 0.1, 0).unwrap()).unwrap();
+    let second = bc.add_transaction(alice.sign_transaction(bob.clone(), 2.0, 0.1, 1).unwrap()).unwrap();
+    let height = bc.chain.len() - 1;
+    let tip = bc.chain.last().unwrap().hash.clone();
+
+    let state = test_state(bc, "shutdown");
+    state.peers.lock().unwrap().insert("http://10.0.0.2:8080".into(), Wallet::new().address()).unwrap();
+    let flushed = shutdown(&state).await;
+    assert_eq!((flushed.height, flushed.mempool, flushed.peers), (height, 2, 1));
+
+    // restart as-is: the chain, both pending transfers and the peer come back
+    let mempool_file = mempool_path(&state.chain_file);
+    let mut again = Blockchain::load_from_file(&state.chain_file).unwrap();
+    assert_eq!(again.chain.last().unwrap().hash, tip);
+    assert_eq!(again.restore_mempool(&mempool_file).unwrap(), (2, 0));
+    assert!(again.mempool.get(&first).is_some() && again.mempool.get(&second).is_some());
+    let mut peers = PeerSet::new(Wallet::new());
+    assert_eq!(peers.restore(&peers_path(&state.chain_file)).unwrap(), 1);
+    assert!(peers.contains("http://10.0.0.2:8080"));
+
+    // the chain moved on while the node was down: the first got mined, and
+    // only the second is still worth restoring
+    again.mempool.remove(&second);
+    again.mine_pending_transactions(alice.address(), false).unwrap();
+    again.save_to_file(&state.chain_file).unwrap();
+    let mut third = Blockchain::load_from_file(&state.chain_file).unwrap();
+    assert_eq!(third.restore_mempool(&mempool_file).unwrap(), (1, 1));
+    assert!(third.mempool.get(&second).is_some());
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::store::{ChainStore, JsonStore, SqliteStore, STORE_PAGE_BLOCKS};
+use mini_blockchain::wallet::Wallet;
+
+use common::{copy_chain, fund, temp_path};
+
+// a chain with a transfer in most blocks, so there's something to compress
+fn busy_chain(blocks: u64) -> (Blockchain, Wallet, Wallet) {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.retarget_interval = 0;
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    let bob = Wallet::new();
+    fund(&mut bc, &alice.address());
+    for nonce in 0..blocks {
+        bc.add_transaction(alice.sign_transaction(bob.address(), 0.1, 0.01, nonce).unwrap()).unwrap();
+        bc.mine_pending_transactions(alice.address(), false).unwrap();
+    }
+    (bc, alice, bob)
+}
+
+#[test]
+fn compressed_and_plain_files_load_the_same_chain() {
+    let (bc, alice, bob) = busy_chain(40);
+    let plain = temp_path("compress.json");
+    let zst = temp_path("compress.json.zst");
+    bc.save_to_file(&plain).unwrap();
+    bc.save_to_file(&zst).unwrap();
+    assert_eq!(&std::fs::read(&zst).unwrap()[..4], &[0x28, 0xb5, 0x2f, 0xfd]);
+    assert!(std::fs::metadata(&zst).unwrap().len() < std::fs::metadata(&plain).unwrap().len());
+
+    // the first bytes decide, not the name
+    let renamed = temp_path("compress-renamed.json");
+    std::fs::copy(&zst, &renamed).unwrap();
+
+    let expected = serde_json::to_string(&bc.chain).unwrap();
+    for path in [&plain, &zst, &renamed] {
+        let loaded = Blockchain::load_from_file(path).unwrap();
+        assert_eq!(serde_json::to_string(&loaded.chain).unwrap(), expected, "{}", path);
+        assert_eq!(loaded.get_balance_nits(&bob.address()).unwrap(), bc.get_balance_nits(&bob.address()).unwrap());
+        assert_eq!(loaded.next_nonce(&alice.address()), bc.next_nonce(&alice.address()));
+        loaded.validate_full().unwrap();
+    }
+    for path in [plain, zst, renamed] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[test]
+fn truncated_compressed_file_is_refused() {
+    let (bc, _, _) = busy_chain(5);
+    let zst = temp_path("compress-cut.json.zst");
+    bc.save_to_file(&zst).unwrap();
+    let bytes = std::fs::read(&zst).unwrap();
+    std::fs::write(&zst, &bytes[..bytes.len() / 2]).unwrap();
+    assert!(Blockchain::load_from_file(&zst).is_err());
+    let _ = std::fs::remove_file(&zst);
+}
+
+fn hashes(store: &dyn ChainStore) -> Vec<String> {
+    store.iter().map(|b| b.unwrap().hash).collect()
+}
+
+#[test]
+fn sqlite_store_gives_back_what_was_appended() {
+    let (mut bc, alice, bob) = busy_chain(5);
+    // more than a page, so iter() has to go back for the rest
+    while bc.chain.len() <= STORE_PAGE_BLOCKS + 3 {
+        bc.mine_pending_transactions(alice.address(), true).unwrap();
+    }
+    let mut store = SqliteStore::in_memory().unwrap();
+    assert_eq!(bc.write_to_store(&mut store).unwrap(), bc.chain.len());
+    assert_eq!(store.len().unwrap(), bc.chain.len());
+    assert_eq!(hashes(&store), bc.chain.iter().map(|b| b.hash.clone()).collect::<Vec<_>>());
+    assert_eq!(store.tip().unwrap().unwrap().hash, bc.chain.last().unwrap().hash);
+    let third = &bc.chain[3];
+    assert_eq!(serde_json::to_string(&store.get_block_by_index(3).unwrap().unwrap()).unwrap(),
+               serde_json::to_string(third).unwrap());
+    assert_eq!(store.get_block_by_hash(&third.hash).unwrap().unwrap().index, 3);
+    assert!(store.get_block_by_index(bc.chain.len()).unwrap().is_none());
+
+    // only the next height goes on top
+    assert!(store.append_block(&bc.chain[2]).is_err());
+    // and saving an unchanged chain writes nothing
+    assert_eq!(bc.write_to_store(&mut store).unwrap(), 0);
+
+    let loaded = Blockchain::from_store(Box::new(store)).unwrap();
+    assert_eq!(serde_json::to_string(&loaded.chain).unwrap(), serde_json::to_string(&bc.chain).unwrap());
+    assert_eq!(loaded.get_balance_nits(&bob.address()).unwrap(), bc.get_balance_nits(&bob.address()).unwrap());
+}
+
+#[test]
+fn a_reorg_truncates_the_store_back_to_the_fork() {
+    let (mut ours, alice, _) = busy_chain(3);
+    let mut theirs = copy_chain(&ours, "store-reorg.json");
+    let base = ours.chain.len();
+    let json = temp_path("store-reorg-store.json");
+    let stores: [Box<dyn ChainStore>; 2] = [Box::new(SqliteStore::in_memory().unwrap()), Box::new(JsonStore::open(&json).unwrap())];
+
+    ours.mine_pending_transactions(alice.address(), true).unwrap();
+    ours.mine_pending_transactions(alice.address(), true).unwrap();
+    let carol = Wallet::new();
+    for _ in 0..3 {
+        theirs.mine_pending_transactions(carol.address(), true).unwrap();
+    }
+    for mut store in stores {
+        ours.write_to_store(store.as_mut()).unwrap();
+        let replaced = ours.chain[base].hash.clone();
+        // only the blocks past the fork are rewritten
+        assert_eq!(theirs.write_to_store(store.as_mut()).unwrap(), 3);
+        assert_eq!(hashes(store.as_ref()), theirs.chain.iter().map(|b| b.hash.clone()).collect::<Vec<_>>());
+        assert!(store.get_block_by_hash(&replaced).unwrap().is_none());
+
+        store.truncate(base).unwrap();
+        assert_eq!(store.len().unwrap(), base);
+        assert_eq!(store.tip().unwrap().unwrap().hash, ours.chain[base - 1].hash);
+    }
+    let _ = std::fs::remove_file(json);
+}
+
+#[test]
+fn pruning_a_store_keeps_the_headers() {
+    let (bc, _, _) = busy_chain(6);
+    let json = temp_path("store-prune.json");
+    let stores: [Box<dyn ChainStore>; 2] = [Box::new(SqliteStore::in_memory().unwrap()), Box::new(JsonStore::open(&json).unwrap())];
+    for mut store in stores {
+        bc.write_to_store(store.as_mut()).unwrap();
+        store.prune_below(4).unwrap();
+        for (stored, block) in store.iter().map(Result::unwrap).zip(&bc.chain) {
+            assert_eq!(stored.hash, block.hash);
+            assert_eq!(stored.merkle_root, block.merkle_root);
+            match stored.index < 4 {
+                true  => assert!(stored.transactions.is_empty(), "#{}", stored.index),
+                false => assert_eq!(stored.transactions.len(), block.transactions.len(), "#{}", stored.index),
+            }
+        }
+        assert!(store.get_block_by_index(2).unwrap().unwrap().transactions.is_empty());
+    }
+    let _ = std::fs::remove_file(json);
+}
+
+#[test]
+fn a_chain_file_imports_into_an_empty_database_once() {
+    let (bc, alice, bob) = busy_chain(8);
+    let file = temp_path("import.json.zst");
+    let db = temp_path("import.sqlite");
+    bc.save_to_file(&file).unwrap();
+
+    let mut store = SqliteStore::open(&db).unwrap();
+    assert_eq!(store.import_from_json(&file).unwrap(), bc.chain.len());
+    let again = store.import_from_json(&file).unwrap_err();
+    assert!(again.contains("already holds a chain"), "{}", again);
+    drop(store);
+
+    let loaded = Blockchain::from_store(Box::new(SqliteStore::open(&db).unwrap())).unwrap();
+    assert_eq!(serde_json::to_string(&loaded.chain).unwrap(), serde_json::to_string(&bc.chain).unwrap());
+    assert_eq!(loaded.difficulty, bc.difficulty);
+    assert_eq!(loaded.coinbase_maturity, 1);
+    for who in [alice.address(), bob.address()] {
+        assert_eq!(loaded.get_balance_nits(&who).unwrap(), bc.get_balance_nits(&who).unwrap());
+        assert_eq!(loaded.next_nonce(&who), bc.next_nonce(&who));
+    }
+
+    // a file that doesn't load isn't imported at all
+    std::fs::write(&file, b"{\"chain\": []}").unwrap();
+    let mut empty = SqliteStore::in_memory().unwrap();
+    assert!(empty.import_from_json(&file).is_err());
+    assert!(empty.is_empty().unwrap());
+    drop(loaded);
+    for path in [file, db.clone(), format!("{}-wal", db), format!("{}-shm", db)] {
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,607 @@
+mod common;
+
+use mini_blockchain::block::Block;
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::error::ChainError;
+use mini_blockchain::transaction::{parse_tokens, Transaction, BURN_ADDRESS};
+use mini_blockchain::wallet::Wallet;
+
+use common::fund;
+
+#[test]
+fn forged_network_transaction_is_refused() {
+    let mut bc = Blockchain::with_prefix("0");
+    let mallory = Wallet::new();
+
+    let unsigned = Transaction::new_nits("NETWORK".into(), mallory.address(), 1_000_000);
+    assert!(matches!(bc.add_transaction(unsigned), Err(ChainError::NetworkTransaction)));
+    // signing it with some key doesn't make it the network's
+    let mut signed = Transaction::new_nits("NETWORK".into(), mallory.address(), 1_000_000);
+    let _ = signed.sign(&mallory);
+    assert!(bc.add_transaction(signed).is_err());
+
+    assert!(bc.mempool.is_empty());
+    assert_eq!(bc.get_balance_nits(&mallory.address()).unwrap(), 0);
+}
+
+#[test]
+fn replace_by_fee_needs_a_higher_fee() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    let bob = Wallet::new().address();
+    fund(&mut bc, &alice.address());
+    let stuck = bc.add_transaction(alice.sign_transaction(bob.clone(), 1.0, 0.1, 0).unwrap()).unwrap();
+
+    // same fee, or less: the pending one stays
+    for fee in [0.1, 0.05] {
+        let err = bc.add_transaction(alice.sign_transaction(bob.clone(), 2.0, fee, 0).unwrap()).unwrap_err();
+        assert!(matches!(err, ChainError::ReplacementFeeTooLow { old: 100, .. }), "{}", err);
+    }
+    assert_eq!(bc.mempool.txids().collect::<Vec<_>>(), vec![stuck.as_str()]);
+
+    // more: it takes the original's place
+    let bumped = bc.add_transaction(alice.sign_transaction(bob.clone(), 1.0, 0.2, 0).unwrap()).unwrap();
+    assert_eq!(bc.mempool.len(), 1);
+    assert!(bc.mempool.get(&stuck).is_none());
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    assert!(bc.find_transaction(&stuck).is_none());
+    assert!(bc.find_transaction(&bumped).is_some());
+    assert_eq!(bc.get_balance(&bob).unwrap(), 1.0);
+}
+
+#[test]
+fn replacement_has_to_be_affordable() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let balance = bc.get_balance(&alice.address()).unwrap();
+    let pending = bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 0).unwrap()).unwrap();
+    let too_much = alice.sign_transaction(Wallet::new().address(), balance, 0.2, 0).unwrap();
+    assert!(matches!(bc.add_transaction(too_much), Err(ChainError::InsufficientFunds { .. })));
+    assert!(bc.mempool.get(&pending).is_some());
+}
+
+#[test]
+fn mempool_bytes_follow_every_change() {
+    let mut bc = Blockchain::with_prefix("0");
+    let (alice, carol) = (Wallet::new(), Wallet::new());
+    fund(&mut bc, &alice.address());
+    fund(&mut bc, &carol.address());
+    let recount = |bc: &Blockchain| bc.mempool.iter().map(Transaction::size).sum::<usize>();
+
+    for nonce in 0..3 {
+        bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, nonce).unwrap()).unwrap();
+    }
+    // a second output makes the replacement a different size
+    let mut bumped = Transaction::new_multi(alice.address(), vec![(Wallet::new().address(), 0.5), (Wallet::new().address(), 0.5)]).unwrap();
+    (bumped.fee, bumped.nonce) = (500, 1);
+    bumped.sign(&alice).unwrap();
+    bc.add_transaction(bumped).unwrap();
+    assert_eq!(bc.mempool.len(), 3);
+    assert_eq!(bc.mempool_bytes(), recount(&bc));
+
+    // carol's only fits by evicting alice's cheapest tip
+    bc.max_mempool_txs = 3;
+    bc.add_transaction(carol.sign_transaction(Wallet::new().address(), 1.0, 0.9, 0).unwrap()).unwrap();
+    assert_eq!(bc.mempool.len(), 3);
+    assert_eq!(bc.mempool_bytes(), recount(&bc));
+
+    bc.max_transactions_per_block = 2;
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    assert_eq!(bc.mempool.len(), 1);
+    assert_eq!(bc.mempool_bytes(), recount(&bc));
+}
+
+#[test]
+fn a_full_pool_evicts_the_cheapest_sender_tip() {
+    let mut bc = Blockchain::with_prefix("0");
+    let wallets: Vec<Wallet> = (0..6).map(|_| Wallet::new()).collect();
+    for wallet in &wallets {
+        fund(&mut bc, &wallet.address());
+    }
+    let [alice, bob, carol, dave, erin, frank] = &wallets[..] else { unreachable!() };
+    let pay = |from: &Wallet, fee: f64, nonce: u64| from.sign_transaction(Wallet::new().address(), 1.0, fee, nonce).unwrap();
+    let pending = |bc: &Blockchain| {
+        let mut ids: Vec<String> = bc.mempool.txids().map(String::from).collect();
+        ids.sort();
+        ids
+    };
+    let sorted = |mut ids: Vec<&String>| {
+        ids.sort();
+        ids.into_iter().cloned().collect::<Vec<_>>()
+    };
+
+    let a0 = bc.add_transaction(pay(alice, 0.1, 0)).unwrap();
+    let a1 = bc.add_transaction(pay(alice, 0.9, 1)).unwrap();
+    bc.add_transaction(pay(bob, 0.3, 0)).unwrap();
+    bc.max_mempool_txs = 3;
+
+    // alice's nonce 0 is the cheapest, but evicting it would strand her nonce 1
+    assert!(matches!(bc.add_transaction(pay(carol, 0.2, 0)), Err(ChainError::MempoolFull)));
+    let c0 = bc.add_transaction(pay(carol, 0.5, 0)).unwrap();
+    assert_eq!(pending(&bc), sorted(vec![&a0, &a1, &c0]));
+
+    let d0 = bc.add_transaction(pay(dave, 0.95, 0)).unwrap();
+    assert_eq!(pending(&bc), sorted(vec![&a0, &a1, &d0]));
+    // once her nonce 1 is gone, her nonce 0 is a tip too
+    let e0 = bc.add_transaction(pay(erin, 2.0, 0)).unwrap();
+    assert_eq!(pending(&bc), sorted(vec![&a0, &d0, &e0]));
+    let f0 = bc.add_transaction(pay(frank, 3.0, 0)).unwrap();
+    assert_eq!(pending(&bc), sorted(vec![&d0, &e0, &f0]));
+}
+
+#[test]
+fn duplicate_transactions_are_refused() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let txn = alice.sign_transaction(Wallet::new().address(), 1.0, 0.0, 0).unwrap();
+
+    let txid = bc.add_transaction(txn.clone()).unwrap();
+    assert!(matches!(bc.add_transaction(txn.clone()), Err(ChainError::AlreadyInMempool)));
+    assert_eq!(bc.mempool.len(), 1);
+
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    let height = bc.chain.len() - 1;
+    let err = bc.add_transaction(txn).unwrap_err();
+    assert!(matches!(err, ChainError::AlreadyConfirmed { index } if index == height), "{}", err);
+    assert!(bc.mempool.is_empty());
+    assert!(bc.find_transaction(&txid).is_some());
+}
+
+#[test]
+fn same_transfer_with_the_next_nonce_is_not_a_duplicate() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    let bob = Wallet::new().address();
+    fund(&mut bc, &alice.address());
+    let first = bc.add_transaction(alice.sign_transaction(bob.clone(), 1.0, 0.0, 0).unwrap()).unwrap();
+    let second = bc.add_transaction(alice.sign_transaction(bob, 1.0, 0.0, 1).unwrap()).unwrap();
+    assert_ne!(first, second);
+    assert_eq!(bc.mempool.len(), 2);
+}
+
+#[test]
+fn block_rewards_unlock_after_coinbase_maturity() {
+    let mut bc = Blockchain::with_prefix("0");
+    assert_eq!(bc.coinbase_maturity, 5);
+    let miner = Wallet::new();
+    bc.mine_pending_transactions(miner.address(), true).unwrap();
+    let reward_height = bc.chain.len() - 1;
+    assert!(bc.immature_balance(&miner.address(), bc.chain.len()) > 0);
+
+    let spend = miner.sign_transaction(Wallet::new().address(), 10.0, 0.0, 0).unwrap();
+    for needed in (1..=4).rev() {
+        let err = bc.add_transaction(spend.clone()).unwrap_err();
+        assert!(matches!(err, ChainError::ImmatureCoinbase { confirmations_needed } if confirmations_needed == needed), "{}", err);
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    assert_eq!(bc.chain.len() - 1, reward_height + 4);
+    assert_eq!(bc.immature_balance(&miner.address(), bc.chain.len()), 0);
+    bc.add_transaction(spend).unwrap();
+    bc.mine_pending_transactions(Wallet::new().address(), false).unwrap();
+    bc.validate().unwrap();
+}
+
+#[test]
+fn block_spending_an_immature_reward_is_invalid() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.retarget_interval = 0;
+    let miner = Wallet::new();
+    bc.mine_pending_transactions(miner.address(), true).unwrap();
+    // mining leaves the spend out, so build the block by hand
+    bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    let mut block = bc.chain.pop().unwrap();
+    block.transactions.insert(0, miner.sign_transaction(Wallet::new().address(), 10.0, 0.0, 0).unwrap());
+    block.merkle_root = Block::compute_merkle_root(&block.transactions);
+    let difficulty = block.difficulty;
+    block.mine(difficulty).unwrap();
+
+    let mut blocks = bc.chain.clone();
+    blocks.push(block);
+    let err = Blockchain::from_blocks(blocks).err().unwrap();
+    assert!(err.contains("coinbase not mature"), "{}", err);
+}
+
+// every field, compared through the JSON the node would send
+fn fields(txn: &Transaction) -> serde_json::Value {
+    serde_json::to_value(txn).unwrap()
+}
+
+#[test]
+fn raw_hex_round_trips() {
+    let alice = Wallet::new();
+    let plain = alice.sign_transaction(Wallet::new().address(), 2.5, 0.2, 7).unwrap();
+
+    let mut everything = Transaction::new_multi(alice.address(), vec![
+        (Wallet::new().address(), 1.0),
+        (Wallet::new().address(), 0.25),
+    ]).unwrap();
+    everything.fee = 50_000;
+    everything.nonce = 3;
+    everything.memo = Some("rent — März".into());
+    everything.lock_until_block = Some(42);
+    everything.sign(&alice).unwrap();
+
+    let unsigned = Transaction::new(alice.address(), Wallet::new().address(), 1.0).unwrap();
+
+    for txn in [plain, everything, unsigned] {
+        let back = Transaction::from_hex(&txn.to_hex().unwrap()).unwrap();
+        assert_eq!(fields(&back), fields(&txn));
+        assert_eq!(back.id(), txn.id());
+        assert_eq!(back.to_hex().unwrap(), txn.to_hex().unwrap());
+    }
+}
+
+#[test]
+fn broken_raw_hex_is_refused() {
+    let alice = Wallet::new();
+    let raw = alice.sign_transaction(Wallet::new().address(), 2.5, 0.2, 7).unwrap().to_hex().unwrap();
+    for broken in [&raw[..raw.len() - 4], &raw[..raw.len() - 1], "zz", "", &format!("{}00", raw)] {
+        assert!(matches!(Transaction::from_hex(broken), Err(ChainError::MalformedTransaction(_))), "{:?}", broken);
+    }
+
+    // well-formed but tampered with: decodes, and the signature gives it away
+    let mut tampered = Transaction::from_hex(&raw).unwrap();
+    tampered.outputs[0].amount += 1;
+    let tampered = Transaction::from_hex(&tampered.to_hex().unwrap()).unwrap();
+    assert!(tampered.validate().is_err());
+}
+
+#[test]
+fn timelocked_transaction_waits_for_its_block() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    let miner = Wallet::new().address();
+    fund(&mut bc, &alice.address());
+    let mut locked = Transaction::new(alice.address(), Wallet::new().address(), 1.0).unwrap();
+    locked.lock_until_block = Some(5);
+    locked.sign(&alice).unwrap();
+    // the lock is signed along with the rest
+    let mut unlocked = locked.clone();
+    unlocked.lock_until_block = None;
+    assert!(matches!(unlocked.validate(), Err(ChainError::InvalidSignature)));
+
+    let txid = bc.add_transaction(locked).unwrap();
+    for height in 2..5 {
+        bc.mine_pending_transactions(miner.clone(), true).unwrap();
+        assert_eq!(bc.chain.len() - 1, height);
+        assert!(bc.mempool.get(&txid).is_some(), "mined into #{}", height);
+    }
+    bc.mine_pending_transactions(miner.clone(), false).unwrap();
+    assert!(bc.mempool.is_empty());
+    assert_eq!(bc.chain[5].transactions[0].id(), txid);
+    bc.validate_full().unwrap();
+
+    // moved a block early by hand, it makes that block invalid
+    let moved = bc.chain[5].transactions.remove(0);
+    bc.chain.truncate(5);
+    let early = &mut bc.chain[4];
+    early.transactions.insert(0, moved);
+    early.merkle_root = Block::compute_merkle_root(&early.transactions);
+    let bits = early.difficulty;
+    early.mine(bits).unwrap();
+    assert!(matches!(bc.validate_full(), Err(ChainError::TransactionLocked { index: 4, position: 0, until: 5 })));
+}
+
+#[test]
+fn fees_go_to_the_miner_and_come_out_of_the_sender() {
+    let mut bc = Blockchain::with_prefix("0");
+    let (alice, bob, carol, miner) = (Wallet::new(), Wallet::new(), Wallet::new(), Wallet::new());
+    fund(&mut bc, &alice.address());
+    fund(&mut bc, &carol.address());
+    let (alice_before, supply_before) = (bc.get_balance_nits(&alice.address()).unwrap(), bc.total_supply());
+
+    bc.add_transaction(alice.sign_transaction(bob.address(), 1.0, 0.25, 0).unwrap()).unwrap();
+    bc.add_transaction(carol.sign_transaction(bob.address(), 1.0, 0.0, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(miner.address(), false).unwrap();
+
+    let block = bc.chain.last().unwrap();
+    let subsidy = bc.reward_at(block.index);
+    assert_eq!(block.total_fees(), 250);
+    assert_eq!(block.coinbase_amount(), subsidy + 250);
+    assert_eq!(bc.get_balance_nits(&alice.address()).unwrap(), alice_before - 1_250);
+    assert_eq!(bc.get_balance_nits(&bob.address()).unwrap(), 2_000);
+    assert_eq!(bc.immature_balance(&miner.address(), bc.chain.len()), subsidy + 250);
+    // fees move coins, they don't make any
+    assert_eq!(bc.total_supply(), supply_before + subsidy);
+    bc.validate_full().unwrap();
+}
+
+#[test]
+fn the_fee_is_signed_and_hashed() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let paid = alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 0).unwrap();
+    let mut cheaper = paid.clone();
+    cheaper.fee = 0;
+    assert_ne!(paid.message_to_sign(), cheaper.message_to_sign());
+    assert!(matches!(cheaper.validate(), Err(ChainError::InvalidSignature)));
+    assert_eq!(Transaction::new_with_fee(alice.address(), Wallet::new().address(), 1.0, 0.1).unwrap().fee, 100);
+
+    bc.add_transaction(paid).unwrap();
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    let tip = bc.chain.len() - 1;
+    bc.chain[tip].transactions[0].fee = 0;
+    assert!(bc.validate().is_err());
+}
+
+#[test]
+fn only_the_next_nonce_gets_in() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    let bob = Wallet::new().address();
+    fund(&mut bc, &alice.address());
+    assert_eq!(bc.next_nonce(&alice.address()), 0);
+
+    let skipped = alice.sign_transaction(bob.clone(), 1.0, 0.0, 1).unwrap();
+    assert_eq!(bc.add_transaction(skipped).unwrap_err(), ChainError::BadNonce { expected: 0, got: 1 });
+    bc.add_transaction(alice.sign_transaction(bob.clone(), 1.0, 0.0, 0).unwrap()).unwrap();
+    // pending ones count
+    assert_eq!(bc.next_nonce(&alice.address()), 1);
+    bc.add_transaction(alice.sign_transaction(bob.clone(), 1.0, 0.0, 1).unwrap()).unwrap();
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    assert_eq!(bc.next_nonce(&alice.address()), 2);
+
+    // the same nonce again, even as a new transfer, is a replay
+    let stale = alice.sign_transaction(bob.clone(), 3.0, 0.0, 0).unwrap();
+    assert_eq!(bc.add_transaction(stale).unwrap_err(), ChainError::BadNonce { expected: 2, got: 0 });
+    // and it's signed, so it can't be bumped after the fact
+    let mut bumped = alice.sign_transaction(bob, 1.0, 0.0, 0).unwrap();
+    bumped.nonce = 2;
+    assert_eq!(bc.add_transaction(bumped).unwrap_err(), ChainError::InvalidSignature);
+    assert!(bc.mempool.is_empty());
+}
+
+#[test]
+fn txids_are_stable_and_tell_transfers_apart() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    let bob = Wallet::new().address();
+    fund(&mut bc, &alice.address());
+    let txn = alice.sign_transaction(bob.clone(), 1.0, 0.0, 0).unwrap();
+    let txid = txn.id();
+    assert_eq!(txid.len(), 64);
+    assert!(txid.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(txn.clone().id(), txid);
+    assert_eq!(Transaction::from_hex(&txn.to_hex().unwrap()).unwrap().id(), txid);
+    assert!(txn.display().starts_with(&txid[..12]));
+
+    // same parties, same amount — but not the same transaction
+    let others = [
+        alice.sign_transaction(bob.clone(), 1.0, 0.0, 1).unwrap(),
+        alice.sign_transaction(bob.clone(), 1.0, 0.1, 0).unwrap(),
+        Transaction::new(alice.address(), bob, 1.0).unwrap(),
+    ];
+    for other in &others {
+        assert_ne!(other.id(), txid);
+    }
+
+    assert_eq!(bc.add_transaction(txn).unwrap(), txid);
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    let found = bc.find_transaction(&txid).unwrap();
+    assert_eq!((found.block_index, found.transaction.id()), (Some(bc.chain.len() - 1), txid));
+}
+
+#[test]
+fn pending_spends_count_against_the_balance() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let balance = bc.get_balance_nits(&alice.address()).unwrap();
+    let half = balance as f64 / 2_000.0;
+
+    bc.add_transaction(alice.sign_transaction(Wallet::new().address(), half, 0.0, 0).unwrap()).unwrap();
+    bc.add_transaction(alice.sign_transaction(Wallet::new().address(), half, 0.0, 1).unwrap()).unwrap();
+    // each fits on its own, a third doesn't fit next to the first two
+    let third = alice.sign_transaction(Wallet::new().address(), 0.001, 0.0, 2).unwrap();
+    let err = bc.add_transaction(third).unwrap_err();
+    assert_eq!(err, ChainError::InsufficientFunds { have: balance, need: balance + 1 });
+    assert!(err.to_string().starts_with("insufficient funds: have"), "{}", err);
+    assert_eq!(bc.mempool.len(), 2);
+
+    bc.mine_pending_transactions(Wallet::new().address(), false).unwrap();
+    assert_eq!(bc.get_balance_nits(&alice.address()).unwrap(), 0);
+    bc.validate_full().unwrap();
+}
+
+#[test]
+fn json_round_trips_blocks_and_transactions() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.5, 0.1, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    let block = bc.chain.last().unwrap();
+
+    let json = serde_json::to_value(block).unwrap();
+    let transfer = &json["transactions"][0];
+    let signature = transfer["signature_hex"].as_str().unwrap();
+    assert_eq!(signature.len(), 128);
+    assert_eq!(signature, signature.to_lowercase());
+    assert_eq!((transfer["amount"].clone(), transfer["amount_tokens"].clone()), (serde_json::json!(1500), serde_json::json!(1.5)));
+    assert!(json["timestamp"].is_u64());
+
+    let back: Block = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(back.calculate_hash(), block.hash);
+    assert_eq!(serde_json::to_value(&back).unwrap(), json);
+
+    // amount_tokens is for reading; nits decide
+    let mut edited = transfer.clone();
+    edited["amount_tokens"] = serde_json::json!(99.0);
+    let txn: Transaction = serde_json::from_value(edited).unwrap();
+    assert_eq!(txn.amount(), 1500);
+    assert_eq!(txn.id(), block.transactions[0].id());
+    let unsigned = serde_json::to_value(Transaction::new(alice.address(), Wallet::new().address(), 1.0).unwrap()).unwrap();
+    assert!(unsigned["signature_hex"].is_null());
+}
+
+#[test]
+fn amounts_that_dont_fit_in_nits_are_refused() {
+    let (alice, bob) = (Wallet::new().address(), Wallet::new().address());
+    let refused = [
+        (-1.0, "negative"), (f64::NAN, "not a number"), (f64::INFINITY, "finite"),
+        (0.0004, "precision"), (1e17, "too large"),
+    ];
+    for (tokens, why) in refused {
+        let err = Transaction::new(alice.clone(), bob.clone(), tokens).err().unwrap();
+        assert!(err.contains(why), "{}: {}", tokens, err);
+    }
+    let err = Transaction::new_with_fee(alice.clone(), bob.clone(), 1.0, -0.5).err().unwrap();
+    assert!(err.starts_with("fee"), "{}", err);
+    assert_eq!(Transaction::new(alice, bob, 2.345).unwrap().amount(), 2345);
+
+    // a decimal string never goes near f64
+    for (text, nits) in [("2.5", 2500), ("12", 12_000), (".001", 1), ("0.1000", 100), ("18446744073709551.615", u64::MAX)] {
+        assert_eq!(parse_tokens(text, "amount"), Ok(nits), "{}", text);
+    }
+    for text in ["-1", "1.0001", "1e3", "", ".", "2.5.1", "18446744073709552"] {
+        assert!(parse_tokens(text, "amount").is_err(), "{:?}", text);
+    }
+}
+
+#[test]
+fn garbage_recipients_are_refused_before_the_mempool() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let garbage = ["bob", "", "abc", "é", &"zz".repeat(32), &"ab".repeat(31), &"02".repeat(32)];
+    for (nonce, to) in garbage.into_iter().enumerate() {
+        let txn = alice.sign_transaction(to.to_string(), 1.0, 0.0, nonce as u64).unwrap();
+        // short or multi-byte, display() cuts it without panicking
+        assert!(txn.display().contains(&to.chars().take(10).collect::<String>()), "{:?}", to);
+        assert!(matches!(txn.validate(), Err(ChainError::InvalidAddress(ref why)) if why.starts_with("recipient")), "{:?}", to);
+        assert!(matches!(bc.add_transaction(txn), Err(ChainError::InvalidAddress(_))), "{:?}", to);
+    }
+    assert!(bc.mempool.is_empty());
+
+    // a real key and the burn address are fine
+    for (nonce, to) in [Wallet::new().address(), BURN_ADDRESS.to_string()].into_iter().enumerate() {
+        bc.add_transaction(alice.sign_transaction(to, 1.0, 0.0, nonce as u64).unwrap()).unwrap();
+    }
+}
+
+#[test]
+fn memos_are_signed_capped_and_paid_for() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let with_memo = |memo: &str, fee: f64| {
+        let mut txn = Transaction::new_with_fee(alice.address(), Wallet::new().address(), 1.0, fee).unwrap();
+        txn.memo = Some(memo.to_string());
+        txn.sign(&alice).unwrap();
+        txn
+    };
+
+    let txn = with_memo("invoice 42", 0.01);
+    txn.validate().unwrap();
+    assert!(txn.display().contains("memo \"invoice 42\""), "{}", txn.display());
+    assert_eq!(serde_json::to_value(&txn).unwrap()["memo"], "invoice 42");
+    let mut edited = txn.clone();
+    edited.memo = Some("invoice 43".into());
+    assert_eq!(edited.validate(), Err(ChainError::InvalidSignature));
+
+    // one nit of fee per byte, and no more than 256 of them
+    assert_eq!(with_memo("invoice 42", 0.005).validate(), Err(ChainError::MemoFeeTooLow { need: 10, got: 5 }));
+    assert_eq!(with_memo(&"x".repeat(257), 1.0).validate(), Err(ChainError::MemoTooLong { len: 257, max: 256 }));
+    with_memo(&"x".repeat(256), 0.256).validate().unwrap();
+
+    // bytes that aren't UTF-8 can only arrive raw, and don't decode
+    let mut raw = hex::decode(with_memo("ab", 0.01).to_hex().unwrap()).unwrap();
+    let memo_at = raw.len() - 65 - 2;
+    raw[memo_at..memo_at + 2].copy_from_slice(&[0xff, 0xfe]);
+    assert!(matches!(Transaction::from_hex(&hex::encode(raw)), Err(ChainError::MalformedTransaction(why)) if why.contains("UTF-8")));
+
+    // once mined, the block commits to it too
+    bc.add_transaction(txn).unwrap();
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    let tip = bc.chain.len() - 1;
+    bc.chain[tip].transactions[0].memo = Some("invoice 43".into());
+    assert!(bc.validate_full().is_err());
+}
+
+#[test]
+fn one_signature_pays_several_recipients() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    let before = bc.get_balance_nits(&alice.address()).unwrap();
+    let (bob, carol, dave) = (Wallet::new().address(), Wallet::new().address(), Wallet::new().address());
+    let mut txn = Transaction::new_multi(alice.address(), vec![(bob.clone(), 1.0), (carol.clone(), 2.0), (dave.clone(), 0.5)]).unwrap();
+    txn.fee = 100;
+    txn.sign(&alice).unwrap();
+    assert_eq!(txn.amount(), 3500);
+    assert!(txn.display().contains(&format!("{}... 2", &carol[..10])), "{}", txn.display());
+    let json = serde_json::to_value(&txn).unwrap();
+    assert_eq!((json["outputs"].as_array().unwrap().len(), json["to"].clone()), (3, serde_json::Value::Null));
+
+    // the signature covers every output
+    let mut redirected = txn.clone();
+    redirected.outputs[2].to = Wallet::new().address();
+    assert_eq!(redirected.validate(), Err(ChainError::InvalidSignature));
+
+    bc.add_transaction(txn).unwrap();
+    bc.mine_pending_transactions(Wallet::new().address(), false).unwrap();
+    assert_eq!(bc.get_balance_nits(&alice.address()).unwrap(), before - 3600);
+    for (who, nits) in [(&bob, 1000), (&carol, 2000), (&dave, 500)] {
+        assert_eq!(bc.get_balance_nits(who).unwrap(), nits);
+    }
+    bc.validate_full().unwrap();
+
+    // new() is the one-output case, and the list can't be empty, zero or repeat itself
+    assert_eq!(Transaction::new(alice.address(), bob.clone(), 1.0).unwrap().outputs.len(), 1);
+    let signed = |outputs: Vec<(String, f64)>| {
+        let mut txn = Transaction::new_multi(alice.address(), outputs).unwrap();
+        txn.sign(&alice).unwrap();
+        txn.validate()
+    };
+    assert_eq!(signed(vec![]), Err(ChainError::NoOutputs));
+    assert_eq!(signed(vec![(bob.clone(), 1.0), (carol.clone(), 0.0)]), Err(ChainError::ZeroAmount));
+    assert!(matches!(signed(vec![(bob.clone(), 1.0), (bob, 2.0)]), Err(ChainError::DuplicateOutput(_))));
+}
+
+#[test]
+fn a_block_adds_up_its_fees_reward_and_payments() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let (alice, carol, miner) = (Wallet::new(), Wallet::new(), Wallet::new().address());
+    fund(&mut bc, &alice.address());
+    fund(&mut bc, &carol.address());
+    let (bob, dave) = (Wallet::new().address(), Wallet::new().address());
+    bc.add_transaction(alice.sign_transaction(bob.clone(), 1.0, 0.25, 0).unwrap()).unwrap();
+    let mut split = Transaction::new_multi(carol.address(), vec![(bob, 1.0), (dave, 2.0)]).unwrap();
+    split.fee = 100;
+    split.sign(&carol).unwrap();
+    bc.add_transaction(split).unwrap();
+    bc.mine_pending_transactions(miner, false).unwrap();
+
+    let block = bc.chain.last().unwrap();
+    let subsidy = bc.reward_at(block.index);
+    assert_eq!((block.total_fees(), block.coinbase_amount(), block.total_transferred()), (350, subsidy + 350, 4_000));
+    let shown = block.display();
+    assert!(shown.contains(&format!("Reward    : {:.3} + 0.350 fees", subsidy as f64 / 1000.0)), "{}", shown);
+    assert!(shown.contains("Moved     : 4.000"), "{}", shown);
+    assert_eq!(bc.stats().total_fees, 0.35);
+
+    // the JSON carries them, and they're ignored on the way back in
+    let json = serde_json::to_value(block).unwrap();
+    assert_eq!((json["total_fees"].as_u64(), json["coinbase_amount"].as_u64(), json["total_transferred"].as_u64()),
+        (Some(350), Some(subsidy + 350), Some(4_000)));
+    let back: Block = serde_json::from_value(json).unwrap();
+    assert!(back.is_intact());
+
+    // a coinbase-only block has nothing but its subsidy, and genesis not even that
+    assert_eq!((bc.chain[1].total_fees(), bc.chain[1].total_transferred()), (0, 0));
+    assert_eq!(bc.chain[1].coinbase_amount(), bc.reward_at(1));
+    assert_eq!(bc.chain[0].coinbase_amount(), 0);
+
+    // transactions written before fees existed load with none
+    let mut old = serde_json::to_value(&block.transactions[0]).unwrap();
+    old.as_object_mut().unwrap().remove("fee");
+    let old: Transaction = serde_json::from_value(old).unwrap();
+    assert_eq!(old.fee, 0);
+}
@@ -0,0 +1,446 @@
+mod common;
+
+use std::sync::Arc;
+
+use mini_blockchain::block::Block;
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::clock::{FixedClock, SteppingClock};
+use mini_blockchain::error::ChainError;
+use mini_blockchain::transaction::Transaction;
+use mini_blockchain::wallet::Wallet;
+
+use common::{copy_chain, fund};
+
+// alice has paid bob once, in block #2
+fn chain_with_a_transfer() -> Blockchain {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(alice.address(), false).unwrap();
+    bc.validate().unwrap();
+    bc
+}
+
+// the block bc would mine next, mined on a copy so bc itself is untouched
+fn copy_of_next_block(bc: &Blockchain, name: &str) -> Block {
+    let mut copy = copy_chain(bc, name);
+    copy.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    copy.chain.pop().unwrap()
+}
+
+#[test]
+fn rewriting_the_miner_is_caught() {
+    let mut bc = chain_with_a_transfer();
+    let tip = bc.chain.len() - 1;
+    bc.chain[tip].miner = Wallet::new().address();
+    let err = bc.validate_full().unwrap_err().to_string();
+    assert_eq!(err, format!("Block #{} hash mismatch", tip));
+}
+
+#[test]
+fn swapping_a_signature_is_caught() {
+    let mut bc = chain_with_a_transfer();
+    let tip = bc.chain.len() - 1;
+    let signature = bc.chain[tip].transactions[0].signature_hex.as_mut().unwrap();
+    *signature = signature.chars().rev().collect();
+    // the signature is part of the txid, so the merkle root no longer matches
+    let err = bc.validate_full().unwrap_err().to_string();
+    assert_eq!(err, format!("Block #{} merkle root mismatch", tip));
+
+    // dropping it altogether is no better
+    let mut bc = chain_with_a_transfer();
+    bc.chain[tip].transactions[0].signature_hex = None;
+    assert!(bc.validate_full().is_err());
+}
+
+#[test]
+fn unmined_block_is_refused() {
+    let mut bc = Blockchain::with_prefix("00");
+    let mut block = copy_of_next_block(&bc, "unmined");
+    // internally consistent, just without the work behind it
+    while block.meets_difficulty() {
+        block.nonce += 1;
+        block.hash = block.calculate_hash();
+    }
+    bc.chain.push(block);
+    assert!(matches!(bc.validate_full(), Err(ChainError::DifficultyNotMet { index: 1, difficulty: 8 })));
+}
+
+#[test]
+fn block_claiming_an_easier_difficulty_is_refused() {
+    let mut bc = Blockchain::with_prefix("00");
+    let mut block = copy_of_next_block(&bc, "easier");
+    block.mine(1).unwrap();
+    bc.chain.push(block);
+    assert!(matches!(bc.validate_full(), Err(ChainError::WrongDifficulty { index: 1, found: 1, expected: 8 })));
+}
+
+// an edit to the genesis block, given an address to pay
+type Tamper = fn(&mut Block, &str);
+
+#[test]
+fn tampered_genesis_is_named_in_the_error() {
+    let mallory = Wallet::new().address();
+    let tampers: [(&str, Tamper); 3] = [
+        ("a transfer out of nowhere", |genesis, to| {
+            genesis.transactions.push(Transaction::new_nits(to.into(), to.into(), 5));
+            genesis.merkle_root = Block::compute_merkle_root(&genesis.transactions);
+        }),
+        ("a parent", |genesis, _| genesis.previous_hash = "1".repeat(64)),
+        ("an index", |genesis, _| genesis.index = 1),
+    ];
+    for (what, tamper) in tampers {
+        let mut bc = Blockchain::with_prefix("0");
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+        tamper(&mut bc.chain[0], &mallory);
+        // re-mined and re-linked, so only the genesis rules can catch it
+        let bits = bc.chain[0].difficulty;
+        bc.chain[0].mine(bits).unwrap();
+        bc.chain[1].previous_hash = bc.chain[0].hash.clone();
+        let bits = bc.chain[1].difficulty;
+        bc.chain[1].mine(bits).unwrap();
+
+        let err = bc.validate_full().unwrap_err().to_string();
+        assert!(err.starts_with("Block #0"), "genesis with {}: {}", what, err);
+    }
+
+    // and a rewritten hash, without the work to back it
+    let mut bc = Blockchain::with_prefix("0");
+    bc.chain[0].hash = "0".repeat(64);
+    let err = bc.validate_full().unwrap_err().to_string();
+    assert!(err.starts_with("Block #0"), "{}", err);
+}
+
+#[test]
+fn validate_trusts_blocks_below_its_checkpoint() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.retarget_interval = u32::MAX;
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 0).unwrap()).unwrap();
+    while bc.chain.len() < 1000 {
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    bc.validate().unwrap();
+    let paid_in = bc.chain.iter().position(|b| b.transactions.len() > 1).unwrap();
+
+    // a forged amount deep in the chain, with no hash redone: only a full pass reads that far back
+    bc.chain[paid_in].transactions[0].outputs[0].amount += 1;
+    bc.validate().unwrap();
+    let err = bc.validate_full().unwrap_err().to_string();
+    assert!(err.starts_with(&format!("Block #{}", paid_in)), "{}", err);
+    // the failed full pass took the checkpoint with it
+    assert!(bc.validate().is_err());
+    bc.chain[paid_in].transactions[0].outputs[0].amount -= 1;
+    bc.validate().unwrap();
+
+    // rolling back below the checkpoint drops it, so the next validate() starts from genesis
+    bc.rollback(1).unwrap();
+    bc.chain[paid_in].transactions[0].outputs[0].amount += 1;
+    assert!(bc.validate().is_err());
+    bc.chain[paid_in].transactions[0].outputs[0].amount -= 1;
+    bc.validate().unwrap();
+
+    // the checkpoint names the tip; a tip that changed under it isn't trusted
+    let tip = bc.chain.len() - 1;
+    bc.chain[tip].miner = Wallet::new().address();
+    assert!(bc.validate().is_err());
+}
+
+#[test]
+fn first_bad_signature_is_reported_whichever_thread_finds_it() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.retarget_interval = u32::MAX;
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    while bc.chain.len() < 600 {
+        if [500, 550].contains(&bc.chain.len()) {
+            let nonce = bc.next_nonce(&alice.address());
+            bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, nonce).unwrap()).unwrap();
+        }
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+
+    // forge an amount in both, and redo everything the amount feeds into —
+    // merkle roots, hashes, links — so only the signatures are left wrong
+    for height in [500, 550] {
+        let block = &mut bc.chain[height];
+        block.transactions[0].outputs[0].amount += 1;
+        block.merkle_root = Block::compute_merkle_root(&block.transactions);
+    }
+    for height in 500..bc.chain.len() {
+        bc.chain[height].previous_hash = bc.chain[height - 1].hash.clone();
+        let bits = bc.chain[height].difficulty;
+        bc.chain[height].mine(bits).unwrap();
+    }
+
+    for _ in 0..5 {
+        let err = bc.validate_full().unwrap_err().to_string();
+        assert!(err.starts_with("Block #500 transaction 0"), "{}", err);
+    }
+}
+
+// block #1 on top of bc's genesis, holding exactly `transactions`
+fn hand_made_block(bc: &Blockchain, transactions: Vec<Transaction>, miner: &str) -> Block {
+    let genesis = &bc.chain[0];
+    let mut block = Block::new(1, transactions, genesis.hash.clone(), miner.into(), genesis.timestamp);
+    block.mine(bc.difficulty_for_height(1)).unwrap();
+    block
+}
+
+// what mine_pending_transactions() would put in block #1
+fn coinbase(to: &str, amount: u64) -> Transaction {
+    let mut txn = Transaction::new_nits("NETWORK".into(), to.into(), amount);
+    txn.nonce = 1;
+    txn
+}
+
+#[test]
+fn greedy_coinbases_are_refused() {
+    let miner = Wallet::new().address();
+    let reward = Blockchain::with_prefix("0").reward;
+    let greedy = [
+        ("two coinbases", vec![coinbase(&miner, 1_000_000 * reward), coinbase(&miner, reward)]),
+        ("an inflated reward", vec![coinbase(&miner, 1_000 * reward)]),
+        ("a short reward", vec![coinbase(&miner, reward - 1)]),
+        ("a reward to someone else", vec![coinbase(&Wallet::new().address(), reward)]),
+        ("no coinbase at all", vec![]),
+    ];
+    for (what, transactions) in greedy {
+        let mut bc = Blockchain::with_prefix("0");
+        let block = hand_made_block(&bc, transactions, &miner);
+        bc.chain.push(block);
+        let err = bc.validate_full().unwrap_err();
+        assert!(matches!(err, ChainError::InvalidCoinbase { index: 1, .. }), "{}: {}", what, err);
+    }
+
+    // the honest block, made the same way, is fine
+    let mut bc = Blockchain::with_prefix("0");
+    let block = hand_made_block(&bc, vec![coinbase(&miner, reward)], &miner);
+    bc.chain.push(block);
+    bc.validate_full().unwrap();
+}
+
+#[test]
+fn the_reward_halves_on_schedule_and_validate_holds_blocks_to_it() {
+    assert_eq!(Blockchain::with_prefix("0").halving_interval, 100);
+    let mut bc = Blockchain::with_prefix("0");
+    bc.halving_interval = 3;
+    let reward = bc.reward;
+    let miner = Wallet::new().address();
+    for _ in 0..7 {
+        bc.mine_pending_transactions(miner.clone(), true).unwrap();
+    }
+    let paid: Vec<u64> = bc.chain[1..].iter().map(|b| b.transactions.last().unwrap().amount()).collect();
+    assert_eq!(paid, [reward, reward, reward / 2, reward / 2, reward / 2, reward / 4, reward / 4]);
+    assert_eq!(bc.total_supply(), paid.iter().sum::<u64>());
+    bc.validate_full().unwrap();
+
+    // halved away to nothing, however far out
+    assert_eq!(bc.reward_at(3 * 64), 0);
+    assert_eq!(bc.reward_at(u32::MAX), 0);
+
+    // a chain mined at the flat rate pays too much once a halving is due
+    let mut flat = Blockchain::with_prefix("0");
+    flat.halving_interval = 0;
+    for _ in 0..3 {
+        flat.mine_pending_transactions(miner.clone(), true).unwrap();
+    }
+    flat.validate_full().unwrap();
+    flat.halving_interval = 2;
+    let err = flat.validate_full().unwrap_err();
+    assert!(matches!(err, ChainError::InvalidCoinbase { index: 2, .. }), "{}", err);
+}
+
+const START: u64 = 1_700_000_000;
+
+// twelve blocks a minute apart, starting at START
+fn evenly_spaced_chain() -> Blockchain {
+    let mut bc = Blockchain::with_clock(4, Arc::new(SteppingClock::new(START, 60)));
+    bc.retarget_interval = u32::MAX;
+    while bc.chain.len() < 13 {
+        bc.mine_pending_transactions(Wallet::new().address(), true).unwrap();
+    }
+    bc
+}
+
+fn is_bad_timestamp(result: Result<(), ChainError>, index: u32) -> bool {
+    matches!(result, Err(ChainError::BadTimestamp { index: i, .. }) if i == index)
+}
+
+#[test]
+fn blocks_too_far_ahead_of_our_clock_are_refused() {
+    let mut bc = evenly_spaced_chain();
+    let tip = bc.chain.last().unwrap().timestamp;
+    let skew = bc.max_future_skew_secs;
+
+    bc.clock = Arc::new(FixedClock(tip - skew));
+    bc.validate_full().unwrap();
+    bc.clock = Arc::new(FixedClock(tip - skew - 1));
+    assert!(is_bad_timestamp(bc.validate_full(), 12));
+
+    // a node whose clock is that far behind won't take the chain from a peer either
+    let mut behind = Blockchain::with_clock(4, Arc::new(FixedClock(START)));
+    behind.max_future_skew_secs = 60;
+    let err = behind.replace_chain(bc.chain.clone()).err().unwrap();
+    assert!(matches!(err, ChainError::BadTimestamp { index: 2, .. }), "{}", err);
+    assert_eq!(behind.chain.len(), 1);
+}
+
+#[test]
+fn blocks_older_than_the_recent_median_are_refused() {
+    let mut bc = evenly_spaced_chain();
+    bc.clock = Arc::new(FixedClock(START + 3600));
+    // the 11 before the tip are blocks #1..=#11, and their median is #6's
+    let median = bc.chain[6].timestamp;
+    for (timestamp, ok) in [(median, true), (median - 1, false), (0, false)] {
+        let tip = bc.chain.last_mut().unwrap();
+        tip.timestamp = timestamp;
+        let bits = tip.difficulty;
+        tip.mine(bits).unwrap();
+        let result = bc.validate_full();
+        assert_eq!(result.is_ok(), ok, "{} against a median of {}: {:?}", timestamp, median, result.err());
+        if !ok {
+            assert!(is_bad_timestamp(result, 12));
+        }
+    }
+}
+
+#[test]
+fn failures_come_back_as_variants_callers_can_match() {
+    let mut bc = Blockchain::with_prefix("0");
+    let alice = Wallet::new();
+    let bob = Wallet::new().address();
+    assert_eq!(bc.mine_pending_transactions(alice.address(), false), Err(ChainError::EmptyMempool));
+    fund(&mut bc, &alice.address());
+
+    let unsigned = Transaction::new(alice.address(), bob.clone(), 1.0).unwrap();
+    assert_eq!(bc.add_transaction(unsigned.clone()), Err(ChainError::UnsignedTransaction));
+    let mut zero = Transaction::new_nits(alice.address(), bob.clone(), 0);
+    zero.sign(&alice).unwrap();
+    assert_eq!(bc.add_transaction(zero), Err(ChainError::ZeroAmount));
+    let mut forged = unsigned;
+    forged.signature_hex = alice.sign_transaction(bob.clone(), 2.0, 0.0, 0).unwrap().signature_hex;
+    assert_eq!(bc.add_transaction(forged), Err(ChainError::InvalidSignature));
+    let huge = alice.sign_transaction(bob.clone(), 1e9, 0.0, 0).unwrap();
+    assert!(matches!(bc.add_transaction(huge), Err(ChainError::InsufficientFunds { need: 1_000_000_000_000, .. })));
+    assert!(matches!(bc.get_balance("nope"), Err(ChainError::InvalidAddress(_))));
+
+    let mut broken = copy_chain(&bc, "typed-errors");
+    broken.chain[1].previous_hash = "1".repeat(64);
+    assert_eq!(broken.validate_full(), Err(ChainError::HashMismatch { index: 1 }));
+    let difficulty = broken.chain[1].difficulty;
+    broken.chain[1].mine(difficulty).unwrap();
+    assert_eq!(broken.validate_full(), Err(ChainError::BrokenLink { index: 1 }));
+
+    // the API's codes, and which ones are the caller's fault
+    assert_eq!(ChainError::InsufficientFunds { have: 0, need: 1 }.code(), "INSUFFICIENT_FUNDS");
+    assert!(ChainError::InvalidSignature.is_validation());
+    assert!(!ChainError::EmptyMempool.is_validation());
+    assert!(!ChainError::NotFound("block".into()).is_validation());
+}
+
+#[test]
+fn issuance_stops_at_the_supply_cap() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let reward = bc.reward;
+    bc.max_supply_nits = Some(reward * 5 / 2);
+    let (alice, bob) = (Wallet::new(), Wallet::new());
+    for _ in 0..4 {
+        bc.mine_pending_transactions(alice.address(), true).unwrap();
+    }
+    // the third block is cut short and the fourth creates nothing
+    let paid: Vec<u64> = bc.chain[1..].iter().map(|b| b.transactions.last().unwrap().amount()).collect();
+    assert_eq!(paid, [reward, reward, reward / 2, 0]);
+    assert_eq!(bc.total_supply(), reward * 5 / 2);
+
+    // miners still collect fees past the cap
+    bc.add_transaction(alice.sign_transaction(bob.address(), 1.0, 0.5, 0).unwrap()).unwrap();
+    bc.mine_pending_transactions(bob.address(), false).unwrap();
+    assert_eq!(bc.chain.last().unwrap().transactions.last().unwrap().amount(), 500);
+    assert_eq!(bc.total_supply(), reward * 5 / 2);
+    bc.validate_full().unwrap();
+
+    // the same blocks under a tighter cap issue too much
+    bc.max_supply_nits = Some(reward * 2);
+    let err = bc.validate_full().unwrap_err();
+    assert!(matches!(err, ChainError::InvalidCoinbase { index: 3, .. }), "{}", err);
+}
+
+#[test]
+fn an_outside_block_is_checked_then_clears_the_mempool() {
+    let mut bc = chain_with_a_transfer();
+    let alice = Wallet::new();
+    fund(&mut bc, &alice.address());
+    bc.add_transaction(alice.sign_transaction(Wallet::new().address(), 1.0, 0.1, 0).unwrap()).unwrap();
+    let miner = Wallet::new().address();
+    let (height, bits) = (bc.chain.len(), bc.difficulty_for_height(bc.chain.len()));
+    let mined = |mut block: Block| {
+        block.mine(bits).unwrap();
+        block
+    };
+    let block = mined(bc.prepare_block(miner.clone(), false).unwrap());
+    // a second go at the same height, for later
+    let rival = mined(bc.prepare_block(Wallet::new().address(), false).unwrap());
+
+    let mut edited = block.clone();
+    edited.nonce += 1;
+    assert!(matches!(bc.try_append_block(edited), Err(ChainError::HashMismatch { .. })));
+    let mut greedy = block.clone();
+    greedy.transactions.last_mut().unwrap().outputs[0].amount *= 2;
+    greedy.merkle_root = Block::compute_merkle_root(&greedy.transactions);
+    assert!(matches!(bc.try_append_block(mined(greedy)), Err(ChainError::InvalidCoinbase { .. })));
+    let mut forged = block.clone();
+    forged.transactions[0].outputs[0].amount += 1;
+    forged.merkle_root = Block::compute_merkle_root(&forged.transactions);
+    assert!(matches!(bc.try_append_block(mined(forged)), Err(ChainError::InvalidTransaction { .. })));
+    assert_eq!((bc.chain.len(), bc.mempool.len()), (height, 1));
+
+    bc.try_append_block(block.clone()).unwrap();
+    assert_eq!((bc.chain.len(), bc.mempool.len()), (height + 1, 0));
+    bc.validate_full().unwrap();
+
+    // built on the tip we had before: fetch new work
+    assert_eq!(bc.try_append_block(block), Err(ChainError::StaleTip));
+    assert_eq!(bc.try_append_block(rival), Err(ChainError::StaleTip));
+}
+
+#[test]
+fn a_block_pays_before_it_spends() {
+    let mut bc = Blockchain::with_prefix("0");
+    bc.coinbase_maturity = 1;
+    let (alice, bob) = (Wallet::new(), Wallet::new());
+    fund(&mut bc, &alice.address());
+    let carol = Wallet::new().address();
+    // bob spends what alice is only now sending him, and pays more to go first
+    let spend = bob.sign_transaction(carol.clone(), 25.0, 2.0, 0).unwrap();
+    let credit = alice.sign_transaction(bob.address(), 30.0, 0.1, 0).unwrap();
+    let (spend_id, credit_id) = (spend.id(), credit.id());
+    bc.mempool.push(spend);
+    bc.mempool.push(credit);
+
+    let bits = bc.difficulty_for_height(bc.chain.len());
+    let mut block = bc.prepare_block(alice.address(), false).unwrap();
+    let order: Vec<String> = block.transactions.iter().filter(|t| t.from != "NETWORK").map(|t| t.id()).collect();
+    assert_eq!(order, [credit_id, spend_id]);
+
+    // the same block the other way round adds up, but spends before it's paid
+    let mut swapped = block.clone();
+    swapped.transactions.swap(0, 1);
+    swapped.merkle_root = Block::compute_merkle_root(&swapped.transactions);
+    swapped.mine(bits).unwrap();
+    let index = swapped.index;
+    assert_eq!(bc.try_append_block(swapped.clone()), Err(ChainError::InvalidOrdering { index, position: 0 }));
+    let mut reordered = copy_chain(&bc, "ordering.json");
+    reordered.chain.push(swapped);
+    assert_eq!(reordered.validate_full(), Err(ChainError::InvalidOrdering { index, position: 0 }));
+    assert!(reordered.recompute_balances().is_err());
+
+    block.mine(bits).unwrap();
+    bc.try_append_block(block).unwrap();
+    bc.validate_full().unwrap();
+    assert_eq!(bc.get_balance_nits(&bob.address()).unwrap(), 3_000);
+    assert_eq!(bc.get_balance_nits(&carol).unwrap(), 25_000);
+}
@@ -0,0 +1,107 @@
+// Fixed inputs with answers recorded in tests/fixtures. Any change to one of
+// these encodings breaks every signature or hash made before it.
+
+use serde::Deserialize;
+
+use mini_blockchain::block::Block;
+use mini_blockchain::canonical::HASH_VERSION;
+use mini_blockchain::transaction::Transaction;
+use mini_blockchain::wallet::{verify_message, Wallet};
+
+fn fixture<T: for<'de> Deserialize<'de>>(name: &str) -> T {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap()
+}
+
+#[derive(Deserialize)]
+struct MessageVector {
+    private_key_hex: String,
+    address:         String,
+    message:         String,
+    signature:       String,
+}
+
+#[test]
+fn signed_messages_match_their_vectors() {
+    let vectors: Vec<MessageVector> = fixture("message_vectors.json");
+    assert!(!vectors.is_empty());
+    for v in &vectors {
+        let wallet = Wallet::from_private_key_hex(&v.private_key_hex).unwrap();
+        assert_eq!(wallet.address(), v.address);
+        // ed25519 is deterministic, so signing again gives the recorded bytes
+        assert_eq!(wallet.sign_message(&v.message), v.signature, "{:?}", v.message);
+        assert_eq!(verify_message(&v.address, &v.message, &v.signature), Ok(true));
+    }
+}
+
+#[test]
+fn signed_messages_dont_verify_for_anything_else() {
+    let vectors: Vec<MessageVector> = fixture("message_vectors.json");
+    for v in &vectors {
+        let other = format!("{}.", v.message);
+        assert_eq!(verify_message(&v.address, &other, &v.signature), Ok(false));
+        let stranger = Wallet::new().address();
+        assert_eq!(verify_message(&stranger, &v.message, &v.signature), Ok(false));
+    }
+    let v = &vectors[0];
+    assert!(verify_message(&v.address, &v.message, "not hex").is_err());
+    assert!(verify_message("abc", &v.message, &v.signature).is_err());
+}
+
+#[derive(Deserialize)]
+struct CanonicalVectors {
+    hash_version: u32,
+    transactions: Vec<TransactionVector>,
+    blocks:       Vec<BlockVector>,
+}
+
+#[derive(Deserialize)]
+struct TransactionVector {
+    name:            String,
+    transaction:     Transaction,
+    message_to_sign: String,
+    id:              String,
+}
+
+#[derive(Deserialize)]
+struct BlockVector {
+    name:  String,
+    block: Block,
+    hash:  String,
+}
+
+#[test]
+fn canonical_hashes_match_their_golden_vectors() {
+    let vectors: CanonicalVectors = fixture("canonical_vectors.json");
+    // a new encoding means new vectors, recorded on purpose
+    assert_eq!(vectors.hash_version, HASH_VERSION);
+    for v in &vectors.transactions {
+        assert_eq!(hex::encode(v.transaction.message_to_sign()), v.message_to_sign, "{}", v.name);
+        assert_eq!(v.transaction.id(), v.id, "{}", v.name);
+    }
+    for v in &vectors.blocks {
+        if !v.block.transactions.is_empty() {
+            assert_eq!(Block::compute_merkle_root(&v.block.transactions), v.block.merkle_root, "{}", v.name);
+        }
+        assert_eq!(v.block.calculate_hash(), v.hash, "{}", v.name);
+    }
+}
+
+#[test]
+fn fields_cant_bleed_into_each_other() {
+    let vectors: CanonicalVectors = fixture("canonical_vectors.json");
+    let block = &vectors.blocks[0].block;
+    // a delimiter-joined encoding read both of these as "a::b::c"
+    let mut left = block.clone();
+    left.miner = "a::b".into();
+    left.previous_hash = "c".into();
+    let mut right = block.clone();
+    right.miner = "a".into();
+    right.previous_hash = "b::c".into();
+    assert_ne!(left.calculate_hash(), right.calculate_hash());
+
+    let txn = &vectors.transactions[0].transaction;
+    let mut memo = txn.clone();
+    memo.memo = Some(String::new());
+    assert_ne!(memo.id(), txn.id());
+}
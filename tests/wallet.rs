@@ -0,0 +1,91 @@
+mod common;
+
+use mini_blockchain::keystore::{Keystore, KeystoreError};
+use mini_blockchain::wallet::Wallet;
+use zeroize::ZeroizeOnDrop;
+
+use common::temp_path;
+
+// only compiles for types that wipe themselves on drop
+fn wipes_on_drop<T: ZeroizeOnDrop>() {}
+
+#[test]
+fn a_keystore_gives_the_wallet_back_for_its_password() {
+    let wallet = Wallet::new();
+    let path = temp_path("keystore-roundtrip.json");
+    Keystore::encrypt(&wallet, "correct horse").unwrap().save(&path).unwrap();
+
+    let keystore = Keystore::load(&path).unwrap();
+    assert_eq!(keystore.address, wallet.address());
+    let back = keystore.decrypt("correct horse").unwrap();
+    assert_eq!(back.address(), wallet.address());
+    assert_eq!(back.export_secret().as_str(), wallet.export_secret().as_str());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn a_keystore_refuses_the_wrong_password_and_an_edited_address() {
+    let keystore = Keystore::encrypt(&Wallet::new(), "correct horse").unwrap();
+    assert!(matches!(keystore.decrypt("battery staple"), Err(KeystoreError::WrongPassword)));
+
+    // the address is bound in, so pairing the key with another one breaks decryption
+    let mut edited = keystore.clone();
+    edited.address = Wallet::new().address();
+    assert!(matches!(edited.decrypt("correct horse"), Err(KeystoreError::WrongPassword)));
+}
+
+#[test]
+fn saving_never_overwrites_a_keystore() {
+    let path = temp_path("keystore-existing.json");
+    let first = Wallet::new();
+    Keystore::encrypt(&first, "pw").unwrap().save(&path).unwrap();
+
+    let err = Keystore::encrypt(&Wallet::new(), "pw").unwrap().save(&path).unwrap_err();
+    assert!(err.to_string().contains("already exists"), "{}", err);
+    assert_eq!(Keystore::load(&path).unwrap().decrypt("pw").unwrap().address(), first.address());
+    let _ = std::fs::remove_file(&path);
+}
+
+// the BIP39 reference phrase, whose seed is the standard
+// 5eb00bbd...ce9e38e4; SHA-512 of that, first 32 bytes, is the key
+const ABANDON_ABOUT: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+#[test]
+fn a_known_phrase_gives_a_known_address() {
+    let wallet = Wallet::from_mnemonic(ABANDON_ABOUT).unwrap();
+    assert_eq!(wallet.export_secret().as_str(), "5f1c199c1e0bea246e12710cef0296a0c4a6ee54b1248365bee3113f755461b5");
+    assert_eq!(wallet.address(), "c00118799709529e6e29f83381ff82fb9ec8d873f839a413d4dc5b7f230e5d13");
+    // extra whitespace isn't part of the phrase
+    let spaced = format!("  {}  ", ABANDON_ABOUT.replace(' ', "   "));
+    assert_eq!(Wallet::from_mnemonic(&spaced).unwrap().address(), wallet.address());
+}
+
+#[test]
+fn a_generated_phrase_restores_its_wallet() {
+    let (wallet, phrase) = Wallet::generate_with_mnemonic();
+    assert_eq!(phrase.split_whitespace().count(), 12);
+    assert_eq!(Wallet::from_mnemonic(&phrase).unwrap().address(), wallet.address());
+}
+
+#[test]
+fn a_phrase_with_a_bad_checksum_is_refused() {
+    // every word valid, but the last one doesn't carry the checksum
+    let err = Wallet::from_mnemonic(&ABANDON_ABOUT.replace("about", "abandon")).err().unwrap();
+    assert!(err.contains("checksum"), "{}", err);
+    let err = Wallet::from_mnemonic(&ABANDON_ABOUT.replace("about", "aboot")).err().unwrap();
+    assert!(err.starts_with("Invalid mnemonic"), "{}", err);
+}
+
+#[test]
+fn only_export_secret_hands_out_the_key() {
+    wipes_on_drop::<Wallet>();
+    let wallet = Wallet::new();
+    let secret = wallet.export_secret();
+    assert_eq!(secret.len(), 64);
+    assert_eq!(Wallet::from_private_key_hex(&secret).unwrap().address(), wallet.address());
+
+    // what goes out over the API is the address and nothing else
+    let info = serde_json::to_value(wallet.to_info()).unwrap();
+    assert_eq!(info, serde_json::json!({"address": wallet.address()}));
+    assert!(!info.to_string().contains(secret.as_str()));
+}
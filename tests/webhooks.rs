@@ -0,0 +1,110 @@
+mod common;
+
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::{web, App, HttpResponse, HttpServer};
+
+use mini_blockchain::blockchain::Blockchain;
+use mini_blockchain::util::LockExt;
+use mini_blockchain::webhooks::{EventKind, Webhook, WebhookRegistry, Webhooks, MAX_WEBHOOK_FAILURES, WEBHOOK_ATTEMPTS};
+
+use common::temp_path;
+
+// an endpoint that answers with whatever status it's set to and notes when it was asked
+struct Endpoint {
+    url:    String,
+    status: Arc<AtomicU16>,
+    hits:   Arc<Mutex<Vec<Instant>>>,
+}
+
+fn endpoint(status: u16) -> Endpoint {
+    let (answer, hits) = (Arc::new(AtomicU16::new(status)), Arc::new(Mutex::new(Vec::new())));
+    let (s, h) = (answer.clone(), hits.clone());
+    let server = HttpServer::new(move || {
+        let (s, h) = (s.clone(), h.clone());
+        App::new().route("/hook", web::post().to(move || {
+            let (s, h) = (s.clone(), h.clone());
+            async move {
+                h.locked().push(Instant::now());
+                HttpResponse::build(actix_web::http::StatusCode::from_u16(s.load(Ordering::SeqCst)).unwrap()).finish()
+            }
+        }))
+    }).workers(1).disable_signals().bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", server.addrs()[0]);
+    actix_web::rt::spawn(server.run());
+    Endpoint { url, status: answer, hits }
+}
+
+// a dispatcher with one endpoint registered for block events
+fn subscribed(to: &Endpoint, name: &str) -> (Webhooks, String, String) {
+    let path = temp_path(name);
+    let webhooks = Webhooks::spawn(WebhookRegistry::load(&path).unwrap());
+    let id = webhooks.registry.locked().register(&to.url, vec![EventKind::Block], None).unwrap().id;
+    (webhooks, id, path)
+}
+
+fn hook(webhooks: &Webhooks, id: &str) -> Webhook {
+    webhooks.registry.locked().list().iter().find(|h| h.id == id).unwrap().clone()
+}
+
+async fn eventually(what: &str, mut check: impl FnMut() -> bool) {
+    for _ in 0..200 {
+        if check() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("gave up waiting for {}", what);
+}
+
+#[actix_web::test]
+async fn a_failing_delivery_is_retried_with_growing_delays() {
+    let to = endpoint(500);
+    let (webhooks, id, path) = subscribed(&to, "webhooks-retry.json");
+    let genesis = Blockchain::with_prefix("0").chain[0].clone();
+
+    webhooks.block_added(&genesis);
+    eventually("the delivery to fail", || hook(&webhooks, &id).failures == 1).await;
+    let hits = to.hits.locked().clone();
+    assert_eq!(hits.len(), WEBHOOK_ATTEMPTS as usize);
+    // half a second, then twice that
+    assert!(hits[1] - hits[0] >= Duration::from_millis(500), "{:?}", hits[1] - hits[0]);
+    assert!(hits[2] - hits[1] >= Duration::from_millis(1000), "{:?}", hits[2] - hits[1]);
+    let failed = hook(&webhooks, &id);
+    assert!(failed.enabled);
+    assert!(failed.last_error.as_deref().unwrap().contains("HTTP 500"), "{:?}", failed.last_error);
+
+    // the next one that gets through wipes the slate
+    to.status.store(204, Ordering::SeqCst);
+    webhooks.block_added(&genesis);
+    eventually("the delivery to land", || hook(&webhooks, &id).last_delivery.is_some()).await;
+    let recovered = hook(&webhooks, &id);
+    assert_eq!((recovered.failures, recovered.last_error), (0, None));
+    assert_eq!(to.hits.locked().len(), WEBHOOK_ATTEMPTS as usize + 1);
+    let _ = std::fs::remove_file(path);
+}
+
+#[actix_web::test]
+async fn an_endpoint_that_keeps_failing_is_disabled() {
+    let to = endpoint(503);
+    let (webhooks, id, path) = subscribed(&to, "webhooks-disable.json");
+    let genesis = Blockchain::with_prefix("0").chain[0].clone();
+
+    for _ in 0..MAX_WEBHOOK_FAILURES {
+        webhooks.block_added(&genesis);
+    }
+    eventually("the endpoint to be disabled", || !hook(&webhooks, &id).enabled).await;
+    assert_eq!(hook(&webhooks, &id).failures, MAX_WEBHOOK_FAILURES);
+    assert_eq!(to.hits.locked().len(), (MAX_WEBHOOK_FAILURES * WEBHOOK_ATTEMPTS) as usize);
+
+    // nothing more is sent its way, and that survives a restart
+    to.status.store(200, Ordering::SeqCst);
+    webhooks.block_added(&genesis);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(to.hits.locked().len(), (MAX_WEBHOOK_FAILURES * WEBHOOK_ATTEMPTS) as usize);
+    let reloaded = WebhookRegistry::load(&path).unwrap();
+    assert!(!reloaded.list().iter().find(|h| h.id == id).unwrap().enabled);
+    let _ = std::fs::remove_file(path);
+}